@@ -0,0 +1,151 @@
+// Picking, snapping and box queries against a drawing of 100k entities, each one has to stay
+// under a millisecond. Run with `cargo bench --bench spatial_index`.
+
+use easycad::model::arc::{Arc, ArcOps};
+use easycad::model::circle::CircleOps;
+use easycad::model::document::Document;
+use easycad::model::entity::Geometry;
+use easycad::model::geometry::BoundingBox;
+use easycad::model::line::LineOps;
+use easycad::model::spatial::SpatialOps;
+use std::time::{Duration, Instant};
+
+const ENTITIES: usize = 100_000;
+const QUERIES: usize = 10_000;
+// a site plan in millimeters, 500 m across
+const EXTENT: f32 = 500_000.0;
+// five pixels at a zoom where the whole plan fits on a 2000 pixel screen
+const PICK_RADIUS: f32 = 5.0 * EXTENT / 2000.0;
+const LIMIT: Duration = Duration::from_millis(1);
+
+// small deterministic generator, the numbers only have to look random
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn point(&mut self) -> [f32; 2] {
+        [self.next() * EXTENT, self.next() * EXTENT]
+    }
+}
+
+fn build_drawing(random: &mut Random) -> Document {
+    let mut document = Document::new();
+
+    for i in 0..ENTITIES {
+        let [x, y] = random.point();
+        let size = 100.0 + random.next() * 5000.0;
+
+        match i % 3 {
+            0 => {
+                let angle = random.next() * std::f32::consts::TAU;
+                let end = [x + size * angle.cos(), y + size * angle.sin()];
+                document.add_line([x, y], end, false);
+            }
+            1 => {
+                document.add_circle([x, y], size / 2.0, false, false, false);
+            }
+            _ => {
+                let start = random.next() * std::f32::consts::TAU;
+                document.add_arc(Arc::new([x, y], size / 2.0, start, start + 2.0), false);
+            }
+        }
+    }
+
+    document
+}
+
+// average and worst time of `query` over `QUERIES` random points
+fn measure(
+    document: &mut Document,
+    random: &mut Random,
+    mut query: impl FnMut(&mut Document, [f32; 2]) -> usize,
+) -> (Duration, Duration, usize) {
+    let mut total = Duration::ZERO;
+    let mut worst = Duration::ZERO;
+    let mut found = 0;
+
+    for _ in 0..QUERIES {
+        let point = random.point();
+        let start = Instant::now();
+        found += query(document, point);
+        let elapsed = start.elapsed();
+
+        total += elapsed;
+        worst = worst.max(elapsed);
+    }
+
+    (total / QUERIES as u32, worst, found)
+}
+
+fn main() {
+    let mut random = Random(0x2545_f491_4f6c_dd1d);
+    let mut document = build_drawing(&mut random);
+
+    let start = Instant::now();
+    document.entities_near([0.0, 0.0], 1.0);
+    println!(
+        "building the index for {} entities: {:?}",
+        ENTITIES,
+        start.elapsed()
+    );
+
+    let pick = measure(&mut document, &mut random, |document, point| {
+        let nearby = document.entities_near(point, PICK_RADIUS);
+        nearby
+            .into_iter()
+            .filter_map(|id| document.entities.get(id))
+            .filter(|entity| entity.hit_distance(point) < PICK_RADIUS)
+            .count()
+    });
+
+    let snap = measure(&mut document, &mut random, |document, point| {
+        let nearby = document.entities_near(point, PICK_RADIUS);
+        nearby
+            .into_iter()
+            .filter_map(|id| document.entities.get(id))
+            .flat_map(|entity| entity.snap_points())
+            .filter(|snap| {
+                (snap[0] - point[0]).abs() < PICK_RADIUS && (snap[1] - point[1]).abs() < PICK_RADIUS
+            })
+            .count()
+    });
+
+    let window = measure(&mut document, &mut random, |document, point| {
+        let corner = [point[0] + EXTENT / 50.0, point[1] + EXTENT / 50.0];
+        document.entities_in(BoundingBox::new(point, corner)).len()
+    });
+
+    // moving one entity between queries, like dragging it around
+    let moved = document.entities.ids().nth(ENTITIES / 2).unwrap_or(1);
+    let edit = measure(&mut document, &mut random, |document, point| {
+        if let Some(entity) = document.entities.get_mut(moved) {
+            entity.translate(1.0, 1.0);
+        }
+        document.entities_near(point, PICK_RADIUS).len()
+    });
+
+    let mut too_slow = false;
+    for (name, (average, worst, found)) in [
+        ("pick", pick),
+        ("snap", snap),
+        ("window (1/50 of the plan)", window),
+        ("move one entity + pick", edit),
+    ] {
+        println!(
+            "{:<28} average {:>10.2?}  worst {:>10.2?}  ({} found)",
+            name, average, worst, found
+        );
+        too_slow |= average >= LIMIT;
+    }
+
+    if too_slow {
+        eprintln!("queries took longer than {:?} on average", LIMIT);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,107 @@
+struct CameraUniform {
+    matrix: mat4x4<f32>,
+    window_size: vec2<f32>,
+};
+@group(0) @binding(0) var<uniform> camera: CameraUniform;
+
+struct InstanceInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+    @location(2) radius: f32,
+    @location(3) thickness: f32,
+    @location(4) start_angle: f32,
+    @location(5) sweep: f32,
+    @location(6) dashes0: vec4<f32>,
+    @location(7) dashes1: vec4<f32>,
+    @location(8) dashes2: vec4<f32>,
+    @location(9) dash_count: u32,
+    @location(10) dash_offset: f32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+    @location(1) local_pos: vec2<f32>,
+    @location(2) thickness: f32,
+    @location(3) radius: f32,
+    @location(4) start_angle: f32,
+    @location(5) sweep: f32,
+    @location(6) world_radius: f32,
+    @location(7) @interpolate(flat) dashes0: vec4<f32>,
+    @location(8) @interpolate(flat) dashes1: vec4<f32>,
+    @location(9) @interpolate(flat) dashes2: vec4<f32>,
+    @location(10) @interpolate(flat) dash_count: u32,
+    @location(11) dash_offset: f32,
+};
+
+const TAU: f32 = 6.2831855;
+
+// same billboard as the circle, the fragment shader cuts away everything outside the angle range
+@vertex
+fn vs_main(
+    @builtin(vertex_index) v_index: u32,
+    input: InstanceInput
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.color = input.color;
+    out.thickness = input.thickness;
+    out.start_angle = input.start_angle;
+    out.sweep = input.sweep;
+    out.world_radius = input.radius;
+    out.dashes0 = input.dashes0;
+    out.dashes1 = input.dashes1;
+    out.dashes2 = input.dashes2;
+    out.dash_count = input.dash_count;
+    out.dash_offset = input.dash_offset;
+
+    let center_proj = camera.matrix * vec4<f32>(input.position, 1.0);
+    let center_screen = center_proj.xy;
+
+    let radius_point_world = input.position + vec3<f32>(input.radius, 0.0, 0.0);
+    let radius_point_proj = camera.matrix * vec4<f32>(radius_point_world, 1.0);
+
+    let screen_radius = distance(center_screen, radius_point_proj.xy);
+    out.radius = screen_radius;
+
+    var corner = vec2<f32>(0.0, 0.0);
+    if (v_index == 0u) { corner = vec2<f32>(-1.0, -1.0); }
+    else if (v_index == 1u) { corner = vec2<f32>(-1.0, 1.0); }
+    else if (v_index == 2u) { corner = vec2<f32>(1.0, -1.0); }
+    else { corner = vec2<f32>(1.0, 1.0); }
+
+    let outer_radius = screen_radius + (input.thickness * 0.5);
+    let pixel_offset = corner * outer_radius;
+    out.local_pos = pixel_offset;
+
+    let final_screen_pos = center_screen + pixel_offset;
+    let clip_x = final_screen_pos.x / (camera.window_size.x * 0.5);
+    let clip_y = final_screen_pos.y / (camera.window_size.y * 0.5);
+
+    out.clip_position = vec4<f32>(clip_x, clip_y, input.position.z, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let dist = length(in.local_pos);
+    let half_thick = in.thickness * 0.5;
+
+    if (dist > in.radius + half_thick || dist < in.radius - half_thick) {
+        discard;
+    }
+
+    // angle of this pixel measured counter-clockwise from the start of the arc
+    let angle = atan2(in.local_pos.y, in.local_pos.x) - in.start_angle;
+    let relative = angle - TAU * floor(angle / TAU);
+    if (relative > in.sweep) {
+        discard;
+    }
+
+    // the pattern starts at the start of the arc
+    let along = relative * in.world_radius + in.dash_offset;
+    if (in_gap(along, in.dashes0, in.dashes1, in.dashes2, in.dash_count, in.thickness)) {
+        discard;
+    }
+
+    return vec4<f32>(in.color, 1.0);
+}
@@ -9,6 +9,11 @@ struct InstanceInput {
     @location(1) color: vec3<f32>,
     @location(2) radius: f32,
     @location(3) thickness: f32,
+    @location(4) dashes0: vec4<f32>,
+    @location(5) dashes1: vec4<f32>,
+    @location(6) dashes2: vec4<f32>,
+    @location(7) dash_count: u32,
+    @location(8) dash_offset: f32,
 };
 
 struct VertexOutput {
@@ -17,8 +22,16 @@ struct VertexOutput {
     @location(1) local_pos: vec2<f32>,   // <--- We pass the offset from center to fragment
     @location(2) thickness: f32,         // Pass to fragment
     @location(3) radius: f32,            // Pass to fragment
+    @location(4) world_radius: f32,      // dash patterns are in world units
+    @location(5) @interpolate(flat) dashes0: vec4<f32>,
+    @location(6) @interpolate(flat) dashes1: vec4<f32>,
+    @location(7) @interpolate(flat) dashes2: vec4<f32>,
+    @location(8) @interpolate(flat) dash_count: u32,
+    @location(9) dash_offset: f32,
 };
 
+const TAU: f32 = 6.2831855;
+
 @vertex
 fn vs_main(
     @builtin(vertex_index) v_index: u32,
@@ -28,6 +41,12 @@ fn vs_main(
     out.color = input.color;
     // out.radius = input.radius;
     out.thickness = input.thickness;
+    out.world_radius = input.radius;
+    out.dashes0 = input.dashes0;
+    out.dashes1 = input.dashes1;
+    out.dashes2 = input.dashes2;
+    out.dash_count = input.dash_count;
+    out.dash_offset = input.dash_offset;
 
     let center_proj = camera.matrix * vec4<f32>(input.position, 1.0);
     let center_screen = center_proj.xy;
@@ -85,5 +104,12 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
         discard;
     }
 
+    // the pattern starts at angle 0 and runs counter-clockwise
+    let angle = atan2(in.local_pos.y, in.local_pos.x);
+    let along = (angle - TAU * floor(angle / TAU)) * in.world_radius + in.dash_offset;
+    if (in_gap(along, in.dashes0, in.dashes1, in.dashes2, in.dash_count, in.thickness)) {
+        discard;
+    }
+
     return vec4<f32>(in.color, 1.0);
 }
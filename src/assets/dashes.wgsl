@@ -0,0 +1,60 @@
+
+// Dash patterns, shared by the line, circle and arc shaders. Pattern lengths and the position
+// along the entity are in world units so the dashes stay put on the drawing while zooming.
+
+// patterns shorter than this on screen would only flicker, they're drawn solid instead
+const MIN_PATTERN_PIXELS: f32 = 4.0;
+
+// whether the point `along` world units down the entity falls into a gap of the pattern
+fn in_gap(
+    along: f32,
+    dashes0: vec4<f32>,
+    dashes1: vec4<f32>,
+    dashes2: vec4<f32>,
+    count: u32,
+    thickness: f32,
+) -> bool {
+    if (count == 0u) {
+        return false;
+    }
+
+    var pattern = array<f32, 12>(
+        dashes0.x, dashes0.y, dashes0.z, dashes0.w,
+        dashes1.x, dashes1.y, dashes1.z, dashes1.w,
+        dashes2.x, dashes2.y, dashes2.z, dashes2.w,
+    );
+
+    var total = 0.0;
+    for (var i = 0u; i < count; i++) {
+        total += abs(pattern[i]);
+    }
+
+    // pixels per world unit
+    let scale = length((camera.matrix * vec4<f32>(1.0, 0.0, 0.0, 0.0)).xy);
+    let total_pixels = total * scale;
+    if (total_pixels < MIN_PATTERN_PIXELS) {
+        return false;
+    }
+
+    let position = (along - total * floor(along / total)) * scale;
+    var start = 0.0;
+    for (var i = 0u; i < count; i++) {
+        let element = pattern[i] * scale;
+
+        if (element > 0.0 && position >= start && position < start + element) {
+            return false;
+        }
+
+        // a dot is as long as the line is thick, one at the start also shows at the very end
+        if (element == 0.0) {
+            let distance_to_dot = abs(position - start);
+            if (min(distance_to_dot, total_pixels - distance_to_dot) <= thickness * 0.5) {
+                return false;
+            }
+        }
+
+        start += abs(element);
+    }
+
+    return true;
+}
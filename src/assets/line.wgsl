@@ -9,11 +9,22 @@ struct InstanceInput {
     @location(1) end_pos: vec3<f32>,
     @location(2) color: vec3<f32>,
     @location(3) thickness: f32, // <--- Per-line width input
+    @location(4) dashes0: vec4<f32>,
+    @location(5) dashes1: vec4<f32>,
+    @location(6) dashes2: vec4<f32>,
+    @location(7) dash_count: u32,
+    @location(8) dash_offset: f32,
 };
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec3<f32>,
+    @location(1) along: f32,             // world units from where the pattern starts
+    @location(2) thickness: f32,
+    @location(3) @interpolate(flat) dashes0: vec4<f32>,
+    @location(4) @interpolate(flat) dashes1: vec4<f32>,
+    @location(5) @interpolate(flat) dashes2: vec4<f32>,
+    @location(6) @interpolate(flat) dash_count: u32,
 };
 
 @vertex
@@ -23,6 +34,11 @@ fn vs_main(
 ) -> VertexOutput {
     var out: VertexOutput;
     out.color = input.color;
+    out.thickness = input.thickness;
+    out.dashes0 = input.dashes0;
+    out.dashes1 = input.dashes1;
+    out.dashes2 = input.dashes2;
+    out.dash_count = input.dash_count;
 
     // 1. Project World Points to "Pixel Space"
     // Apply camera matrix to get screen positions
@@ -58,6 +74,13 @@ fn vs_main(
         pos_pixel = p1 - offset;
     }
 
+    // the dash pattern is laid out along the line in world units
+    if (v_index < 2u) {
+        out.along = input.dash_offset;
+    } else {
+        out.along = input.dash_offset + distance(input.start_pos.xy, input.end_pos.xy);
+    }
+
     // 5. Convert Pixel Space back to Clip Space (-1.0 to 1.0)
     // Note: This assumes your camera matrix outputted pixels relative to center
     // Adjust based on your specific coordinate system logic
@@ -71,5 +94,9 @@ fn vs_main(
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    if (in_gap(in.along, in.dashes0, in.dashes1, in.dashes2, in.dash_count, in.thickness)) {
+        discard;
+    }
+
     return vec4<f32>(in.color, 1.0);
 }
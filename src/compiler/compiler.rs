@@ -1,7 +1,11 @@
 // used to compile .cad files
+use crate::model::arc::{Arc, ArcOps};
 use crate::model::circle::CircleOps;
+use crate::model::document::Document;
+use crate::model::ellipse::{Ellipse, EllipseOps};
 use crate::model::line::LineOps;
-use crate::State;
+use crate::model::polyline::PolylineOps;
+use crate::model::shape::{polygon, rectangle, Corners};
 use anyhow::{anyhow, Context, Result};
 use std::collections::HashMap;
 
@@ -16,6 +20,12 @@ pub struct Compiler {
     points: HashMap<String, Point>,
 }
 
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Compiler {
     pub fn new() -> Self {
         Self {
@@ -30,7 +40,12 @@ impl Compiler {
             .context("Failed to evaluate expression")
     }
 
-    pub fn process_line(&mut self, state: &mut State, line: &str, line_num: usize) -> Result<()> {
+    pub fn process_line(
+        &mut self,
+        document: &mut Document,
+        line: &str,
+        line_num: usize,
+    ) -> Result<()> {
         let parts: Vec<&str> = line.split_whitespace().collect();
         if parts.is_empty() || parts[0].starts_with("//") {
             return Ok(());
@@ -84,7 +99,7 @@ impl Compiler {
                     anyhow!("Error on line {line_num}. Unknown point: {}", parts[3])
                 })?;
 
-                state.add_line(
+                document.add_line(
                     [p1.x as f32, p1.y as f32],
                     [p2.x as f32, p2.y as f32],
                     false,
@@ -103,18 +118,211 @@ impl Compiler {
                 })?;
                 let radius = self.eval_exp(parts[3])?;
 
-                state.add_circle(
+                document.add_circle(
                     [center.x as f32, center.y as f32],
                     radius as f32,
-                    [1.0, 1.0, 1.0],
                     false,
                     false,
                     false,
                 );
             }
+            // arc name point radius start_angle end_angle (degrees, counter-clockwise)
+            "arc" => {
+                if parts.len() < 6 {
+                    return Err(anyhow!(
+                        "Error on line {line_num}. Usage: arc <name> <center_point> <expression> <start_angle> <end_angle>"
+                    ));
+                }
+
+                let center = self.points.get(parts[2]).ok_or_else(|| {
+                    anyhow!("Error on line {line_num}. Unknown point: {}", parts[2])
+                })?;
+                let radius = self.eval_exp(parts[3])?;
+                let start_angle = self.eval_exp(parts[4])?;
+                let end_angle = self.eval_exp(parts[5])?;
+
+                document.add_arc(
+                    Arc::new(
+                        [center.x as f32, center.y as f32],
+                        radius as f32,
+                        start_angle.to_radians() as f32,
+                        end_angle.to_radians() as f32,
+                    ),
+                    false,
+                );
+            }
+            // ellipse name center_point major_radius minor_radius rotation (degrees, of the major axis)
+            "ellipse" => {
+                if parts.len() < 6 {
+                    return Err(anyhow!(
+                        "Error on line {line_num}. Usage: ellipse <name> <center_point> <expression> <expression> <rotation>"
+                    ));
+                }
+
+                let center = self.points.get(parts[2]).ok_or_else(|| {
+                    anyhow!("Error on line {line_num}. Unknown point: {}", parts[2])
+                })?;
+                let major = self.eval_exp(parts[3])?;
+                let minor = self.eval_exp(parts[4])?;
+                let rotation = self.eval_exp(parts[5])?.to_radians();
+
+                let center = [center.x as f32, center.y as f32];
+                let axis_end = [
+                    center[0] + (major * rotation.cos()) as f32,
+                    center[1] + (major * rotation.sin()) as f32,
+                ];
+                document.add_ellipse(Ellipse::from_axes(center, axis_end, minor as f32), false);
+            }
+            // rect name corner_point corner_point
+            "rect" => {
+                if parts.len() < 4 {
+                    return Err(anyhow!(
+                        "Error on line {line_num}. Usage: rect <name> <corner_point> <corner_point>"
+                    ));
+                }
+
+                let a = self.points.get(parts[2]).ok_or_else(|| {
+                    anyhow!("Error on line {line_num}. Unknown point: {}", parts[2])
+                })?;
+                let b = self.points.get(parts[3]).ok_or_else(|| {
+                    anyhow!("Error on line {line_num}. Unknown point: {}", parts[3])
+                })?;
+
+                let vertices = rectangle(
+                    [a.x as f32, a.y as f32],
+                    [b.x as f32, b.y as f32],
+                    Corners::Sharp,
+                );
+                document.add_polyline(vertices, true, false);
+            }
+            // polygon name center_point sides radius (corners on the circle, the first one to the right)
+            "polygon" => {
+                if parts.len() < 5 {
+                    return Err(anyhow!(
+                        "Error on line {line_num}. Usage: polygon <name> <center_point> <expression> <expression>"
+                    ));
+                }
+
+                let center = self.points.get(parts[2]).ok_or_else(|| {
+                    anyhow!("Error on line {line_num}. Unknown point: {}", parts[2])
+                })?;
+                let sides = self.eval_exp(parts[3])?;
+                let radius = self.eval_exp(parts[4])?;
+
+                let center = [center.x as f32, center.y as f32];
+                let vertices = polygon(
+                    center,
+                    [center[0] + radius as f32, center[1]],
+                    sides.round() as usize,
+                    true,
+                );
+                document.add_polyline(vertices, true, false);
+            }
             _ => {}
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::entity::Entity;
+
+    fn compile(document: &mut Document, src: &str) -> Result<()> {
+        let mut compiler = Compiler::new();
+        for (i, line) in src.lines().enumerate() {
+            compiler.process_line(document, line, i)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn points_and_params_place_entities() {
+        let mut document = Document::new();
+        let src = "\
+            // a box with a hole
+            param width = 60
+            param height = width / 2
+            point A 0 0
+            point B width 0
+            point C width/2 height/2
+            line bottom A B
+            circle hole C height/4
+            rect outline A B
+        ";
+        compile(&mut document, src).unwrap();
+
+        let entities: Vec<&Entity> = document.entities.iter().collect();
+        assert_eq!(entities.len(), 3);
+        match entities[0] {
+            Entity::Line(line) => {
+                assert_eq!(line.start(), [0.0, 0.0]);
+                assert_eq!(line.end(), [60.0, 0.0]);
+            }
+            other => panic!("expected a line, got {}", other.kind_name()),
+        }
+        match entities[1] {
+            Entity::Circle(circle) => {
+                assert_eq!(circle.center.position, [30.0, 15.0, 0.0]);
+                assert_eq!(circle.radius, 7.5);
+            }
+            other => panic!("expected a circle, got {}", other.kind_name()),
+        }
+        assert!(matches!(entities[2], Entity::Polyline(rect) if rect.closed));
+    }
+
+    #[test]
+    fn arcs_take_degrees() {
+        let mut document = Document::new();
+        compile(&mut document, "point O 1 1\narc a O 2 0 90").unwrap();
+
+        match document.entities.iter().next() {
+            Some(Entity::Arc(arc)) => {
+                assert_eq!(arc.center, [1.0, 1.0]);
+                assert!((arc.end_angle - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+            }
+            other => panic!("expected an arc, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn polygon_gets_one_vertex_per_side() {
+        let mut document = Document::new();
+        compile(&mut document, "point O 0 0\npolygon hex O 6 10").unwrap();
+
+        match document.entities.iter().next() {
+            Some(Entity::Polyline(polyline)) => {
+                assert_eq!(polyline.vertices.len(), 6);
+                assert_eq!(polyline.vertices[0].position, [10.0, 0.0]);
+            }
+            other => panic!("expected a polyline, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unknown_points_are_reported_with_the_line() {
+        let mut document = Document::new();
+        let error = compile(&mut document, "point A 0 0\nline l A B").unwrap_err();
+
+        assert_eq!(error.to_string(), "Error on line 1. Unknown point: B");
+        assert!(document.entities.is_empty());
+    }
+
+    #[test]
+    fn bad_usage_is_an_error() {
+        let mut document = Document::new();
+        let mut compiler = Compiler::new();
+
+        assert!(compiler
+            .process_line(&mut document, "param x 5", 0)
+            .is_err());
+        assert!(compiler
+            .process_line(&mut document, "point A 1", 0)
+            .is_err());
+        assert!(compiler
+            .process_line(&mut document, "point A 1 (", 0)
+            .is_err());
+    }
+}
@@ -1,15 +1,35 @@
 use crate::graphics::camera::Camera;
-use crate::graphics::gui_elements::{Text, UiMode};
-use crate::model::circle::flatten_circles_for_snap;
-use crate::model::circle::Circle;
+use crate::graphics::gui_elements::UiMode;
+use crate::model::arc::{Arc, ArcOps};
 use crate::model::circle::CircleOps;
-use crate::model::line::Line;
+use crate::model::ellipse::EllipseOps;
+use crate::model::entity::{Entity, Geometry};
+use crate::model::fillet::FilletOps;
+use crate::model::geometry::distance;
+use crate::model::grip::{GripOps, GRIP_SIZE};
+use crate::model::history::{Change, HistoryOps};
 use crate::model::line::LineOps;
+use crate::model::offset::OffsetOps;
+use crate::model::polyline::PolylineOps;
+use crate::model::selection::{SelectionBox, SelectionOps};
+use crate::model::snap::APERTURE;
+use crate::model::spatial::SpatialOps;
+use crate::model::text::Text;
+use crate::model::trim::TrimOps;
+use crate::ArrayState;
+use crate::BreakState;
+use crate::CornerState;
+use crate::DrawArcMode;
 use crate::DrawLineMode;
+use crate::DrawPolylineMode;
 use crate::DrawingState;
 use crate::FuncState;
 use crate::Mode;
+use crate::OffsetState;
 use crate::State;
+use crate::StretchState;
+use crate::TransformState;
+use crate::TrimState;
 use winit::event::KeyEvent;
 use winit::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent};
 use winit::keyboard::KeyCode;
@@ -39,6 +59,98 @@ pub fn handle_input(state: &mut State, event: &WindowEvent) -> bool {
                 KeyCode::KeyC if state.mode == Mode::Normal => {
                     state.mode = Mode::DrawCircle;
                 }
+                KeyCode::KeyP if state.modifiers.shift_key() => {
+                    state.start_polygon();
+                }
+                KeyCode::KeyQ => {
+                    state.start_rectangle();
+                }
+                KeyCode::KeyF if state.mode == Mode::DrawRectangle(None) => {
+                    state.toggle_rectangle_corners(false);
+                }
+                KeyCode::KeyC if state.mode == Mode::DrawRectangle(None) => {
+                    state.toggle_rectangle_corners(true);
+                }
+                KeyCode::KeyI if matches!(state.mode, Mode::DrawPolygon(_)) => {
+                    state.toggle_polygon_inscribed();
+                }
+                KeyCode::KeyP if state.mode == Mode::Normal => {
+                    state.mode = Mode::DrawPolyline(DrawPolylineMode::Line);
+                }
+                // close the polyline back to its first vertex and start over
+                KeyCode::KeyC
+                    if matches!(state.drawing_state, DrawingState::WaitingForNextVertex) =>
+                {
+                    state.document.finish_polyline(true);
+                    state.drawing_state = DrawingState::Idle;
+                    state.update_instance_buffers();
+                }
+                KeyCode::KeyU
+                    if matches!(state.drawing_state, DrawingState::WaitingForNextVertex) =>
+                {
+                    state.document.undo_polyline_vertex();
+                    if state.document.active_polyline_id.is_none() {
+                        state.drawing_state = DrawingState::Idle;
+                    } else if let Some(cursor) = state.cursor_position {
+                        let arc_segment = state.mode == Mode::DrawPolyline(DrawPolylineMode::Arc);
+                        state.document.update_polyline(cursor, arc_segment);
+                    }
+                    state.update_instance_buffers();
+                }
+                KeyCode::KeyA if state.modifiers.control_key() => {
+                    if matches!(
+                        state.mode,
+                        Mode::Normal
+                            | Mode::Selection
+                            | Mode::Move(FuncState::Selection)
+                            | Mode::Copy(FuncState::Selection)
+                            | Mode::Trim(TrimState::SelectEdges)
+                            | Mode::Extend(TrimState::SelectEdges)
+                            | Mode::Rotate(TransformState::Selection)
+                            | Mode::Scale(TransformState::Selection)
+                            | Mode::Mirror(TransformState::Selection)
+                            | Mode::Array(ArrayState::Selection)
+                            | Mode::Join
+                            | Mode::Explode
+                            | Mode::Overkill
+                    ) {
+                        if state.document.select_all() > 0 && state.mode == Mode::Normal {
+                            state.mode = Mode::Selection;
+                        }
+                        state.update_instance_buffers();
+                    }
+                }
+                KeyCode::KeyA if matches!(state.mode, Mode::DrawPolyline(_)) => {
+                    if state.mode == Mode::DrawPolyline(DrawPolylineMode::Line) {
+                        state.mode = Mode::DrawPolyline(DrawPolylineMode::Arc);
+                        state.ui.add_notification("Polyline: arc segments");
+                    } else {
+                        state.mode = Mode::DrawPolyline(DrawPolylineMode::Line);
+                        state.ui.add_notification("Polyline: line segments");
+                    }
+                }
+                KeyCode::F8 => {
+                    state.ui.ortho = !state.ui.ortho;
+                    if state.ui.ortho {
+                        state.ui.add_notification("Ortho on");
+                    } else {
+                        state.ui.add_notification("Ortho off");
+                    }
+                }
+                KeyCode::F3 => {
+                    let modes = &mut state.ui.snap_modes;
+                    modes.enabled = !modes.enabled;
+                    if modes.enabled {
+                        state.ui.add_notification("Object snap on");
+                    } else {
+                        state.snap = None;
+                        state.indicators.clear();
+                        state.ui.add_notification("Object snap off");
+                    }
+                }
+                KeyCode::KeyK if state.modifiers.shift_key() => {
+                    state.start_overkill();
+                }
                 KeyCode::KeyK if state.mode == Mode::Normal => {
                     state.mode = Mode::Copy(FuncState::Selection);
                 }
@@ -46,23 +158,104 @@ pub fn handle_input(state: &mut State, event: &WindowEvent) -> bool {
                     state.ui.ui_config.open_right_side_panel =
                         !state.ui.ui_config.open_right_side_panel;
                 }
+                KeyCode::KeyR
+                    if state.modifiers.shift_key()
+                        && matches!(state.mode, Mode::Normal | Mode::Selection) =>
+                {
+                    state.start_transform(Mode::Rotate);
+                }
+                KeyCode::KeyR
+                    if matches!(
+                        state.mode,
+                        Mode::Rotate(TransformState::Target(_, None))
+                            | Mode::Scale(TransformState::Target(_, None))
+                    ) =>
+                {
+                    state.start_reference();
+                }
+                KeyCode::KeyR if state.mode == Mode::Normal => {
+                    state.mode = Mode::DrawArc(DrawArcMode::ThreePoint);
+                }
+                // switching how the arc is picked only makes sense before its first point
+                KeyCode::KeyR
+                    if matches!(state.mode, Mode::DrawArc(_))
+                        && matches!(state.drawing_state, DrawingState::Idle) =>
+                {
+                    if state.mode == Mode::DrawArc(DrawArcMode::ThreePoint) {
+                        state.mode = Mode::DrawArc(DrawArcMode::CenterStartEnd);
+                        state.ui.add_notification("Arc: center, start, end");
+                    } else {
+                        state.mode = Mode::DrawArc(DrawArcMode::ThreePoint);
+                        state.ui.add_notification("Arc: start, point on arc, end");
+                    }
+                }
+                KeyCode::KeyX if state.modifiers.shift_key() => {
+                    state.start_restructure(Mode::Explode);
+                }
+                KeyCode::KeyX => {
+                    state.start_trim(false);
+                }
+                KeyCode::KeyF => {
+                    state.start_corner(state.modifiers.shift_key());
+                }
+                KeyCode::KeyE if state.modifiers.shift_key() => {
+                    state.start_ellipse();
+                }
+                KeyCode::KeyE => {
+                    state.start_trim(true);
+                }
+                KeyCode::KeyM
+                    if state.modifiers.shift_key()
+                        && matches!(state.mode, Mode::Normal | Mode::Selection) =>
+                {
+                    state.start_transform(Mode::Mirror);
+                }
                 KeyCode::KeyM if state.mode == Mode::Normal => {
                     state.mode = Mode::Move(FuncState::Selection);
                 }
+                KeyCode::KeyD if matches!(state.mode, Mode::Mirror(_)) => {
+                    state.toggle_mirror_source();
+                }
+                KeyCode::KeyS
+                    if state.modifiers.shift_key()
+                        && !state.modifiers.control_key()
+                        && matches!(state.mode, Mode::Normal | Mode::Selection) =>
+                {
+                    state.start_transform(Mode::Scale);
+                }
                 KeyCode::KeyS => {
                     if state.modifiers.control_key() {
-                        let saved = state.save_to_dxf();
-
-                        match saved {
-                            Ok(_) => println!("file saved"),
-                            Err(error) => eprintln!("i/o error while saving file: {}", error),
-                        };
+                        if state.modifiers.shift_key() {
+                            state.save_as();
+                        } else {
+                            state.save();
+                        }
                     } else {
                         if state.mode == Mode::Normal {
                             state.mode = Mode::Selection;
                         }
                     }
                 }
+                KeyCode::KeyO
+                    if !state.modifiers.control_key()
+                        && matches!(state.mode, Mode::Normal | Mode::Selection)
+                        && !state.is_mid_operation() =>
+                {
+                    state.start_offset();
+                }
+                // offsets go through the clicked point again instead of a typed distance
+                KeyCode::KeyT if matches!(state.mode, Mode::Offset(_)) => {
+                    state.offset_distance = None;
+                    state.ui.add_notification("Offset through a point");
+                }
+                KeyCode::KeyR if matches!(state.mode, Mode::Offset(_)) => {
+                    state.offset_round = !state.offset_round;
+                    if state.offset_round {
+                        state.ui.add_notification("Round corners");
+                    } else {
+                        state.ui.add_notification("Sharp corners");
+                    }
+                }
                 KeyCode::KeyO => {
                     if state.modifiers.control_key() {
                         let mut path: String = String::new();
@@ -96,158 +289,192 @@ pub fn handle_input(state: &mut State, event: &WindowEvent) -> bool {
                         state.mode = Mode::DrawLine(DrawLineMode::Ortho);
                     } else if state.mode == Mode::DrawLine(DrawLineMode::Ortho) {
                         state.mode = Mode::DrawLine(DrawLineMode::Normal);
+                    } else if matches!(state.drawing_state, DrawingState::DraggingGrip(_)) {
+                        state.ui.ortho = !state.ui.ortho;
                     }
                 }
+                KeyCode::KeyA
+                    if state.modifiers.shift_key()
+                        && matches!(state.mode, Mode::Normal | Mode::Selection) =>
+                {
+                    state.start_array();
+                }
                 KeyCode::KeyA => {
                     state.mode = Mode::Measure(None);
                 }
                 KeyCode::Delete => {
                     if state.mode == Mode::Normal {
                         state.mode = Mode::Delete;
-                    } else if state.mode == Mode::Selection {
-                        state.lines.retain(|line| !line.selected);
-                        state.circles.retain(|circle| !circle.selected);
-
-                        state.update_instance_buffer();
-                        state.update_circle_instance_buffer();
+                    } else if state.mode == Mode::Selection && !state.is_mid_operation() {
+                        state.delete_selected();
+                    }
+                }
+                // undoing in the middle of an operation would pull the entities out from under it
+                KeyCode::KeyZ if state.modifiers.control_key() && !state.is_mid_operation() => {
+                    if state.modifiers.shift_key() {
+                        state.redo();
+                    } else {
+                        state.undo();
                     }
                 }
+                KeyCode::KeyB => {
+                    state.start_break();
+                }
+                KeyCode::KeyJ => {
+                    state.start_restructure(Mode::Join);
+                }
+                KeyCode::KeyT
+                    if state.modifiers.shift_key()
+                        && matches!(state.mode, Mode::Normal | Mode::Selection) =>
+                {
+                    state.start_stretch();
+                }
                 KeyCode::KeyT if state.mode == Mode::Normal => {
                     state.mode = Mode::CreateText;
                 }
+                // only puts the grip back, the selection stays
+                KeyCode::Escape if matches!(state.drawing_state, DrawingState::DraggingGrip(_)) => {
+                    state.document.cancel_grip_drag();
+                    state.drawing_state = DrawingState::Idle;
+                    state.snap = None;
+                    state.indicators.clear();
+                    state.update_instance_buffers();
+                }
                 KeyCode::Escape => {
                     if state.mode == Mode::DrawLine(DrawLineMode::Normal)
                         || state.mode == Mode::DrawLine(DrawLineMode::Ortho)
                     {
                         if let DrawingState::WaitingForSecondPoint(_start_pos) = state.drawing_state
                         {
-                            state.cancel_drawing_line();
+                            state.document.cancel_drawing_line();
+                            state.drawing_state = DrawingState::Idle;
+                            state.mode = Mode::Normal;
+                            state.update_instance_buffers();
                         }
                     }
 
                     if state.mode == Mode::DrawCircle {
                         if let DrawingState::WaitingForRadius(_start_pos) = state.drawing_state {
-                            state.cancel_drawing_circle();
+                            state.document.cancel_drawing_circle();
+                            state.drawing_state = DrawingState::Idle;
+                            state.mode = Mode::Normal;
+                            state.update_instance_buffers();
                         }
                     }
 
-                    if matches!(
-                        state.mode,
-                        Mode::Selection | Mode::Move(FuncState::Selection)
-                    ) {
-                        if state.lines.iter().any(|line| line.selected) {
-                            state.unselect_lines();
-                        }
-                        if state.circles.iter().any(|circle| circle.selected) {
-                            state.unselect_circles();
-                        }
+                    if let DrawingState::WaitingForArcEnd(..) = state.drawing_state {
+                        state.document.cancel_drawing_arc();
+                        state.update_instance_buffers();
                     }
 
-                    state.mode = Mode::Normal;
-                    state.snap = None;
-                    state.drawing_state = DrawingState::Idle;
-
-                    for indicator in &mut state.indicators {
-                        indicator.vertices[0].position = [0.0, 0.0, 0.0];
-                        indicator.vertices[1].position = [0.0, 0.0, 0.0];
+                    if let DrawingState::WaitingForEllipseOtherAxis(..) = state.drawing_state {
+                        state.document.cancel_drawing_ellipse();
+                        state.update_instance_buffers();
                     }
-                }
-                KeyCode::Enter => {
-                    if matches!(state.mode, Mode::Move(FuncState::Selection)) {
-                        state.mode = Mode::Move(FuncState::SelectPoint);
+
+                    // whatever was clicked so far is kept
+                    if let DrawingState::WaitingForNextVertex = state.drawing_state {
+                        state.document.finish_polyline(false);
+                        state.update_instance_buffers();
                     }
-                    if matches!(state.mode, Mode::Copy(FuncState::Selection)) {
-                        state.mode = Mode::Copy(FuncState::SelectPoint);
+
+                    if let Mode::Move(FuncState::Move(_)) | Mode::Copy(FuncState::Copy(_)) =
+                        state.mode
+                    {
+                        state.cancel_move();
                     }
-                }
-                KeyCode::Digit0 => {
-                    if matches!(
-                        state.drawing_state,
-                        DrawingState::WaitingForSecondPoint(_) | DrawingState::WaitingForRadius(_)
-                    ) {
-                        state.ui.push_digit('0')
+
+                    if state.document.active_transform.is_some() {
+                        state.cancel_transform();
                     }
-                }
-                KeyCode::Digit1 => {
-                    if matches!(
-                        state.drawing_state,
-                        DrawingState::WaitingForSecondPoint(_) | DrawingState::WaitingForRadius(_)
-                    ) {
-                        state.ui.push_digit('1')
+
+                    if let Mode::Array(_) = state.mode {
+                        state.cancel_array();
                     }
-                }
-                KeyCode::Digit2 => {
-                    if matches!(
-                        state.drawing_state,
-                        DrawingState::WaitingForSecondPoint(_) | DrawingState::WaitingForRadius(_)
-                    ) {
-                        state.ui.push_digit('2')
+
+                    if state.document.active_stretch.is_some() {
+                        state.cancel_stretch();
                     }
-                }
-                KeyCode::Digit3 => {
-                    if matches!(
-                        state.drawing_state,
-                        DrawingState::WaitingForSecondPoint(_) | DrawingState::WaitingForRadius(_)
-                    ) {
-                        state.ui.push_digit('3')
+
+                    if state.document.active_shape_id.is_some() {
+                        state.cancel_shape();
                     }
-                }
-                KeyCode::Digit4 => {
+
                     if matches!(
-                        state.drawing_state,
-                        DrawingState::WaitingForSecondPoint(_) | DrawingState::WaitingForRadius(_)
+                        state.mode,
+                        Mode::Selection
+                            | Mode::Move(FuncState::Selection)
+                            | Mode::Trim(TrimState::SelectEdges)
+                            | Mode::Extend(TrimState::SelectEdges)
+                            | Mode::Offset(_)
+                            | Mode::Fillet(_)
+                            | Mode::Chamfer(_)
+                            | Mode::Rotate(_)
+                            | Mode::Scale(_)
+                            | Mode::Mirror(_)
+                            | Mode::Stretch(_)
+                            | Mode::Break(_)
+                            | Mode::Join
+                            | Mode::Explode
+                            | Mode::Overkill
                     ) {
-                        state.ui.push_digit('4')
+                        state.unselect_all();
                     }
+
+                    state.mode = Mode::Normal;
+                    state.snap = None;
+                    state.snap_engine.reset();
+                    state.ui.selection_box = None;
+                    state.trim_edges.clear();
+                    state.drawing_state = DrawingState::Idle;
+                    state.indicators.clear();
                 }
-                KeyCode::Digit5 => {
-                    if matches!(
-                        state.drawing_state,
-                        DrawingState::WaitingForSecondPoint(_) | DrawingState::WaitingForRadius(_)
-                    ) {
-                        state.ui.push_digit('5')
+                KeyCode::Enter => {
+                    if let DrawingState::WaitingForNextVertex = state.drawing_state {
+                        state.document.finish_polyline(false);
+                        state.drawing_state = DrawingState::Idle;
+                        state.update_instance_buffers();
                     }
-                }
-                KeyCode::Digit6 => {
-                    if matches!(
-                        state.drawing_state,
-                        DrawingState::WaitingForSecondPoint(_) | DrawingState::WaitingForRadius(_)
-                    ) {
-                        state.ui.push_digit('6')
+                    if matches!(state.mode, Mode::Move(FuncState::Selection)) {
+                        state.mode = Mode::Move(FuncState::SelectPoint);
                     }
-                }
-                KeyCode::Digit7 => {
-                    if matches!(
-                        state.drawing_state,
-                        DrawingState::WaitingForSecondPoint(_) | DrawingState::WaitingForRadius(_)
-                    ) {
-                        state.ui.push_digit('7')
+                    if matches!(state.mode, Mode::Copy(FuncState::Selection)) {
+                        state.mode = Mode::Copy(FuncState::SelectPoint);
                     }
-                }
-                KeyCode::Digit8 => {
                     if matches!(
-                        state.drawing_state,
-                        DrawingState::WaitingForSecondPoint(_) | DrawingState::WaitingForRadius(_)
+                        state.mode,
+                        Mode::Trim(TrimState::SelectEdges) | Mode::Extend(TrimState::SelectEdges)
                     ) {
-                        state.ui.push_digit('8')
+                        state.finish_edge_selection();
                     }
-                }
-                KeyCode::Digit9 => {
                     if matches!(
-                        state.drawing_state,
-                        DrawingState::WaitingForSecondPoint(_) | DrawingState::WaitingForRadius(_)
+                        state.mode,
+                        Mode::Rotate(TransformState::Selection)
+                            | Mode::Scale(TransformState::Selection)
+                            | Mode::Mirror(TransformState::Selection)
                     ) {
-                        state.ui.push_digit('9')
+                        state.finish_transform_selection();
                     }
-                }
-                KeyCode::Period => {
-                    if matches!(
-                        state.drawing_state,
-                        DrawingState::WaitingForSecondPoint(_) | DrawingState::WaitingForRadius(_)
-                    ) {
-                        state.ui.push_digit('.')
+                    match state.mode {
+                        Mode::Array(ArrayState::Selection) => state.finish_array_selection(),
+                        Mode::Array(ArrayState::Setup) => state.finish_array(),
+                        Mode::Break(BreakState::Second(..)) => state.finish_break(None),
+                        Mode::Join | Mode::Explode => state.finish_restructure(),
+                        Mode::Overkill => state.finish_overkill(),
+                        _ => {}
                     }
                 }
+                KeyCode::Digit0 if takes_numbers(state) => state.ui.push_digit('0'),
+                KeyCode::Digit1 if takes_numbers(state) => state.ui.push_digit('1'),
+                KeyCode::Digit2 if takes_numbers(state) => state.ui.push_digit('2'),
+                KeyCode::Digit3 if takes_numbers(state) => state.ui.push_digit('3'),
+                KeyCode::Digit4 if takes_numbers(state) => state.ui.push_digit('4'),
+                KeyCode::Digit5 if takes_numbers(state) => state.ui.push_digit('5'),
+                KeyCode::Digit6 if takes_numbers(state) => state.ui.push_digit('6'),
+                KeyCode::Digit7 if takes_numbers(state) => state.ui.push_digit('7'),
+                KeyCode::Digit8 if takes_numbers(state) => state.ui.push_digit('8'),
+                KeyCode::Digit9 if takes_numbers(state) => state.ui.push_digit('9'),
+                KeyCode::Period if takes_numbers(state) => state.ui.push_digit('.'),
                 _ => {}
             }
 
@@ -305,7 +532,6 @@ pub fn handle_input(state: &mut State, event: &WindowEvent) -> bool {
 
         // converting cursor position into world space and storing it
         WindowEvent::CursorMoved { position, .. } => {
-            let snap_treshold: f32 = 5.0 / state.camera.zoom;
             let screen = [position.x as f32, position.y as f32];
             state.last_screen_position_for_pan = Some(screen);
 
@@ -313,6 +539,9 @@ pub fn handle_input(state: &mut State, event: &WindowEvent) -> bool {
 
             state.cursor_position = Some(world);
             state.ui.cursor_position = Some(world);
+            if let Some(selection) = &mut state.ui.selection_box {
+                selection.end = world;
+            }
 
             let cen_x = screen[0] - state.size.width as f32 / 2.0;
             let cen_y = state.size.height as f32 / 2.0 - screen[1];
@@ -323,85 +552,101 @@ pub fn handle_input(state: &mut State, event: &WindowEvent) -> bool {
                 state.mode,
                 Mode::DrawLine(_)
                     | Mode::DrawCircle
+                    | Mode::DrawArc(_)
+                    | Mode::DrawEllipse(_)
+                    | Mode::DrawPolyline(_)
+                    | Mode::DrawRectangle(_)
+                    | Mode::DrawPolygon(_)
                     | Mode::Copy(_)
                     | Mode::Move(_)
                     | Mode::Measure(_)
-            ) {
-                state.snap = None;
-
-                for line in &mut state.lines {
-                    if !line.is_drawing {
-                        for vertex in line.vertices {
-                            let x = vertex.position[0];
-                            let y = vertex.position[1];
-
-                            let diffx = x - world[0];
-                            let diffy = y - world[1];
-
-                            if diffx.abs() < snap_treshold && diffy.abs() < snap_treshold {
-                                state.snap = Some([x, y]);
-                                break;
-                            }
-                        }
-                    }
-                }
-
-                let circle_snap_vertexes = flatten_circles_for_snap(&mut state.circles);
-                for vertex in circle_snap_vertexes {
-                    let x = vertex.position[0];
-                    let y = vertex.position[1];
-
-                    let diffx = x - world[0];
-                    let diffy = y - world[1];
-
-                    if diffx.abs() < snap_treshold && diffy.abs() < snap_treshold {
-                        state.snap = Some([x, y]);
-                        break;
-                    }
-                }
-
-                match state.snap {
-                    Some(_vertex) => {
-                        // there's a bug if you zoom out while there's snap turned on, should dissapear once i figure how to reset snap
-                        state.move_indicators_to_cursor([world[0], world[1]]);
-                        state.update_axis_vertex_buffer();
-                    }
-                    None => {
-                        for indicator in &mut state.indicators {
-                            indicator.vertices[0].position = [0.0, 0.0, 0.0];
-                            indicator.vertices[1].position = [0.0, 0.0, 0.0];
-                        }
-                    }
-                }
+                    | Mode::Rotate(_)
+                    | Mode::Scale(_)
+                    | Mode::Mirror(_)
+                    | Mode::Array(ArrayState::Setup)
+                    | Mode::Stretch(StretchState::BasePoint(_) | StretchState::Displace(..))
+                    | Mode::Break(_)
+            ) || matches!(state.drawing_state, DrawingState::DraggingGrip(_))
+            {
+                let last = last_point(state);
+                let snap = state.snap_engine.find(
+                    &mut state.document,
+                    world,
+                    APERTURE / state.camera.zoom,
+                    last,
+                    state.ui.snap_modes,
+                );
+                state.snap = snap.map(|snap| snap.point);
+                state.show_snap_marker(snap);
             }
 
             if let DrawingState::WaitingForSecondPoint(_start_pos) = state.drawing_state {
-                state.update_line([world[0], world[1]], true);
+                let ortho = state.mode == Mode::DrawLine(DrawLineMode::Ortho);
+                let cursor = state.snap.unwrap_or(world);
+                state.document.update_line(cursor, ortho, true);
+                state.update_instance_buffers();
             }
             if let DrawingState::WaitingForRadius(_start_pos) = state.drawing_state {
-                state.update_circle([world[0], world[1]], true);
+                let cursor = state.snap.unwrap_or(world);
+                state.document.update_circle(cursor, true);
+                state.update_instance_buffers();
+            }
+            if let (DrawingState::WaitingForArcEnd(first, second), Mode::DrawArc(arc_mode)) =
+                (&state.drawing_state, &state.mode)
+            {
+                let cursor = state.snap.unwrap_or(world);
+                if let Some(shape) = arc_preview(*arc_mode, *first, *second, cursor) {
+                    state.document.update_arc(shape, true);
+                    state.update_instance_buffers();
+                }
+            }
+            if let DrawingState::WaitingForEllipseOtherAxis(..) = state.drawing_state {
+                let cursor = state.snap.unwrap_or(world);
+                state.preview_ellipse(cursor);
+            }
+            if let DrawingState::DraggingGrip(anchor) = state.drawing_state {
+                let target = grip_target(state, anchor, world);
+                state.document.drag_grip(target);
+                state.update_instance_buffers();
+            }
+            if let DrawingState::WaitingForNextVertex = state.drawing_state {
+                let arc_segment = state.mode == Mode::DrawPolyline(DrawPolylineMode::Arc);
+                let cursor = state.snap.unwrap_or(world);
+                state.document.update_polyline(cursor, arc_segment);
+                state.update_instance_buffers();
+            }
+            if let Mode::Rotate(TransformState::Target(base, _))
+            | Mode::Scale(TransformState::Target(base, _))
+            | Mode::Mirror(TransformState::Target(base, _)) = state.mode
+            {
+                let target = grip_target(state, base, world);
+                state.preview_transform(target);
+            }
+            if let Mode::Stretch(StretchState::Displace(_, base)) = state.mode {
+                let target = grip_target(state, base, world);
+                state.preview_stretch(target);
+            }
+            if let Mode::DrawRectangle(Some(_)) | Mode::DrawPolygon(Some(_)) = state.mode {
+                let cursor = state.snap.unwrap_or(world);
+                state.preview_shape(cursor);
             }
             if let Mode::Move(FuncState::Move(starting_position))
             | Mode::Copy(FuncState::Copy(starting_position)) = state.mode
             {
-                let diff1 = starting_position[0] - world[0];
-                let diff2 = starting_position[1] - world[1];
+                let dx = world[0] - starting_position[0];
+                let dy = world[1] - starting_position[1];
 
-                for line in &mut state.lines {
-                    if line.selected {
-                        line.move_line(diff1, diff2);
-                    }
-                }
-                for circle in &mut state.circles {
-                    if circle.selected {
-                        circle.move_circle(diff1, diff2);
-                    }
+                for entity in state.document.entities.filter_mut(Entity::selected) {
+                    entity.translate(dx, dy);
                 }
 
-                state.update_instance_buffer();
-                state.update_circle_instance_buffer();
+                state.update_instance_buffers();
 
-                state.mode = Mode::Move(FuncState::Move([world[0], world[1]]));
+                if matches!(state.mode, Mode::Move(_)) {
+                    state.mode = Mode::Move(FuncState::Move([world[0], world[1]]));
+                } else {
+                    state.mode = Mode::Copy(FuncState::Copy([world[0], world[1]]));
+                }
             }
 
             true
@@ -416,6 +661,8 @@ pub fn handle_input(state: &mut State, event: &WindowEvent) -> bool {
             state.mode,
             Mode::DrawCircle
                 | Mode::DrawLine(_)
+                | Mode::DrawArc(_)
+                | Mode::DrawPolyline(_)
                 | Mode::Move(FuncState::SelectPoint)
                 | Mode::Move(FuncState::Move(_))
                 | Mode::Copy(FuncState::SelectPoint)
@@ -437,7 +684,21 @@ pub fn handle_input(state: &mut State, event: &WindowEvent) -> bool {
 
                             state.drawing_state =
                                 DrawingState::WaitingForSecondPoint(snap_or_position);
-                            state.add_line(snap_or_position, snap_or_position, true);
+                            state
+                                .document
+                                .add_line(snap_or_position, snap_or_position, true);
+                            state.update_instance_buffers();
+                        }
+                        Mode::DrawArc(_) => {
+                            let snap_or_position = state.snap.unwrap_or(position);
+                            state.drawing_state =
+                                DrawingState::WaitingForArcSecondPoint(snap_or_position);
+                        }
+                        Mode::DrawPolyline(_) => {
+                            let snap_or_position = state.snap.unwrap_or(position);
+                            state.document.start_polyline(snap_or_position);
+                            state.update_instance_buffers();
+                            state.drawing_state = DrawingState::WaitingForNextVertex;
                         }
                         Mode::DrawCircle => {
                             let mut snap_or_position: [f32; 2] = position;
@@ -447,14 +708,10 @@ pub fn handle_input(state: &mut State, event: &WindowEvent) -> bool {
                             }
 
                             state.drawing_state = DrawingState::WaitingForRadius(snap_or_position);
-                            state.add_circle(
-                                snap_or_position,
-                                0.0,
-                                [1.0, 1.0, 1.0],
-                                false,
-                                false,
-                                true,
-                            );
+                            state
+                                .document
+                                .add_circle(snap_or_position, 0.0, false, false, true);
+                            state.update_instance_buffers();
                         }
                         Mode::Measure(first_pos) => {
                             let snap_or_pos = state.snap.unwrap_or(position);
@@ -476,8 +733,7 @@ pub fn handle_input(state: &mut State, event: &WindowEvent) -> bool {
                         }
                         // move lines from this selection point
                         Mode::Move(FuncState::SelectPoint) | Mode::Copy(FuncState::SelectPoint) => {
-                            let mut new_lines = Vec::new();
-                            let mut new_circles = Vec::new();
+                            let mut new_entities = Vec::new();
 
                             let pos: [f32; 2];
                             if let Some(snap_pos) = state.snap {
@@ -492,100 +748,129 @@ pub fn handle_input(state: &mut State, event: &WindowEvent) -> bool {
                                 state.mode = Mode::Copy(FuncState::Copy(pos));
                             }
 
-                            for line in &mut state.lines {
-                                if line.selected {
-                                    let mut new_line = *line;
-                                    line.selected = false;
-                                    line.del = matches!(state.mode, Mode::Move(_));
+                            for entity in state.document.entities.filter_mut(Entity::selected) {
+                                let mut new_entity = entity.clone();
+                                entity.set_selected(false);
+                                entity.set_del(matches!(state.mode, Mode::Move(_)));
 
-                                    new_line.is_drawing = true;
-                                    new_lines.push(new_line);
-                                }
-                            }
-                            for circle in &mut state.circles {
-                                if circle.selected {
-                                    let mut new_circle = *circle;
-                                    circle.selected = false;
-                                    circle.del = matches!(state.mode, Mode::Move(_));
-
-                                    new_circle.is_drawing = true;
-                                    new_circles.push(new_circle);
-                                }
+                                new_entity.set_drawing(true);
+                                new_entities.push(new_entity);
                             }
 
-                            for new_line in new_lines {
-                                state.lines.push(new_line);
-                            }
-                            for new_circle in new_circles {
-                                state.add_circle(
-                                    [new_circle.center.position[0], new_circle.center.position[1]],
-                                    new_circle.radius,
-                                    new_circle.center.color,
-                                    new_circle.selected,
-                                    new_circle.del,
-                                    new_circle.is_drawing,
-                                );
+                            // the copies get their own ids, they only become real entities on a copy
+                            for mut new_entity in new_entities {
+                                let id = state.document.next_id();
+                                new_entity.set_id(id);
+                                state.document.entities.insert(id, new_entity);
                             }
+
+                            state.update_instance_buffers();
                         }
                         // second click: move the selected objects "HERE"
                         Mode::Move(FuncState::Move(starting_position))
                         | Mode::Copy(FuncState::Copy(starting_position)) => {
-                            let diff1: f32;
-                            let diff2: f32;
-
-                            if let Some(snap_pos) = state.snap {
-                                diff1 = starting_position[0] - snap_pos[0];
-                                diff2 = starting_position[1] - snap_pos[1];
-                            } else {
-                                diff1 = starting_position[0] - position[0];
-                                diff2 = starting_position[1] - position[1];
+                            let end = state.snap.unwrap_or(position);
+                            let dx = end[0] - starting_position[0];
+                            let dy = end[1] - starting_position[1];
+
+                            let is_move = matches!(state.mode, Mode::Move(_));
+                            let mut changes = Vec::new();
+
+                            let mut previews: Vec<Entity> = state
+                                .document
+                                .entities
+                                .iter()
+                                .filter(|entity| entity.selected() && entity.is_drawing())
+                                .cloned()
+                                .collect();
+
+                            for preview in &mut previews {
+                                preview.translate(dx, dy);
+                                preview.set_selected(false);
+                                preview.set_drawing(false);
                             }
 
-                            for line in &mut state.lines {
-                                if line.selected {
-                                    line.move_line(diff1, diff2);
-
-                                    line.selected = false;
-                                    line.is_drawing = false;
+                            if is_move {
+                                // a move keeps the ids: the originals take over the position of
+                                // their preview (both were created in the same order) and the previews go
+                                let originals: Vec<Entity> = state
+                                    .document
+                                    .entities
+                                    .iter()
+                                    .filter(|entity| entity.del())
+                                    .cloned()
+                                    .collect();
+                                for (original, preview) in originals.into_iter().zip(previews) {
+                                    let mut moved = preview;
+                                    state.document.entities.remove(moved.id());
+                                    moved.set_id(original.id());
+                                    state.document.entities.insert(moved.id(), moved.clone());
+                                    changes.push(Change::Modify(original, moved));
+                                }
+                            } else {
+                                for preview in previews {
+                                    state
+                                        .document
+                                        .entities
+                                        .insert(preview.id(), preview.clone());
+                                    changes.push(Change::Add(preview));
                                 }
                             }
-                            for circle in &mut state.circles {
-                                if circle.selected {
-                                    circle.move_circle(diff1, diff2);
 
-                                    circle.selected = false;
-                                    circle.is_drawing = false;
+                            // texts have no preview, they go over in one step
+                            let texts: Vec<Text> = state
+                                .document
+                                .texts
+                                .iter()
+                                .filter(|text| text.selected)
+                                .cloned()
+                                .collect();
+                            for text in texts {
+                                let mut moved = text.clone();
+                                moved.position = [text.position[0] + dx, text.position[1] + dy];
+                                moved.selected = false;
+                                if is_move {
+                                    if let Some(existing) =
+                                        state.document.texts.iter_mut().find(|t| t.id == text.id)
+                                    {
+                                        *existing = moved.clone();
+                                    }
+                                    changes.push(Change::ModifyText(text, moved));
+                                } else {
+                                    moved.id = state.document.next_id();
+                                    state.document.texts.push(moved.clone());
+                                    changes.push(Change::AddText(moved));
                                 }
                             }
+                            state.document.unselect_all();
 
-                            // todo
-                            state.lines.retain(|line: &Line| !line.del);
-                            state.circles.retain(|circle: &Circle| !circle.del);
+                            let name = if is_move { "Move" } else { "Copy" };
+                            state.document.record(name, changes);
 
-                            state.update_instance_buffer();
-                            // state.update_circle_vertex_buffer();
-                            state.update_circle_instance_buffer();
+                            state.update_instance_buffers();
 
                             state.mode = Mode::Normal;
 
-                            for indicator in &mut state.indicators {
-                                indicator.vertices[0].position = [0.0, 0.0, 0.0];
-                                indicator.vertices[1].position = [0.0, 0.0, 0.0];
-                            }
+                            state.indicators.clear();
                         }
                         Mode::CreateText => {
                             // create a new text object
                             let snap_or_pos = state.snap.unwrap_or(position);
-                            state.ui.texts.push(Text {
+                            let id = state.document.next_id();
+                            state.document.texts.push(Text {
+                                id,
                                 position: snap_or_pos,
                                 contents: egui::WidgetText::from("Text"),
                                 rect: None,
                                 editing: true,
+                                selected: false,
                                 annotative: false,
+                                layer: state.document.current_layer,
                             });
                             state.ui.mode = UiMode::TextEdit;
                             state.ui.text_edited.contents = String::from("Text");
                             state.ui.text_edited.annotative = false;
+                            state.ui.text_edited.original = None;
                             state.mode = Mode::Normal;
                         }
                         _ => {}
@@ -599,9 +884,9 @@ pub fn handle_input(state: &mut State, event: &WindowEvent) -> bool {
                                 snap_or_position = snap_positions;
                             }
 
-                            // println!("{:?}", state.lines);
-                            state.update_line(snap_or_position, false);
-                            // println!("{:?}", state.lines);
+                            let ortho = state.mode == Mode::DrawLine(DrawLineMode::Ortho);
+                            state.document.update_line(snap_or_position, ortho, false);
+                            state.update_instance_buffers();
                             state.drawing_state = DrawingState::Idle;
                         }
                         _ => {}
@@ -614,10 +899,52 @@ pub fn handle_input(state: &mut State, event: &WindowEvent) -> bool {
                                 snap_or_position = snap_positions;
                             }
 
-                            state.update_circle(snap_or_position, false);
+                            state.document.update_circle(snap_or_position, false);
+                            state.update_instance_buffers();
                             state.drawing_state = DrawingState::Idle;
                         }
                     }
+                    DrawingState::WaitingForArcSecondPoint(first) => {
+                        if let Mode::DrawArc(arc_mode) = state.mode {
+                            let second = state.snap.unwrap_or(position);
+
+                            // nothing sensible to show until the cursor moves off the second point
+                            let placeholder = match arc_mode {
+                                DrawArcMode::ThreePoint => Arc::new(first, 0.0, 0.0, 0.0),
+                                DrawArcMode::CenterStartEnd => {
+                                    Arc::from_center(first, second, second)
+                                }
+                            };
+                            state.document.add_arc(placeholder, true);
+                            state.update_instance_buffers();
+                            state.drawing_state = DrawingState::WaitingForArcEnd(first, second);
+                        }
+                    }
+                    DrawingState::WaitingForArcEnd(first, second) => {
+                        if let Mode::DrawArc(arc_mode) = state.mode {
+                            let end = state.snap.unwrap_or(position);
+
+                            // three points on a line don't make an arc, wait for a better click
+                            if let Some(shape) = arc_preview(arc_mode, first, second, end) {
+                                state.document.update_arc(shape, false);
+                                state.update_instance_buffers();
+                                state.drawing_state = DrawingState::Idle;
+                            }
+                        }
+                    }
+                    DrawingState::WaitingForNextVertex => {
+                        let arc_segment = state.mode == Mode::DrawPolyline(DrawPolylineMode::Arc);
+                        let snap_or_position = state.snap.unwrap_or(position);
+                        state
+                            .document
+                            .add_polyline_vertex(snap_or_position, arc_segment);
+                        state.update_instance_buffers();
+                    }
+                    // grips are only picked up outside of the drawing modes, ellipses have their
+                    // own clicks
+                    DrawingState::DraggingGrip(_)
+                    | DrawingState::WaitingForEllipseAxis(_)
+                    | DrawingState::WaitingForEllipseOtherAxis(..) => {}
                 }
             }
             true
@@ -635,53 +962,367 @@ pub fn handle_input(state: &mut State, event: &WindowEvent) -> bool {
                 | Mode::Selection
                 | Mode::Move(FuncState::Selection)
                 | Mode::Copy(FuncState::Selection)
+                | Mode::Trim(TrimState::SelectEdges)
+                | Mode::Extend(TrimState::SelectEdges)
+                | Mode::Rotate(TransformState::Selection)
+                | Mode::Scale(TransformState::Selection)
+                | Mode::Mirror(TransformState::Selection)
+                | Mode::Array(ArrayState::Selection)
+                | Mode::Join
+                | Mode::Explode
+                | Mode::Overkill
                 | Mode::Delete
         ) =>
         {
             if let Some(position) = state.cursor_position {
-                let mut update: bool = false;
+                // a grip that's being dragged is put down with the next click
+                if let DrawingState::DraggingGrip(anchor) = state.drawing_state {
+                    let target = grip_target(state, anchor, position);
+                    state.document.drag_grip(target);
+                    state.document.finish_grip_drag();
+                    state.drawing_state = DrawingState::Idle;
+                    state.update_instance_buffers();
+                    return true;
+                }
+
+                if matches!(state.mode, Mode::Normal | Mode::Selection)
+                    && !state.modifiers.shift_key()
+                {
+                    let radius = GRIP_SIZE / state.camera.zoom;
+                    if let Some((id, index)) = state.document.grip_at(position, radius) {
+                        if let Some(anchor) = state.document.start_grip_drag(id, index) {
+                            state.drawing_state = DrawingState::DraggingGrip(anchor);
+                            state.update_instance_buffers();
+                        }
+                        return true;
+                    }
+                }
 
                 let treshold = 5.0 / state.camera.zoom;
 
-                for line in &mut state.lines {
-                    let a = line.vertices[0].position;
-                    let b = line.vertices[1].position;
+                // the index narrows it down to what's close, the exact distance decides
+                let hits: Vec<u64> = state
+                    .document
+                    .entities_near(position, treshold)
+                    .into_iter()
+                    .filter(|id| {
+                        state.document.entities.get(*id).is_some_and(|entity| {
+                            // hidden, frozen and locked layers can't be picked from
+                            state.document.layers.is_selectable(entity.layer())
+                                && entity.hit_distance(position) < treshold
+                        })
+                    })
+                    .collect();
+
+                let texts = if hits.is_empty() {
+                    texts_at(state, position)
+                } else {
+                    Vec::new()
+                };
+
+                // clicking into empty space starts a selection box
+                if hits.is_empty() && texts.is_empty() {
+                    state.ui.selection_box = Some(SelectionBox::new(position));
+                } else {
+                    change_selection(state, &hits, &texts);
+                }
+            }
+            true
+        }
 
-                    let d =
-                        point_segment_distance(position[0], position[1], a[0], a[1], b[0], b[1]);
+        // trims or extends whatever is clicked
+        WindowEvent::MouseInput {
+            state: ElementState::Pressed,
+            button: MouseButton::Left,
+            ..
+        } if matches!(
+            state.mode,
+            Mode::Trim(TrimState::Pick) | Mode::Extend(TrimState::Pick)
+        ) =>
+        {
+            if let Some(position) = state.cursor_position {
+                if let Some(id) = entity_at(state, position) {
+                    let edges = state.trim_edges.clone();
+                    let changed = if let Mode::Extend(_) = state.mode {
+                        state.document.extend(id, position, &edges)
+                    } else {
+                        state.document.trim(id, position, &edges)
+                    };
 
-                    if d < treshold && !line.selected {
-                        if !matches!(state.mode, Mode::Move(_) | Mode::Copy(_) | Mode::Delete) {
-                            state.mode = Mode::Selection;
+                    if changed {
+                        state.update_instance_buffers();
+                    } else if let Mode::Extend(_) = state.mode {
+                        state.ui.add_notification("No edge to extend to");
+                    } else {
+                        state.ui.add_notification("No edge to cut at");
+                    }
+                }
+            }
+            true
+        }
+
+        // picks what to offset, then the side the copy goes on
+        WindowEvent::MouseInput {
+            state: ElementState::Pressed,
+            button: MouseButton::Left,
+            ..
+        } if matches!(state.mode, Mode::Offset(_)) => {
+            if let Some(position) = state.cursor_position {
+                match state.mode {
+                    Mode::Offset(OffsetState::Side(id)) => {
+                        let distance = state.offset_distance;
+                        let round = state.offset_round;
+                        if state
+                            .document
+                            .offset(id, distance, position, round)
+                            .is_none()
+                        {
+                            state.ui.add_notification("Can't offset that way");
+                        }
+                        state.document.set_selected(&[id], false);
+                        state.mode = Mode::Offset(OffsetState::Pick);
+                        state.update_instance_buffers();
+                    }
+                    _ => {
+                        if let Some(id) = entity_at(state, position) {
+                            state.document.set_selected(&[id], true);
+                            state.mode = Mode::Offset(OffsetState::Side(id));
+                            state.update_instance_buffers();
                         }
-                        line.selected = true;
-                        update = true;
                     }
                 }
+            }
+            true
+        }
+
+        // the first line or segment is held on to until the second one is picked
+        WindowEvent::MouseInput {
+            state: ElementState::Pressed,
+            button: MouseButton::Left,
+            ..
+        } if matches!(state.mode, Mode::Fillet(_) | Mode::Chamfer(_)) => {
+            let Some(position) = state.cursor_position else {
+                return true;
+            };
+            let Some(id) = entity_at(state, position) else {
+                return true;
+            };
 
-                for circle in &mut state.circles {
-                    let [cx, cy, ..] = circle.center.position;
-                    let rad = circle.radius;
+            let chamfer = matches!(state.mode, Mode::Chamfer(_));
+            let next = |corner: CornerState| {
+                if chamfer {
+                    Mode::Chamfer(corner)
+                } else {
+                    Mode::Fillet(corner)
+                }
+            };
 
-                    let d = circle_hit(position[0], position[1], cx, cy, rad);
+            match state.mode {
+                Mode::Fillet(CornerState::Second(first, picked))
+                | Mode::Chamfer(CornerState::Second(first, picked)) => {
+                    let done = if chamfer {
+                        let size = state.chamfer;
+                        state
+                            .document
+                            .chamfer((first, picked), (id, position), size)
+                    } else {
+                        let radius = state.fillet_radius;
+                        state
+                            .document
+                            .fillet((first, picked), (id, position), radius)
+                    };
+                    if !done {
+                        state.ui.add_notification(
+                            "Doesn't fit, pick two lines or neighbouring segments",
+                        );
+                    }
+                    state.document.set_selected(&[first], false);
+                    state.mode = next(CornerState::First);
+                }
+                _ => {
+                    state.document.set_selected(&[id], true);
+                    state.mode = next(CornerState::Second(id, position));
+                }
+            }
+            state.update_instance_buffers();
+            true
+        }
 
-                    if d < treshold && !circle.selected {
-                        if !matches!(state.mode, Mode::Move(_) | Mode::Copy(_) | Mode::Delete) {
-                            state.mode = Mode::Selection;
-                        }
-                        circle.selected = true;
-                        update = true;
+        // rotate, scale and mirror take the base point, a reference if asked for, then the target
+        WindowEvent::MouseInput {
+            state: ElementState::Pressed,
+            button: MouseButton::Left,
+            ..
+        } if matches!(
+            state.mode,
+            Mode::Rotate(_) | Mode::Scale(_) | Mode::Mirror(_)
+        ) =>
+        {
+            if let Some(position) = state.cursor_position {
+                match state.mode {
+                    Mode::Rotate(TransformState::BasePoint)
+                    | Mode::Scale(TransformState::BasePoint)
+                    | Mode::Mirror(TransformState::BasePoint) => {
+                        let base = state.snap.unwrap_or(position);
+                        state.set_transform_base(base);
+                    }
+                    Mode::Rotate(TransformState::Reference(base))
+                    | Mode::Scale(TransformState::Reference(base)) => {
+                        let point = grip_target(state, base, position);
+                        state.set_reference(point);
+                    }
+                    Mode::Rotate(TransformState::Target(base, _))
+                    | Mode::Scale(TransformState::Target(base, _))
+                    | Mode::Mirror(TransformState::Target(base, _)) => {
+                        let point = grip_target(state, base, position);
+                        state.apply_transform(point);
                     }
+                    _ => {}
                 }
+            }
+            true
+        }
 
-                if update {
-                    if state.mode == Mode::Delete {
-                        state.lines.retain(|line| !line.selected);
-                        state.circles.retain(|circle| !circle.selected);
+        // the stretch window is always a crossing box, whichever way it's dragged
+        WindowEvent::MouseInput {
+            state: ElementState::Pressed,
+            button: MouseButton::Left,
+            ..
+        } if matches!(state.mode, Mode::Stretch(_)) => {
+            if let Some(position) = state.cursor_position {
+                match state.mode {
+                    Mode::Stretch(StretchState::Window) => {
+                        state.ui.selection_box = Some(SelectionBox::new(position));
                     }
-                    state.update_instance_buffer();
-                    // state.update_circle_vertex_buffer();
-                    state.update_circle_instance_buffer();
+                    Mode::Stretch(StretchState::BasePoint(_)) => {
+                        let base = state.snap.unwrap_or(position);
+                        state.set_stretch_base(base);
+                    }
+                    Mode::Stretch(StretchState::Displace(_, base)) => {
+                        let point = grip_target(state, base, position);
+                        state.finish_stretch(point);
+                    }
+                    _ => {}
+                }
+            }
+            true
+        }
+
+        // the first axis, then how far out the other one goes
+        WindowEvent::MouseInput {
+            state: ElementState::Pressed,
+            button: MouseButton::Left,
+            ..
+        } if matches!(state.mode, Mode::DrawEllipse(_)) => {
+            if let Some(position) = state.cursor_position {
+                let point = state.snap.unwrap_or(position);
+                state.place_ellipse_point(point);
+            }
+            true
+        }
+
+        // the first corner or the center, then where the shape ends
+        WindowEvent::MouseInput {
+            state: ElementState::Pressed,
+            button: MouseButton::Left,
+            ..
+        } if matches!(state.mode, Mode::DrawRectangle(_) | Mode::DrawPolygon(_)) => {
+            if let Some(position) = state.cursor_position {
+                let point = state.snap.unwrap_or(position);
+                state.place_shape_point(point);
+            }
+            true
+        }
+
+        // picks what to break and where, then the second point
+        WindowEvent::MouseInput {
+            state: ElementState::Pressed,
+            button: MouseButton::Left,
+            ..
+        } if matches!(state.mode, Mode::Break(_)) => {
+            if let Some(position) = state.cursor_position {
+                let point = state.snap.unwrap_or(position);
+                match state.mode {
+                    Mode::Break(BreakState::Pick) => {
+                        if let Some(id) = entity_at(state, position) {
+                            state.pick_break(id, point);
+                        }
+                    }
+                    Mode::Break(BreakState::Second(..)) => state.finish_break(Some(point)),
+                    _ => {}
+                }
+            }
+            true
+        }
+
+        // while an array is set up a click places the polar center or picks the path
+        WindowEvent::MouseInput {
+            state: ElementState::Pressed,
+            button: MouseButton::Left,
+            ..
+        } if state.mode == Mode::Array(ArrayState::Setup) => {
+            if let Some(position) = state.cursor_position {
+                let entity = entity_at(state, position);
+                state.pick_for_array(position, entity);
+            }
+            true
+        }
+
+        // letting go of a grip away from where it was picked up puts it down, otherwise it
+        // keeps following the cursor until the next click
+        WindowEvent::MouseInput {
+            state: ElementState::Released,
+            button: MouseButton::Left,
+            ..
+        } if matches!(state.drawing_state, DrawingState::DraggingGrip(_)) => {
+            let picked_up = state.document.active_grip.as_ref().and_then(|drag| {
+                drag.original
+                    .grips()
+                    .get(drag.index)
+                    .map(|grip| grip.position)
+            });
+
+            if let (Some(position), Some(picked_up), DrawingState::DraggingGrip(anchor)) =
+                (state.cursor_position, picked_up, &state.drawing_state)
+            {
+                if distance(position, picked_up) * state.camera.zoom > GRIP_SIZE {
+                    let target = grip_target(state, *anchor, position);
+                    state.document.drag_grip(target);
+                    state.document.finish_grip_drag();
+                    state.drawing_state = DrawingState::Idle;
+                    state.update_instance_buffers();
+                }
+            }
+            true
+        }
+
+        WindowEvent::MouseInput {
+            state: ElementState::Released,
+            button: MouseButton::Left,
+            ..
+        } if state.ui.selection_box.is_some() => {
+            if let Some(selection) = state.ui.selection_box.take() {
+                // letting go without dragging was just a click into empty space
+                let dragged = distance(selection.start, selection.end) * state.camera.zoom;
+                if dragged >= 3.0 && state.mode == Mode::Stretch(StretchState::Window) {
+                    let crossing = if selection.crossing() {
+                        selection
+                    } else {
+                        SelectionBox {
+                            start: selection.end,
+                            end: selection.start,
+                        }
+                    };
+                    let ids = state.document.entities_in_box(crossing);
+                    let texts = state.document.texts_in_box(crossing);
+                    state.document.set_selected(&ids, true);
+                    state.document.set_texts_selected(&texts, true);
+                    state.update_instance_buffers();
+                    state.set_stretch_window(crossing.area());
+                } else if dragged >= 3.0 {
+                    let ids = state.document.entities_in_box(selection);
+                    let texts = state.document.texts_in_box(selection);
+                    change_selection(state, &ids, &texts);
                 }
             }
             true
@@ -697,7 +1338,7 @@ pub fn handle_input(state: &mut State, event: &WindowEvent) -> bool {
                 let viewport_rect = state.ui.viewport_rect();
                 let pixels_per_point = state.ui.pixels_per_point();
 
-                for text in &mut state.ui.texts {
+                for text in &mut state.document.texts {
                     if let Some(rect) = text.rect {
                         let pos2_position = world_to_screen(
                             position[0],
@@ -711,6 +1352,7 @@ pub fn handle_input(state: &mut State, event: &WindowEvent) -> bool {
                             state.ui.mode = UiMode::TextEdit;
                             state.ui.text_edited.contents = text.contents.text().to_string();
                             state.ui.text_edited.annotative = text.annotative;
+                            state.ui.text_edited.original = Some(text.clone());
                             text.editing = true;
                             // make it quit the loop
                             return true;
@@ -762,36 +1404,17 @@ pub fn handle_input(state: &mut State, event: &WindowEvent) -> bool {
 
 // helper functions
 
-fn point_segment_distance(px: f32, py: f32, ax: f32, ay: f32, bx: f32, by: f32) -> f32 {
-    let abx: f32 = bx - ax;
-    let aby: f32 = by - ay;
-
-    // handle zero length segment
-    let ab_len_squared = abx * abx + aby * aby;
-    if ab_len_squared == 0.0 {
-        let dx: f32 = px - ax;
-        let dy: f32 = py - ay;
-        return (dx * dx + dy * dy).sqrt();
+// the arc that clicking at `cursor` would create
+fn arc_preview(
+    mode: DrawArcMode,
+    first: [f32; 2],
+    second: [f32; 2],
+    cursor: [f32; 2],
+) -> Option<Arc> {
+    match mode {
+        DrawArcMode::ThreePoint => Arc::through_points(first, second, cursor),
+        DrawArcMode::CenterStartEnd => Some(Arc::from_center(first, second, cursor)),
     }
-
-    let t = ((px - ax) * abx + (py - ay) * aby) / ab_len_squared;
-
-    let t = t.clamp(0.0, 1.0);
-
-    let cx: f32 = ax + t * abx;
-    let cy: f32 = ay + t * aby;
-
-    let dx: f32 = px - cx;
-    let dy: f32 = py - cy;
-    (dx * dx + dy * dy).sqrt()
-}
-
-fn circle_hit(px: f32, py: f32, cx: f32, cy: f32, r: f32) -> f32 {
-    let dx = px - cx;
-    let dy = py - cy;
-    let dist = (dx * dx + dy * dy).sqrt();
-
-    (dist - r).abs()
 }
 
 pub fn screen_to_world(
@@ -826,3 +1449,161 @@ pub fn world_to_screen(
 
     egui::pos2(screen_x, screen_y)
 }
+
+// whether typed digits go into the numeric input
+fn takes_numbers(state: &State) -> bool {
+    matches!(
+        state.drawing_state,
+        DrawingState::WaitingForSecondPoint(_)
+            | DrawingState::WaitingForRadius(_)
+            | DrawingState::WaitingForEllipseOtherAxis(..)
+            | DrawingState::DraggingGrip(_)
+    ) || matches!(
+        state.mode,
+        Mode::Offset(_)
+            | Mode::Fillet(_)
+            | Mode::Chamfer(_)
+            | Mode::Overkill
+            | Mode::DrawRectangle(_)
+            | Mode::DrawPolygon(_)
+            | Mode::Rotate(TransformState::Reference(_) | TransformState::Target(..))
+            | Mode::Scale(TransformState::Reference(_) | TransformState::Target(..))
+    )
+}
+
+// texts painted over `position`, picked by where they showed up on screen
+fn texts_at(state: &State, position: [f32; 2]) -> Vec<u64> {
+    let screen = world_to_screen(
+        position[0],
+        position[1],
+        state.ui.viewport_rect(),
+        &state.camera,
+        state.ui.pixels_per_point(),
+    );
+
+    state
+        .document
+        .texts
+        .iter()
+        .filter(|text| text.rect.is_some_and(|rect| rect.contains(screen)))
+        .filter(|text| state.document.layers.is_selectable(text.layer))
+        .map(|text| text.id)
+        .collect()
+}
+
+// the pickable entity closest to `position`, if any is within a few pixels
+fn entity_at(state: &mut State, position: [f32; 2]) -> Option<u64> {
+    let treshold = 5.0 / state.camera.zoom;
+
+    state
+        .document
+        .entities_near(position, treshold)
+        .into_iter()
+        .filter_map(|id| {
+            let entity = state.document.entities.get(id)?;
+            let hit = entity.hit_distance(position);
+            (state.document.layers.is_selectable(entity.layer()) && hit < treshold)
+                .then_some((id, hit))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(id, _)| id)
+}
+
+// Selects what was picked or, holding shift, takes it out of the selection again. In delete mode
+// it's gone right away. Texts can't be trim or extend edges.
+fn change_selection(state: &mut State, ids: &[u64], texts: &[u64]) {
+    let texts = if matches!(state.mode, Mode::Trim(_) | Mode::Extend(_)) {
+        &[]
+    } else {
+        texts
+    };
+    if ids.is_empty() && texts.is_empty() {
+        return;
+    }
+
+    if state.mode == Mode::Delete {
+        state.document.set_selected(ids, true);
+        state.document.set_texts_selected(texts, true);
+        state.delete_selected();
+        return;
+    }
+
+    let unselect = state.modifiers.shift_key();
+    state.document.pick(ids, texts, unselect);
+
+    let anything_selected = state.document.entities.iter().any(Entity::selected)
+        || state.document.texts.iter().any(|text| text.selected);
+    if !unselect
+        && !matches!(
+            state.mode,
+            Mode::Move(_)
+                | Mode::Copy(_)
+                | Mode::Trim(_)
+                | Mode::Extend(_)
+                | Mode::Rotate(_)
+                | Mode::Scale(_)
+                | Mode::Mirror(_)
+                | Mode::Array(_)
+                | Mode::Join
+                | Mode::Explode
+                | Mode::Overkill
+        )
+    {
+        state.mode = Mode::Selection;
+    } else if state.mode == Mode::Selection && !anything_selected {
+        state.mode = Mode::Normal;
+    }
+
+    state.update_instance_buffers();
+}
+
+// Where a dragged grip, or the point a rotate/scale/mirror follows, goes for the cursor at
+// `cursor`: onto the snap if there is one, otherwise straight across or up from the anchor with
+// ortho on.
+pub fn grip_target(state: &State, anchor: [f32; 2], cursor: [f32; 2]) -> [f32; 2] {
+    match state.snap {
+        Some(snap) => snap,
+        None if state.ui.ortho => {
+            if (cursor[0] - anchor[0]).abs() > (cursor[1] - anchor[1]).abs() {
+                [cursor[0], anchor[1]]
+            } else {
+                [anchor[0], cursor[1]]
+            }
+        }
+        None => cursor,
+    }
+}
+
+// the point the segment being drawn starts from, perpendicular and tangent snaps are measured from it
+fn last_point(state: &State) -> Option<[f32; 2]> {
+    match state.drawing_state {
+        DrawingState::WaitingForSecondPoint(point)
+        | DrawingState::WaitingForRadius(point)
+        | DrawingState::WaitingForArcSecondPoint(point)
+        | DrawingState::WaitingForArcEnd(_, point)
+        | DrawingState::WaitingForEllipseAxis(point)
+        | DrawingState::WaitingForEllipseOtherAxis(point, _)
+        | DrawingState::DraggingGrip(point) => Some(point),
+        // the last vertex is the one following the cursor
+        DrawingState::WaitingForNextVertex => match state
+            .document
+            .active_polyline_id
+            .and_then(|id| state.document.entities.get(id))
+        {
+            Some(Entity::Polyline(polyline)) if polyline.vertices.len() >= 2 => {
+                Some(polyline.vertices[polyline.vertices.len() - 2].position)
+            }
+            _ => None,
+        },
+        DrawingState::Idle => match state.mode {
+            Mode::Measure(Some(point))
+            | Mode::Rotate(TransformState::Reference(point) | TransformState::Target(point, _))
+            | Mode::Scale(TransformState::Reference(point) | TransformState::Target(point, _))
+            | Mode::Mirror(TransformState::Target(point, _))
+            | Mode::Stretch(StretchState::Displace(_, point))
+            | Mode::DrawRectangle(Some(point))
+            | Mode::DrawPolygon(Some(point)) => Some(point),
+            _ => None,
+        },
+    }
+}
@@ -1,19 +1,73 @@
-use crate::Line;
 use egui::{Align2, Context, Margin, Rect};
-use std::fmt;
 use std::time::{Duration, Instant};
 
 use crate::events::input::world_to_screen;
 
 use crate::graphics::camera::Camera;
-use crate::model::circle::Circle;
+use crate::model::array::{ArrayKind, ArraySettings};
+use crate::model::color::{Color, EntityColor, NAMED_COLORS};
+use crate::model::document::{
+    lineweight_to_thickness, thickness_to_lineweight, Document, LINEWEIGHTS,
+};
+use crate::model::entity::{Entity, Geometry, SELECTION_COLOR};
+use crate::model::history::Change;
+use crate::model::layer::{LayerOps, DEFAULT_LAYER};
+use crate::model::linetype::{EntityLinetype, Linetype};
+use crate::model::selection::SelectionBox;
+use crate::model::snap::{SnapModes, SNAP_KINDS};
+use crate::model::store::EntityStore;
+use crate::model::text::{Text, TEXT_HEIGHT};
+use dxf::enums::AcadVersion;
+use std::collections::HashSet;
 
 const ICON_CIRCLE: &str = "\u{e18a}";
 const ICON_LINE: &str = "\u{e6d2}";
+const ICON_ARC: &str = "\u{ea0e}";
+const ICON_ELLIPSE: &str = "\u{e812}";
+const ICON_POLYLINE: &str = "\u{e6d4}";
+const ICON_RECTANGLE: &str = "\u{e3f0}";
+const ICON_POLYGON: &str = "\u{e6d0}";
+const ICON_TRIM: &str = "\u{eae0}";
+const ICON_EXTEND: &str = "\u{e064}";
+const ICON_OFFSET: &str = "\u{e1cc}";
+const ICON_FILLET: &str = "\u{e026}";
+const ICON_CHAMFER: &str = "\u{e2ae}";
+const ICON_ROTATE: &str = "\u{e036}";
+const ICON_SCALE: &str = "\u{ed6e}";
+const ICON_MIRROR: &str = "\u{ed6a}";
+const ICON_ARRAY: &str = "\u{e464}";
+const ICON_STRETCH: &str = "\u{e534}";
+const ICON_BREAK: &str = "\u{e2e4}";
+const ICON_JOIN: &str = "\u{e2e2}";
+const ICON_EXPLODE: &str = "\u{e0a2}";
+const ICON_OVERKILL: &str = "\u{ec54}";
 const ICON_OPEN: &str = "\u{e230}";
 const ICON_SAVE: &str = "\u{e248}";
+const ICON_SAVE_AS: &str = "\u{eaf4}";
 const ICON_TOGGLE_THEME: &str = "\u{e6f4}";
 const ICON_AXIS: &str = "\u{ee64}";
+const ICON_UNDO: &str = "\u{e038}";
+const ICON_REDO: &str = "\u{e036}";
+const ICON_LAYERS: &str = "\u{e466}";
+const ICON_PLUS: &str = "\u{e3d4}";
+const ICON_VISIBLE: &str = "\u{e220}";
+const ICON_HIDDEN: &str = "\u{e224}";
+const ICON_FROZEN: &str = "\u{e5aa}";
+const ICON_THAWED: &str = "\u{e472}";
+const ICON_LOCKED: &str = "\u{e2fa}";
+const ICON_UNLOCKED: &str = "\u{e306}";
+const ICON_DELETE: &str = "\u{e4a6}";
+
+// versions offered when saving, R12 has no LWPOLYLINE/MTEXT so those get written the old way
+const DXF_VERSIONS: [AcadVersion; 7] = [
+    AcadVersion::R12,
+    AcadVersion::R2000,
+    AcadVersion::R2004,
+    AcadVersion::R2007,
+    AcadVersion::R2010,
+    AcadVersion::R2013,
+    AcadVersion::R2018,
+];
 
 #[derive(Clone, Debug)]
 pub struct UiState {
@@ -25,12 +79,22 @@ pub struct UiState {
     pub text_edited: TextReplacement,
     pub numeric_active: bool,
     pub axis_active: bool,
+    pub snap_modes: SnapModes,
+    // keeps dragged grips horizontal or vertical
+    pub ortho: bool,
+    // the rubber band being dragged out, if any
+    pub selection_box: Option<SelectionBox>,
 
-    pub texts: Vec<Text>,
     pub action: Option<UiAction>,
     pub mode: UiMode,
     pub notifications: Vec<Notification>,
     pub cursor_position: Option<[f32; 2]>,
+    pub can_undo: bool,
+    pub can_redo: bool,
+    // layer whose name is being edited in the layer panel and what's typed so far
+    pub layer_rename: Option<(u64, String)>,
+    // what the array dialog shows, kept for the next array
+    pub array: ArraySettings,
 }
 
 #[derive(Clone, Debug)]
@@ -40,51 +104,62 @@ pub struct Notification {
     ttl: Duration,
 }
 
-#[derive(Clone)]
-pub struct Text {
-    pub position: [f32; 2],
-    pub contents: egui::WidgetText,
-    pub rect: Option<egui::Rect>,
-    pub editing: bool,
-    pub annotative: bool,
-}
-
 #[derive(Clone, Debug)]
 pub struct TextReplacement {
     pub contents: String,
     pub annotative: bool,
-}
-
-impl fmt::Debug for Text {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("Text")
-            .field("contents", &self.contents.text())
-            .field("position", &self.position)
-            .finish()
-    }
+    // the text as it was before editing started, None if it was just created
+    pub original: Option<Text>,
 }
 
 #[derive(Clone, Debug)]
 pub enum UiAction {
     DrawLine,
     DrawCircle,
+    DrawArc,
+    DrawEllipse,
+    DrawPolyline,
+    DrawRectangle,
+    DrawPolygon,
+    Trim,
+    Extend,
+    Offset,
+    Fillet,
+    Chamfer,
+    Rotate,
+    Scale,
+    Mirror,
+    Array,
+    Stretch,
+    Break,
+    Join,
+    Explode,
+    Overkill,
+    ArrayChanged,
+    ArrayApplied,
+    ArrayCancelled,
     OpenFilePath(String),
     SaveFile,
+    SaveFileAs,
     Input(String),
     TextEdited(TextReplacement),
     TextEditCancelled,
     ChangeTheme,
+    Undo,
+    Redo,
 }
 
 #[derive(Clone, Debug)]
 pub enum UiMode {
     Normal,
     TextEdit,
+    Array,
 }
 
 #[derive(Clone, Debug, Copy)]
 pub struct UiConfig {
     pub open_right_side_panel: bool,
+    pub open_layer_panel: bool,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -123,9 +198,11 @@ impl UiState {
         let text_edited = TextReplacement {
             contents: String::new(),
             annotative: false,
+            original: None,
         };
         let config = UiConfig {
             open_right_side_panel: true,
+            open_layer_panel: false,
         };
 
         Self {
@@ -136,11 +213,17 @@ impl UiState {
             text_edited,
             numeric_active: false,
             axis_active: true,
+            snap_modes: SnapModes::default(),
+            ortho: false,
+            selection_box: None,
             action: None,
             mode: UiMode::Normal,
-            texts: Vec::new(),
             notifications: Vec::new(),
             cursor_position: None,
+            can_undo: false,
+            can_redo: false,
+            layer_rename: None,
+            array: ArraySettings::default(),
         }
     }
 
@@ -195,9 +278,9 @@ impl UiState {
         &mut self,
         ui: &Context,
         camera: &mut Camera,
-        lines: &mut Vec<Line>,
-        circles: &mut Vec<Circle>,
+        document: &mut Document,
         dirty: &mut bool,
+        edits: &mut Vec<Change>,
     ) {
         self.ui_context = Some(ui.clone());
         self.setup_custom_fonts();
@@ -243,6 +326,40 @@ impl UiState {
 
         ui.set_visuals(visuals);
 
+        // blue for a window, green and dashed for a crossing box
+        if let Some(selection) = self.selection_box {
+            let corner =
+                |p: [f32; 2]| world_to_screen(p[0], p[1], viewport_rect, camera, pixels_per_point);
+            let rect = Rect::from_two_pos(corner(selection.start), corner(selection.end));
+            let (fill, stroke) = if selection.crossing() {
+                (
+                    egui::Color32::from_rgba_unmultiplied(40, 200, 80, 40),
+                    egui::Color32::from_rgb(40, 200, 80),
+                )
+            } else {
+                (
+                    egui::Color32::from_rgba_unmultiplied(40, 120, 255, 40),
+                    egui::Color32::from_rgb(40, 120, 255),
+                )
+            };
+
+            let painter = ui.layer_painter(egui::LayerId::background());
+            painter.rect_filled(rect, 0.0, fill);
+            let stroke = egui::Stroke::new(1.0, stroke);
+            if selection.crossing() {
+                let c = [
+                    rect.left_top(),
+                    rect.right_top(),
+                    rect.right_bottom(),
+                    rect.left_bottom(),
+                    rect.left_top(),
+                ];
+                painter.extend(egui::Shape::dashed_line(&c, stroke, 6.0, 4.0));
+            } else {
+                painter.rect_stroke(rect, 0.0, stroke);
+            }
+        }
+
         egui::TopBottomPanel::top("top_panel")
             .resizable(false)
             .exact_height(30.0)
@@ -257,7 +374,16 @@ impl UiState {
 
                 let painter = ui.painter();
 
-                for text in &mut self.texts {
+                let [r, g, b] = SELECTION_COLOR.map(|c| (c * 255.0) as u8);
+                let selection_color = egui::Color32::from_rgb(r, g, b);
+
+                for text in document.texts.iter_mut() {
+                    // texts on hidden layers can't be picked either
+                    if !document.layers.is_shown(text.layer) {
+                        text.rect = None;
+                        continue;
+                    }
+
                     let screen_position = world_to_screen(
                         text.position[0],
                         text.position[1],
@@ -270,9 +396,13 @@ impl UiState {
                         egui::Align2::LEFT_BOTTOM,
                         text.contents.text(),
                         egui::FontId::proportional(
-                            14.0 * if text.annotative { 1.0 } else { camera.zoom },
+                            TEXT_HEIGHT * if text.annotative { 1.0 } else { camera.zoom },
                         ),
-                        egui::Color32::WHITE,
+                        if text.selected {
+                            selection_color
+                        } else {
+                            egui::Color32::WHITE
+                        },
                     );
                     text.rect = Some(rect);
                 }
@@ -286,6 +416,86 @@ impl UiState {
                         self.action = Some(UiAction::DrawCircle);
                     }
 
+                    if ui.button(ICON_ARC).clicked() {
+                        self.action = Some(UiAction::DrawArc);
+                    }
+
+                    if ui.button(ICON_ELLIPSE).on_hover_text("Ellipse").clicked() {
+                        self.action = Some(UiAction::DrawEllipse);
+                    }
+
+                    if ui.button(ICON_POLYLINE).clicked() {
+                        self.action = Some(UiAction::DrawPolyline);
+                    }
+
+                    if ui
+                        .button(ICON_RECTANGLE)
+                        .on_hover_text("Rectangle")
+                        .clicked()
+                    {
+                        self.action = Some(UiAction::DrawRectangle);
+                    }
+
+                    if ui.button(ICON_POLYGON).on_hover_text("Polygon").clicked() {
+                        self.action = Some(UiAction::DrawPolygon);
+                    }
+
+                    if ui.button(ICON_TRIM).on_hover_text("Trim").clicked() {
+                        self.action = Some(UiAction::Trim);
+                    }
+
+                    if ui.button(ICON_EXTEND).on_hover_text("Extend").clicked() {
+                        self.action = Some(UiAction::Extend);
+                    }
+
+                    if ui.button(ICON_OFFSET).on_hover_text("Offset").clicked() {
+                        self.action = Some(UiAction::Offset);
+                    }
+
+                    if ui.button(ICON_FILLET).on_hover_text("Fillet").clicked() {
+                        self.action = Some(UiAction::Fillet);
+                    }
+
+                    if ui.button(ICON_CHAMFER).on_hover_text("Chamfer").clicked() {
+                        self.action = Some(UiAction::Chamfer);
+                    }
+
+                    if ui.button(ICON_ROTATE).on_hover_text("Rotate").clicked() {
+                        self.action = Some(UiAction::Rotate);
+                    }
+
+                    if ui.button(ICON_SCALE).on_hover_text("Scale").clicked() {
+                        self.action = Some(UiAction::Scale);
+                    }
+
+                    if ui.button(ICON_MIRROR).on_hover_text("Mirror").clicked() {
+                        self.action = Some(UiAction::Mirror);
+                    }
+
+                    if ui.button(ICON_ARRAY).on_hover_text("Array").clicked() {
+                        self.action = Some(UiAction::Array);
+                    }
+
+                    if ui.button(ICON_STRETCH).on_hover_text("Stretch").clicked() {
+                        self.action = Some(UiAction::Stretch);
+                    }
+
+                    if ui.button(ICON_BREAK).on_hover_text("Break").clicked() {
+                        self.action = Some(UiAction::Break);
+                    }
+
+                    if ui.button(ICON_JOIN).on_hover_text("Join").clicked() {
+                        self.action = Some(UiAction::Join);
+                    }
+
+                    if ui.button(ICON_EXPLODE).on_hover_text("Explode").clicked() {
+                        self.action = Some(UiAction::Explode);
+                    }
+
+                    if ui.button(ICON_OVERKILL).on_hover_text("Overkill").clicked() {
+                        self.action = Some(UiAction::Overkill);
+                    }
+
                     if ui.button(ICON_OPEN).clicked() {
                         if let Some(path) = rfd::FileDialog::new()
                             .add_filter(".dxf, .cad", &["dxf", "cad"])
@@ -295,10 +505,57 @@ impl UiState {
                         }
                     }
 
-                    if ui.button(ICON_SAVE).clicked() {
+                    if ui
+                        .button(ICON_SAVE)
+                        .on_hover_text("Save (Ctrl+S)")
+                        .clicked()
+                    {
                         self.action = Some(UiAction::SaveFile);
                     }
 
+                    if ui
+                        .button(ICON_SAVE_AS)
+                        .on_hover_text("Save As (Ctrl+Shift+S)")
+                        .clicked()
+                    {
+                        self.action = Some(UiAction::SaveFileAs);
+                    }
+
+                    egui::ComboBox::from_id_source("dxf_version")
+                        .selected_text(format!("{:?}", document.dxf_version))
+                        .width(60.0)
+                        .show_ui(ui, |ui| {
+                            for version in DXF_VERSIONS {
+                                ui.selectable_value(
+                                    &mut document.dxf_version,
+                                    version,
+                                    format!("{:?}", version),
+                                );
+                            }
+                        })
+                        .response
+                        .on_hover_text("DXF version to save as");
+
+                    if ui
+                        .add_enabled(self.can_undo, egui::Button::new(ICON_UNDO))
+                        .on_hover_text("Undo (Ctrl+Z)")
+                        .clicked()
+                    {
+                        self.action = Some(UiAction::Undo);
+                    }
+
+                    if ui
+                        .add_enabled(self.can_redo, egui::Button::new(ICON_REDO))
+                        .on_hover_text("Redo (Ctrl+Shift+Z)")
+                        .clicked()
+                    {
+                        self.action = Some(UiAction::Redo);
+                    }
+
+                    if ui.button(ICON_LAYERS).on_hover_text("Layers").clicked() {
+                        self.ui_config.open_layer_panel = !self.ui_config.open_layer_panel;
+                    }
+
                     if ui.button(ICON_TOGGLE_THEME).clicked() {
                         if let Some(ind) = THEMES
                             .iter()
@@ -327,6 +584,34 @@ impl UiState {
                         if ui.button(ICON_AXIS).clicked() {
                             self.axis_active = !self.axis_active;
                         }
+
+                        ui.separator();
+                        if ui
+                            .selectable_label(self.ortho, "ORTHO")
+                            .on_hover_text("Ortho (F8)")
+                            .clicked()
+                        {
+                            self.ortho = !self.ortho;
+                        }
+                        let modes = &mut self.snap_modes;
+                        if ui
+                            .selectable_label(modes.enabled, "OSNAP")
+                            .on_hover_text("Object snap (F3)")
+                            .clicked()
+                        {
+                            modes.enabled = !modes.enabled;
+                        }
+                        ui.add_enabled_ui(modes.enabled, |ui| {
+                            for kind in SNAP_KINDS {
+                                if ui
+                                    .selectable_label(modes.contains(kind), kind.abbreviation())
+                                    .on_hover_text(kind.name())
+                                    .clicked()
+                                {
+                                    modes.toggle(kind);
+                                }
+                            }
+                        });
                     });
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                         if let Some(cursor_pos) = self.cursor_position {
@@ -436,6 +721,137 @@ impl UiState {
                 });
         }
 
+        // array dialog, every change shows up in the preview right away
+        if matches!(self.mode, UiMode::Array) {
+            let before = self.array;
+            egui::Window::new("Array")
+                .collapsible(false)
+                .resizable(false)
+                .movable(true)
+                .frame(frame)
+                .show(ui, |ui| {
+                    let style = ui.style_mut();
+
+                    style.spacing.button_padding = egui::vec2(7.0, 4.0);
+                    style.text_styles.insert(
+                        egui::TextStyle::Button,
+                        egui::FontId::new(12.0, egui::FontFamily::Proportional),
+                    );
+
+                    let array = &mut self.array;
+                    ui.horizontal(|ui| {
+                        ui.selectable_value(&mut array.kind, ArrayKind::Rectangular, "Rectangular");
+                        ui.selectable_value(&mut array.kind, ArrayKind::Polar, "Polar");
+                        ui.selectable_value(&mut array.kind, ArrayKind::Path, "Path");
+                    });
+                    ui.add_space(5.0);
+
+                    egui::Grid::new("array_grid")
+                        .num_columns(2)
+                        .spacing([10.0, 5.0])
+                        .show(ui, |ui| match array.kind {
+                            ArrayKind::Rectangular => {
+                                ui.label("Rows");
+                                ui.add(egui::DragValue::new(&mut array.rows).clamp_range(1..=100));
+                                ui.end_row();
+                                ui.label("Columns");
+                                ui.add(
+                                    egui::DragValue::new(&mut array.columns).clamp_range(1..=100),
+                                );
+                                ui.end_row();
+                                ui.label("Row spacing");
+                                ui.add(egui::DragValue::new(&mut array.row_spacing).speed(0.1));
+                                ui.end_row();
+                                ui.label("Column spacing");
+                                ui.add(egui::DragValue::new(&mut array.column_spacing).speed(0.1));
+                                ui.end_row();
+                            }
+                            ArrayKind::Polar => {
+                                ui.label("Items");
+                                ui.add(
+                                    egui::DragValue::new(&mut array.count).clamp_range(1..=1000),
+                                );
+                                ui.end_row();
+                                ui.label("Fill angle");
+                                ui.add(
+                                    egui::DragValue::new(&mut array.fill_angle)
+                                        .clamp_range(-360.0..=360.0)
+                                        .suffix("°"),
+                                );
+                                ui.end_row();
+                                ui.label("Center");
+                                ui.horizontal(|ui| {
+                                    ui.add(
+                                        egui::DragValue::new(&mut array.center[0]).prefix("x: "),
+                                    );
+                                    ui.add(
+                                        egui::DragValue::new(&mut array.center[1]).prefix("y: "),
+                                    );
+                                });
+                                ui.end_row();
+                                ui.label("");
+                                ui.checkbox(&mut array.rotate_items, "Rotate items");
+                                ui.end_row();
+                            }
+                            ArrayKind::Path => {
+                                ui.label("Path");
+                                let picked = array
+                                    .path
+                                    .and_then(|id| document.entities.get(id))
+                                    .map(Entity::kind_name);
+                                ui.label(picked.unwrap_or("click one in the drawing"));
+                                ui.end_row();
+                                let mut by_spacing = array.path_spacing.is_some();
+                                ui.label("");
+                                ui.checkbox(&mut by_spacing, "Fixed spacing");
+                                ui.end_row();
+                                match (by_spacing, &mut array.path_spacing) {
+                                    (true, Some(spacing)) => {
+                                        ui.label("Spacing");
+                                        ui.add(
+                                            egui::DragValue::new(spacing)
+                                                .speed(0.1)
+                                                .clamp_range(0.001..=f32::MAX),
+                                        );
+                                    }
+                                    (true, None) => array.path_spacing = Some(10.0),
+                                    (false, _) => {
+                                        array.path_spacing = None;
+                                        ui.label("Items");
+                                        ui.add(
+                                            egui::DragValue::new(&mut array.count)
+                                                .clamp_range(1..=1000),
+                                        );
+                                    }
+                                }
+                                ui.end_row();
+                                ui.label("");
+                                ui.checkbox(&mut array.rotate_items, "Align items with the path");
+                                ui.end_row();
+                            }
+                        });
+
+                    if array.kind == ArrayKind::Polar {
+                        ui.add_space(5.0);
+                        ui.label("Click in the drawing to place the center");
+                    }
+                    ui.add_space(5.0);
+
+                    ui.horizontal(|ui| {
+                        if ui.button("Cancel").clicked() {
+                            self.action = Some(UiAction::ArrayCancelled);
+                        }
+                        if ui.button("Apply").clicked() {
+                            self.action = Some(UiAction::ArrayApplied);
+                        }
+                    });
+                });
+
+            if self.action.is_none() && self.array != before {
+                self.action = Some(UiAction::ArrayChanged);
+            }
+        }
+
         // side panel
         if self.ui_config.open_right_side_panel {
             egui::SidePanel::right("right panel")
@@ -447,229 +863,548 @@ impl UiState {
                     ui.heading("Properties");
                     ui.separator();
 
-                    let mut sel_line = Vec::new();
-                    let mut sel_circle = Vec::new();
-
-                    for line in lines {
-                        if line.selected {
-                            sel_line.push(line);
-                        }
-                    }
-                    for circle in circles {
-                        if circle.selected {
-                            sel_circle.push(circle);
+                    // only read here, an edit goes through `get_mut` once it's made so the
+                    // spatial index isn't told about every selected entity on every frame
+                    let selected: Vec<&Entity> = document
+                        .entities
+                        .iter()
+                        .filter(|entity| entity.selected())
+                        .collect();
+                    let mut pending: Option<PendingEdit> = None;
+
+                    let obj_type = match selected.first() {
+                        Some(first)
+                            if selected
+                                .iter()
+                                .all(|entity| entity.kind_name() == first.kind_name()) =>
+                        {
+                            first.kind_name()
                         }
-                    }
-
-                    let num_lines = sel_line.len();
-                    let num_circles = sel_circle.len();
-                    let obj_type = if num_lines > 0 && num_circles > 0 {
-                        "Undefined"
-                    } else if num_lines > 0 {
-                        "Line"
-                    } else if num_circles > 0 {
-                        "Circle"
-                    } else {
-                        "Undefined"
+                        _ => "Undefined",
                     };
 
                     ui.label(format!("Type: {}", obj_type));
-                    ui.label(format!("Number of objects: {}", num_lines + num_circles));
+                    ui.label(format!("Number of objects: {}", selected.len()));
 
                     egui::Grid::new("properties_grid")
                         .num_columns(2)
                         .spacing([40.0, 4.0])
                         .striped(true)
                         .show(ui, |ui| {
-                            let mut changed = false;
+                            // layer, color, lineweight and linetype go for any selection,
+                            // "varies" when the selected objects don't agree
+                            if let Some(first) = selected.first() {
+                                // None for ByLayer
+                                let lineweight_of = |entity: &Entity| {
+                                    (!entity.thickness_by_layer())
+                                        .then(|| thickness_to_lineweight(entity.thickness()))
+                                };
+                                let (layer, color) = (first.layer(), first.color());
+                                let lineweight = lineweight_of(first);
+                                let (linetype, linetype_scale) =
+                                    (first.linetype(), first.linetype_scale());
+                                let agree = |same: &dyn Fn(&Entity) -> bool, label: String| {
+                                    if selected.iter().all(|entity| same(entity)) {
+                                        label
+                                    } else {
+                                        "*varies*".to_string()
+                                    }
+                                };
 
-                            if obj_type == "Line" && sel_line.len() == 1 {
-                                ui.label("Thickness");
-                                changed |= ui
-                                    .add(
-                                        egui::DragValue::new(&mut sel_line[0].thickness).speed(0.1),
-                                    )
-                                    .changed();
-                                ui.end_row();
+                                let layer_shown = agree(
+                                    &|entity| entity.layer() == layer,
+                                    document.layers.name(layer).to_string(),
+                                );
+                                let color_shown =
+                                    agree(&|entity| entity.color() == color, color_label(color));
+                                let lineweight_shown = agree(
+                                    &|entity| lineweight_of(entity) == lineweight,
+                                    lineweight.map_or("ByLayer".to_string(), |lineweight| {
+                                        lineweight_label(Some(lineweight))
+                                    }),
+                                );
+                                let linetype_shown = agree(
+                                    &|entity| entity.linetype() == linetype,
+                                    linetype_label(linetype, &document.linetypes),
+                                );
 
-                                ui.label("Length");
-                                let mut line_len = sel_line[0].get_len();
-                                if ui
-                                    .add(egui::DragValue::new(&mut line_len).speed(0.1))
-                                    .changed()
-                                {
-                                    let start_pos = [
-                                        sel_line[0].vertices[0].position[0],
-                                        sel_line[0].vertices[0].position[1],
-                                    ];
-                                    sel_line[0].finish_line_with_length(start_pos, line_len);
-                                    changed = true;
-                                }
+                                ui.label("Layer");
+                                egui::ComboBox::from_id_source("selection_layer")
+                                    .selected_text(layer_shown)
+                                    .show_ui(ui, |ui| {
+                                        for layer in &document.layers {
+                                            if ui.selectable_label(false, &layer.name).clicked() {
+                                                let id = layer.id;
+                                                pending = Some(Box::new(move |entity| {
+                                                    entity.set_layer(id)
+                                                }));
+                                            }
+                                        }
+                                    });
                                 ui.end_row();
 
-                                ui.label("Start X coordinate");
-                                changed |= ui
-                                    .add(egui::DragValue::new(
-                                        &mut sel_line[0].vertices[0].position[0],
-                                    ))
-                                    .changed();
-                                ui.end_row();
+                                ui.label("Color");
+                                ui.horizontal(|ui| {
+                                    let mut picked = None;
+
+                                    egui::ComboBox::from_id_source("selection_color")
+                                        .selected_text(color_shown)
+                                        .show_ui(ui, |ui| {
+                                            let choices =
+                                                [EntityColor::ByLayer, EntityColor::ByBlock]
+                                                    .into_iter()
+                                                    .chain(NAMED_COLORS.iter().map(
+                                                        |(index, _)| {
+                                                            EntityColor::Explicit(Color::Index(
+                                                                *index,
+                                                            ))
+                                                        },
+                                                    ));
+                                            for choice in choices {
+                                                if ui
+                                                    .selectable_label(false, color_label(choice))
+                                                    .clicked()
+                                                {
+                                                    picked = Some(choice);
+                                                }
+                                            }
+                                        });
+
+                                    // anything outside the list comes from the picker
+                                    let mut rgb = match color {
+                                        EntityColor::Explicit(color) => color.to_rgb(),
+                                        EntityColor::ByLayer => document
+                                            .layers
+                                            .of(layer)
+                                            .map_or([255; 3], |layer| layer.color.to_rgb()),
+                                        EntityColor::ByBlock => [255; 3],
+                                    };
+                                    if ui.color_edit_button_srgb(&mut rgb).changed() {
+                                        picked = Some(EntityColor::Explicit(Color::Rgb(rgb)));
+                                    }
 
-                                ui.label("Start Y coordinate");
-                                changed |= ui
-                                    .add(egui::DragValue::new(
-                                        &mut sel_line[0].vertices[0].position[1],
-                                    ))
-                                    .changed();
+                                    if let Some(picked) = picked {
+                                        pending =
+                                            Some(Box::new(move |entity| entity.set_color(picked)));
+                                    }
+                                });
                                 ui.end_row();
 
-                                ui.label("End X coordinate");
-                                changed |= ui
-                                    .add(egui::DragValue::new(
-                                        &mut sel_line[0].vertices[1].position[0],
-                                    ))
-                                    .changed();
+                                ui.label("Lineweight");
+                                egui::ComboBox::from_id_source("selection_lineweight")
+                                    .selected_text(lineweight_shown)
+                                    .show_ui(ui, |ui| {
+                                        if ui.selectable_label(false, "ByLayer").clicked() {
+                                            pending = Some(Box::new(move |entity| {
+                                                entity.set_thickness_by_layer(true)
+                                            }));
+                                        }
+                                        for lineweight in LINEWEIGHTS {
+                                            let label = lineweight_label(Some(lineweight));
+                                            if ui.selectable_label(false, label).clicked() {
+                                                pending = Some(Box::new(move |entity| {
+                                                    entity.set_thickness(
+                                                        lineweight_to_thickness(lineweight)
+                                                            .unwrap_or(1.0),
+                                                    );
+                                                    entity.set_thickness_by_layer(false);
+                                                }));
+                                            }
+                                        }
+                                    });
                                 ui.end_row();
 
-                                ui.label("End Y coordinate");
-                                changed |= ui
-                                    .add(egui::DragValue::new(
-                                        &mut sel_line[0].vertices[1].position[1],
-                                    ))
-                                    .changed();
+                                ui.label("Linetype");
+                                egui::ComboBox::from_id_source("selection_linetype")
+                                    .selected_text(linetype_shown)
+                                    .show_ui(ui, |ui| {
+                                        let choices =
+                                            [EntityLinetype::ByLayer, EntityLinetype::ByBlock]
+                                                .into_iter()
+                                                .chain(document.linetypes.iter().map(|linetype| {
+                                                    EntityLinetype::Explicit(linetype.id)
+                                                }));
+                                        for choice in choices {
+                                            let label = linetype_label(choice, &document.linetypes);
+                                            if ui.selectable_label(false, label).clicked() {
+                                                pending = Some(Box::new(move |entity| {
+                                                    entity.set_linetype(choice)
+                                                }));
+                                            }
+                                        }
+                                    });
                                 ui.end_row();
-                            } else if obj_type == "Line" && sel_line.len() > 1 {
-                                ui.label("Thickness");
-                                let mut thickness = sel_line[0].thickness;
+
+                                ui.label("Linetype scale");
+                                let mut scale = linetype_scale;
                                 if ui
-                                    .add(egui::DragValue::new(&mut thickness).speed(0.25))
+                                    .add(
+                                        egui::DragValue::new(&mut scale)
+                                            .speed(0.01)
+                                            .clamp_range(0.001..=f32::MAX),
+                                    )
                                     .changed()
                                 {
-                                    for line in &mut sel_line {
-                                        line.thickness = thickness;
-                                    }
-                                    changed = true;
+                                    pending = Some(Box::new(move |entity| {
+                                        entity.set_linetype_scale(scale)
+                                    }));
                                 }
                                 ui.end_row();
+                            }
 
-                                ui.label("Length");
-                                let mut line_len = sel_line[0].get_len();
-                                if ui
-                                    .add(egui::DragValue::new(&mut line_len).speed(0.1))
-                                    .changed()
-                                {
-                                    for line in &mut sel_line {
-                                        let start_pos = [
-                                            line.vertices[0].position[0],
-                                            line.vertices[0].position[1],
-                                        ];
-                                        line.finish_line_with_length(start_pos, line_len);
+                            if selected.len() == 1 {
+                                for property in selected[0].properties() {
+                                    ui.label(property.name);
+                                    let mut value = property.value;
+                                    if ui
+                                        .add(egui::DragValue::new(&mut value).speed(property.speed))
+                                        .changed()
+                                    {
+                                        pending = Some(Box::new(move |entity| {
+                                            entity.set_property(property.name, value)
+                                        }));
                                     }
-                                    changed = true;
+                                    ui.end_row();
                                 }
-                                ui.end_row();
+                            } else if selected.len() > 1 {
+                                // shared properties edit the whole selection, the rest are only
+                                // shown when every selected object is of the same type
+                                for property in selected[0].properties() {
+                                    let on_all = selected.iter().all(|entity| {
+                                        entity.properties().iter().any(|p| p.name == property.name)
+                                    });
+
+                                    if property.shared && on_all {
+                                        ui.label(property.name);
+                                        let mut value = property.value;
+                                        if ui
+                                            .add(
+                                                egui::DragValue::new(&mut value)
+                                                    .speed(property.speed),
+                                            )
+                                            .changed()
+                                        {
+                                            pending = Some(Box::new(move |entity| {
+                                                entity.set_property(property.name, value)
+                                            }));
+                                        }
+                                        ui.end_row();
+                                    } else if obj_type != "Undefined" {
+                                        ui.label(property.name);
+                                        ui.label(format!("{:.3}", property.value));
+                                        ui.end_row();
+                                    }
+                                }
+                            }
+                        });
 
-                                ui.label("Start X coordinate");
-                                ui.label(format!(
-                                    "{:.3}",
-                                    &mut sel_line[0].vertices[0].position[0]
-                                ));
-                                ui.end_row();
+                    if let Some(apply) = pending {
+                        let ids: Vec<u64> = selected.iter().map(|entity| entity.id()).collect();
+                        edit(document, &ids, edits, apply);
+                        *dirty = true;
+                    }
+                });
+        }
 
-                                ui.label("Start Y coordinate");
-                                ui.label(format!(
-                                    "{:.3}",
-                                    &mut sel_line[0].vertices[0].position[1]
-                                ));
-                                ui.end_row();
+        if self.ui_config.open_layer_panel {
+            self.layer_panel(ui, document, dirty);
+        }
+    }
 
-                                ui.label("End X coordinate");
-                                ui.label(format!(
-                                    "{:.3}",
-                                    &mut sel_line[0].vertices[1].position[0]
-                                ));
-                                ui.end_row();
+    // the layer manager, shown left of the properties
+    fn layer_panel(&mut self, ui: &Context, document: &mut Document, dirty: &mut bool) {
+        egui::SidePanel::right("layer panel")
+            .resizable(true)
+            .default_width(300.0)
+            .width_range(200.0..=500.0)
+            .show(ui, |ui| {
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    ui.heading("Layers");
+                    if ui.button(ICON_PLUS).on_hover_text("New layer").clicked() {
+                        let id = document.new_layer();
+                        self.layer_rename = Some((id, document.layers.name(id).to_string()));
+                        ui.memory_mut(|memory| memory.request_focus(layer_name_id(id)));
+                    }
+                });
+                ui.separator();
+
+                let in_use: HashSet<u64> = document
+                    .entities
+                    .iter()
+                    .map(Entity::layer)
+                    .chain(document.texts.iter().map(|text| text.layer))
+                    .collect();
+                let current_layer = document.current_layer;
+                let mut make_current = None;
+                let mut rename = None;
+                let mut delete = None;
+                let mut changed = false;
+
+                egui::Grid::new("layers_grid")
+                    .num_columns(9)
+                    .spacing([6.0, 4.0])
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for layer in document.layers.iter_mut() {
+                            let id = layer.id;
+
+                            if ui
+                                .add_enabled(
+                                    !layer.frozen,
+                                    egui::RadioButton::new(id == current_layer, ""),
+                                )
+                                .on_hover_text("Current layer")
+                                .clicked()
+                            {
+                                make_current = Some(id);
+                            }
 
-                                ui.label("End Y coordinate");
-                                ui.label(format!(
-                                    "{:.3}",
-                                    &mut sel_line[0].vertices[1].position[1]
-                                ));
-                                ui.end_row();
+                            match &mut self.layer_rename {
+                                Some((rename_id, buffer)) if *rename_id == id => {
+                                    let response = ui.add(
+                                        egui::TextEdit::singleline(buffer)
+                                            .id(layer_name_id(id))
+                                            .desired_width(100.0),
+                                    );
+                                    if response.lost_focus() {
+                                        if !ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                                            rename = Some((id, buffer.clone()));
+                                        }
+                                        self.layer_rename = None;
+                                    }
+                                }
+                                _ => {
+                                    let response = ui
+                                        .add(
+                                            egui::Label::new(&layer.name)
+                                                .sense(egui::Sense::click()),
+                                        )
+                                        .on_hover_text("Double click to rename");
+                                    if response.double_clicked() && id != DEFAULT_LAYER {
+                                        self.layer_rename = Some((id, layer.name.clone()));
+                                        ui.memory_mut(|memory| {
+                                            memory.request_focus(layer_name_id(id))
+                                        });
+                                    }
+                                }
                             }
 
-                            if obj_type == "Circle" && sel_circle.len() == 1 {
-                                ui.label("Thickness");
-                                changed |= ui
-                                    .add(
-                                        egui::DragValue::new(&mut sel_circle[0].thickness)
-                                            .speed(0.1),
-                                    )
-                                    .changed();
-                                ui.end_row();
+                            let visibility = if layer.visible {
+                                ICON_VISIBLE
+                            } else {
+                                ICON_HIDDEN
+                            };
+                            if ui.button(visibility).on_hover_text("On / off").clicked() {
+                                layer.visible = !layer.visible;
+                                changed = true;
+                            }
 
-                                ui.label("Radius");
-                                changed |= ui
-                                    .add(egui::DragValue::new(&mut sel_circle[0].radius).speed(0.1))
-                                    .changed();
-                                ui.end_row();
+                            // the current layer can't be frozen
+                            let freeze = if layer.frozen {
+                                ICON_FROZEN
+                            } else {
+                                ICON_THAWED
+                            };
+                            if ui
+                                .add_enabled(id != current_layer, egui::Button::new(freeze))
+                                .on_hover_text("Freeze / thaw")
+                                .clicked()
+                            {
+                                layer.frozen = !layer.frozen;
+                                changed = true;
+                            }
 
-                                ui.label("Start X coordinate");
-                                changed |= ui
-                                    .add(egui::DragValue::new(
-                                        &mut sel_circle[0].center.position[0],
-                                    ))
-                                    .changed();
-                                ui.end_row();
+                            let lock = if layer.locked {
+                                ICON_LOCKED
+                            } else {
+                                ICON_UNLOCKED
+                            };
+                            if ui.button(lock).on_hover_text("Lock / unlock").clicked() {
+                                layer.locked = !layer.locked;
+                                changed = true;
+                            }
 
-                                ui.label("Start Y coordinate");
-                                changed |= ui
-                                    .add(egui::DragValue::new(
-                                        &mut sel_circle[0].center.position[1],
-                                    ))
-                                    .changed();
-                                ui.end_row();
-                            } else if obj_type == "Circle" && sel_circle.len() > 1 {
-                                ui.label("Thickness");
-                                let mut thickness = sel_circle[0].thickness;
-                                if ui
-                                    .add(egui::DragValue::new(&mut thickness).speed(0.25))
-                                    .changed()
-                                {
-                                    for circle in &mut sel_circle {
-                                        circle.thickness = thickness;
-                                    }
-                                    changed = true;
-                                }
-                                ui.end_row();
+                            let mut rgb = layer.color.to_rgb();
+                            if ui.color_edit_button_srgb(&mut rgb).changed() {
+                                layer.color = Color::Rgb(rgb);
+                                changed = true;
+                            }
 
-                                ui.label("Radius");
-                                let mut radius = sel_circle[0].radius;
-                                if ui
-                                    .add(egui::DragValue::new(&mut radius).speed(0.1))
-                                    .changed()
-                                {
-                                    for circle in &mut sel_circle {
-                                        circle.radius = radius;
+                            egui::ComboBox::from_id_source(("layer lineweight", id))
+                                .selected_text(lineweight_label(layer.lineweight))
+                                .width(70.0)
+                                .show_ui(ui, |ui| {
+                                    let choices =
+                                        std::iter::once(None).chain(LINEWEIGHTS.map(Some));
+                                    for lineweight in choices {
+                                        if ui
+                                            .selectable_value(
+                                                &mut layer.lineweight,
+                                                lineweight,
+                                                lineweight_label(lineweight),
+                                            )
+                                            .clicked()
+                                        {
+                                            changed = true;
+                                        }
                                     }
-                                    changed = true;
-                                }
-                                ui.end_row();
+                                })
+                                .response
+                                .on_hover_text(
+                                    "Lineweight of the objects on this layer set to ByLayer",
+                                );
 
-                                ui.label("Start X coordinate");
-                                ui.label(format!("{:.3}", &mut sel_circle[0].center.position[0]));
-                                ui.end_row();
+                            egui::ComboBox::from_id_source(("layer linetype", id))
+                                .selected_text(document.linetypes.name(layer.linetype))
+                                .width(90.0)
+                                .show_ui(ui, |ui| {
+                                    for linetype in &document.linetypes {
+                                        if ui
+                                            .selectable_value(
+                                                &mut layer.linetype,
+                                                linetype.id,
+                                                &linetype.name,
+                                            )
+                                            .on_hover_text(&linetype.description)
+                                            .clicked()
+                                        {
+                                            changed = true;
+                                        }
+                                    }
+                                })
+                                .response
+                                .on_hover_text(
+                                    "Linetype of the objects on this layer set to ByLayer",
+                                );
 
-                                ui.label("Start Y coordinate");
-                                ui.label(format!("{:.3}", &mut sel_circle[0].center.position[1]));
-                                ui.end_row();
+                            // layers with something on them aren't deleted out from under it
+                            let deletable =
+                                id != DEFAULT_LAYER && id != current_layer && !in_use.contains(&id);
+                            if ui
+                                .add_enabled(deletable, egui::Button::new(ICON_DELETE))
+                                .on_hover_text("Delete (only empty layers)")
+                                .clicked()
+                            {
+                                delete = Some(id);
                             }
 
-                            if changed {
-                                *dirty = true;
-                            }
-                        });
+                            ui.end_row();
+                        }
+                    });
+
+                ui.separator();
+                // scales the dashes of every linetype in the drawing
+                ui.horizontal(|ui| {
+                    ui.label("Global linetype scale");
+                    if ui
+                        .add(
+                            egui::DragValue::new(&mut document.linetype_scale)
+                                .speed(0.01)
+                                .clamp_range(0.001..=f32::MAX),
+                        )
+                        .changed()
+                    {
+                        changed = true;
+                    }
                 });
+
+                if let Some(id) = make_current {
+                    document.set_current_layer(id);
+                }
+                if let Some((id, name)) = rename {
+                    document.rename_layer(id, &name);
+                }
+                if let Some(id) = delete {
+                    document.delete_layer(id);
+                }
+
+                if changed {
+                    *dirty = true;
+                }
+            });
+    }
+}
+
+// an edit made in the properties panel, applied to the selection once the panel is drawn
+type PendingEdit = Box<dyn Fn(&mut Entity)>;
+
+// changes the entities `ids`, keeping what they were before so the edit can be undone
+fn edit(
+    document: &mut Document,
+    ids: &[u64],
+    edits: &mut Vec<Change>,
+    apply: impl Fn(&mut Entity),
+) {
+    for id in ids {
+        let Some(entity) = document.entities.get_mut(*id) else {
+            continue;
+        };
+        let before = entity.clone();
+        apply(entity);
+        if *entity != before {
+            edits.push(Change::Modify(before, entity.clone()));
         }
     }
 }
+
+fn layer_name_id(layer: u64) -> egui::Id {
+    egui::Id::new(("layer name", layer))
+}
+
+fn color_label(color: EntityColor) -> String {
+    match color {
+        EntityColor::ByLayer => "ByLayer".to_string(),
+        EntityColor::ByBlock => "ByBlock".to_string(),
+        EntityColor::Explicit(Color::Index(index)) => NAMED_COLORS
+            .iter()
+            .find(|(named, _)| *named == index)
+            .map_or(format!("Color {}", index), |(_, name)| name.to_string()),
+        EntityColor::Explicit(Color::Rgb([r, g, b])) => format!("{}, {}, {}", r, g, b),
+    }
+}
+
+fn linetype_label(linetype: EntityLinetype, linetypes: &EntityStore<Linetype>) -> String {
+    match linetype {
+        EntityLinetype::ByLayer => "ByLayer".to_string(),
+        EntityLinetype::ByBlock => "ByBlock".to_string(),
+        EntityLinetype::Explicit(id) => linetypes.name(id).to_string(),
+    }
+}
+
+fn lineweight_label(lineweight: Option<i16>) -> String {
+    match lineweight {
+        Some(lineweight) => format!("{:.2} mm", lineweight as f32 / 100.0),
+        None => "Default".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::line::LineOps;
+    use crate::model::selection::SelectionOps;
+    use crate::model::store::Changes;
+
+    #[test]
+    fn showing_the_selection_leaves_the_spatial_index_alone() {
+        let mut ui_state = UiState::new();
+        let mut camera = Camera::new(0.0, 0.0, 1.0);
+        let mut document = Document::new();
+        for i in 0..10 {
+            document.add_line([0.0, i as f32], [10.0, i as f32], false);
+        }
+        document.select_all();
+        document.entities.take_changes();
+
+        let (mut dirty, mut edits) = (false, Vec::new());
+        let ctx = Context::default();
+        let _ = ctx.run(egui::RawInput::default(), |ctx| {
+            ui_state.gui(ctx, &mut camera, &mut document, &mut dirty, &mut edits)
+        });
+
+        assert!(ui_state.ui_config.open_right_side_panel);
+        assert_eq!(document.entities.take_changes(), Changes::Ids(Vec::new()));
+        assert!(edits.is_empty() && !dirty);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::model::{circle::CircleInstance, line::LineInstance};
+use crate::model::{arc::ArcInstance, circle::CircleInstance, line::LineInstance};
 use crate::Vertex;
 use egui_wgpu::wgpu;
 use wgpu::{BindGroupLayout, Device, ShaderModule, SurfaceConfiguration};
@@ -105,6 +105,53 @@ impl Pipeline {
         Self { render_pipeline }
     }
 
+    pub fn new_arc_pipeline(
+        device: &Device,
+        config: &SurfaceConfiguration,
+        shader: &ShaderModule,
+        camera_bind_group_layout: &BindGroupLayout,
+    ) -> Self {
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Render Pipeline Arc"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[ArcInstance::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                strip_index_format: None,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        Self { render_pipeline }
+    }
+
     pub fn new_xy_axis_pipeline(
         device: &Device,
         config: &SurfaceConfiguration,
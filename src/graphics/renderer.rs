@@ -1,8 +1,16 @@
+use crate::events::input::grip_target;
 use crate::graphics::gui_elements::UiAction;
+use crate::model::circle::CircleOps;
+use crate::model::grip::GripOps;
+use crate::model::history::{Change, Command, HistoryOps};
+use crate::model::line::LineOps;
+use crate::DrawArcMode;
 use crate::DrawLineMode;
+use crate::DrawPolylineMode;
 use crate::DrawingState;
 use crate::Mode;
 use crate::State;
+use crate::TransformState;
 use egui::WidgetText;
 use egui_wgpu::wgpu;
 use egui_wgpu::ScreenDescriptor;
@@ -70,13 +78,19 @@ pub fn render(state: &mut State) -> Result<(), wgpu::SurfaceError> {
         render_pass.set_pipeline(&state.render_pipeline);
         render_pass.set_vertex_buffer(0, state.instance_buffer.slice(..));
         render_pass.set_bind_group(0, &state.camera_bind_group, &[]);
-        render_pass.draw(0..4, 0..state.lines.len() as u32);
+        render_pass.draw(0..4, 0..state.num_line_instances);
 
         // circles
         render_pass.set_pipeline(&state.render_pipeline2);
         render_pass.set_bind_group(0, &state.camera_bind_group, &[]);
         render_pass.set_vertex_buffer(0, state.instance_buffer_circle.slice(..));
-        render_pass.draw(0..4, 0..state.circles.len() as u32);
+        render_pass.draw(0..4, 0..state.num_circle_instances);
+
+        // arcs
+        render_pass.set_pipeline(&state.render_pipeline_arc);
+        render_pass.set_bind_group(0, &state.camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, state.instance_buffer_arc.slice(..));
+        render_pass.draw(0..4, 0..state.num_arc_instances);
     }
 
     let screen_descriptor = ScreenDescriptor {
@@ -84,11 +98,12 @@ pub fn render(state: &mut State) -> Result<(), wgpu::SurfaceError> {
         pixels_per_point: state.window().scale_factor() as f32,
     };
 
-    let lines = &mut state.lines;
-    let circles = &mut state.circles;
+    state.ui.can_undo = state.document.history.can_undo();
+    state.ui.can_redo = state.document.history.can_redo();
 
     let State {
         ui,
+        document,
         egui,
         device,
         queue,
@@ -98,6 +113,8 @@ pub fn render(state: &mut State) -> Result<(), wgpu::SurfaceError> {
     } = state;
 
     let mut buffers_need_update: bool = false;
+    // what the properties panel changed, so it can be undone
+    let mut edits = Vec::new();
 
     egui.draw(
         device,
@@ -106,7 +123,15 @@ pub fn render(state: &mut State) -> Result<(), wgpu::SurfaceError> {
         window,
         &view,
         screen_descriptor,
-        |ui_ctx| ui.gui(ui_ctx, camera, lines, circles, &mut buffers_need_update),
+        |ui_ctx| {
+            ui.gui(
+                ui_ctx,
+                camera,
+                document,
+                &mut buffers_need_update,
+                &mut edits,
+            )
+        },
     );
 
     if let Some(action) = ui.action.take() {
@@ -117,6 +142,72 @@ pub fn render(state: &mut State) -> Result<(), wgpu::SurfaceError> {
             UiAction::DrawCircle => {
                 state.mode = Mode::DrawCircle;
             }
+            UiAction::DrawArc => {
+                state.mode = Mode::DrawArc(DrawArcMode::ThreePoint);
+            }
+            UiAction::DrawEllipse => {
+                state.start_ellipse();
+            }
+            UiAction::DrawPolyline => {
+                state.mode = Mode::DrawPolyline(DrawPolylineMode::Line);
+            }
+            UiAction::DrawRectangle => {
+                state.start_rectangle();
+            }
+            UiAction::DrawPolygon => {
+                state.start_polygon();
+            }
+            UiAction::Trim => {
+                state.start_trim(false);
+            }
+            UiAction::Extend => {
+                state.start_trim(true);
+            }
+            UiAction::Offset => {
+                state.start_offset();
+            }
+            UiAction::Fillet => {
+                state.start_corner(false);
+            }
+            UiAction::Chamfer => {
+                state.start_corner(true);
+            }
+            UiAction::Rotate => {
+                state.start_transform(Mode::Rotate);
+            }
+            UiAction::Scale => {
+                state.start_transform(Mode::Scale);
+            }
+            UiAction::Mirror => {
+                state.start_transform(Mode::Mirror);
+            }
+            UiAction::Array => {
+                state.start_array();
+            }
+            UiAction::Stretch => {
+                state.start_stretch();
+            }
+            UiAction::Break => {
+                state.start_break();
+            }
+            UiAction::Join => {
+                state.start_restructure(Mode::Join);
+            }
+            UiAction::Explode => {
+                state.start_restructure(Mode::Explode);
+            }
+            UiAction::Overkill => {
+                state.start_overkill();
+            }
+            UiAction::ArrayChanged => {
+                state.refresh_array();
+            }
+            UiAction::ArrayApplied => {
+                state.finish_array();
+            }
+            UiAction::ArrayCancelled => {
+                state.cancel_array();
+            }
             UiAction::OpenFilePath(path) => {
                 let len = path.len();
                 let extension: &str = &path[len - 3..len];
@@ -134,15 +225,42 @@ pub fn render(state: &mut State) -> Result<(), wgpu::SurfaceError> {
                 };
             }
             UiAction::SaveFile => {
-                let saved = state.save_to_dxf();
-                match saved {
-                    Ok(_) => println!("file saved"),
-                    Err(error) => eprintln!("i/o error while saving file: {}", error),
-                };
+                state.save();
+            }
+            UiAction::SaveFileAs => {
+                state.save_as();
+            }
+            UiAction::Undo | UiAction::Redo => {
+                if !state.is_mid_operation() {
+                    if matches!(action, UiAction::Undo) {
+                        state.undo();
+                    } else {
+                        state.redo();
+                    }
+                }
             }
             UiAction::ChangeTheme => {
-                state.update_instance_buffer();
-                state.update_circle_instance_buffer();
+                state.update_instance_buffers();
+            }
+            UiAction::Input(value) if matches!(state.mode, Mode::Fillet(_) | Mode::Chamfer(_)) => {
+                state.set_corner_size(&value);
+            }
+            UiAction::Input(value)
+                if matches!(state.mode, Mode::DrawRectangle(_) | Mode::DrawPolygon(_)) =>
+            {
+                state.shape_input(&value);
+            }
+            UiAction::Input(value) if state.mode == Mode::Overkill => {
+                state.set_overkill_tolerance(&value);
+            }
+            UiAction::Input(value)
+                if matches!(
+                    state.mode,
+                    Mode::Rotate(TransformState::Reference(_) | TransformState::Target(..))
+                        | Mode::Scale(TransformState::Reference(_) | TransformState::Target(..))
+                ) =>
+            {
+                state.transform_by(&value);
             }
             UiAction::Input(value) => {
                 let desired_value: f32 = value.parse().unwrap_or_else(|_err| {
@@ -150,52 +268,95 @@ pub fn render(state: &mut State) -> Result<(), wgpu::SurfaceError> {
                     0.0
                 });
 
-                if desired_value > 0.0 {
+                if desired_value > 0.0 && matches!(state.mode, Mode::Offset(_)) {
+                    state.offset_distance = Some(desired_value);
+                    state
+                        .ui
+                        .add_notification(&format!("Offset distance {}", desired_value));
+                } else if desired_value > 0.0 {
                     match state.drawing_state {
                         DrawingState::WaitingForSecondPoint(start_pos) => {
-                            if let Some(i) = state.active_line_index {
-                                let last_line = &mut state.lines[i];
-
-                                last_line.finish_line_with_length(start_pos, desired_value);
-
-                                state.active_line_index = None;
-                                state.drawing_state = DrawingState::Idle;
-                                state.update_instance_buffer();
-                            }
+                            state.document.finish_active_line(start_pos, desired_value);
+                            state.drawing_state = DrawingState::Idle;
+                            state.update_instance_buffers();
                         }
                         DrawingState::WaitingForRadius(_start_pos) => {
-                            if let Some(i) = state.active_circle_index {
-                                let last_circle = &mut state.circles[i];
-
-                                last_circle.finish_circle_with_radius(desired_value);
-
-                                state.active_circle_index = None;
+                            state.document.finish_active_circle(desired_value);
+                            state.drawing_state = DrawingState::Idle;
+                            state.update_instance_buffers();
+                        }
+                        // the grip goes that far from its anchor towards the cursor
+                        DrawingState::DraggingGrip(anchor) => {
+                            let cursor = state.cursor_position.unwrap_or(anchor);
+                            let cursor = grip_target(state, anchor, cursor);
+                            let [dx, dy] = [cursor[0] - anchor[0], cursor[1] - anchor[1]];
+                            let length = (dx * dx + dy * dy).sqrt();
+                            if length > 0.0 {
+                                let scale = desired_value / length;
+                                let target = [anchor[0] + dx * scale, anchor[1] + dy * scale];
+                                state.document.drag_grip(target);
+                                state.document.finish_grip_drag();
                                 state.drawing_state = DrawingState::Idle;
-                                state.update_circle_instance_buffer();
+                                state.update_instance_buffers();
                             }
                         }
-                        DrawingState::Idle => {}
+                        DrawingState::WaitingForEllipseOtherAxis(..) => {
+                            state.finish_ellipse(desired_value);
+                        }
+                        DrawingState::Idle
+                        | DrawingState::WaitingForArcSecondPoint(_)
+                        | DrawingState::WaitingForEllipseAxis(_)
+                        | DrawingState::WaitingForArcEnd(..)
+                        | DrawingState::WaitingForNextVertex => {}
                     }
                 }
             }
             UiAction::TextEdited(text) => {
-                if let Some(text_to_edit) = state.ui.texts.iter_mut().find(|t| t.editing) {
+                if let Some(text_to_edit) = state.document.texts.iter_mut().find(|t| t.editing) {
                     text_to_edit.contents = WidgetText::from(text.contents);
                     text_to_edit.annotative = text.annotative;
                     text_to_edit.editing = false;
+
+                    let edited = text_to_edit.clone();
+                    match text.original {
+                        Some(original) => {
+                            if original.contents.text() != edited.contents.text()
+                                || original.annotative != edited.annotative
+                            {
+                                state
+                                    .document
+                                    .record("Text", vec![Change::ModifyText(original, edited)]);
+                            }
+                        }
+                        None => state.document.record("Text", vec![Change::AddText(edited)]),
+                    }
                 }
             }
             UiAction::TextEditCancelled => {
-                if let Some(text_to_edit) = state.ui.texts.iter_mut().find(|t| t.editing) {
+                if let Some(text_to_edit) = state.document.texts.iter_mut().find(|t| t.editing) {
                     text_to_edit.editing = false;
+
+                    // a freshly placed text stays in the drawing even if editing is cancelled
+                    if state.ui.text_edited.original.is_none() {
+                        let created = text_to_edit.clone();
+                        state
+                            .document
+                            .record("Text", vec![Change::AddText(created)]);
+                    }
                 }
             }
         }
     }
 
     if buffers_need_update {
-        state.update_instance_buffer();
-        state.update_circle_instance_buffer();
+        if !edits.is_empty() {
+            state
+                .document
+                .history
+                .push_or_merge(Command::new("Properties", edits));
+        }
+
+        state.update_instance_buffers();
     }
 
     state.queue.submit(iter::once(encoder.finish()));
@@ -1,24 +1,38 @@
 use egui_wgpu::wgpu;
 
-mod compiler;
+pub mod compiler;
 mod events;
 mod graphics;
-mod model;
+pub mod model;
 
-use crate::model::circle::flatten_circles_to_instances;
-use crate::model::line::flatten_lines_to_instances;
+use crate::model::entity::flatten_entities_to_instances;
 use events::input;
 use graphics::camera;
 use graphics::gui;
 use graphics::pipeline::Pipeline;
 use graphics::renderer;
 use graphics::vertex::Vertex;
-use model::circle::Circle;
-use model::circle::CircleOps;
+use model::array::{ArrayKind, ArrayOps};
+use model::document::Document;
+use model::ellipse::{Ellipse, EllipseOps};
+use model::entity::Entity;
+use model::fillet::Chamfer;
+use model::geometry::BoundingBox;
+use model::geometry::{distance, sub};
+use model::grip::{GripOps, GRIP_SIZE};
+use model::history::HistoryOps;
+use model::join::JoinOps;
 use model::line::flatten_lines;
 use model::line::Line;
-
-use compiler::compiler::Compiler;
+use model::overkill::{OverkillOps, DEFAULT_OVERKILL_TOLERANCE};
+use model::path::EPSILON;
+use model::polyline::PolylineVertex;
+use model::selection::SelectionOps;
+use model::shape::{polygon, rectangle, Corners, ShapeOps, MAX_POLYGON_SIDES};
+use model::snap::{Snap, SnapEngine};
+use model::stretch::StretchOps;
+use model::style::Style;
+use model::transform::{Transform, TransformOps};
 
 use egui_wgpu::wgpu::util::DeviceExt;
 use egui_winit::winit;
@@ -28,19 +42,11 @@ use egui_winit::winit::{
     window::{Window, WindowBuilder},
 };
 use gui::EguiRenderer;
-use model::line::LineOps;
 use winit::keyboard::ModifiersState;
 use winit::window::CursorIcon;
 
-use dxf::entities::EntityType;
-use dxf::entities::*;
-use dxf::Drawing;
-
-use std::fs;
-use std::time::Instant as OtherInstant;
-
 use crate::graphics::gui_elements::ColorScheme;
-use crate::graphics::gui_elements::UiState;
+use crate::graphics::gui_elements::{UiMode, UiState};
 
 const AXIS_COORDINATES: [Vertex; 4] = [
     Vertex {
@@ -61,11 +67,47 @@ const AXIS_COORDINATES: [Vertex; 4] = [
     },
 ];
 
+// a line of the snap marker or a grip in pixels, `selected` draws it red
+fn indicator(a: [f32; 2], b: [f32; 2], selected: bool) -> Line {
+    Line {
+        vertices: [
+            Vertex {
+                position: [a[0], a[1], 0.0],
+                color: [1.0, 1.0, 1.0],
+            },
+            Vertex {
+                position: [b[0], b[1], 0.0],
+                color: [1.0, 1.0, 1.0],
+            },
+        ],
+        id: 0,
+        style: Style {
+            thickness_by_layer: false,
+            ..Style::new(0, 1.0)
+        },
+        selected,
+        del: false,
+        is_drawing: false,
+    }
+}
+
 #[derive(Debug)]
 enum DrawingState {
     Idle,
     WaitingForSecondPoint([f32; 2]),
     WaitingForRadius([f32; 2]),
+    // first point of an arc placed, no preview yet
+    WaitingForArcSecondPoint([f32; 2]),
+    // two points placed, the arc follows the cursor
+    WaitingForArcEnd([f32; 2], [f32; 2]),
+    // an end of the first axis, or the center, placed
+    WaitingForEllipseAxis([f32; 2]),
+    // the center and the end of the first axis, the other axis follows the cursor
+    WaitingForEllipseOtherAxis([f32; 2], [f32; 2]),
+    // polyline started, its last vertex follows the cursor
+    WaitingForNextVertex,
+    // a grip of a selected entity follows the cursor, typed lengths go from the anchor
+    DraggingGrip([f32; 2]),
 }
 
 #[derive(Debug, PartialEq)]
@@ -77,9 +119,30 @@ enum Mode {
     // Measure(Option<[Vertex; 2]>),
     Measure(Option<[f32; 2]>),
     DrawLine(DrawLineMode),
+    DrawArc(DrawArcMode),
+    DrawEllipse(DrawEllipseMode),
+    DrawPolyline(DrawPolylineMode),
+    // the first corner once it's placed
+    DrawRectangle(Option<[f32; 2]>),
+    // the center once it's placed
+    DrawPolygon(Option<[f32; 2]>),
     Move(FuncState),
     Copy(FuncState),
     CreateText,
+    Trim(TrimState),
+    Extend(TrimState),
+    Offset(OffsetState),
+    Fillet(CornerState),
+    Chamfer(CornerState),
+    Rotate(TransformState),
+    Scale(TransformState),
+    Mirror(TransformState),
+    Array(ArrayState),
+    Stretch(StretchState),
+    Break(BreakState),
+    Join,
+    Explode,
+    Overkill,
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
@@ -88,6 +151,83 @@ enum DrawLineMode {
     Ortho, // 0, 90, 180, 270 degrees
 }
 
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum DrawArcMode {
+    ThreePoint,     // start, a point on the arc, end
+    CenterStartEnd, // center, start (sets the radius), end direction
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum DrawEllipseMode {
+    AxisEnd, // both ends of one axis, then how far out the other one goes
+    Center,  // center, end of one axis, how far out the other one goes
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum DrawPolylineMode {
+    Line,
+    Arc, // segments continue tangent to the previous one
+}
+
+// trim and extend first take the edges to cut at or extend to, then what to cut off or extend
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum TrimState {
+    SelectEdges,
+    Pick,
+}
+
+// offset takes the entity to copy, then the side to put the copy on
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum OffsetState {
+    Pick,
+    Side(u64),
+}
+
+// fillet and chamfer take two lines, or two segments of a polyline, and where each was picked
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum CornerState {
+    First,
+    Second(u64, [f32; 2]),
+}
+
+// Rotate, scale and mirror take a selection, a base point (the first point of the mirror axis) and
+// then the angle, factor or second axis point from the cursor or typed in. Rotate and scale can
+// measure against a reference angle or length from the base point instead.
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum TransformState {
+    Selection,
+    BasePoint,
+    // base point, waiting for the end of the reference
+    Reference([f32; 2]),
+    // base point and the end of the reference if there is one
+    Target([f32; 2], Option<[f32; 2]>),
+}
+
+// an array takes a selection, then the dialog sets it up while the preview shows the copies
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum ArrayState {
+    Selection,
+    Setup,
+}
+
+// Stretch takes a crossing window, then a base point and where it goes. Only the vertices inside
+// the window follow.
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum StretchState {
+    Window,
+    BasePoint(BoundingBox),
+    // the window and the base point
+    Displace(BoundingBox, [f32; 2]),
+}
+
+// break takes the entity and the first point on it, then the second point or Enter to split it
+// right there
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum BreakState {
+    Pick,
+    Second(u64, [f32; 2]),
+}
+
 #[derive(PartialEq, PartialOrd, Debug)]
 enum FuncState {
     Selection,
@@ -106,28 +246,49 @@ struct State<'a> {
 
     render_pipeline: wgpu::RenderPipeline,
     render_pipeline2: wgpu::RenderPipeline,
+    render_pipeline_arc: wgpu::RenderPipeline,
     xy_axis_render_pipeline: wgpu::RenderPipeline,
 
     ui: UiState,
 
     // line instance buffer
     instance_buffer: wgpu::Buffer,
+    num_line_instances: u32,
     // circle instance buffer
     instance_buffer_circle: wgpu::Buffer,
+    num_circle_instances: u32,
+    // arc instance buffer
+    instance_buffer_arc: wgpu::Buffer,
+    num_arc_instances: u32,
     axis_vertex_buffer: wgpu::Buffer,
 
-    lines: Vec<Line>,
-    // next_line_id: u64,
-    active_line_index: Option<usize>,
-    active_circle_index: Option<usize>,
-
-    circles: Vec<Circle>,
+    document: Document,
     indicators: Vec<Line>,
     num_vertices_indicators: u32,
+    // grips of the selection and whether each is being dragged
+    grips: Vec<([f32; 2], bool)>,
+    // edges picked for trim/extend, none means everything in the drawing
+    trim_edges: Vec<u64>,
+    // typed offset distance, without one an offset goes through the clicked point
+    offset_distance: Option<f32>,
+    // polyline offsets get round corners where the sides move apart
+    offset_round: bool,
+    fillet_radius: f32,
+    chamfer: Chamfer,
+    // mirror leaves the originals where they are
+    mirror_keep_source: bool,
+    // ends and sizes closer than this are the same for overkill
+    overkill_tolerance: f32,
+    // rectangles get sharp, rounded or cut corners
+    rectangle_corners: Corners,
+    polygon_sides: usize,
+    // the polygon's corners are on the circle through the cursor, otherwise its sides touch it
+    polygon_inscribed: bool,
 
     drawing_state: DrawingState,
     mode: Mode,
     snap: Option<[f32; 2]>,
+    snap_engine: SnapEngine,
 
     // cursor position in world coordinates
     cursor_position: Option<[f32; 2]>,
@@ -231,12 +392,35 @@ impl<'a> State<'a> {
 
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("line shader module"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("assets/line.wgsl").into()),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(
+                    include_str!("assets/line.wgsl"),
+                    include_str!("assets/dashes.wgsl")
+                )
+                .into(),
+            ),
         });
 
         let circle_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("circle shader module"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("assets/circle.wgsl").into()),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(
+                    include_str!("assets/circle.wgsl"),
+                    include_str!("assets/dashes.wgsl")
+                )
+                .into(),
+            ),
+        });
+
+        let arc_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("arc shader module"),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(
+                    include_str!("assets/arc.wgsl"),
+                    include_str!("assets/dashes.wgsl")
+                )
+                .into(),
+            ),
         });
 
         let xy_axis_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
@@ -244,23 +428,27 @@ impl<'a> State<'a> {
             source: wgpu::ShaderSource::Wgsl(include_str!("assets/xy_axis.wgsl").into()),
         });
 
-        let lines = Vec::new();
         let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("lines instance buffer"),
             usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
             contents: &[],
         });
 
-        let circles = Vec::new();
         let instance_buffer_circle = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("circle instance buffer"),
             usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
             contents: &[],
         });
 
+        let instance_buffer_arc = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("arc instance buffer"),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            contents: &[],
+        });
+
         let snap = None;
 
-        let mut indicators = Vec::new();
+        let indicators = Vec::new();
 
         let axis_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("vertex buffer"),
@@ -283,6 +471,9 @@ impl<'a> State<'a> {
             &camera_bind_group_layout,
         )
         .render_pipeline;
+        let render_pipeline_arc =
+            Pipeline::new_arc_pipeline(&device, &config, &arc_shader, &camera_bind_group_layout)
+                .render_pipeline;
         let xy_axis_render_pipeline = Pipeline::new_xy_axis_pipeline(
             &device,
             &config,
@@ -301,26 +492,6 @@ impl<'a> State<'a> {
 
         let dragging = false;
 
-        for _i in 0..4 {
-            indicators.push(Line {
-                vertices: [
-                    Vertex {
-                        position: [0.0, 0.0, 0.0],
-                        color: [1.0, 1.0, 1.0],
-                    },
-                    Vertex {
-                        position: [0.0, 0.0, 0.0],
-                        color: [1.0, 1.0, 1.0],
-                    },
-                ],
-                // id: i,
-                thickness: 1.0,
-                selected: false,
-                del: false,
-                is_drawing: false,
-            });
-        }
-
         Self {
             window,
             queue,
@@ -331,27 +502,39 @@ impl<'a> State<'a> {
 
             render_pipeline,
             render_pipeline2,
+            render_pipeline_arc,
             xy_axis_render_pipeline,
 
             ui: UiState::new(),
 
             instance_buffer,
+            num_line_instances: 0,
             instance_buffer_circle,
+            num_circle_instances: 0,
+            instance_buffer_arc,
+            num_arc_instances: 0,
             axis_vertex_buffer,
 
-            lines,
-            // next_line_id: 0,
-            active_line_index: None,
-            active_circle_index: None,
-
-            circles,
+            document: Document::new(),
             indicators,
+            grips: Vec::new(),
+            trim_edges: Vec::new(),
+            offset_distance: None,
+            offset_round: false,
+            fillet_radius: 0.0,
+            chamfer: Chamfer::Distances(0.0, 0.0),
+            mirror_keep_source: true,
+            overkill_tolerance: DEFAULT_OVERKILL_TOLERANCE,
+            rectangle_corners: Corners::Sharp,
+            polygon_sides: 6,
+            polygon_inscribed: true,
 
             num_vertices_indicators: 0,
 
             drawing_state: DrawingState::Idle,
             mode: Mode::Normal,
             snap,
+            snap_engine: SnapEngine::default(),
             cursor_position: None,
             last_position_for_pan: None,
             last_screen_position_for_pan: None,
@@ -377,37 +560,58 @@ impl<'a> State<'a> {
         }
     }
 
-    // update lines instance buffer
-    pub fn update_instance_buffer(&mut self) {
+    // rebuild the instance buffers of every pipeline from the entity store
+    pub fn update_instance_buffers(&mut self) {
+        let batch = flatten_entities_to_instances(&self.document, self.ui.theme.color_scheme);
+        self.grips = self.document.shown_grips();
+
         self.instance_buffer = self
             .device
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("instance buffer"),
                 usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-                contents: bytemuck::cast_slice(&flatten_lines_to_instances(
-                    &mut self.lines,
-                    self.ui.theme.color_scheme,
-                )),
+                contents: bytemuck::cast_slice(&batch.lines),
             });
-    }
+        self.num_line_instances = batch.lines.len() as u32;
 
-    // update circle instance buffer
-    pub fn update_circle_instance_buffer(&mut self) {
         self.instance_buffer_circle =
             self.device
                 .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                     label: Some("circle instance buffer"),
                     usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-                    contents: bytemuck::cast_slice(&flatten_circles_to_instances(
-                        &mut self.circles,
-                        self.ui.theme.color_scheme,
-                        // self.camera.zoom,
-                    )),
+                    contents: bytemuck::cast_slice(&batch.circles),
                 });
+        self.num_circle_instances = batch.circles.len() as u32;
+
+        self.instance_buffer_arc =
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("arc instance buffer"),
+                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                    contents: bytemuck::cast_slice(&batch.arcs),
+                });
+        self.num_arc_instances = batch.arcs.len() as u32;
     }
 
     pub fn update_axis_vertex_buffer(&mut self) {
-        let flat_indicators = flatten_lines(&mut self.indicators, self.ui.theme.color_scheme);
+        let mut flat_indicators = flatten_lines(&mut self.indicators, self.ui.theme.color_scheme);
+
+        // grips stay the same size on screen, the one being dragged is red
+        let zoom = self.camera.zoom;
+        let mut grips: Vec<Line> = Vec::with_capacity(self.grips.len() * 4);
+        for (position, hot) in &self.grips {
+            let [x, y] = [position[0] * zoom, position[1] * zoom];
+            let corners = [
+                [x - GRIP_SIZE, y - GRIP_SIZE],
+                [x + GRIP_SIZE, y - GRIP_SIZE],
+                [x + GRIP_SIZE, y + GRIP_SIZE],
+                [x - GRIP_SIZE, y + GRIP_SIZE],
+            ];
+            for i in 0..4 {
+                grips.push(indicator(corners[i], corners[(i + 1) % 4], *hot));
+            }
+        }
+        flat_indicators.extend(flatten_lines(&mut grips, self.ui.theme.color_scheme));
 
         let mut out = Vec::with_capacity(4 + flat_indicators.len());
         if self.ui.axis_active {
@@ -415,7 +619,7 @@ impl<'a> State<'a> {
         }
         out.extend_from_slice(&flat_indicators);
 
-        if self.ui.theme.color_scheme == ColorScheme::Light {
+        if self.ui.axis_active && self.ui.theme.color_scheme == ColorScheme::Light {
             out[2].color = [0.0, 0.0, 0.0];
             out[3].color = [0.0, 0.0, 0.0];
         }
@@ -430,25 +634,45 @@ impl<'a> State<'a> {
         self.num_vertices_indicators = out.len() as u32;
     }
 
-    pub fn move_indicators_to_cursor(&mut self, point: [f32; 2]) {
-        let treshold: f32 = 10.0;
+    // draws the marker of `snap` around its point, nothing if there's none
+    pub fn show_snap_marker(&mut self, snap: Option<Snap>) {
+        // half the marker's size and the dash and gap of an extension's path, in pixels
+        let size: f32 = 6.0;
+        let dash: f32 = 4.0;
 
-        for i in 0..4 {
-            let base_x = point[0] * self.camera.zoom;
-            let base_y = point[1] * self.camera.zoom;
+        self.indicators.clear();
+        let Some(snap) = snap else {
+            return;
+        };
 
-            let (dx1, dy1, dx2, dy2) = match i {
-                0 => (1.0, 1.0, -1.0, 1.0),
-                1 => (-1.0, 1.0, -1.0, -1.0),
-                2 => (1.0, -1.0, -1.0, -1.0),
-                3 => (1.0, 1.0, 1.0, -1.0),
-                _ => return,
-            };
+        let zoom = self.camera.zoom;
+        let center = [snap.point[0] * zoom, snap.point[1] * zoom];
+        let mut segments: Vec<[[f32; 2]; 2]> = snap
+            .kind
+            .marker()
+            .into_iter()
+            .map(|segment| segment.map(|p| [center[0] + p[0] * size, center[1] + p[1] * size]))
+            .collect();
+
+        if let Some(from) = snap.from {
+            let from = [from[0] * zoom, from[1] * zoom];
+            let length = distance(from, center);
+            let steps = ((length / (2.0 * dash)) as usize).min(500);
+            for step in 0..steps {
+                let t0 = step as f32 * 2.0 * dash / length;
+                let t1 = t0 + dash / length;
+                let at = |t: f32| {
+                    [
+                        from[0] + (center[0] - from[0]) * t,
+                        from[1] + (center[1] - from[1]) * t,
+                    ]
+                };
+                segments.push([at(t0), at(t1)]);
+            }
+        }
 
-            self.indicators[i].vertices[0].position[0] = base_x + dx1 * treshold;
-            self.indicators[i].vertices[0].position[1] = base_y + dy1 * treshold;
-            self.indicators[i].vertices[1].position[0] = base_x + dx2 * treshold;
-            self.indicators[i].vertices[1].position[1] = base_y + dy2 * treshold;
+        for [a, b] in segments {
+            self.indicators.push(indicator(a, b, false));
         }
     }
 
@@ -461,79 +685,1022 @@ impl<'a> State<'a> {
         renderer::render(self)
     }
 
-    pub fn save_to_dxf(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let mut drawing = Drawing::new();
+    pub fn delete_selected(&mut self) {
+        self.document.delete_selected();
+        self.update_instance_buffers();
+    }
+
+    pub fn unselect_all(&mut self) {
+        self.document.unselect_all();
+        self.update_instance_buffers();
+    }
+
+    // true while a line/circle is being drawn or a move/copy/rotate/... is being previewed
+    pub fn is_mid_operation(&self) -> bool {
+        !matches!(self.drawing_state, DrawingState::Idle)
+            || matches!(
+                self.mode,
+                Mode::Move(FuncState::Move(_)) | Mode::Copy(FuncState::Copy(_))
+            )
+            || self.document.active_transform.is_some()
+            || self.document.active_array.is_some()
+            || self.document.active_stretch.is_some()
+            || self.document.active_shape_id.is_some()
+    }
+
+    pub fn cancel_move(&mut self) {
+        self.document.cancel_move();
+        self.update_instance_buffers();
+    }
+
+    // whatever is selected already counts as an edge
+    pub fn start_trim(&mut self, extend: bool) {
+        if !matches!(self.mode, Mode::Normal | Mode::Selection) || self.is_mid_operation() {
+            return;
+        }
+
+        if extend {
+            self.mode = Mode::Extend(TrimState::SelectEdges);
+            self.ui
+                .add_notification("Select boundary edges, Enter when done (none for all)");
+        } else {
+            self.mode = Mode::Trim(TrimState::SelectEdges);
+            self.ui
+                .add_notification("Select cutting edges, Enter when done (none for all)");
+        }
+    }
+
+    // the selection becomes the edges, from now on clicks trim or extend
+    pub fn finish_edge_selection(&mut self) {
+        self.trim_edges = self
+            .document
+            .entities
+            .iter()
+            .filter(|entity| entity.selected())
+            .map(Entity::id)
+            .collect();
+        self.unselect_all();
+
+        if let Mode::Extend(_) = self.mode {
+            self.mode = Mode::Extend(TrimState::Pick);
+            self.ui.add_notification("Click near the ends to extend");
+        } else {
+            self.mode = Mode::Trim(TrimState::Pick);
+            self.ui.add_notification("Click the parts to cut off");
+        }
+    }
 
-        for line_entity in &self.lines {
-            let start_position = line_entity.vertices[0].position;
-            let end_position = line_entity.vertices[1].position;
+    pub fn start_offset(&mut self) {
+        if !matches!(self.mode, Mode::Normal | Mode::Selection) || self.is_mid_operation() {
+            return;
+        }
 
-            let line = dxf::entities::Line::new(
-                dxf::Point::new(start_position[0] as f64, start_position[1] as f64, 0.0),
-                dxf::Point::new(end_position[0] as f64, end_position[1] as f64, 0.0),
+        self.unselect_all();
+        self.mode = Mode::Offset(OffsetState::Pick);
+        match self.offset_distance {
+            Some(distance) => self.ui.add_notification(&format!(
+                "Offset {}: click an object, type a new distance or press T to go through a point",
+                distance
+            )),
+            None => self
+                .ui
+                .add_notification("Offset through a point: click an object or type a distance"),
+        }
+    }
+
+    pub fn start_corner(&mut self, chamfer: bool) {
+        if !matches!(self.mode, Mode::Normal | Mode::Selection) || self.is_mid_operation() {
+            return;
+        }
+
+        self.unselect_all();
+        if chamfer {
+            self.mode = Mode::Chamfer(CornerState::First);
+            self.ui.add_notification(&format!(
+                "Chamfer {}: click two lines, or type distances like 2, 2,3 or 2<45",
+                self.chamfer.describe()
+            ));
+        } else {
+            self.mode = Mode::Fillet(CornerState::First);
+            self.ui.add_notification(&format!(
+                "Fillet radius {}: click two lines, or type a new radius",
+                self.fillet_radius
+            ));
+        }
+    }
+
+    // a radius or chamfer distances typed while filleting or chamfering
+    pub fn set_corner_size(&mut self, text: &str) {
+        if let Mode::Chamfer(_) = self.mode {
+            match Chamfer::parse(text) {
+                Some(chamfer) => {
+                    self.chamfer = chamfer;
+                    self.ui
+                        .add_notification(&format!("Chamfer {}", chamfer.describe()));
+                }
+                None => self
+                    .ui
+                    .add_notification("Type distances like 2 or 2,3 or 2<45"),
+            }
+            return;
+        }
+
+        match text.trim().parse::<f32>() {
+            Ok(radius) if radius >= 0.0 => {
+                self.fillet_radius = radius;
+                self.ui
+                    .add_notification(&format!("Fillet radius {}", radius));
+            }
+            _ => self.ui.add_notification("The radius has to be a number"),
+        }
+    }
+
+    // `mode` is Mode::Rotate, Mode::Scale or Mode::Mirror, a selection that's already there is used
+    pub fn start_transform(&mut self, mode: fn(TransformState) -> Mode) {
+        if !matches!(self.mode, Mode::Normal | Mode::Selection) || self.is_mid_operation() {
+            return;
+        }
+
+        self.mode = mode(TransformState::Selection);
+        if self.document.entities.iter().any(Entity::selected)
+            || self.document.texts.iter().any(|text| text.selected)
+        {
+            self.finish_transform_selection();
+        } else {
+            self.ui.add_notification("Select objects, Enter when done");
+        }
+    }
+
+    pub fn finish_transform_selection(&mut self) {
+        let nothing_selected = !self.document.entities.iter().any(Entity::selected)
+            && !self.document.texts.iter().any(|text| text.selected);
+        if nothing_selected {
+            self.ui.add_notification("Nothing is selected");
+            return;
+        }
+
+        match self.mode {
+            Mode::Rotate(_) => {
+                self.mode = Mode::Rotate(TransformState::BasePoint);
+                self.ui.add_notification("Click the point to rotate around");
+            }
+            Mode::Scale(_) => {
+                self.mode = Mode::Scale(TransformState::BasePoint);
+                self.ui.add_notification("Click the point to scale from");
+            }
+            Mode::Mirror(_) => {
+                self.mode = Mode::Mirror(TransformState::BasePoint);
+                self.ui
+                    .add_notification("Click the first point of the mirror axis");
+            }
+            _ => {}
+        }
+    }
+
+    // the selection starts following the cursor around `base`
+    pub fn set_transform_base(&mut self, base: [f32; 2]) {
+        let mirror = matches!(self.mode, Mode::Mirror(_));
+        // a mirror shows the copy next to the originals until it's clear whether they stay
+        if !self.document.start_transform(mirror) {
+            return;
+        }
+
+        match self.mode {
+            Mode::Rotate(_) => {
+                self.mode = Mode::Rotate(TransformState::Target(base, None));
+                self.ui
+                    .add_notification("Click or type the angle, R for a reference angle");
+            }
+            Mode::Scale(_) => {
+                self.mode = Mode::Scale(TransformState::Target(base, None));
+                self.ui
+                    .add_notification("Click or type the factor, R for a reference length");
+            }
+            Mode::Mirror(_) => {
+                self.mode = Mode::Mirror(TransformState::Target(base, None));
+                self.ui.add_notification(if self.mirror_keep_source {
+                    "Click the second point of the axis, D deletes the originals"
+                } else {
+                    "Click the second point of the axis, D keeps the originals"
+                });
+            }
+            _ => {}
+        }
+        self.update_instance_buffers();
+    }
+
+    // rotate and scale measure from `base` to a point clicked next instead of the x axis or 1
+    pub fn start_reference(&mut self) {
+        let base = match self.mode {
+            Mode::Rotate(TransformState::Target(base, _)) => {
+                self.mode = Mode::Rotate(TransformState::Reference(base));
+                self.ui
+                    .add_notification("Click or type the reference angle");
+                base
+            }
+            Mode::Scale(TransformState::Target(base, _)) => {
+                self.mode = Mode::Scale(TransformState::Reference(base));
+                self.ui
+                    .add_notification("Click or type the reference length");
+                base
+            }
+            _ => return,
+        };
+
+        // back where it started while the reference gets picked
+        self.document.preview_transform(&Transform::Scale {
+            center: base,
+            factor: 1.0,
+        });
+        self.update_instance_buffers();
+    }
+
+    pub fn set_reference(&mut self, point: [f32; 2]) {
+        match self.mode {
+            Mode::Rotate(TransformState::Reference(base)) if distance(base, point) > EPSILON => {
+                self.mode = Mode::Rotate(TransformState::Target(base, Some(point)));
+                self.ui.add_notification("Click or type the new angle");
+            }
+            Mode::Scale(TransformState::Reference(base)) if distance(base, point) > EPSILON => {
+                self.mode = Mode::Scale(TransformState::Target(base, Some(point)));
+                self.ui.add_notification("Click or type the new length");
+            }
+            _ => {}
+        }
+    }
+
+    pub fn toggle_mirror_source(&mut self) {
+        self.mirror_keep_source = !self.mirror_keep_source;
+        if self.mirror_keep_source {
+            self.ui.add_notification("Mirror: keep the originals");
+        } else {
+            self.ui.add_notification("Mirror: delete the originals");
+        }
+    }
+
+    // what the rotate/scale/mirror in progress does with the cursor at `point`
+    fn transform_through(&self, point: [f32; 2]) -> Option<Transform> {
+        match self.mode {
+            Mode::Rotate(TransformState::Target(center, reference)) => {
+                let angle_of = |p: [f32; 2]| (p[1] - center[1]).atan2(p[0] - center[0]);
+                (distance(center, point) > EPSILON).then(|| Transform::Rotate {
+                    center,
+                    angle: angle_of(point) - reference.map_or(0.0, angle_of),
+                })
+            }
+            Mode::Scale(TransformState::Target(center, reference)) => {
+                let length = reference.map_or(1.0, |reference| distance(center, reference));
+                let factor = distance(center, point) / length;
+                (factor > EPSILON).then_some(Transform::Scale { center, factor })
+            }
+            Mode::Mirror(TransformState::Target(a, _)) => {
+                (distance(a, point) > EPSILON).then_some(Transform::Mirror { a, b: point })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn preview_transform(&mut self, point: [f32; 2]) {
+        if let Some(transform) = self.transform_through(point) {
+            self.document.preview_transform(&transform);
+            self.update_instance_buffers();
+        }
+    }
+
+    pub fn apply_transform(&mut self, point: [f32; 2]) {
+        if let Some(transform) = self.transform_through(point) {
+            self.finish_transform(transform);
+        }
+    }
+
+    // An angle in degrees or a factor typed in. While picking a reference it's the reference
+    // angle or length instead.
+    pub fn transform_by(&mut self, text: &str) {
+        let Ok(value) = text.trim().parse::<f32>() else {
+            self.ui.add_notification(&format!(
+                "Couldn't read \"{}\" as a number, type an angle in degrees or a factor",
+                text.trim()
+            ));
+            return;
+        };
+
+        // a typed reference becomes a point that far from the base in that direction
+        let typed_point = |base: [f32; 2], angle: f32, length: f32| {
+            [
+                base[0] + length * angle.cos(),
+                base[1] + length * angle.sin(),
+            ]
+        };
+
+        match self.mode {
+            Mode::Rotate(TransformState::Reference(base)) => {
+                self.set_reference(typed_point(base, value.to_radians(), 1.0))
+            }
+            Mode::Scale(TransformState::Reference(_) | TransformState::Target(..))
+                if value <= EPSILON =>
+            {
+                self.ui
+                    .add_notification("A scale factor or length has to be more than 0")
+            }
+            Mode::Scale(TransformState::Reference(base)) => {
+                self.set_reference(typed_point(base, 0.0, value))
+            }
+            Mode::Rotate(TransformState::Target(center, reference)) => {
+                let reference =
+                    reference.map_or(0.0, |p| (p[1] - center[1]).atan2(p[0] - center[0]));
+                self.finish_transform(Transform::Rotate {
+                    center,
+                    angle: value.to_radians() - reference,
+                });
+            }
+            Mode::Scale(TransformState::Target(center, reference)) => {
+                let length = reference.map_or(1.0, |reference| distance(center, reference));
+                self.finish_transform(Transform::Scale {
+                    center,
+                    factor: value / length,
+                });
+            }
+            // nothing selected yet, or still waiting for the base point
+            _ => self
+                .ui
+                .add_notification("Select objects and pick a base point before typing a value"),
+        }
+    }
+
+    fn finish_transform(&mut self, transform: Transform) {
+        let name = match self.mode {
+            Mode::Rotate(_) => "Rotate",
+            Mode::Scale(_) => "Scale",
+            _ => "Mirror",
+        };
+        self.document
+            .finish_transform(name, &transform, self.mirror_keep_source);
+        self.mode = Mode::Normal;
+        self.snap = None;
+        self.indicators.clear();
+        self.update_instance_buffers();
+    }
+
+    pub fn cancel_transform(&mut self) {
+        self.document.cancel_transform();
+        self.update_instance_buffers();
+    }
+
+    pub fn start_array(&mut self) {
+        if !matches!(self.mode, Mode::Normal | Mode::Selection) || self.is_mid_operation() {
+            return;
+        }
+
+        self.mode = Mode::Array(ArrayState::Selection);
+        if self.document.entities.iter().any(Entity::selected)
+            || self.document.texts.iter().any(|text| text.selected)
+        {
+            self.finish_array_selection();
+        } else {
+            self.ui.add_notification("Select objects, Enter when done");
+        }
+    }
+
+    pub fn finish_array_selection(&mut self) {
+        if !self.document.entities.iter().any(Entity::selected)
+            && !self.document.texts.iter().any(|text| text.selected)
+        {
+            self.ui.add_notification("Nothing is selected");
+            return;
+        }
+
+        self.mode = Mode::Array(ArrayState::Setup);
+        self.ui.mode = UiMode::Array;
+        self.refresh_array();
+    }
+
+    // builds the preview again for what's in the dialog now
+    pub fn refresh_array(&mut self) {
+        if self.mode != Mode::Array(ArrayState::Setup) {
+            return;
+        }
+
+        self.document.preview_array(&self.ui.array);
+        self.update_instance_buffers();
+    }
+
+    // a click into the drawing while setting up picks the polar center or the path
+    pub fn pick_for_array(&mut self, position: [f32; 2], entity: Option<u64>) {
+        match self.ui.array.kind {
+            ArrayKind::Rectangular => return,
+            ArrayKind::Polar => self.ui.array.center = self.snap.unwrap_or(position),
+            ArrayKind::Path => {
+                // the selection and its copies can't be what they're copied along
+                let usable = entity
+                    .and_then(|id| self.document.entities.get(id))
+                    .is_some_and(|entity| !entity.selected() && !entity.is_drawing());
+                if !usable {
+                    return;
+                }
+                self.ui.array.path = entity;
+            }
+        }
+        self.refresh_array();
+    }
+
+    pub fn finish_array(&mut self) {
+        let count = self.document.finish_array();
+        self.ui.add_notification(&format!("{} copies added", count));
+        self.close_array();
+    }
+
+    pub fn cancel_array(&mut self) {
+        self.document.cancel_array();
+        self.close_array();
+    }
+
+    fn close_array(&mut self) {
+        self.document.unselect_all();
+        self.mode = Mode::Normal;
+        self.ui.mode = UiMode::Normal;
+        self.snap = None;
+        self.indicators.clear();
+        self.update_instance_buffers();
+    }
+
+    pub fn start_stretch(&mut self) {
+        if !matches!(self.mode, Mode::Normal | Mode::Selection) || self.is_mid_operation() {
+            return;
+        }
+
+        // the window decides what's stretched, an earlier selection would only get in the way
+        self.unselect_all();
+        self.mode = Mode::Stretch(StretchState::Window);
+        self.ui
+            .add_notification("Drag a crossing window around the points to move");
+    }
+
+    // what the window crosses is selected, what's inside it will move
+    pub fn set_stretch_window(&mut self, area: BoundingBox) {
+        if !self.document.entities.iter().any(Entity::selected)
+            && !self.document.texts.iter().any(|text| text.selected)
+        {
+            self.ui.add_notification("Nothing was crossed, try again");
+            return;
+        }
+
+        self.mode = Mode::Stretch(StretchState::BasePoint(area));
+        self.ui.add_notification("Click the base point");
+    }
+
+    pub fn set_stretch_base(&mut self, base: [f32; 2]) {
+        let Mode::Stretch(StretchState::BasePoint(area)) = self.mode else {
+            return;
+        };
+        if self.document.start_stretch(area) {
+            self.mode = Mode::Stretch(StretchState::Displace(area, base));
+            self.ui.add_notification("Click where the base point goes");
+            self.update_instance_buffers();
+        }
+    }
+
+    pub fn preview_stretch(&mut self, point: [f32; 2]) {
+        if let Mode::Stretch(StretchState::Displace(_, base)) = self.mode {
+            self.document
+                .preview_stretch([point[0] - base[0], point[1] - base[1]]);
+            self.update_instance_buffers();
+        }
+    }
+
+    pub fn finish_stretch(&mut self, point: [f32; 2]) {
+        if let Mode::Stretch(StretchState::Displace(_, base)) = self.mode {
+            self.document
+                .finish_stretch([point[0] - base[0], point[1] - base[1]]);
+            self.mode = Mode::Normal;
+            self.snap = None;
+            self.indicators.clear();
+            self.update_instance_buffers();
+        }
+    }
+
+    pub fn cancel_stretch(&mut self) {
+        self.document.cancel_stretch();
+        self.update_instance_buffers();
+    }
+
+    pub fn start_break(&mut self) {
+        if !matches!(self.mode, Mode::Normal | Mode::Selection) || self.is_mid_operation() {
+            return;
+        }
+
+        self.unselect_all();
+        self.mode = Mode::Break(BreakState::Pick);
+        self.ui.add_notification("Click the object where it breaks");
+    }
+
+    // the picked entity stays selected so it's clear what's going to break
+    pub fn pick_break(&mut self, id: u64, first: [f32; 2]) {
+        self.document.set_selected(&[id], true);
+        self.mode = Mode::Break(BreakState::Second(id, first));
+        self.ui
+            .add_notification("Click the second point, Enter breaks it at the first one");
+        self.update_instance_buffers();
+    }
+
+    pub fn finish_break(&mut self, second: Option<[f32; 2]>) {
+        let Mode::Break(BreakState::Second(id, first)) = self.mode else {
+            return;
+        };
+        if !self.document.break_at(id, first, second) {
+            self.ui.add_notification(
+                "A circle breaks between two points, anything else not at its end",
             );
+            return;
+        }
+
+        self.document.unselect_all();
+        self.mode = Mode::Break(BreakState::Pick);
+        self.snap = None;
+        self.indicators.clear();
+        self.update_instance_buffers();
+    }
+
+    // `mode` is Mode::Join or Mode::Explode, a selection that's already there is used right away
+    pub fn start_restructure(&mut self, mode: Mode) {
+        if !matches!(self.mode, Mode::Normal | Mode::Selection) || self.is_mid_operation() {
+            return;
+        }
 
-            drawing.add_entity(Entity::new(EntityType::Line(line)));
+        self.mode = mode;
+        if self.document.entities.iter().any(Entity::selected) {
+            self.finish_restructure();
+        } else {
+            self.ui.add_notification("Select objects, Enter when done");
         }
+    }
 
-        drawing.save_file("C:/Users/krist/Desktop/test.dxf")?;
+    pub fn finish_restructure(&mut self) {
+        let ids: Vec<u64> = self
+            .document
+            .entities
+            .iter()
+            .filter(|entity| entity.selected())
+            .map(Entity::id)
+            .collect();
+
+        match self.mode {
+            Mode::Join => {
+                let report = self.document.join(&ids);
+                if report.results == 0 {
+                    self.ui.add_notification("Nothing meets end to end");
+                } else {
+                    self.ui.add_notification(&format!(
+                        "{} objects joined into {}",
+                        report.entities, report.results
+                    ));
+                }
+            }
+            Mode::Explode => match self.document.explode(&ids) {
+                0 => self.ui.add_notification("No polylines selected"),
+                exploded => self
+                    .ui
+                    .add_notification(&format!("{} polylines exploded", exploded)),
+            },
+            _ => return,
+        }
 
-        Ok(())
+        self.document.unselect_all();
+        self.mode = Mode::Normal;
+        self.update_instance_buffers();
     }
 
-    pub fn load_from_cad(&mut self, file_path: String) -> Result<(), Box<dyn std::error::Error>> {
-        let src = fs::read_to_string(&file_path)?;
-        let mut compiler = Compiler::new();
+    // a selection that's already there is cleaned up right away
+    pub fn start_overkill(&mut self) {
+        if !matches!(self.mode, Mode::Normal | Mode::Selection) || self.is_mid_operation() {
+            return;
+        }
+
+        self.mode = Mode::Overkill;
+        if self.document.entities.iter().any(Entity::selected) {
+            self.finish_overkill();
+        } else {
+            self.ui.add_notification(&format!(
+                "Overkill tolerance {}: select objects and Enter, Enter alone for the whole \
+                 drawing, or type a new tolerance",
+                self.overkill_tolerance
+            ));
+        }
+    }
+
+    pub fn set_overkill_tolerance(&mut self, text: &str) {
+        match text.trim().parse::<f32>() {
+            Ok(tolerance) if tolerance > 0.0 => {
+                self.overkill_tolerance = tolerance;
+                self.ui
+                    .add_notification(&format!("Overkill tolerance {}", tolerance));
+            }
+            _ => self
+                .ui
+                .add_notification("It has to be a number more than 0"),
+        }
+    }
+
+    // cleans up the selection, or everything shown if nothing is selected
+    pub fn finish_overkill(&mut self) {
+        let selected: Vec<u64> = self
+            .document
+            .entities
+            .iter()
+            .filter(|entity| entity.selected())
+            .map(Entity::id)
+            .collect();
+        let ids = if selected.is_empty() {
+            self.document
+                .entities
+                .iter()
+                .filter(|entity| self.document.layers.is_selectable(entity.layer()))
+                .map(Entity::id)
+                .collect()
+        } else {
+            selected
+        };
+
+        let report = self.document.overkill(&ids, self.overkill_tolerance);
+        if report.total() == 0 {
+            self.ui.add_notification("Nothing to clean up");
+        } else {
+            self.ui.add_notification(&format!(
+                "Removed {} duplicates, {} overlapping lines and {} without length or radius",
+                report.duplicates, report.merged, report.degenerate
+            ));
+        }
+
+        self.document.unselect_all();
+        self.mode = Mode::Normal;
+        self.update_instance_buffers();
+    }
 
-        println!("Compiling {}...", file_path);
-        for (i, line) in src.lines().enumerate() {
-            let trimmed_line = line.trim();
-            if trimmed_line.is_empty() {
-                continue;
+    // starts an ellipse, or switches how it's picked if no point has been placed yet
+    pub fn start_ellipse(&mut self) {
+        let ellipse_mode = match self.mode {
+            Mode::DrawEllipse(DrawEllipseMode::AxisEnd)
+                if matches!(self.drawing_state, DrawingState::Idle) =>
+            {
+                DrawEllipseMode::Center
+            }
+            Mode::DrawEllipse(_) if matches!(self.drawing_state, DrawingState::Idle) => {
+                DrawEllipseMode::AxisEnd
+            }
+            Mode::Normal | Mode::Selection if !self.is_mid_operation() => {
+                self.unselect_all();
+                DrawEllipseMode::AxisEnd
             }
+            _ => return,
+        };
 
-            if let Err(e) = compiler.process_line(self, line, i) {
-                eprintln!("error: {e}");
+        self.mode = Mode::DrawEllipse(ellipse_mode);
+        match ellipse_mode {
+            DrawEllipseMode::AxisEnd => self
+                .ui
+                .add_notification("Ellipse: both ends of an axis, then the other axis"),
+            DrawEllipseMode::Center => self
+                .ui
+                .add_notification("Ellipse: center, end of an axis, then the other axis"),
+        }
+    }
+
+    // the first click places an axis end or the center, the second one the first axis, the third
+    // one finishes the ellipse
+    pub fn place_ellipse_point(&mut self, point: [f32; 2]) {
+        let Mode::DrawEllipse(ellipse_mode) = self.mode else {
+            return;
+        };
+
+        match self.drawing_state {
+            DrawingState::Idle => {
+                self.drawing_state = DrawingState::WaitingForEllipseAxis(point);
+            }
+            DrawingState::WaitingForEllipseAxis(first) if distance(first, point) > EPSILON => {
+                let (center, axis_end) = match ellipse_mode {
+                    DrawEllipseMode::AxisEnd => (
+                        [(first[0] + point[0]) / 2.0, (first[1] + point[1]) / 2.0],
+                        point,
+                    ),
+                    DrawEllipseMode::Center => (first, point),
+                };
+                self.document
+                    .add_ellipse(Ellipse::from_axes(center, axis_end, 0.0), true);
+                self.drawing_state = DrawingState::WaitingForEllipseOtherAxis(center, axis_end);
+                self.ui
+                    .add_notification("Click or type how far out the other axis goes");
+                self.update_instance_buffers();
+            }
+            DrawingState::WaitingForEllipseOtherAxis(center, _) => {
+                self.finish_ellipse(distance(center, point));
             }
+            _ => {}
         }
+    }
 
-        Ok(())
+    // the ellipse with its other axis reaching `point`
+    pub fn preview_ellipse(&mut self, point: [f32; 2]) {
+        if let DrawingState::WaitingForEllipseOtherAxis(center, axis_end) = self.drawing_state {
+            let shape = Ellipse::from_axes(center, axis_end, distance(center, point));
+            self.document.update_ellipse(shape, true);
+            self.update_instance_buffers();
+        }
     }
 
-    pub fn load_from_dxf(&mut self, file_path: String) -> Result<(), Box<dyn std::error::Error>> {
-        let time_to_load_drawing = OtherInstant::now();
-        println!("loading...");
-        let drawing = Drawing::load_file(file_path)?;
-        println!("drawing took: {:?}", time_to_load_drawing.elapsed());
-
-        let now = OtherInstant::now();
-        for e in drawing.entities() {
-            println!("entity: {:?}", e);
-            match e.specific {
-                EntityType::Line(ref line) => {
-                    self.add_line(
-                        [line.p1.x as f32, line.p1.y as f32],
-                        [line.p2.x as f32, line.p2.y as f32],
-                        false,
-                    );
+    // `other` is half the length of the second axis
+    pub fn finish_ellipse(&mut self, other: f32) {
+        let DrawingState::WaitingForEllipseOtherAxis(center, axis_end) = self.drawing_state else {
+            return;
+        };
+        if other <= EPSILON {
+            return;
+        }
+
+        let shape = Ellipse::from_axes(center, axis_end, other);
+        self.document.update_ellipse(shape, false);
+        self.drawing_state = DrawingState::Idle;
+        self.update_instance_buffers();
+    }
+
+    pub fn start_rectangle(&mut self) {
+        if !matches!(self.mode, Mode::Normal | Mode::Selection) || self.is_mid_operation() {
+            return;
+        }
+
+        self.unselect_all();
+        self.mode = Mode::DrawRectangle(None);
+        self.ui
+            .add_notification("Click the first corner, F rounds the corners and C cuts them off");
+    }
+
+    pub fn start_polygon(&mut self) {
+        if !matches!(self.mode, Mode::Normal | Mode::Selection) || self.is_mid_operation() {
+            return;
+        }
+
+        self.unselect_all();
+        self.mode = Mode::DrawPolygon(None);
+        self.ui.add_notification(&format!(
+            "{} sides, {}: click the center, type the number of sides, I switches",
+            self.polygon_sides,
+            if self.polygon_inscribed {
+                "inscribed"
+            } else {
+                "circumscribed"
+            }
+        ));
+    }
+
+    // switches between sharp corners and rounded (or cut off) ones, sized like Fillet (Chamfer)
+    pub fn toggle_rectangle_corners(&mut self, chamfer: bool) {
+        self.rectangle_corners = match (self.rectangle_corners, chamfer) {
+            (Corners::Fillet(_), false) | (Corners::Chamfer(..), true) => Corners::Sharp,
+            (_, false) => Corners::Fillet(self.fillet_radius),
+            (_, true) => {
+                let (first, second) = self
+                    .chamfer
+                    .distances(std::f32::consts::FRAC_PI_2)
+                    .unwrap_or((0.0, 0.0));
+                Corners::Chamfer(first, second)
+            }
+        };
+
+        match self.rectangle_corners {
+            Corners::Sharp => self.ui.add_notification("Sharp corners"),
+            Corners::Fillet(radius) => self.ui.add_notification(&format!(
+                "Rounded corners, radius {}: type a new radius",
+                radius
+            )),
+            Corners::Chamfer(..) => self.ui.add_notification(&format!(
+                "Cut off corners, {}: type new distances",
+                self.chamfer.describe()
+            )),
+        }
+        self.refresh_shape();
+    }
+
+    pub fn toggle_polygon_inscribed(&mut self) {
+        self.polygon_inscribed = !self.polygon_inscribed;
+        if self.polygon_inscribed {
+            self.ui
+                .add_notification("Inscribed, the corners go through the cursor");
+        } else {
+            self.ui
+                .add_notification("Circumscribed, the sides touch the cursor");
+        }
+        self.refresh_shape();
+    }
+
+    fn shape_through(&self, point: [f32; 2]) -> Option<(&'static str, Vec<PolylineVertex>)> {
+        match self.mode {
+            Mode::DrawRectangle(Some(corner))
+                if (point[0] - corner[0]).abs() > EPSILON
+                    && (point[1] - corner[1]).abs() > EPSILON =>
+            {
+                Some((
+                    "Rectangle",
+                    rectangle(corner, point, self.rectangle_corners),
+                ))
+            }
+            Mode::DrawPolygon(Some(center)) if distance(center, point) > EPSILON => Some((
+                "Polygon",
+                polygon(center, point, self.polygon_sides, self.polygon_inscribed),
+            )),
+            _ => None,
+        }
+    }
+
+    pub fn preview_shape(&mut self, point: [f32; 2]) {
+        if let Some((_, vertices)) = self.shape_through(point) {
+            self.document.preview_shape(vertices);
+            self.update_instance_buffers();
+        }
+    }
+
+    // shows a changed corner style or polygon right away instead of on the next cursor move
+    fn refresh_shape(&mut self) {
+        if let Some(cursor) = self.cursor_position {
+            let point = self.snap.unwrap_or(cursor);
+            self.preview_shape(point);
+        }
+    }
+
+    // the first click places the corner or center, the second one finishes the shape
+    pub fn place_shape_point(&mut self, point: [f32; 2]) {
+        match self.mode {
+            Mode::DrawRectangle(None) => {
+                self.mode = Mode::DrawRectangle(Some(point));
+                self.ui
+                    .add_notification("Click the other corner or type width,height");
+            }
+            Mode::DrawPolygon(None) => {
+                self.mode = Mode::DrawPolygon(Some(point));
+                self.ui.add_notification("Click or type the radius");
+            }
+            _ => {
+                if let Some((name, vertices)) = self.shape_through(point) {
+                    self.finish_shape(name, vertices);
                 }
-                EntityType::Circle(ref circle) => {
-                    self.add_circle(
-                        [circle.center.x as f32, circle.center.y as f32],
-                        circle.radius as f32,
-                        [1.0, 1.0, 1.0],
-                        false,
-                        false,
-                        false,
-                    );
+            }
+        }
+    }
+
+    // Corner sizes or the number of sides before the first click, the width,height or radius
+    // after it.
+    pub fn shape_input(&mut self, text: &str) {
+        let number = |text: &str| text.trim().parse::<f32>().ok().filter(|v| *v > 0.0);
+        let cursor = self.cursor_position.unwrap_or([0.0, 0.0]);
+
+        match (&self.mode, self.rectangle_corners) {
+            (Mode::DrawRectangle(None), Corners::Fillet(_)) => match number(text) {
+                Some(radius) => {
+                    self.fillet_radius = radius;
+                    self.rectangle_corners = Corners::Fillet(radius);
+                    self.ui
+                        .add_notification(&format!("Corner radius {}", radius));
                 }
-                _ => {}
+                None => self.ui.add_notification("The radius has to be a number"),
+            },
+            (Mode::DrawRectangle(None), Corners::Chamfer(..)) => {
+                let distances = Chamfer::parse(text).and_then(|chamfer| {
+                    Some((chamfer, chamfer.distances(std::f32::consts::FRAC_PI_2)?))
+                });
+                match distances {
+                    Some((chamfer, (first, second))) => {
+                        self.chamfer = chamfer;
+                        self.rectangle_corners = Corners::Chamfer(first, second);
+                        self.ui
+                            .add_notification(&format!("Corners cut off {}", chamfer.describe()));
+                    }
+                    None => self
+                        .ui
+                        .add_notification("Type distances like 2, 2,3 or 2<45"),
+                }
+            }
+            (&Mode::DrawRectangle(Some(corner)), _) => {
+                let size = match text.split_once(',') {
+                    Some((width, height)) => number(width).zip(number(height)),
+                    None => number(text).map(|side| (side, side)),
+                };
+                let Some((width, height)) = size else {
+                    self.ui
+                        .add_notification("Type width,height, or one number for a square");
+                    return;
+                };
+                // towards wherever the cursor is from the first corner
+                let [dx, dy] = sub(cursor, corner);
+                let other = [
+                    corner[0] + width * if dx < 0.0 { -1.0 } else { 1.0 },
+                    corner[1] + height * if dy < 0.0 { -1.0 } else { 1.0 },
+                ];
+                let vertices = rectangle(corner, other, self.rectangle_corners);
+                self.finish_shape("Rectangle", vertices);
+            }
+            (Mode::DrawPolygon(None), _) => match text.trim().parse::<usize>() {
+                Ok(sides) if (3..=MAX_POLYGON_SIDES).contains(&sides) => {
+                    self.polygon_sides = sides;
+                    self.ui.add_notification(&format!("{} sides", sides));
+                }
+                _ => self
+                    .ui
+                    .add_notification(&format!("A polygon has 3 to {} sides", MAX_POLYGON_SIDES)),
+            },
+            (&Mode::DrawPolygon(Some(center)), _) => {
+                let Some(radius) = number(text) else {
+                    self.ui.add_notification("The radius has to be a number");
+                    return;
+                };
+                // a corner, or the middle of a side, towards the cursor
+                let [dx, dy] = sub(cursor, center);
+                let angle = if dx.abs() + dy.abs() > EPSILON {
+                    dy.atan2(dx)
+                } else {
+                    0.0
+                };
+                let through = [
+                    center[0] + radius * angle.cos(),
+                    center[1] + radius * angle.sin(),
+                ];
+                let vertices = polygon(center, through, self.polygon_sides, self.polygon_inscribed);
+                self.finish_shape("Polygon", vertices);
+            }
+            _ => self
+                .ui
+                .add_notification("Press F or C first to type a corner size"),
+        }
+    }
+
+    // the shape goes into the drawing and the next one can be started right away
+    fn finish_shape(&mut self, name: &'static str, vertices: Vec<PolylineVertex>) {
+        self.document.finish_shape(name, vertices);
+        self.mode = match self.mode {
+            Mode::DrawPolygon(_) => Mode::DrawPolygon(None),
+            _ => Mode::DrawRectangle(None),
+        };
+        self.snap = None;
+        self.indicators.clear();
+        self.update_instance_buffers();
+    }
+
+    pub fn cancel_shape(&mut self) {
+        self.document.cancel_shape();
+        self.update_instance_buffers();
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(name) = self.document.undo() {
+            self.ui.add_notification(&format!("Undo {}", name));
+            self.update_instance_buffers();
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(name) = self.document.redo() {
+            self.ui.add_notification(&format!("Redo {}", name));
+            self.update_instance_buffers();
+        }
+    }
+
+    // saves over the file the document came from, asks where to put it the first time
+    pub fn save(&mut self) {
+        match self.document.file_path.clone() {
+            Some(path) => self.save_to_dxf(path),
+            None => self.save_as(),
+        }
+    }
+
+    pub fn save_as(&mut self) {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter(".dxf", &["dxf"])
+            .set_file_name("drawing.dxf")
+            .save_file()
+        {
+            self.save_to_dxf(path.with_extension("dxf").display().to_string());
+        }
+    }
+
+    fn save_to_dxf(&mut self, path: String) {
+        match self.document.save_to_dxf(&path) {
+            Ok(_) => {
+                self.ui.add_notification(&format!("Saved {}", path));
+                self.document.file_path = Some(path);
+            }
+            Err(error) => {
+                eprintln!("i/o error while saving file: {}", error);
+                self.ui.add_notification("Saving failed");
             }
         }
+    }
 
-        println!("{:?}", self.active_line_index);
-        println!("⏱ now took: {:?}", now.elapsed());
+    pub fn load_from_cad(&mut self, file_path: String) -> Result<(), Box<dyn std::error::Error>> {
+        let loaded = self.document.load_from_cad(file_path);
+        self.update_instance_buffers();
+        loaded
+    }
 
+    pub fn load_from_dxf(&mut self, file_path: String) -> Result<(), Box<dyn std::error::Error>> {
+        let summary = self.document.load_from_dxf(file_path)?;
+        self.ui.add_notification(&summary.message());
+        self.update_instance_buffers();
         Ok(())
     }
 }
@@ -551,8 +1718,7 @@ pub async fn run() {
 
     let mut state = State::new(&window).await;
 
-    state.update_instance_buffer();
-    state.update_circle_instance_buffer();
+    state.update_instance_buffers();
     event_loop
         .run(move |event, control_flow| {
             match event {
@@ -595,7 +1761,12 @@ pub async fn run() {
                             | Mode::DrawLine(DrawLineMode::Ortho) => {
                                 state.window.set_cursor_icon(CursorIcon::Crosshair);
                             }
-                            Mode::DrawCircle => {
+                            Mode::DrawCircle
+                            | Mode::DrawArc(_)
+                            | Mode::DrawEllipse(_)
+                            | Mode::DrawPolyline(_)
+                            | Mode::DrawRectangle(_)
+                            | Mode::DrawPolygon(_) => {
                                 state.window.set_cursor_icon(CursorIcon::Crosshair);
                             }
                             Mode::Selection => {
@@ -616,6 +1787,42 @@ pub async fn run() {
                             Mode::CreateText => {
                                 state.window.set_cursor_icon(CursorIcon::Crosshair);
                             }
+                            Mode::Trim(_)
+                            | Mode::Extend(_)
+                            | Mode::Offset(_)
+                            | Mode::Fillet(_)
+                            | Mode::Chamfer(_) => {
+                                state.window.set_cursor_icon(CursorIcon::Pointer);
+                            }
+                            Mode::Rotate(TransformState::Selection)
+                            | Mode::Scale(TransformState::Selection)
+                            | Mode::Mirror(TransformState::Selection) => {
+                                state.window.set_cursor_icon(CursorIcon::Pointer);
+                            }
+                            Mode::Rotate(_) | Mode::Scale(_) | Mode::Mirror(_) => {
+                                state.window.set_cursor_icon(CursorIcon::Crosshair);
+                            }
+                            Mode::Array(ArrayState::Selection) => {
+                                state.window.set_cursor_icon(CursorIcon::Pointer);
+                            }
+                            Mode::Array(ArrayState::Setup) => {
+                                state.window.set_cursor_icon(CursorIcon::Crosshair);
+                            }
+                            Mode::Stretch(StretchState::Window) => {
+                                state.window.set_cursor_icon(CursorIcon::Pointer);
+                            }
+                            Mode::Stretch(_) => {
+                                state.window.set_cursor_icon(CursorIcon::Move);
+                            }
+                            Mode::Break(BreakState::Pick)
+                            | Mode::Join
+                            | Mode::Explode
+                            | Mode::Overkill => {
+                                state.window.set_cursor_icon(CursorIcon::Pointer);
+                            }
+                            Mode::Break(BreakState::Second(..)) => {
+                                state.window.set_cursor_icon(CursorIcon::Crosshair);
+                            }
                         }
                     }
                 }
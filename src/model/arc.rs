@@ -0,0 +1,427 @@
+use crate::model::document::Document;
+use crate::model::entity::{Entity, Geometry, Property, RenderBatch, Stroke};
+use crate::model::geometry::{along, distance, line_circle, BoundingBox};
+use crate::model::grip::Grip;
+use crate::model::history::{Change, HistoryOps};
+use crate::model::linetype::Dashes;
+use crate::model::polyline::arc_from_bulge;
+use crate::model::style::Style;
+use crate::model::transform::Transform;
+use dxf::entities::EntityType;
+use egui_wgpu::wgpu;
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+// angles are in radians and the arc always runs counter-clockwise from start to end, same as DXF
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Arc {
+    pub id: u64,
+    pub center: [f32; 2],
+    pub radius: f32,
+    pub start_angle: f32,
+    pub end_angle: f32,
+    pub selected: bool,
+    pub del: bool,
+    pub is_drawing: bool,
+    pub style: Style,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ArcInstance {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+    pub radius: f32,
+    pub thickness: f32,
+    pub start_angle: f32,
+    pub sweep: f32,
+    pub dashes: Dashes,
+}
+
+impl Arc {
+    pub fn new(center: [f32; 2], radius: f32, start_angle: f32, end_angle: f32) -> Self {
+        Self {
+            id: 0,
+            center,
+            radius,
+            start_angle: start_angle.rem_euclid(TAU),
+            end_angle: end_angle.rem_euclid(TAU),
+            selected: false,
+            del: false,
+            is_drawing: false,
+            style: Style::new(0, 2.0),
+        }
+    }
+
+    // arc starting at `start`, passing through `mid` and ending at `end`
+    // None if the points are on one line
+    pub fn through_points(start: [f32; 2], mid: [f32; 2], end: [f32; 2]) -> Option<Self> {
+        let [ax, ay] = start;
+        let [bx, by] = mid;
+        let [cx, cy] = end;
+
+        let d = 2.0 * (ax * (by - cy) + bx * (cy - ay) + cx * (ay - by));
+        if d.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let a2 = ax * ax + ay * ay;
+        let b2 = bx * bx + by * by;
+        let c2 = cx * cx + cy * cy;
+        let center = [
+            (a2 * (by - cy) + b2 * (cy - ay) + c2 * (ay - by)) / d,
+            (a2 * (cx - bx) + b2 * (ax - cx) + c2 * (bx - ax)) / d,
+        ];
+
+        let angle_of = |p: [f32; 2]| (p[1] - center[1]).atan2(p[0] - center[0]);
+        let arc = Arc::new(
+            center,
+            distance(center, start),
+            angle_of(start),
+            angle_of(end),
+        );
+
+        // the points may have been picked clockwise, then the arc runs the other way around
+        if arc.contains_angle(angle_of(mid)) {
+            Some(arc)
+        } else {
+            Some(Arc::new(center, arc.radius, arc.end_angle, arc.start_angle))
+        }
+    }
+
+    // arc around `center`, from the direction of `start` to the direction of `end`
+    pub fn from_center(center: [f32; 2], start: [f32; 2], end: [f32; 2]) -> Self {
+        let start_angle = (start[1] - center[1]).atan2(start[0] - center[0]);
+        let end_angle = (end[1] - center[1]).atan2(end[0] - center[0]);
+
+        Arc::new(center, distance(center, start), start_angle, end_angle)
+    }
+
+    pub fn sweep(&self) -> f32 {
+        (self.end_angle - self.start_angle).rem_euclid(TAU)
+    }
+
+    pub fn length(&self) -> f32 {
+        self.radius * self.sweep()
+    }
+
+    pub fn contains_angle(&self, angle: f32) -> bool {
+        (angle - self.start_angle).rem_euclid(TAU) <= self.sweep()
+    }
+
+    pub fn point_at(&self, angle: f32) -> [f32; 2] {
+        [
+            self.center[0] + self.radius * angle.cos(),
+            self.center[1] + self.radius * angle.sin(),
+        ]
+    }
+
+    pub fn start_point(&self) -> [f32; 2] {
+        self.point_at(self.start_angle)
+    }
+
+    pub fn end_point(&self) -> [f32; 2] {
+        self.point_at(self.end_angle)
+    }
+
+    pub fn mid_point(&self) -> [f32; 2] {
+        self.point_at(self.start_angle + self.sweep() / 2.0)
+    }
+}
+
+impl Geometry for Arc {
+    fn hit_distance(&self, point: [f32; 2]) -> f32 {
+        let angle = (point[1] - self.center[1]).atan2(point[0] - self.center[0]);
+
+        if self.contains_angle(angle) {
+            (distance(point, self.center) - self.radius).abs()
+        } else {
+            distance(point, self.start_point()).min(distance(point, self.end_point()))
+        }
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        let mut bbox = BoundingBox::new(self.start_point(), self.end_point());
+
+        // the arc bulges out past its endpoints wherever it crosses an axis
+        for quadrant in 0..4 {
+            let angle = quadrant as f32 * FRAC_PI_2;
+            if self.contains_angle(angle) {
+                let p = self.point_at(angle);
+                bbox = bbox.union(&BoundingBox::new(p, p));
+            }
+        }
+
+        bbox
+    }
+
+    fn crosses(&self, area: &BoundingBox) -> bool {
+        if area.contains_point(self.start_point()) || area.contains_point(self.end_point()) {
+            return true;
+        }
+
+        // both ends are outside, so it has to cut through an edge
+        area.edges().iter().any(|&(from, to)| {
+            line_circle((from, to), self.center, self.radius)
+                .into_iter()
+                .any(|p| {
+                    along(p, from, to).is_some_and(|t| (0.0..=1.0).contains(&t))
+                        && self.contains_angle((p[1] - self.center[1]).atan2(p[0] - self.center[0]))
+                })
+        })
+    }
+
+    fn snap_points(&self) -> Vec<[f32; 2]> {
+        vec![
+            self.start_point(),
+            self.end_point(),
+            self.mid_point(),
+            self.center,
+        ]
+    }
+
+    // start, middle and end reshape the arc through the other two, the center moves it
+    fn grips(&self) -> Vec<Grip> {
+        vec![
+            Grip::new(self.start_point()),
+            Grip::new(self.mid_point()),
+            Grip::new(self.end_point()),
+            Grip::new(self.center),
+        ]
+    }
+
+    fn move_grip(&mut self, index: usize, position: [f32; 2]) {
+        let mut points = [self.start_point(), self.mid_point(), self.end_point()];
+        if index >= points.len() {
+            self.translate(position[0] - self.center[0], position[1] - self.center[1]);
+            return;
+        }
+
+        points[index] = position;
+        if let Some(arc) = Arc::through_points(points[0], points[1], points[2]) {
+            self.center = arc.center;
+            self.radius = arc.radius;
+            self.start_angle = arc.start_angle;
+            self.end_angle = arc.end_angle;
+        }
+    }
+
+    fn translate(&mut self, dx: f32, dy: f32) {
+        self.center[0] += dx;
+        self.center[1] += dy;
+    }
+
+    // the ends inside move and the arc keeps how far around it goes, same as a polyline arc
+    fn stretch(&mut self, area: &BoundingBox, by: [f32; 2]) {
+        let (start, end) = (self.start_point(), self.end_point());
+        let moved = |p: [f32; 2]| {
+            if area.contains_point(p) {
+                [p[0] + by[0], p[1] + by[1]]
+            } else {
+                p
+            }
+        };
+        let (new_start, new_end) = (moved(start), moved(end));
+        if new_start == start && new_end == end {
+            return;
+        }
+        if new_start != start && new_end != end {
+            self.translate(by[0], by[1]);
+            return;
+        }
+
+        let stretched = arc_from_bulge(new_start, new_end, (self.sweep() / 4.0).tan());
+        self.center = stretched.center;
+        self.radius = stretched.radius;
+        self.start_angle = stretched.start_angle;
+        self.end_angle = stretched.end_angle;
+    }
+
+    fn transform(&mut self, transform: &Transform) {
+        self.center = transform.apply(self.center);
+        self.radius = transform.length(self.radius);
+        let (start, end) = (
+            transform.angle(self.start_angle),
+            transform.angle(self.end_angle),
+        );
+        // mirrored, the arc runs clockwise from start to end, so they trade places
+        (self.start_angle, self.end_angle) = if transform.flips() {
+            (end, start)
+        } else {
+            (start, end)
+        };
+    }
+
+    fn properties(&self) -> Vec<Property> {
+        vec![
+            Property::new("Thickness", self.style.thickness, 0.1, true),
+            Property::new("Radius", self.radius, 0.1, true),
+            Property::new("Start angle", self.start_angle.to_degrees(), 1.0, false),
+            Property::new("End angle", self.end_angle.to_degrees(), 1.0, false),
+            Property::new("Center X coordinate", self.center[0], 1.0, false),
+            Property::new("Center Y coordinate", self.center[1], 1.0, false),
+        ]
+    }
+
+    fn set_property(&mut self, name: &str, value: f32) {
+        match name {
+            "Thickness" => self.style.thickness = value,
+            "Radius" => self.radius = value,
+            "Start angle" => self.start_angle = value.to_radians().rem_euclid(TAU),
+            "End angle" => self.end_angle = value.to_radians().rem_euclid(TAU),
+            "Center X coordinate" => self.center[0] = value,
+            "Center Y coordinate" => self.center[1] = value,
+            _ => {}
+        }
+    }
+
+    fn to_dxf(&self) -> EntityType {
+        EntityType::Arc(dxf::entities::Arc::new(
+            dxf::Point::new(self.center[0] as f64, self.center[1] as f64, 0.0),
+            self.radius as f64,
+            self.start_angle.to_degrees() as f64,
+            self.end_angle.to_degrees() as f64,
+        ))
+    }
+
+    fn push_instances(&self, batch: &mut RenderBatch, stroke: Stroke) {
+        batch.arcs.push(ArcInstance {
+            position: [self.center[0], self.center[1], 0.0],
+            color: stroke.color,
+            radius: self.radius,
+            thickness: stroke.thickness,
+            start_angle: self.start_angle,
+            sweep: self.sweep(),
+            dashes: stroke.dashes,
+        });
+    }
+}
+
+impl ArcInstance {
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<ArcInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                // center
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                // color
+                wgpu::VertexAttribute {
+                    offset: 12,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                // radius
+                wgpu::VertexAttribute {
+                    offset: 24,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                // thickness
+                wgpu::VertexAttribute {
+                    offset: 28,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                // start angle
+                wgpu::VertexAttribute {
+                    offset: 32,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                // sweep
+                wgpu::VertexAttribute {
+                    offset: 36,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                // dash pattern, four elements each
+                wgpu::VertexAttribute {
+                    offset: 40,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: 56,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: 72,
+                    shader_location: 8,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                // pattern elements used
+                wgpu::VertexAttribute {
+                    offset: 88,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                // pattern offset
+                wgpu::VertexAttribute {
+                    offset: 92,
+                    shader_location: 10,
+                    format: wgpu::VertexFormat::Float32,
+                },
+            ],
+        }
+    }
+}
+
+pub trait ArcOps {
+    fn add_arc(&mut self, arc: Arc, is_drawing: bool) -> u64;
+    fn update_arc(&mut self, shape: Arc, is_drawing_flag: bool);
+    fn cancel_drawing_arc(&mut self);
+}
+
+impl ArcOps for Document {
+    fn add_arc(&mut self, arc: Arc, is_drawing: bool) -> u64 {
+        let id = self.next_id();
+
+        self.entities.insert(
+            id,
+            Entity::Arc(Arc {
+                id,
+                is_drawing,
+                style: Style::new(self.current_layer, self.settings.line_thickness),
+                ..arc
+            }),
+        );
+
+        if is_drawing {
+            self.active_arc_id = Some(id);
+        }
+
+        id
+    }
+
+    // gives the arc being drawn the geometry of `shape`
+    fn update_arc(&mut self, shape: Arc, is_drawing_flag: bool) {
+        if let Some(arc) = self
+            .active_arc_id
+            .and_then(|id| self.entities.get_mut(id))
+            .and_then(Entity::as_arc_mut)
+        {
+            arc.center = shape.center;
+            arc.radius = shape.radius;
+            arc.start_angle = shape.start_angle;
+            arc.end_angle = shape.end_angle;
+            arc.is_drawing = is_drawing_flag;
+            let finished_arc = *arc;
+
+            if !is_drawing_flag {
+                self.active_arc_id = None;
+                self.record("Arc", vec![Change::Add(Entity::Arc(finished_arc))]);
+            }
+        }
+    }
+
+    fn cancel_drawing_arc(&mut self) {
+        if let Some(id) = self.active_arc_id.take() {
+            self.entities.remove(id);
+        }
+    }
+}
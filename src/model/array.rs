@@ -0,0 +1,420 @@
+use crate::model::document::Document;
+use crate::model::entity::{Entity, Geometry};
+use crate::model::geometry::{sub, BoundingBox};
+use crate::model::history::{Change, HistoryOps};
+use crate::model::path::{Path, EPSILON};
+use crate::model::text::Text;
+use crate::model::transform::Transform;
+use std::collections::HashSet;
+
+// an array with more copies than this is almost certainly a typo
+pub const MAX_ARRAY_ITEMS: usize = 10_000;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ArrayKind {
+    Rectangular,
+    Polar,
+    Path,
+}
+
+// everything the array dialog edits, kept from one array to the next
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ArraySettings {
+    pub kind: ArrayKind,
+    pub rows: usize,
+    pub columns: usize,
+    // negative spacings go down and to the left
+    pub row_spacing: f32,
+    pub column_spacing: f32,
+    // items of a polar or path array, the selection itself is the first one
+    pub count: usize,
+    pub center: [f32; 2],
+    // in degrees, counter-clockwise for a positive angle
+    pub fill_angle: f32,
+    // polar items turn around the center, path items with the path
+    pub rotate_items: bool,
+    // line, arc, circle or polyline the items go along
+    pub path: Option<u64>,
+    // path items this far apart instead of `count` of them spread over the whole path
+    pub path_spacing: Option<f32>,
+}
+
+impl Default for ArraySettings {
+    fn default() -> Self {
+        Self {
+            kind: ArrayKind::Rectangular,
+            rows: 2,
+            columns: 3,
+            row_spacing: 10.0,
+            column_spacing: 10.0,
+            count: 6,
+            center: [0.0, 0.0],
+            fill_angle: 360.0,
+            rotate_items: true,
+            path: None,
+            path_spacing: None,
+        }
+    }
+}
+
+impl ArraySettings {
+    // What takes the selection to each copy, `base` is the middle of the selection. The selection
+    // itself isn't in there.
+    pub fn placements(&self, base: [f32; 2], path: Option<&Path>) -> Vec<Vec<Transform>> {
+        match self.kind {
+            ArrayKind::Rectangular => (0..self.rows)
+                .flat_map(|row| (0..self.columns).map(move |column| (row, column)))
+                .skip(1)
+                .take(MAX_ARRAY_ITEMS)
+                .map(|(row, column)| {
+                    vec![Transform::Translate {
+                        by: [
+                            column as f32 * self.column_spacing,
+                            row as f32 * self.row_spacing,
+                        ],
+                    }]
+                })
+                .collect(),
+            ArrayKind::Polar => {
+                let count = self.count.min(MAX_ARRAY_ITEMS);
+                // a full circle would put the last item on top of the first one
+                let step = if self.fill_angle.abs() >= 360.0 - EPSILON {
+                    self.fill_angle / count as f32
+                } else {
+                    self.fill_angle / count.saturating_sub(1).max(1) as f32
+                };
+
+                (1..count)
+                    .map(|i| {
+                        let turn = Transform::Rotate {
+                            center: self.center,
+                            angle: (step * i as f32).to_radians(),
+                        };
+                        if self.rotate_items {
+                            vec![turn]
+                        } else {
+                            vec![Transform::Translate {
+                                by: sub(turn.apply(base), base),
+                            }]
+                        }
+                    })
+                    .collect()
+            }
+            ArrayKind::Path => {
+                let Some(path) = path else {
+                    return Vec::new();
+                };
+                let length = path.arc_length();
+                let distances: Vec<f32> = match self.path_spacing {
+                    Some(spacing) if spacing > EPSILON => (1..MAX_ARRAY_ITEMS)
+                        .map(|i| i as f32 * spacing)
+                        .take_while(|walked| *walked <= length + EPSILON)
+                        .collect(),
+                    Some(_) => Vec::new(),
+                    None => {
+                        let count = self.count.min(MAX_ARRAY_ITEMS);
+                        // a closed path comes back around to the first item
+                        let gaps = if path.closed {
+                            count
+                        } else {
+                            count.saturating_sub(1).max(1)
+                        };
+                        (1..count)
+                            .map(|i| length * i as f32 / gaps as f32)
+                            .collect()
+                    }
+                };
+
+                let angle_of = |d: [f32; 2]| d[1].atan2(d[0]);
+                let (start, start_direction) = path.at_distance(0.0);
+                distances
+                    .into_iter()
+                    .map(|walked| {
+                        let (point, direction) = path.at_distance(walked);
+                        let mut item = vec![Transform::Translate {
+                            by: sub(point, start),
+                        }];
+                        if self.rotate_items {
+                            item.push(Transform::Rotate {
+                                center: point,
+                                angle: angle_of(direction) - angle_of(start_direction),
+                            });
+                        }
+                        item
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+// the preview's entities and texts, they're thrown away and built again on every change
+#[derive(Clone, Debug, Default)]
+pub struct ArrayPreview {
+    pub entities: Vec<u64>,
+    pub texts: Vec<u64>,
+}
+
+pub trait ArrayOps {
+    // shows the copies `settings` make of the selection, in place of the last preview
+    fn preview_array(&mut self, settings: &ArraySettings);
+    // the copies in the preview become part of the drawing, returns how many there are
+    fn finish_array(&mut self) -> usize;
+    fn cancel_array(&mut self);
+}
+
+fn clear_preview(document: &mut Document) {
+    if let Some(preview) = document.active_array.take() {
+        let entities: HashSet<u64> = preview.entities.into_iter().collect();
+        let texts: HashSet<u64> = preview.texts.into_iter().collect();
+        document
+            .entities
+            .retain(|entity| !entities.contains(&entity.id()));
+        document.texts.retain(|text| !texts.contains(&text.id));
+    }
+}
+
+impl ArrayOps for Document {
+    fn preview_array(&mut self, settings: &ArraySettings) {
+        clear_preview(self);
+
+        let originals: Vec<Entity> = self
+            .entities
+            .iter()
+            .filter(|entity| entity.selected())
+            .cloned()
+            .collect();
+        let texts: Vec<Text> = self
+            .texts
+            .iter()
+            .filter(|text| text.selected)
+            .cloned()
+            .collect();
+
+        let bounds = originals
+            .iter()
+            .map(Entity::bounding_box)
+            .chain(
+                texts
+                    .iter()
+                    .map(|text| BoundingBox::around(text.position, 0.0)),
+            )
+            .reduce(|a, b| a.union(&b));
+        let Some(bounds) = bounds else {
+            return;
+        };
+        let base = [
+            (bounds.min[0] + bounds.max[0]) / 2.0,
+            (bounds.min[1] + bounds.max[1]) / 2.0,
+        ];
+        let path = settings
+            .path
+            .and_then(|id| self.entities.get(id))
+            .and_then(Path::of);
+
+        let mut preview = ArrayPreview::default();
+        for item in settings.placements(base, path.as_ref()) {
+            for original in &originals {
+                let mut copy = original.clone();
+                for transform in &item {
+                    copy.transform(transform);
+                }
+                copy.set_selected(false);
+                // keeps snaps and picking off the copies until they're for real
+                copy.set_drawing(true);
+                preview.entities.push(self.add_entity(copy));
+            }
+            // texts only get moved, same as with rotate
+            for original in &texts {
+                let mut copy = original.clone();
+                copy.id = self.next_id();
+                copy.selected = false;
+                for transform in &item {
+                    copy.position = transform.apply(copy.position);
+                }
+                preview.texts.push(copy.id);
+                self.texts.push(copy);
+            }
+        }
+
+        self.active_array = Some(preview);
+    }
+
+    fn finish_array(&mut self) -> usize {
+        let Some(preview) = self.active_array.take() else {
+            return 0;
+        };
+
+        let mut changes = Vec::new();
+        for id in &preview.entities {
+            if let Some(entity) = self.entities.get_mut(*id) {
+                entity.set_drawing(false);
+                changes.push(Change::Add(entity.clone()));
+            }
+        }
+        let texts: HashSet<u64> = preview.texts.into_iter().collect();
+        changes.extend(
+            self.texts
+                .iter()
+                .filter(|text| texts.contains(&text.id))
+                .map(|text| Change::AddText(text.clone())),
+        );
+
+        let count = changes.len();
+        self.record("Array", changes);
+        count
+    }
+
+    fn cancel_array(&mut self) {
+        clear_preview(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::circle::CircleOps;
+    use crate::model::line::LineOps;
+
+    // a selected circle of radius 1 at `center`
+    fn selected_circle(center: [f32; 2]) -> (Document, u64) {
+        let mut document = Document::new();
+        let id = document.add_circle(center, 1.0, true, false, false);
+        (document, id)
+    }
+
+    fn centers(document: &Document) -> Vec<[f32; 2]> {
+        document
+            .entities
+            .iter()
+            .filter_map(|entity| match entity {
+                Entity::Circle(circle) => {
+                    Some([circle.center.position[0], circle.center.position[1]])
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn has_center(centers: &[[f32; 2]], point: [f32; 2]) -> bool {
+        centers
+            .iter()
+            .any(|c| (c[0] - point[0]).abs() < 1e-3 && (c[1] - point[1]).abs() < 1e-3)
+    }
+
+    #[test]
+    fn rectangular_arrays_fill_the_grid() {
+        let (mut document, _) = selected_circle([0.0, 0.0]);
+        let settings = ArraySettings {
+            rows: 2,
+            columns: 3,
+            row_spacing: 5.0,
+            column_spacing: -10.0,
+            ..ArraySettings::default()
+        };
+
+        document.preview_array(&settings);
+        assert_eq!(document.finish_array(), 5);
+
+        let centers = centers(&document);
+        assert_eq!(centers.len(), 6);
+        for point in [[-10.0, 0.0], [-20.0, 0.0], [0.0, 5.0], [-20.0, 5.0]] {
+            assert!(has_center(&centers, point), "{:?}", point);
+        }
+        assert!(document.entities.iter().all(|entity| !entity.is_drawing()));
+    }
+
+    #[test]
+    fn a_new_preview_replaces_the_last_one() {
+        let (mut document, _) = selected_circle([0.0, 0.0]);
+
+        document.preview_array(&ArraySettings::default());
+        assert_eq!(document.entities.len(), 6);
+        let settings = ArraySettings {
+            rows: 1,
+            columns: 2,
+            ..ArraySettings::default()
+        };
+        document.preview_array(&settings);
+        assert_eq!(document.entities.len(), 2);
+
+        document.cancel_array();
+        assert_eq!(document.entities.len(), 1);
+        assert!(document.active_array.is_none());
+        assert!(!document.history.can_undo());
+    }
+
+    #[test]
+    fn polar_arrays_go_around_the_center() {
+        let (mut document, _) = selected_circle([5.0, 0.0]);
+        let settings = ArraySettings {
+            kind: ArrayKind::Polar,
+            count: 4,
+            ..ArraySettings::default()
+        };
+
+        document.preview_array(&settings);
+        assert_eq!(document.finish_array(), 3);
+
+        let centers = centers(&document);
+        for point in [[0.0, 5.0], [-5.0, 0.0], [0.0, -5.0]] {
+            assert!(has_center(&centers, point), "{:?}", point);
+        }
+    }
+
+    #[test]
+    fn a_partial_fill_angle_ends_on_the_last_item() {
+        let (mut document, _) = selected_circle([5.0, 0.0]);
+        let settings = ArraySettings {
+            kind: ArrayKind::Polar,
+            count: 3,
+            fill_angle: 180.0,
+            ..ArraySettings::default()
+        };
+
+        document.preview_array(&settings);
+        document.finish_array();
+
+        let centers = centers(&document);
+        assert!(has_center(&centers, [0.0, 5.0]));
+        assert!(has_center(&centers, [-5.0, 0.0]));
+    }
+
+    #[test]
+    fn path_arrays_spread_along_the_path() {
+        let (mut document, _) = selected_circle([0.0, 0.0]);
+        let path = document.add_line([0.0, 0.0], [30.0, 0.0], false);
+        let settings = ArraySettings {
+            kind: ArrayKind::Path,
+            count: 4,
+            path: Some(path),
+            ..ArraySettings::default()
+        };
+
+        document.preview_array(&settings);
+        assert_eq!(document.finish_array(), 3);
+        let centers = centers(&document);
+        for point in [[10.0, 0.0], [20.0, 0.0], [30.0, 0.0]] {
+            assert!(has_center(&centers, point), "{:?}", point);
+        }
+
+        // a spacing instead of a count stops at the end of the path
+        let spaced = ArraySettings {
+            path_spacing: Some(12.0),
+            ..settings
+        };
+        document.preview_array(&spaced);
+        assert_eq!(document.finish_array(), 2);
+    }
+
+    #[test]
+    fn undo_removes_every_copy_at_once() {
+        let (mut document, id) = selected_circle([0.0, 0.0]);
+
+        document.preview_array(&ArraySettings::default());
+        document.finish_array();
+        assert_eq!(document.undo(), Some("Array"));
+
+        assert_eq!(document.entities.ids().collect::<Vec<_>>(), vec![id]);
+    }
+}
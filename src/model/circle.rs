@@ -1,15 +1,23 @@
-use crate::graphics::gui_elements::ColorScheme;
 use crate::graphics::vertex::Vertex;
-use crate::{DrawingState, Mode, State};
+use crate::model::document::Document;
+use crate::model::entity::{Entity, Geometry, Property, RenderBatch, Stroke};
+use crate::model::geometry::{circle_crosses_box, circle_hit, distance, BoundingBox};
+use crate::model::grip::Grip;
+use crate::model::history::{Change, HistoryOps};
+use crate::model::linetype::Dashes;
+use crate::model::style::Style;
+use crate::model::transform::Transform;
+use dxf::entities::EntityType;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Circle {
+    pub id: u64,
     pub radius: f32,
     pub center: Vertex,
     pub selected: bool,
     pub del: bool,
     pub is_drawing: bool,
-    pub thickness: f32,
+    pub style: Style,
 }
 
 #[repr(C)]
@@ -19,12 +27,12 @@ pub struct CircleInstance {
     pub color: [f32; 3],
     pub radius: f32,
     pub thickness: f32,
+    pub dashes: Dashes,
 }
 
 impl Circle {
-    pub fn move_circle(&mut self, dx: f32, dy: f32) {
-        self.center.position[0] -= dx;
-        self.center.position[1] -= dy;
+    pub fn center(&self) -> [f32; 2] {
+        [self.center.position[0], self.center.position[1]]
     }
 
     pub fn finish_circle_with_radius(&mut self, radius: f32) {
@@ -33,6 +41,104 @@ impl Circle {
     }
 }
 
+impl Geometry for Circle {
+    fn hit_distance(&self, point: [f32; 2]) -> f32 {
+        circle_hit(point, self.center(), self.radius)
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        BoundingBox::around(self.center(), self.radius)
+    }
+
+    fn crosses(&self, area: &BoundingBox) -> bool {
+        circle_crosses_box(self.center(), self.radius, area)
+    }
+
+    // the four quadrants and the center
+    fn snap_points(&self) -> Vec<[f32; 2]> {
+        let [x, y] = self.center();
+        let r = self.radius;
+
+        vec![[x, y + r], [x - r, y], [x, y - r], [x + r, y], [x, y]]
+    }
+
+    // the center and the four quadrants, which set the radius
+    fn grips(&self) -> Vec<Grip> {
+        let center = self.center();
+        let mut grips = vec![Grip::new(center)];
+        for [dx, dy] in [[1.0, 0.0], [0.0, 1.0], [-1.0, 0.0], [0.0, -1.0]] {
+            let quadrant = [center[0] + dx * self.radius, center[1] + dy * self.radius];
+            grips.push(Grip::anchored(quadrant, center));
+        }
+        grips
+    }
+
+    fn move_grip(&mut self, index: usize, position: [f32; 2]) {
+        let center = self.center();
+        if index == 0 {
+            self.translate(position[0] - center[0], position[1] - center[1]);
+        } else {
+            self.radius = distance(center, position);
+        }
+    }
+
+    fn translate(&mut self, dx: f32, dy: f32) {
+        self.center.position[0] += dx;
+        self.center.position[1] += dy;
+    }
+
+    // a circle goes along if its center is inside, it can't be stretched out of round
+    fn stretch(&mut self, area: &BoundingBox, by: [f32; 2]) {
+        if area.contains_point([self.center.position[0], self.center.position[1]]) {
+            self.translate(by[0], by[1]);
+        }
+    }
+
+    fn transform(&mut self, transform: &Transform) {
+        let [x, y] = transform.apply([self.center.position[0], self.center.position[1]]);
+        self.center.position = [x, y, self.center.position[2]];
+        self.radius = transform.length(self.radius);
+    }
+
+    fn properties(&self) -> Vec<Property> {
+        vec![
+            Property::new("Thickness", self.style.thickness, 0.1, true),
+            Property::new("Radius", self.radius, 0.1, true),
+            Property::new("Center X coordinate", self.center.position[0], 1.0, false),
+            Property::new("Center Y coordinate", self.center.position[1], 1.0, false),
+        ]
+    }
+
+    fn set_property(&mut self, name: &str, value: f32) {
+        match name {
+            "Thickness" => self.style.thickness = value,
+            "Radius" => self.finish_circle_with_radius(value),
+            "Center X coordinate" => self.center.position[0] = value,
+            "Center Y coordinate" => self.center.position[1] = value,
+            _ => {}
+        }
+    }
+
+    fn to_dxf(&self) -> EntityType {
+        let [x, y] = self.center();
+
+        EntityType::Circle(dxf::entities::Circle::new(
+            dxf::Point::new(x as f64, y as f64, 0.0),
+            self.radius as f64,
+        ))
+    }
+
+    fn push_instances(&self, batch: &mut RenderBatch, stroke: Stroke) {
+        batch.circles.push(CircleInstance {
+            position: self.center.position,
+            color: stroke.color,
+            radius: self.radius,
+            thickness: stroke.thickness,
+            dashes: stroke.dashes,
+        });
+    }
+}
+
 impl CircleInstance {
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
@@ -59,6 +165,31 @@ impl CircleInstance {
                     shader_location: 3,
                     format: wgpu::VertexFormat::Float32,
                 },
+                wgpu::VertexAttribute {
+                    offset: 32,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: 48,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: 64,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: 80,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                wgpu::VertexAttribute {
+                    offset: 84,
+                    shader_location: 8,
+                    format: wgpu::VertexFormat::Float32,
+                },
             ],
         }
     }
@@ -69,137 +200,90 @@ pub trait CircleOps {
         &mut self,
         coordinates: [f32; 2],
         radius: f32,
-        color: [f32; 3],
         selected_flag: bool,
         del_flag: bool,
         is_drawing: bool,
-    );
+    ) -> u64;
     fn update_circle(&mut self, position: [f32; 2], is_drawing_flag: bool);
+    fn finish_active_circle(&mut self, radius: f32);
     fn cancel_drawing_circle(&mut self);
-    fn unselect_circles(&mut self);
 }
 
-// flatten vector of circles into flat vector of vertices
-pub fn flatten_circles_to_instances(
-    circles: &mut [Circle],
-    color_scheme: ColorScheme,
-    // zoom: f32,
-) -> Vec<CircleInstance> {
-    circles
-        .iter()
-        .map(|circle| CircleInstance {
-            position: circle.center.position,
-            color: if circle.selected {
-                [1.0, 0.0, 0.0]
-            } else if color_scheme == ColorScheme::Light {
-                [0.0, 0.0, 0.0]
-            } else {
-                [1.0, 1.0, 1.0]
-            },
-            radius: circle.radius,
-            thickness: circle.thickness,
-        })
-        .collect()
-}
-
-pub fn flatten_circles_for_snap(circles: &mut [Circle]) -> Vec<Vertex> {
-    let mut flat = Vec::new();
-
-    for circle in circles.iter_mut() {
-        if !circle.is_drawing {
-            let x = circle.center.position[0];
-            let y = circle.center.position[1];
-
-            flat.extend([
-                Vertex {
-                    position: [x, y + circle.radius, 0.0],
-                    color: circle.center.color,
-                }, // vertex above venter point
-                Vertex {
-                    position: [x - circle.radius, y, 0.0],
-                    color: circle.center.color,
-                }, // vertex to the left of center point
-                Vertex {
-                    position: [x, y - circle.radius, 0.0],
-                    color: circle.center.color,
-                }, // vertex below venter point
-                Vertex {
-                    position: [x + circle.radius, y, 0.0],
-                    color: circle.center.color,
-                }, // vertex to the right of venter point
-                circle.center,
-            ]);
-        }
-    }
-
-    flat
-}
-
-impl<'a> CircleOps for State<'a> {
+impl CircleOps for Document {
     fn add_circle(
         &mut self,
         coordinates: [f32; 2],
         radius: f32,
-        color: [f32; 3],
         selected_flag: bool,
         del_flag: bool,
         is_drawing: bool,
-    ) {
-        self.circles.push({
-            Circle {
+    ) -> u64 {
+        let id = self.next_id();
+
+        self.entities.insert(
+            id,
+            Entity::Circle(Circle {
+                id,
                 center: Vertex {
                     position: [coordinates[0], coordinates[1], 0.0],
-                    color,
+                    color: [1.0, 1.0, 1.0],
                 },
                 radius,
                 selected: selected_flag,
                 del: del_flag,
                 is_drawing,
-                thickness: 5.0,
-            }
-        });
+                style: Style::new(self.current_layer, self.settings.circle_thickness),
+            }),
+        );
 
         if is_drawing {
-            let index = self.circles.len() - 1;
-            self.active_circle_index = Some(index);
+            self.active_circle_id = Some(id);
         }
 
-        self.update_circle_instance_buffer();
+        id
     }
 
     fn update_circle(&mut self, position: [f32; 2], is_drawing_flag: bool) {
-        if let Some(i) = self.active_circle_index {
+        if let Some(circle) = self
+            .active_circle_id
+            .and_then(|id| self.entities.get_mut(id))
+            .and_then(Entity::as_circle_mut)
+        {
             let world_x = position[0];
             let world_y = position[1];
 
-            let circle = &mut self.circles[i];
             let center = circle.center;
             let dx = world_x - center.position[0];
             let dy = world_y - center.position[1];
             circle.radius = (dx * dx + dy * dy).sqrt();
             circle.is_drawing = is_drawing_flag;
+            let finished_circle = *circle;
 
             if !is_drawing_flag {
-                self.active_circle_index = None;
+                self.active_circle_id = None;
+                self.record("Circle", vec![Change::Add(Entity::Circle(finished_circle))]);
             }
-            self.update_circle_instance_buffer();
         }
     }
 
-    fn cancel_drawing_circle(&mut self) {
-        self.circles.pop();
-        self.drawing_state = DrawingState::Idle;
-        self.mode = Mode::Normal;
-        self.update_circle_instance_buffer();
-    }
+    // finishes the circle being drawn with a typed in radius
+    fn finish_active_circle(&mut self, radius: f32) {
+        if let Some(circle) = self
+            .active_circle_id
+            .and_then(|id| self.entities.get_mut(id))
+            .and_then(Entity::as_circle_mut)
+        {
+            circle.finish_circle_with_radius(radius);
+            let finished_circle = *circle;
 
-    fn unselect_circles(&mut self) {
-        for circle in &mut self.circles {
-            if circle.selected {
-                circle.selected = false;
-            }
+            self.active_circle_id = None;
+            self.record("Circle", vec![Change::Add(Entity::Circle(finished_circle))]);
         }
+    }
 
-        self.update_circle_instance_buffer();
+    fn cancel_drawing_circle(&mut self) {
+        if let Some(id) = self.active_circle_id.take() {
+            self.entities.remove(id);
+        }
     }
 }
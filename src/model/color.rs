@@ -0,0 +1,147 @@
+use crate::graphics::gui_elements::ColorScheme;
+
+// A color the way DXF stores it: an index into the AutoCAD Color Index (ACI) palette, or a true
+// color for files from R2004 on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Color {
+    Index(u8),
+    Rgb([u8; 3]),
+}
+
+// index 7 is white on a dark background and black on a light one
+pub const FOREGROUND: u8 = 7;
+
+impl Color {
+    pub fn to_rgb(self) -> [u8; 3] {
+        match self {
+            Color::Index(index) => aci_to_rgb(index),
+            Color::Rgb(rgb) => rgb,
+        }
+    }
+
+    // what the shaders get
+    pub fn to_render(self, color_scheme: ColorScheme) -> [f32; 3] {
+        if self == Color::Index(FOREGROUND) && color_scheme == ColorScheme::Light {
+            return [0.0, 0.0, 0.0];
+        }
+
+        self.to_rgb().map(|channel| channel as f32 / 255.0)
+    }
+
+    // closest palette entry, for versions that can't hold a true color
+    pub fn to_index(self) -> u8 {
+        match self {
+            Color::Index(index) => index,
+            Color::Rgb(rgb) => (1..=255)
+                .min_by_key(|index| {
+                    aci_to_rgb(*index)
+                        .iter()
+                        .zip(rgb)
+                        .map(|(a, b)| (*a as i32 - b as i32).pow(2))
+                        .sum::<i32>()
+                })
+                .unwrap_or(FOREGROUND),
+        }
+    }
+
+    // group code 420 packs true colors as 0xRRGGBB
+    pub fn from_true_color(value: i32) -> Self {
+        Color::Rgb([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+    }
+
+    pub fn true_color(self) -> Option<i32> {
+        match self {
+            Color::Index(_) => None,
+            Color::Rgb([r, g, b]) => Some((r as i32) << 16 | (g as i32) << 8 | b as i32),
+        }
+    }
+}
+
+// The ACI palette. Past the first nine colors it's 24 hues 15 degrees apart, each in five shades
+// that alternate between full and washed out, followed by six greys.
+pub fn aci_to_rgb(index: u8) -> [u8; 3] {
+    match index {
+        1 => [255, 0, 0],
+        2 => [255, 255, 0],
+        3 => [0, 255, 0],
+        4 => [0, 255, 255],
+        5 => [0, 0, 255],
+        6 => [255, 0, 255],
+        8 => [128, 128, 128],
+        9 => [192, 192, 192],
+        10..=249 => {
+            let hue = (index / 10 - 1) as f32 * 15.0;
+            let value = [255.0, 165.0, 127.0, 76.0, 38.0][(index % 10 / 2) as usize];
+            let washed_out = index % 2 == 1;
+
+            hue_to_rgb(hue).map(|channel| {
+                let channel = channel * value;
+                if washed_out {
+                    (channel + (value - channel) / 2.0) as u8
+                } else {
+                    channel as u8
+                }
+            })
+        }
+        250..=255 => {
+            [[51; 3], [91; 3], [132; 3], [173; 3], [214; 3], [255; 3]][index as usize - 250]
+        }
+        // 7, and 0 which only means ByBlock
+        _ => [255, 255, 255],
+    }
+}
+
+// fully saturated color of a hue in degrees, channels from 0 to 1
+fn hue_to_rgb(hue: f32) -> [f32; 3] {
+    let x = 1.0 - ((hue / 60.0) % 2.0 - 1.0).abs();
+
+    match (hue / 60.0) as u32 {
+        0 => [1.0, x, 0.0],
+        1 => [x, 1.0, 0.0],
+        2 => [0.0, 1.0, x],
+        3 => [0.0, x, 1.0],
+        4 => [x, 0.0, 1.0],
+        _ => [1.0, 0.0, x],
+    }
+}
+
+// The color an entity asks for. Blocks are exploded on import, so ByBlock only survives on
+// entities that weren't in one; AutoCAD draws those in the foreground color and so do we.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EntityColor {
+    ByLayer,
+    ByBlock,
+    Explicit(Color),
+}
+
+impl EntityColor {
+    pub fn to_render(self, layer_color: Color, color_scheme: ColorScheme) -> [f32; 3] {
+        match self {
+            EntityColor::ByLayer => layer_color,
+            EntityColor::ByBlock => Color::Index(FOREGROUND),
+            EntityColor::Explicit(color) => color,
+        }
+        .to_render(color_scheme)
+    }
+
+    pub fn to_dxf(self) -> dxf::Color {
+        match self {
+            EntityColor::ByLayer => dxf::Color::by_layer(),
+            EntityColor::ByBlock => dxf::Color::by_block(),
+            EntityColor::Explicit(color) => dxf::Color::from_index(color.to_index()),
+        }
+    }
+}
+
+// names AutoCAD gives the first few palette entries
+pub const NAMED_COLORS: [(u8, &str); 9] = [
+    (1, "Red"),
+    (2, "Yellow"),
+    (3, "Green"),
+    (4, "Cyan"),
+    (5, "Blue"),
+    (6, "Magenta"),
+    (7, "White"),
+    (8, "Grey"),
+    (9, "Light grey"),
+];
@@ -0,0 +1,653 @@
+use crate::compiler::compiler::Compiler;
+use crate::model::array::ArrayPreview;
+use crate::model::color::EntityColor;
+use crate::model::ellipse::Ellipse;
+use crate::model::entity::{Entity, Geometry};
+use crate::model::grip::GripDrag;
+use crate::model::history::{Change, History, HistoryOps};
+use crate::model::import::{import_drawing, ImportSummary};
+use crate::model::layer::{patch_layer_table, Layer, DEFAULT_LAYER};
+use crate::model::linetype::{standard_linetypes, Linetype, CONTINUOUS};
+use crate::model::spatial::SpatialIndex;
+use crate::model::store::EntityStore;
+use crate::model::stretch::StretchPreview;
+use crate::model::text::Text;
+use crate::model::transform::TransformPreview;
+use dxf::entities::EntityType;
+use dxf::enums::{AcadVersion, Units};
+use dxf::{Drawing, Point};
+
+use std::fs;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Settings {
+    // thickness new entities get
+    pub line_thickness: f32,
+    pub circle_thickness: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            line_thickness: 2.0,
+            circle_thickness: 5.0,
+        }
+    }
+}
+
+// Everything that gets drawn, saved and undone, without any of the window/GPU side.
+// `State` owns one and is responsible for pushing it to the screen, so this can be used on its own
+// (compiling .cad files, importing DXF, tests on machines without a GPU).
+#[derive(Debug)]
+pub struct Document {
+    pub entities: EntityStore<Entity>,
+    // where the entities are, only up to date right after a query through `SpatialOps`
+    pub(crate) index: SpatialIndex,
+    pub texts: Vec<Text>,
+    pub settings: Settings,
+    pub history: History,
+    pub layers: EntityStore<Layer>,
+    // the layer new entities go on
+    pub current_layer: u64,
+    pub linetypes: EntityStore<Linetype>,
+    // every dash pattern is multiplied by this, $LTSCALE in DXF
+    pub linetype_scale: f32,
+    // ids keep counting up no matter what gets deleted, 0 is reserved for the snap indicators
+    next_entity_id: u64,
+    // entities that are in the middle of being drawn
+    pub active_line_id: Option<u64>,
+    pub active_circle_id: Option<u64>,
+    pub active_arc_id: Option<u64>,
+    pub active_ellipse_id: Option<u64>,
+    pub active_polyline_id: Option<u64>,
+    // the rectangle or polygon following the cursor
+    pub active_shape_id: Option<u64>,
+    pub active_grip: Option<GripDrag>,
+    pub active_transform: Option<TransformPreview>,
+    pub active_array: Option<ArrayPreview>,
+    pub active_stretch: Option<StretchPreview>,
+    // where Save writes to, None until the document was opened from or saved to a DXF
+    pub file_path: Option<String>,
+    pub dxf_version: AcadVersion,
+    // what one unit of the drawing is, taken over from the first DXF opened into it
+    pub units: Units,
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Document {
+    pub fn new() -> Self {
+        let mut layers = EntityStore::new();
+        layers.insert(DEFAULT_LAYER, Layer::new(DEFAULT_LAYER, "0"));
+
+        let mut linetypes = EntityStore::new();
+        linetypes.insert(CONTINUOUS, Linetype::continuous());
+
+        let mut document = Self {
+            entities: EntityStore::new(),
+            index: SpatialIndex::default(),
+            texts: Vec::new(),
+            settings: Settings::default(),
+            history: History::default(),
+            layers,
+            current_layer: DEFAULT_LAYER,
+            linetypes,
+            linetype_scale: 1.0,
+            next_entity_id: 1,
+            active_line_id: None,
+            active_circle_id: None,
+            active_arc_id: None,
+            active_ellipse_id: None,
+            active_polyline_id: None,
+            active_shape_id: None,
+            active_grip: None,
+            active_transform: None,
+            active_array: None,
+            active_stretch: None,
+            file_path: None,
+            // oldest version that still has LWPOLYLINE and lineweights
+            dxf_version: AcadVersion::R2000,
+            units: Units::Unitless,
+        };
+
+        standard_linetypes(&mut document);
+        document
+    }
+
+    pub fn next_id(&mut self) -> u64 {
+        let id = self.next_entity_id;
+        self.next_entity_id += 1;
+        id
+    }
+
+    // puts an entity that was built elsewhere into the document under a fresh id
+    pub fn add_entity(&mut self, mut entity: Entity) -> u64 {
+        let id = self.next_id();
+        entity.set_id(id);
+        self.entities.insert(id, entity);
+        id
+    }
+
+    pub fn delete_selected(&mut self) {
+        let mut changes: Vec<Change> = self
+            .entities
+            .iter()
+            .filter(|entity| entity.selected())
+            .map(|entity| Change::Remove(entity.clone()))
+            .collect();
+        changes.extend(
+            self.texts
+                .iter()
+                .filter(|text| text.selected)
+                .map(|text| Change::RemoveText(text.clone())),
+        );
+
+        self.entities.retain(|entity| !entity.selected());
+        self.texts.retain(|text| !text.selected);
+
+        self.record("Delete", changes);
+    }
+
+    pub fn unselect_all(&mut self) {
+        for entity in self.entities.filter_flags_mut(Entity::selected) {
+            entity.set_selected(false);
+        }
+        for text in &mut self.texts {
+            text.selected = false;
+        }
+    }
+
+    // throws away the preview copies of a move/copy and puts the originals back
+    pub fn cancel_move(&mut self) {
+        self.entities
+            .retain(|entity| !(entity.selected() && entity.is_drawing()));
+
+        for entity in self.entities.filter_flags_mut(Entity::del) {
+            entity.set_del(false);
+        }
+        for text in &mut self.texts {
+            text.selected = false;
+        }
+    }
+
+    // records every entity and text created since `first_id` was handed out as one undo step
+    fn record_added_since(&mut self, name: &'static str, first_id: u64) {
+        let mut changes: Vec<Change> = self
+            .entities
+            .iter_from(first_id)
+            .map(|entity| Change::Add(entity.clone()))
+            .collect();
+
+        changes.extend(
+            self.texts
+                .iter()
+                .filter(|text| text.id >= first_id)
+                .map(|text| Change::AddText(text.clone())),
+        );
+
+        self.record(name, changes);
+    }
+
+    pub fn save_to_dxf(&self, file_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut drawing = Drawing::new();
+        drawing.header.version = self.dxf_version;
+        drawing.header.default_drawing_units = self.units;
+        drawing.header.line_type_scale = self.linetype_scale as f64;
+
+        // these go first, adding a layer or entity creates an empty linetype for any name the
+        // drawing doesn't have yet
+        for linetype in self.linetypes.iter().filter(|lt| lt.id != CONTINUOUS) {
+            drawing.add_line_type(linetype.to_dxf());
+        }
+
+        // a new drawing already has its own layer "0"
+        for layer in &self.layers {
+            let existing = drawing
+                .layers_mut()
+                .find(|existing| existing.name.eq_ignore_ascii_case(&layer.name));
+            match existing {
+                Some(existing) => layer.write_dxf(existing, &self.linetypes),
+                None => {
+                    let mut new = dxf::tables::Layer::default();
+                    layer.write_dxf(&mut new, &self.linetypes);
+                    drawing.add_layer(new);
+                }
+            }
+        }
+
+        for entity in &self.entities {
+            let mut dxf_entity = dxf::entities::Entity::new(entity.to_dxf());
+            dxf_entity.common.layer = self.layers.name(entity.layer()).to_string();
+            dxf_entity.common.color = entity.color().to_dxf();
+            if let EntityColor::Explicit(color) = entity.color() {
+                dxf_entity.common.color_24_bit = color.true_color().unwrap_or(0);
+            }
+            dxf_entity.common.line_type_name = entity.linetype().to_dxf(&self.linetypes);
+            dxf_entity.common.line_type_scale = entity.linetype_scale() as f64;
+            dxf_entity.common.lineweight_enum_value = if entity.thickness_by_layer() {
+                LINEWEIGHT_BY_LAYER
+            } else {
+                thickness_to_lineweight(entity.thickness())
+            };
+            add_for_version(&mut drawing, dxf_entity);
+        }
+
+        for text in &self.texts {
+            let mut dxf_text = dxf::entities::Entity::new(text.to_dxf());
+            dxf_text.common.layer = self.layers.name(text.layer).to_string();
+            add_for_version(&mut drawing, dxf_text);
+        }
+
+        let mut written = Vec::new();
+        drawing.save(&mut written)?;
+        let true_color = self.dxf_version >= AcadVersion::R2004;
+        let patched =
+            patch_layer_table(&String::from_utf8_lossy(&written), &self.layers, true_color);
+        fs::write(file_path, patched)?;
+
+        Ok(())
+    }
+
+    pub fn load_from_cad(&mut self, file_path: String) -> Result<(), Box<dyn std::error::Error>> {
+        let src = fs::read_to_string(&file_path)?;
+        let mut compiler = Compiler::new();
+        let first_id = self.next_entity_id;
+
+        log::debug!("compiling {}", file_path);
+        for (i, line) in src.lines().enumerate() {
+            let trimmed_line = line.trim();
+            if trimmed_line.is_empty() {
+                continue;
+            }
+
+            if let Err(e) = compiler.process_line(self, line, i) {
+                log::error!("{e}");
+            }
+        }
+
+        self.record_added_since("Open", first_id);
+
+        Ok(())
+    }
+
+    pub fn load_from_dxf(
+        &mut self,
+        file_path: String,
+    ) -> Result<ImportSummary, Box<dyn std::error::Error>> {
+        let time_to_load_drawing = Instant::now();
+        let drawing = Drawing::load_file(&file_path)?;
+        log::debug!(
+            "reading the drawing took {:?}",
+            time_to_load_drawing.elapsed()
+        );
+
+        let now = Instant::now();
+        let first_id = self.next_entity_id;
+        // same as for units and layers, only an empty document becomes the file
+        let adopt = self.entities.is_empty() && self.texts.is_empty();
+        let mut summary = import_drawing(self, &drawing, &file_path);
+
+        self.record_added_since("Open", first_id);
+        // a merged drawing saved over the file would change it behind the user's back, and so
+        // would one missing what couldn't be imported
+        if adopt {
+            self.dxf_version = drawing.header.version;
+            if !summary.incomplete() {
+                self.file_path = Some(file_path);
+            } else {
+                summary.kept_original = true;
+            }
+        }
+        log::debug!("importing it took {:?}", now.elapsed());
+
+        Ok(summary)
+    }
+}
+
+// lineweights AutoCAD lets you pick from, in 1/100 mm
+pub const LINEWEIGHTS: [i16; 24] = [
+    0, 5, 9, 13, 15, 18, 20, 25, 30, 35, 40, 50, 53, 60, 70, 80, 90, 100, 106, 120, 140, 158, 200,
+    211,
+];
+
+// lineweight codes that aren't a width
+pub const LINEWEIGHT_BY_LAYER: i16 = -1;
+pub const LINEWEIGHT_BY_BLOCK: i16 = -2;
+pub const LINEWEIGHT_DEFAULT: i16 = -3;
+
+// AutoCAD draws its default 0.25 mm lineweight one pixel wide
+const LINEWEIGHT_PER_PIXEL: f32 = 25.0;
+
+// None for ByLayer, ByBlock and the default, which aren't widths
+pub fn lineweight_to_thickness(lineweight: i16) -> Option<f32> {
+    (lineweight >= 0).then(|| (lineweight as f32 / LINEWEIGHT_PER_PIXEL).max(1.0))
+}
+
+pub fn thickness_to_lineweight(thickness: f32) -> i16 {
+    let wanted = thickness * LINEWEIGHT_PER_PIXEL;
+
+    LINEWEIGHTS
+        .into_iter()
+        .min_by(|a, b| {
+            (*a as f32 - wanted)
+                .abs()
+                .total_cmp(&(*b as f32 - wanted).abs())
+        })
+        .unwrap_or(0)
+}
+
+// Adds the entity in a form the drawing's version can hold. Older versions don't know LWPOLYLINE
+// (R14) and MTEXT (R13), those get written as the entities that came before them.
+fn add_for_version(drawing: &mut Drawing, entity: dxf::entities::Entity) {
+    let version = drawing.header.version;
+
+    match entity.specific {
+        EntityType::LwPolyline(ref lw_polyline) if version < AcadVersion::R14 => {
+            let mut polyline = dxf::entities::Polyline::default();
+            polyline.set_is_closed(lw_polyline.get_is_closed());
+
+            for vertex in &lw_polyline.vertices {
+                let mut old_vertex =
+                    dxf::entities::Vertex::new(Point::new(vertex.x, vertex.y, 0.0));
+                old_vertex.bulge = vertex.bulge;
+                polyline.add_vertex(drawing, old_vertex);
+            }
+
+            drawing.add_entity(dxf::entities::Entity {
+                common: entity.common.clone(),
+                specific: EntityType::Polyline(polyline),
+            });
+        }
+        // there are no ellipses before R13, they go out as the straight pieces they're drawn with
+        EntityType::Ellipse(ref ellipse) if version < AcadVersion::R13 => {
+            let shape = Ellipse::new(
+                [ellipse.center.x as f32, ellipse.center.y as f32],
+                [ellipse.major_axis.x as f32, ellipse.major_axis.y as f32],
+                ellipse.minor_axis_ratio as f32,
+                ellipse.start_parameter as f32,
+                ellipse.end_parameter as f32,
+            );
+            let mut points = shape.points();
+            if shape.is_closed() {
+                points.pop();
+            }
+
+            let mut polyline = dxf::entities::Polyline::default();
+            polyline.set_is_closed(shape.is_closed());
+            for [x, y] in points {
+                let vertex = dxf::entities::Vertex::new(Point::new(x as f64, y as f64, 0.0));
+                polyline.add_vertex(drawing, vertex);
+            }
+
+            drawing.add_entity(dxf::entities::Entity {
+                common: entity.common.clone(),
+                specific: EntityType::Polyline(polyline),
+            });
+        }
+        EntityType::MText(ref m_text) if version < AcadVersion::R13 => {
+            // one TEXT per line, stacked downwards so the last one sits on the insertion point
+            let lines: Vec<&str> = m_text.text.split("\\P").collect();
+            let line_height = m_text.initial_text_height * 5.0 / 3.0;
+
+            for (i, line) in lines.iter().enumerate() {
+                let offset = (lines.len() - 1 - i) as f64 * line_height;
+
+                drawing.add_entity(dxf::entities::Entity {
+                    common: entity.common.clone(),
+                    specific: EntityType::Text(dxf::entities::Text {
+                        location: Point::new(
+                            m_text.insertion_point.x,
+                            m_text.insertion_point.y + offset,
+                            0.0,
+                        ),
+                        text_height: m_text.initial_text_height,
+                        value: line.to_string(),
+                        ..Default::default()
+                    }),
+                });
+            }
+        }
+        _ => {
+            drawing.add_entity(entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::arc::{Arc, ArcOps};
+    use crate::model::color::Color;
+    use crate::model::layer::LayerOps;
+    use crate::model::line::LineOps;
+    use crate::model::linetype::{EntityLinetype, LinetypeOps};
+    use crate::model::polyline::{PolylineOps, PolylineVertex};
+    use std::f32::consts::FRAC_PI_2;
+
+    // one file per test, they run in parallel
+    fn temp_path(name: &str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("easycad-{}-{}.dxf", name, std::process::id()));
+        path.to_string_lossy().into_owned()
+    }
+
+    fn round_trip(document: &Document, name: &str) -> (Document, ImportSummary) {
+        let path = temp_path(name);
+        document.save_to_dxf(&path).unwrap();
+
+        let mut loaded = Document::new();
+        let summary = loaded.load_from_dxf(path.clone()).unwrap();
+        fs::remove_file(path).unwrap();
+        (loaded, summary)
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    fn only<T>(items: Vec<T>) -> T {
+        assert_eq!(items.len(), 1);
+        items.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn layers_survive_a_round_trip() {
+        let mut document = Document::new();
+        let walls = document.add_layer("Walls");
+        let hidden = document.add_layer("Hidden");
+        let dashed = document.linetypes.find("DASHED").unwrap().id;
+        {
+            let layer = document.layers.get_mut(walls).unwrap();
+            layer.color = Color::Index(1);
+            layer.locked = true;
+            layer.linetype = dashed;
+            layer.lineweight = Some(50);
+        }
+        {
+            let layer = document.layers.get_mut(hidden).unwrap();
+            layer.frozen = true;
+            layer.visible = false;
+        }
+
+        let (loaded, _) = round_trip(&document, "layers");
+
+        let layer = loaded.layers.find("Walls").unwrap();
+        assert_eq!(layer.color, Color::Index(1));
+        assert!(layer.locked && !layer.frozen && layer.visible);
+        assert_eq!(loaded.linetypes.name(layer.linetype), "DASHED");
+        assert_eq!(layer.lineweight, Some(50));
+
+        let layer = loaded.layers.find("Hidden").unwrap();
+        assert!(layer.frozen && !layer.visible);
+    }
+
+    #[test]
+    fn entity_colors_and_linetypes_survive_a_round_trip() {
+        let mut document = Document::new();
+        document.dxf_version = AcadVersion::R2010;
+        let pattern = vec![5.0, -2.0, 0.0, -2.0];
+        let custom = document.add_linetype("CUSTOM", "Custom __ . __", pattern.clone());
+
+        document.add_line([0.0, 0.0], [10.0, 0.0], false);
+        let indexed = document.add_line([0.0, 5.0], [10.0, 5.0], false);
+        let true_color = document.add_line([0.0, 10.0], [10.0, 10.0], false);
+        {
+            let line = document.entities.get_mut(indexed).unwrap();
+            line.set_color(EntityColor::Explicit(Color::Index(3)));
+            line.set_linetype(EntityLinetype::Explicit(custom));
+            line.set_linetype_scale(2.0);
+        }
+        document
+            .entities
+            .get_mut(true_color)
+            .unwrap()
+            .set_color(EntityColor::Explicit(Color::Rgb([200, 100, 50])));
+
+        let (loaded, _) = round_trip(&document, "colors");
+
+        let linetype = loaded.linetypes.find("CUSTOM").unwrap();
+        assert_eq!(linetype.pattern, pattern);
+
+        let lines: Vec<&Entity> = loaded.entities.iter().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].color(), EntityColor::ByLayer);
+        assert_eq!(lines[0].linetype(), EntityLinetype::ByLayer);
+        assert_eq!(lines[1].color(), EntityColor::Explicit(Color::Index(3)));
+        assert_eq!(lines[1].linetype(), EntityLinetype::Explicit(linetype.id));
+        assert!(close(lines[1].linetype_scale(), 2.0));
+        assert_eq!(
+            lines[2].color(),
+            EntityColor::Explicit(Color::Rgb([200, 100, 50]))
+        );
+    }
+
+    #[test]
+    fn curves_survive_a_round_trip() {
+        let mut document = Document::new();
+        document.add_arc(Arc::new([1.0, 2.0], 3.0, 0.0, FRAC_PI_2), false);
+        document.add_polyline(
+            vec![
+                PolylineVertex::new([0.0, 0.0], 1.0),
+                PolylineVertex::new([10.0, 0.0], 0.0),
+                PolylineVertex::new([10.0, 5.0], 0.0),
+            ],
+            true,
+            false,
+        );
+
+        let (loaded, summary) = round_trip(&document, "curves");
+        assert!(!summary.incomplete());
+
+        let mut arcs = Vec::new();
+        let mut polylines = Vec::new();
+        for entity in &loaded.entities {
+            match entity {
+                Entity::Arc(arc) => arcs.push(arc),
+                Entity::Polyline(polyline) => polylines.push(polyline),
+                _ => panic!("unexpected {}", entity.kind_name()),
+            }
+        }
+
+        let arc = only(arcs);
+        assert_eq!(arc.center, [1.0, 2.0]);
+        assert!(close(arc.radius, 3.0));
+        assert!(close(arc.start_angle, 0.0) && close(arc.end_angle, FRAC_PI_2));
+
+        let polyline = only(polylines);
+        assert!(polyline.closed);
+        assert_eq!(polyline.vertices[0], PolylineVertex::new([0.0, 0.0], 1.0));
+        assert_eq!(polyline.vertices[2], PolylineVertex::new([10.0, 5.0], 0.0));
+    }
+
+    #[test]
+    fn texts_keep_their_layer() {
+        let mut document = Document::new();
+        let notes = document.add_layer("Notes");
+        let id = document.next_id();
+        document.texts.push(Text {
+            id,
+            position: [1.0, 2.0],
+            contents: egui::WidgetText::from("hello"),
+            rect: None,
+            editing: false,
+            selected: false,
+            annotative: false,
+            layer: notes,
+        });
+
+        let (loaded, _) = round_trip(&document, "texts");
+
+        let text = only(loaded.texts.iter().collect());
+        assert_eq!(text.contents.text(), "hello");
+        assert_eq!(loaded.layers.name(text.layer), "Notes");
+    }
+
+    #[test]
+    fn only_an_empty_document_takes_over_the_file() {
+        let mut document = Document::new();
+        document.add_line([0.0, 0.0], [10.0, 0.0], false);
+        let path = temp_path("adopt");
+        document.save_to_dxf(&path).unwrap();
+
+        let mut empty = Document::new();
+        empty.load_from_dxf(path.clone()).unwrap();
+        assert_eq!(empty.file_path.as_deref(), Some(path.as_str()));
+
+        let mut drawing = Document::new();
+        drawing.add_line([0.0, 5.0], [10.0, 5.0], false);
+        drawing.load_from_dxf(path.clone()).unwrap();
+        assert_eq!(drawing.file_path, None);
+        assert_eq!(drawing.entities.len(), 2);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn a_file_with_skipped_entities_is_not_saved_over() {
+        let mut document = Document::new();
+        document.add_line([0.0, 0.0], [10.0, 0.0], false);
+        let path = temp_path("skipped");
+        document.save_to_dxf(&path).unwrap();
+
+        // something we don't import, right at the start of the entities
+        let text = fs::read_to_string(&path).unwrap();
+        let patched = text.replacen(
+            "ENTITIES\r\n",
+            "ENTITIES\r\n  0\r\nWIPEOUT\r\n  8\r\n0\r\n",
+            1,
+        );
+        assert_ne!(text, patched);
+        fs::write(&path, patched).unwrap();
+
+        let mut loaded = Document::new();
+        let summary = loaded.load_from_dxf(path.clone()).unwrap();
+        assert_eq!(summary.skipped.get("WIPEOUT"), Some(&1));
+        assert!(summary.kept_original);
+        assert_eq!(loaded.file_path, None);
+        assert_eq!(loaded.entities.len(), 1);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn undo_takes_back_a_whole_open() {
+        let mut document = Document::new();
+        document.add_line([0.0, 0.0], [10.0, 0.0], false);
+        document.add_line([0.0, 5.0], [10.0, 5.0], false);
+        let path = temp_path("undo-open");
+        document.save_to_dxf(&path).unwrap();
+
+        let mut loaded = Document::new();
+        loaded.load_from_dxf(path.clone()).unwrap();
+        assert_eq!(loaded.entities.len(), 2);
+        assert_eq!(loaded.undo(), Some("Open"));
+        assert!(loaded.entities.is_empty());
+
+        fs::remove_file(path).unwrap();
+    }
+}
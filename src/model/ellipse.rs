@@ -0,0 +1,799 @@
+use crate::model::document::Document;
+use crate::model::entity::{Entity, Geometry, Property, RenderBatch, Stroke};
+use crate::model::geometry::{
+    add, cross, distance, dot, scale, segment_crosses_box, sub, BoundingBox,
+};
+use crate::model::grip::Grip;
+use crate::model::history::{Change, HistoryOps};
+use crate::model::line::LineInstance;
+use crate::model::path::{Piece, EPSILON};
+use crate::model::style::Style;
+use crate::model::transform::Transform;
+use dxf::entities::EntityType;
+use std::f32::consts::{PI, TAU};
+
+// enough straight pieces that they don't show as corners at any sensible zoom
+const SEGMENTS_PER_TURN: f32 = 256.0;
+
+// ends of an elliptical arc closer than this (in radians) make it a whole ellipse
+const FULL_TURN_EPSILON: f32 = 1e-5;
+
+// Points are center + major_axis * cos(t) + minor_axis * sin(t), where the minor axis is the major
+// one turned a quarter counter-clockwise and shortened by `ratio`. An elliptical arc runs
+// counter-clockwise from parameter `start_param` to `end_param`, a whole ellipse from 0 to TAU,
+// same as DXF.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ellipse {
+    pub id: u64,
+    pub center: [f32; 2],
+    // from the center to one end of the major axis
+    pub major_axis: [f32; 2],
+    // length of the minor axis over the major one, up to 1
+    pub ratio: f32,
+    pub start_param: f32,
+    pub end_param: f32,
+    pub selected: bool,
+    pub del: bool,
+    pub is_drawing: bool,
+    pub style: Style,
+}
+
+// how far around it goes from `start` to `end`, all the way if they meet
+fn sweep_between(start: f32, end: f32) -> f32 {
+    let sweep = (end - start).rem_euclid(TAU);
+    if (FULL_TURN_EPSILON..=TAU - FULL_TURN_EPSILON).contains(&sweep) {
+        sweep
+    } else {
+        TAU
+    }
+}
+
+impl Ellipse {
+    pub fn new(center: [f32; 2], major_axis: [f32; 2], ratio: f32, start: f32, end: f32) -> Self {
+        let (start_param, end_param) = if sweep_between(start, end) == TAU {
+            (0.0, TAU)
+        } else {
+            (start.rem_euclid(TAU), end.rem_euclid(TAU))
+        };
+
+        Self {
+            id: 0,
+            center,
+            major_axis,
+            ratio,
+            start_param,
+            end_param,
+            selected: false,
+            del: false,
+            is_drawing: false,
+            style: Style::new(0, 2.0),
+        }
+    }
+
+    // Ellipse through center + p * cos(t) + q * sin(t) for t from `start` to `end`. `p` and `q`
+    // don't have to be its axes, any pair of conjugate half diameters will do, which is what
+    // the axes turn into when the ellipse is stretched or mirrored.
+    pub fn from_conjugate(
+        center: [f32; 2],
+        p: [f32; 2],
+        q: [f32; 2],
+        start: f32,
+        end: f32,
+    ) -> Self {
+        let sweep = sweep_between(start, end);
+
+        // running clockwise, the same points come out counter-clockwise with `q` turned over
+        let (q, start) = if cross(p, q) < 0.0 {
+            (scale(q, -1.0), -(start + sweep))
+        } else {
+            (q, start)
+        };
+
+        // the parameter where the curve is furthest from the center, the end of the major axis
+        let shift = 0.5 * (2.0 * dot(p, q)).atan2(dot(p, p) - dot(q, q));
+        let (sin, cos) = shift.sin_cos();
+        let major = add(scale(p, cos), scale(q, sin));
+        let minor = sub(scale(q, cos), scale(p, sin));
+
+        let length = dot(major, major).sqrt();
+        let ratio = if length > 0.0 {
+            (dot(minor, minor).sqrt() / length).min(1.0)
+        } else {
+            0.0
+        };
+
+        let start = start - shift;
+        Ellipse::new(center, major, ratio, start, start + sweep)
+    }
+
+    // a whole ellipse with one axis from `center` to `axis_end` and the other `other` long on
+    // either side
+    pub fn from_axes(center: [f32; 2], axis_end: [f32; 2], other: f32) -> Self {
+        let axis = sub(axis_end, center);
+        let length = dot(axis, axis).sqrt();
+        let turned = if length > 0.0 {
+            scale([-axis[1], axis[0]], other / length)
+        } else {
+            [0.0, other]
+        };
+
+        Ellipse::from_conjugate(center, axis, turned, 0.0, TAU)
+    }
+
+    pub fn minor_axis(&self) -> [f32; 2] {
+        [
+            -self.major_axis[1] * self.ratio,
+            self.major_axis[0] * self.ratio,
+        ]
+    }
+
+    pub fn major_radius(&self) -> f32 {
+        dot(self.major_axis, self.major_axis).sqrt()
+    }
+
+    pub fn minor_radius(&self) -> f32 {
+        self.major_radius() * self.ratio
+    }
+
+    // direction of the major axis, in radians from the x axis
+    pub fn rotation(&self) -> f32 {
+        self.major_axis[1].atan2(self.major_axis[0])
+    }
+
+    pub fn sweep(&self) -> f32 {
+        sweep_between(self.start_param, self.end_param)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.sweep() == TAU
+    }
+
+    pub fn contains_param(&self, t: f32) -> bool {
+        (t - self.start_param).rem_euclid(TAU) <= self.sweep()
+    }
+
+    pub fn point_at(&self, t: f32) -> [f32; 2] {
+        let (sin, cos) = t.sin_cos();
+        add(
+            self.center,
+            add(scale(self.major_axis, cos), scale(self.minor_axis(), sin)),
+        )
+    }
+
+    // direction the curve runs in at `t`, as long as the parameter's speed
+    fn tangent_at(&self, t: f32) -> [f32; 2] {
+        let (sin, cos) = t.sin_cos();
+        sub(scale(self.minor_axis(), cos), scale(self.major_axis, sin))
+    }
+
+    pub fn start_point(&self) -> [f32; 2] {
+        self.point_at(self.start_param)
+    }
+
+    pub fn end_point(&self) -> [f32; 2] {
+        self.point_at(self.start_param + self.sweep())
+    }
+
+    pub fn mid_point(&self) -> [f32; 2] {
+        self.point_at(self.start_param + self.sweep() / 2.0)
+    }
+
+    // the ends of both axes, the major one first
+    pub fn quadrants(&self) -> [[f32; 2]; 4] {
+        let (major, minor) = (self.major_axis, self.minor_axis());
+        [
+            add(self.center, major),
+            add(self.center, minor),
+            sub(self.center, major),
+            sub(self.center, minor),
+        ]
+    }
+
+    // Parameter of the point on the curve closest to `point`. The closest of a few points along
+    // the curve gets refined with Newton's method, there's no closed form for it.
+    pub fn param_near(&self, point: [f32; 2]) -> f32 {
+        let sweep = self.sweep();
+        let samples = 32;
+        let mut best = (0..=samples)
+            .map(|i| sweep * i as f32 / samples as f32)
+            .min_by(|a, b| {
+                let to = |t: f32| distance(self.point_at(self.start_param + t), point);
+                to(*a).total_cmp(&to(*b))
+            })
+            .unwrap_or(0.0);
+
+        for _ in 0..8 {
+            let t = self.start_param + best;
+            let on_curve = self.point_at(t);
+            let (off, tangent) = (sub(on_curve, point), self.tangent_at(t));
+            let slope = dot(tangent, tangent) + dot(off, sub(self.center, on_curve));
+            if slope.abs() < f32::EPSILON {
+                break;
+            }
+            best -= dot(off, tangent) / slope;
+            best = if self.is_closed() {
+                best.rem_euclid(TAU)
+            } else {
+                best.clamp(0.0, sweep)
+            };
+        }
+
+        self.start_param + best
+    }
+
+    // the straight pieces it's drawn with, from the start to the end
+    pub fn points(&self) -> Vec<[f32; 2]> {
+        let sweep = self.sweep();
+        let segments = (sweep / TAU * SEGMENTS_PER_TURN).ceil().max(4.0) as usize;
+
+        (0..=segments)
+            .map(|i| self.point_at(self.start_param + sweep * i as f32 / segments as f32))
+            .collect()
+    }
+
+    pub fn length(&self) -> f32 {
+        self.points()
+            .windows(2)
+            .map(|pair| distance(pair[0], pair[1]))
+            .sum()
+    }
+
+    // the straight pieces it's drawn with, for trimming other things at it
+    pub fn pieces(&self) -> Vec<Piece> {
+        self.points()
+            .windows(2)
+            .map(|pair| Piece::Line(pair[0], pair[1]))
+            .collect()
+    }
+
+    // Sorted parameters of everywhere an edge crosses it, counted from `start_param`. The edges
+    // get crossed with the pieces it's drawn with and the parameters found from there.
+    pub fn cuts(&self, edges: &[Piece]) -> Vec<f32> {
+        let sweep = self.sweep();
+        let mut cuts: Vec<f32> = self
+            .pieces()
+            .iter()
+            .flat_map(|piece| {
+                edges.iter().flat_map(move |edge| {
+                    piece
+                        .intersections(edge)
+                        .into_iter()
+                        .filter(|p| piece.param_on(*p).is_some() && edge.param_on(*p).is_some())
+                })
+            })
+            .map(|p| (self.param_near(p) - self.start_param).rem_euclid(TAU))
+            .map(|cut| {
+                if self.is_closed() && cut >= sweep - EPSILON {
+                    0.0
+                } else {
+                    cut.min(sweep)
+                }
+            })
+            .collect();
+
+        cuts.sort_by(f32::total_cmp);
+        cuts.dedup_by(|a, b| (*a - *b).abs() < EPSILON);
+        cuts
+    }
+
+    // What's left after cutting out the stretch around parameter `pick` between the cuts on
+    // either side of it, the same as trimming a circle or an arc. None if there's nothing to cut
+    // it off at.
+    pub fn trimmed(&self, cuts: &[f32], pick: f32) -> Option<Vec<Ellipse>> {
+        let sweep = self.sweep();
+        let pick = (pick - self.start_param).rem_euclid(TAU);
+        let part = |from: f32, to: f32| Ellipse {
+            start_param: (self.start_param + from).rem_euclid(TAU),
+            end_param: (self.start_param + to).rem_euclid(TAU),
+            selected: false,
+            ..*self
+        };
+
+        if self.is_closed() {
+            if cuts.len() < 2 {
+                return None;
+            }
+            // from the first cut after the pick around to the last one before it
+            let before = cuts.iter().rev().find(|cut| **cut < pick).or(cuts.last())?;
+            let after = cuts.iter().find(|cut| **cut > pick).or(cuts.first())?;
+            return Some(vec![part(*after, *before)]);
+        }
+
+        let before = cuts
+            .iter()
+            .rev()
+            .find(|cut| **cut < pick && **cut > EPSILON);
+        let after = cuts
+            .iter()
+            .find(|cut| **cut > pick && **cut < sweep - EPSILON);
+        if before.is_none() && after.is_none() {
+            return None;
+        }
+
+        let mut parts = Vec::new();
+        if let Some(before) = before {
+            parts.push(part(0.0, *before));
+        }
+        if let Some(after) = after {
+            parts.push(part(*after, sweep));
+        }
+        Some(parts)
+    }
+
+    // takes over the shape of `other` and keeps everything else
+    fn reshape(&mut self, other: Ellipse) {
+        self.center = other.center;
+        self.major_axis = other.major_axis;
+        self.ratio = other.ratio;
+        self.start_param = other.start_param;
+        self.end_param = other.end_param;
+    }
+
+    // the same ellipse with half axes `major` and `minor` long, they trade places if `minor`
+    // is the longer one
+    fn resized(&self, major: f32, minor: f32) -> Ellipse {
+        let length = self.major_radius();
+        if length <= 0.0 {
+            return *self;
+        }
+        let axis = scale(self.major_axis, major / length);
+        let turned = scale([-self.major_axis[1], self.major_axis[0]], minor / length);
+        Ellipse::from_conjugate(
+            self.center,
+            axis,
+            turned,
+            self.start_param,
+            self.start_param + self.sweep(),
+        )
+    }
+}
+
+impl Geometry for Ellipse {
+    fn hit_distance(&self, point: [f32; 2]) -> f32 {
+        distance(point, self.point_at(self.param_near(point)))
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        let mut bbox = BoundingBox::new(self.start_point(), self.end_point());
+
+        // x and y are furthest out where they stop growing, half a turn apart
+        let (major, minor) = (self.major_axis, self.minor_axis());
+        for t in [minor[0].atan2(major[0]), minor[1].atan2(major[1])] {
+            for t in [t, t + PI] {
+                if self.contains_param(t) {
+                    let p = self.point_at(t);
+                    bbox = bbox.union(&BoundingBox::new(p, p));
+                }
+            }
+        }
+
+        bbox
+    }
+
+    fn crosses(&self, area: &BoundingBox) -> bool {
+        self.points()
+            .windows(2)
+            .any(|pair| segment_crosses_box(pair[0], pair[1], area))
+    }
+
+    // the ends of the axes that are on it and the center, the ends of an elliptical arc
+    fn snap_points(&self) -> Vec<[f32; 2]> {
+        let mut points: Vec<[f32; 2]> = (0..4)
+            .map(|i| i as f32 * PI / 2.0)
+            .filter(|t| self.contains_param(*t))
+            .map(|t| self.point_at(t))
+            .collect();
+        points.push(self.center);
+        if !self.is_closed() {
+            points.push(self.start_point());
+            points.push(self.end_point());
+        }
+        points
+    }
+
+    // the center, then the ends of the axes, which set how long they are
+    fn grips(&self) -> Vec<Grip> {
+        let mut grips = vec![Grip::new(self.center)];
+        grips.extend(
+            self.quadrants()
+                .iter()
+                .map(|quadrant| Grip::anchored(*quadrant, self.center)),
+        );
+        grips
+    }
+
+    fn move_grip(&mut self, index: usize, position: [f32; 2]) {
+        let reach = distance(self.center, position);
+        let resized = match index {
+            0 => {
+                self.translate(position[0] - self.center[0], position[1] - self.center[1]);
+                return;
+            }
+            // the major axis follows the grip around, the elliptical arc turns with it
+            1 | 3 => {
+                let axis = sub(position, self.center);
+                let axis = if index == 3 { scale(axis, -1.0) } else { axis };
+                Ellipse {
+                    major_axis: axis,
+                    ..*self
+                }
+                .resized(reach, self.minor_radius())
+            }
+            _ => self.resized(self.major_radius(), reach),
+        };
+        self.reshape(resized);
+    }
+
+    fn translate(&mut self, dx: f32, dy: f32) {
+        self.center[0] += dx;
+        self.center[1] += dy;
+    }
+
+    // an ellipse goes along if its center is inside, same as a circle
+    fn stretch(&mut self, area: &BoundingBox, by: [f32; 2]) {
+        if area.contains_point(self.center) {
+            self.translate(by[0], by[1]);
+        }
+    }
+
+    fn transform(&mut self, transform: &Transform) {
+        let center = transform.apply(self.center);
+        let along = |axis: [f32; 2]| sub(transform.apply(add(self.center, axis)), center);
+        let transformed = Ellipse::from_conjugate(
+            center,
+            along(self.major_axis),
+            along(self.minor_axis()),
+            self.start_param,
+            self.start_param + self.sweep(),
+        );
+        self.reshape(transformed);
+    }
+
+    fn properties(&self) -> Vec<Property> {
+        let mut properties = vec![
+            Property::new("Thickness", self.style.thickness, 0.1, true),
+            Property::new("Major radius", self.major_radius(), 0.1, true),
+            Property::new("Minor radius", self.minor_radius(), 0.1, true),
+            Property::new("Rotation", self.rotation().to_degrees(), 1.0, false),
+        ];
+        if !self.is_closed() {
+            properties.push(Property::new(
+                "Start parameter",
+                self.start_param.to_degrees(),
+                1.0,
+                false,
+            ));
+            properties.push(Property::new(
+                "End parameter",
+                self.end_param.to_degrees(),
+                1.0,
+                false,
+            ));
+        }
+        properties.push(Property::new(
+            "Center X coordinate",
+            self.center[0],
+            1.0,
+            false,
+        ));
+        properties.push(Property::new(
+            "Center Y coordinate",
+            self.center[1],
+            1.0,
+            false,
+        ));
+        properties
+    }
+
+    fn set_property(&mut self, name: &str, value: f32) {
+        match name {
+            "Thickness" => self.style.thickness = value,
+            "Major radius" => {
+                let resized = self.resized(value, self.minor_radius());
+                self.reshape(resized);
+            }
+            "Minor radius" => {
+                let resized = self.resized(self.major_radius(), value);
+                self.reshape(resized);
+            }
+            "Rotation" => {
+                let (sin, cos) = value.to_radians().sin_cos();
+                self.major_axis = scale([cos, sin], self.major_radius());
+            }
+            "Start parameter" => self.start_param = value.to_radians().rem_euclid(TAU),
+            "End parameter" => self.end_param = value.to_radians().rem_euclid(TAU),
+            "Center X coordinate" => self.center[0] = value,
+            "Center Y coordinate" => self.center[1] = value,
+            _ => {}
+        }
+    }
+
+    fn to_dxf(&self) -> EntityType {
+        EntityType::Ellipse(dxf::entities::Ellipse {
+            center: dxf::Point::new(self.center[0] as f64, self.center[1] as f64, 0.0),
+            major_axis: dxf::Vector::new(self.major_axis[0] as f64, self.major_axis[1] as f64, 0.0),
+            minor_axis_ratio: self.ratio as f64,
+            start_parameter: self.start_param as f64,
+            // a whole ellipse goes all the way around to TAU
+            end_parameter: (self.start_param + self.sweep()) as f64,
+            ..Default::default()
+        })
+    }
+
+    fn push_instances(&self, batch: &mut RenderBatch, stroke: Stroke) {
+        let mut offset = 0.0;
+
+        for pair in self.points().windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            batch.lines.push(LineInstance {
+                start: [a[0], a[1], 0.0],
+                end: [b[0], b[1], 0.0],
+                color: stroke.color,
+                thickness: stroke.thickness,
+                dashes: stroke.dashes.starting_at(offset),
+            });
+            offset += distance(a, b);
+        }
+    }
+}
+
+pub trait EllipseOps {
+    fn add_ellipse(&mut self, ellipse: Ellipse, is_drawing: bool) -> u64;
+    fn update_ellipse(&mut self, shape: Ellipse, is_drawing_flag: bool);
+    fn cancel_drawing_ellipse(&mut self);
+}
+
+impl EllipseOps for Document {
+    fn add_ellipse(&mut self, ellipse: Ellipse, is_drawing: bool) -> u64 {
+        let id = self.next_id();
+
+        self.entities.insert(
+            id,
+            Entity::Ellipse(Ellipse {
+                id,
+                is_drawing,
+                style: Style::new(self.current_layer, self.settings.circle_thickness),
+                ..ellipse
+            }),
+        );
+
+        if is_drawing {
+            self.active_ellipse_id = Some(id);
+        }
+
+        id
+    }
+
+    // gives the ellipse being drawn the geometry of `shape`
+    fn update_ellipse(&mut self, shape: Ellipse, is_drawing_flag: bool) {
+        if let Some(ellipse) = self
+            .active_ellipse_id
+            .and_then(|id| self.entities.get_mut(id))
+            .and_then(Entity::as_ellipse_mut)
+        {
+            ellipse.reshape(shape);
+            ellipse.is_drawing = is_drawing_flag;
+            let finished_ellipse = *ellipse;
+
+            if !is_drawing_flag {
+                self.active_ellipse_id = None;
+                self.record(
+                    "Ellipse",
+                    vec![Change::Add(Entity::Ellipse(finished_ellipse))],
+                );
+            }
+        }
+    }
+
+    fn cancel_drawing_ellipse(&mut self) {
+        if let Some(id) = self.active_ellipse_id.take() {
+            self.entities.remove(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::line::LineOps;
+    use crate::model::offset::OffsetOps;
+    use crate::model::trim::TrimOps;
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+    use std::fs;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    fn near(a: [f32; 2], b: [f32; 2]) -> bool {
+        distance(a, b) < 1e-3
+    }
+
+    // Ramanujan's approximation, good to far better than the drawn pieces for these shapes
+    fn perimeter(a: f32, b: f32) -> f32 {
+        let h = ((a - b) / (a + b)).powi(2);
+        PI * (a + b) * (1.0 + 3.0 * h / (10.0 + (4.0 - 3.0 * h).sqrt()))
+    }
+
+    fn ellipse_of(document: &Document, id: u64) -> Ellipse {
+        match document.entities.get(id) {
+            Some(Entity::Ellipse(ellipse)) => *ellipse,
+            other => panic!("expected an ellipse, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn a_turned_ellipse() {
+        // 45 degrees round, half axes 3√2 and 1.5√2
+        let ellipse = Ellipse::new([1.0, 2.0], [3.0, 3.0], 0.5, 0.0, TAU);
+        assert!(ellipse.is_closed());
+        assert!(close(ellipse.rotation(), FRAC_PI_4));
+
+        let [a, b] = [ellipse.major_radius(), ellipse.minor_radius()];
+        assert!((ellipse.length() - perimeter(a, b)).abs() < 1e-2);
+
+        let quadrants = ellipse.quadrants();
+        for (quadrant, expected) in
+            quadrants
+                .iter()
+                .zip([[4.0, 5.0], [-0.5, 3.5], [-2.0, -1.0], [2.5, 0.5]])
+        {
+            assert!(near(*quadrant, expected), "{:?}", quadrant);
+        }
+
+        // sqrt(a² cos² + b² sin²) across either way at 45 degrees
+        let reach = 11.25_f32.sqrt();
+        let bbox = ellipse.bounding_box();
+        assert!(near(bbox.min, [1.0 - reach, 2.0 - reach]), "{:?}", bbox);
+        assert!(near(bbox.max, [1.0 + reach, 2.0 + reach]), "{:?}", bbox);
+    }
+
+    #[test]
+    fn an_elliptical_arc() {
+        // the top of an ellipse 4 by 2, from 45 to 135 degrees of its parameter
+        let arc = Ellipse::new([0.0, 0.0], [4.0, 0.0], 0.5, FRAC_PI_4, 3.0 * FRAC_PI_4);
+        assert!(!arc.is_closed());
+        assert!(close(arc.sweep(), FRAC_PI_2));
+
+        let (start, end) = (arc.start_point(), arc.end_point());
+        assert!(near(start, [8.0_f32.sqrt(), 2.0_f32.sqrt()]), "{:?}", start);
+        assert!(near(end, [-(8.0_f32.sqrt()), 2.0_f32.sqrt()]), "{:?}", end);
+        assert!(near(arc.mid_point(), [0.0, 2.0]));
+
+        // only the top of the minor axis is on it
+        let snaps = arc.snap_points();
+        assert!(snaps.iter().any(|p| near(*p, [0.0, 2.0])));
+        assert!(!snaps
+            .iter()
+            .any(|p| near(*p, [4.0, 0.0]) || near(*p, [0.0, -2.0])));
+
+        let bbox = arc.bounding_box();
+        assert!(
+            near(bbox.min, [-(8.0_f32.sqrt()), 2.0_f32.sqrt()]),
+            "{:?}",
+            bbox
+        );
+        assert!(near(bbox.max, [8.0_f32.sqrt(), 2.0]), "{:?}", bbox);
+
+        // a quarter of the way round from the end of the major axis is a quarter of the length
+        let quarter = Ellipse::new([0.0, 0.0], [4.0, 0.0], 0.5, 0.0, FRAC_PI_2);
+        assert!((quarter.length() - perimeter(4.0, 2.0) / 4.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn ends_that_meet_make_a_whole_ellipse() {
+        let ellipse = Ellipse::new([0.0, 0.0], [4.0, 0.0], 0.5, 1.0, 1.0 + TAU);
+        assert!(ellipse.is_closed());
+        assert_eq!((ellipse.start_param, ellipse.end_param), (0.0, TAU));
+    }
+
+    #[test]
+    fn ellipses_survive_a_round_trip() {
+        let mut document = Document::new();
+        let whole = Ellipse::new([1.0, 2.0], [3.0, 3.0], 0.5, 0.0, TAU);
+        let arc = Ellipse::new([5.0, 5.0], [0.0, 4.0], 0.25, 1.0, 4.0);
+        document.add_ellipse(whole, false);
+        document.add_ellipse(arc, false);
+
+        let path =
+            std::env::temp_dir().join(format!("easycad-ellipses-{}.dxf", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        document.save_to_dxf(&path).unwrap();
+        let mut loaded = Document::new();
+        let summary = loaded.load_from_dxf(path.clone()).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(summary.imported.get("ELLIPSE"), Some(&2));
+        let mut ellipses: Vec<Ellipse> = loaded
+            .entities
+            .iter()
+            .filter_map(|entity| match entity {
+                Entity::Ellipse(ellipse) => Some(*ellipse),
+                _ => None,
+            })
+            .collect();
+        ellipses.sort_by(|a, b| a.center[0].total_cmp(&b.center[0]));
+
+        for (loaded, saved) in ellipses.iter().zip([whole, arc]) {
+            assert_eq!(loaded.center, saved.center);
+            assert_eq!(loaded.major_axis, saved.major_axis);
+            assert!(close(loaded.ratio, saved.ratio));
+            assert!(close(loaded.start_param, saved.start_param));
+            assert!(close(loaded.end_param, saved.end_param));
+        }
+    }
+
+    #[test]
+    fn trimming_a_whole_ellipse_keeps_the_far_side() {
+        let mut document = Document::new();
+        let id = document.add_ellipse(Ellipse::new([0.0, 0.0], [4.0, 0.0], 0.5, 0.0, TAU), false);
+        let edge = document.add_line([0.0, -5.0], [0.0, 5.0], false);
+
+        assert!(document.trim(id, [4.0, 0.0], &[edge]));
+        let left = ellipse_of(&document, id);
+        assert!(close(left.start_param, FRAC_PI_2) && close(left.end_param, 3.0 * FRAC_PI_2));
+        assert!(near(left.mid_point(), [-4.0, 0.0]));
+        assert_eq!(document.entities.len(), 2);
+
+        assert_eq!(document.undo(), Some("Trim"));
+        assert!(ellipse_of(&document, id).is_closed());
+    }
+
+    #[test]
+    fn trimming_the_middle_of_an_elliptical_arc_splits_it() {
+        let mut document = Document::new();
+        let id = document.add_ellipse(Ellipse::new([0.0, 0.0], [4.0, 0.0], 0.5, 0.0, PI), false);
+        let edges = [
+            document.add_line([-2.0, -5.0], [-2.0, 5.0], false),
+            document.add_line([2.0, -5.0], [2.0, 5.0], false),
+        ];
+
+        assert!(document.trim(id, [0.0, 2.0], &edges));
+        let right = ellipse_of(&document, id);
+        assert!(near(right.start_point(), [4.0, 0.0]));
+        assert!(
+            near(right.end_point(), [2.0, 3.0_f32.sqrt()]),
+            "{:?}",
+            right.end_point()
+        );
+
+        let new = document.entities.ids().max().unwrap();
+        let left = ellipse_of(&document, new);
+        assert!(near(left.start_point(), [-2.0, 3.0_f32.sqrt()]));
+        assert!(near(left.end_point(), [-4.0, 0.0]));
+    }
+
+    #[test]
+    fn ellipses_cut_what_crosses_them() {
+        let mut document = Document::new();
+        document.add_ellipse(Ellipse::new([0.0, 0.0], [4.0, 0.0], 0.5, 0.0, TAU), false);
+        let line = document.add_line([-10.0, 0.0], [10.0, 0.0], false);
+
+        assert!(document.trim(line, [0.0, 0.0], &[]));
+        let Some(Entity::Line(left)) = document.entities.get(line) else {
+            panic!("not a line");
+        };
+        assert!(near(left.start(), [-10.0, 0.0]) && near(left.end(), [-4.0, 0.0]));
+    }
+
+    #[test]
+    fn nothing_to_cut_at_leaves_it_alone() {
+        let mut document = Document::new();
+        let id = document.add_ellipse(Ellipse::new([0.0, 0.0], [4.0, 0.0], 0.5, 0.0, TAU), false);
+        // a single crossing can't cut a closed curve in two
+        document.add_line([0.0, 1.0], [0.0, 5.0], false);
+
+        assert!(!document.trim(id, [4.0, 0.0], &[]));
+        assert!(ellipse_of(&document, id).is_closed());
+    }
+
+    #[test]
+    fn ellipses_cant_be_offset() {
+        let mut document = Document::new();
+        let id = document.add_ellipse(Ellipse::new([0.0, 0.0], [4.0, 0.0], 0.5, 0.0, TAU), false);
+
+        assert_eq!(document.offset(id, Some(1.0), [6.0, 0.0], false), None);
+        assert_eq!(document.entities.len(), 1);
+        assert_eq!(document.undo(), None);
+    }
+}
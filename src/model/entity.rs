@@ -0,0 +1,342 @@
+use crate::graphics::gui_elements::ColorScheme;
+use crate::model::arc::{Arc, ArcInstance};
+use crate::model::circle::{Circle, CircleInstance};
+use crate::model::color::EntityColor;
+use crate::model::document::Document;
+use crate::model::ellipse::Ellipse;
+use crate::model::geometry::BoundingBox;
+use crate::model::grip::Grip;
+use crate::model::line::{Line, LineInstance};
+use crate::model::linetype::{Dashes, EntityLinetype, LinetypeOps};
+use crate::model::point::Point;
+use crate::model::polyline::Polyline;
+use crate::model::style::Style;
+use crate::model::transform::Transform;
+use dxf::entities::EntityType;
+
+// Everything a shape has to be able to do so the rest of the app doesn't need to know which
+// shape it is dealing with. Adding a new entity type means implementing this and adding a
+// variant to `Entity`.
+pub trait Geometry {
+    // distance from `point` to the nearest point of the shape, used for picking
+    fn hit_distance(&self, point: [f32; 2]) -> f32;
+    fn bounding_box(&self) -> BoundingBox;
+    // whether any part of the shape is inside `area`, used for crossing selections
+    fn crosses(&self, area: &BoundingBox) -> bool;
+    fn snap_points(&self) -> Vec<[f32; 2]>;
+    // handles shown while the entity is selected, dragging one reshapes it
+    fn grips(&self) -> Vec<Grip>;
+    // puts grip `index` of `grips` at `position`
+    fn move_grip(&mut self, index: usize, position: [f32; 2]);
+    fn translate(&mut self, dx: f32, dy: f32);
+    // moves the points that define the shape and are inside `area` by `by`, the rest stay put
+    fn stretch(&mut self, area: &BoundingBox, by: [f32; 2]);
+    // rotates, scales or mirrors the shape
+    fn transform(&mut self, transform: &Transform);
+    // values shown (and editable) in the properties panel
+    fn properties(&self) -> Vec<Property>;
+    fn set_property(&mut self, name: &str, value: f32);
+    fn to_dxf(&self) -> EntityType;
+    fn push_instances(&self, batch: &mut RenderBatch, stroke: Stroke);
+}
+
+// how an entity is drawn once its layer, linetype and selection are looked up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stroke {
+    pub color: [f32; 3],
+    pub thickness: f32,
+    pub dashes: Dashes,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Property {
+    pub name: &'static str,
+    pub value: f32,
+    pub speed: f64,
+    // can be set on a whole selection at once (thickness yes, start x of 10 lines no)
+    pub shared: bool,
+}
+
+impl Property {
+    pub fn new(name: &'static str, value: f32, speed: f64, shared: bool) -> Self {
+        Self {
+            name,
+            value,
+            speed,
+            shared,
+        }
+    }
+}
+
+// GPU instances of everything that's visible, grouped by the pipeline that draws them
+#[derive(Debug, Default)]
+pub struct RenderBatch {
+    pub lines: Vec<LineInstance>,
+    pub circles: Vec<CircleInstance>,
+    pub arcs: Vec<ArcInstance>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Entity {
+    Line(Line),
+    Circle(Circle),
+    Arc(Arc),
+    Ellipse(Ellipse),
+    Polyline(Polyline),
+    Point(Point),
+}
+
+// runs the same expression on whatever shape is inside the entity
+macro_rules! each {
+    ($entity:expr, $shape:ident => $body:expr) => {
+        match $entity {
+            Entity::Line($shape) => $body,
+            Entity::Circle($shape) => $body,
+            Entity::Arc($shape) => $body,
+            Entity::Ellipse($shape) => $body,
+            Entity::Polyline($shape) => $body,
+            Entity::Point($shape) => $body,
+        }
+    };
+}
+
+impl Entity {
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            Entity::Line(_) => "Line",
+            Entity::Circle(_) => "Circle",
+            Entity::Arc(_) => "Arc",
+            Entity::Ellipse(_) => "Ellipse",
+            Entity::Polyline(_) => "Polyline",
+            Entity::Point(_) => "Point",
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        each!(self, shape => shape.id)
+    }
+
+    pub fn set_id(&mut self, id: u64) {
+        each!(self, shape => shape.id = id)
+    }
+
+    pub fn selected(&self) -> bool {
+        each!(self, shape => shape.selected)
+    }
+
+    pub fn set_selected(&mut self, selected: bool) {
+        each!(self, shape => shape.selected = selected)
+    }
+
+    pub fn is_drawing(&self) -> bool {
+        each!(self, shape => shape.is_drawing)
+    }
+
+    pub fn set_drawing(&mut self, is_drawing: bool) {
+        each!(self, shape => shape.is_drawing = is_drawing)
+    }
+
+    pub fn style(&self) -> Style {
+        each!(self, shape => shape.style)
+    }
+
+    pub fn set_style(&mut self, style: Style) {
+        each!(self, shape => shape.style = style)
+    }
+
+    pub fn thickness(&self) -> f32 {
+        each!(self, shape => shape.style.thickness)
+    }
+
+    pub fn set_thickness(&mut self, thickness: f32) {
+        each!(self, shape => shape.style.thickness = thickness)
+    }
+
+    pub fn layer(&self) -> u64 {
+        each!(self, shape => shape.style.layer)
+    }
+
+    pub fn set_layer(&mut self, layer: u64) {
+        each!(self, shape => shape.style.layer = layer)
+    }
+
+    pub fn color(&self) -> EntityColor {
+        each!(self, shape => shape.style.color)
+    }
+
+    pub fn set_color(&mut self, color: EntityColor) {
+        each!(self, shape => shape.style.color = color)
+    }
+
+    pub fn thickness_by_layer(&self) -> bool {
+        each!(self, shape => shape.style.thickness_by_layer)
+    }
+
+    pub fn set_thickness_by_layer(&mut self, by_layer: bool) {
+        each!(self, shape => shape.style.thickness_by_layer = by_layer)
+    }
+
+    pub fn linetype(&self) -> EntityLinetype {
+        each!(self, shape => shape.style.linetype)
+    }
+
+    pub fn set_linetype(&mut self, linetype: EntityLinetype) {
+        each!(self, shape => shape.style.linetype = linetype)
+    }
+
+    pub fn linetype_scale(&self) -> f32 {
+        each!(self, shape => shape.style.linetype_scale)
+    }
+
+    pub fn set_linetype_scale(&mut self, scale: f32) {
+        each!(self, shape => shape.style.linetype_scale = scale)
+    }
+
+    // layer, color, lineweight and linetype of `other`, for something made out of it
+    pub fn copy_style(&mut self, other: &Entity) {
+        self.set_style(other.style())
+    }
+
+    // marks the original of a move that's in progress
+    pub fn del(&self) -> bool {
+        each!(self, shape => shape.del)
+    }
+
+    pub fn set_del(&mut self, del: bool) {
+        each!(self, shape => shape.del = del)
+    }
+
+    pub fn as_line_mut(&mut self) -> Option<&mut Line> {
+        match self {
+            Entity::Line(line) => Some(line),
+            _ => None,
+        }
+    }
+
+    pub fn as_circle_mut(&mut self) -> Option<&mut Circle> {
+        match self {
+            Entity::Circle(circle) => Some(circle),
+            _ => None,
+        }
+    }
+
+    pub fn as_arc_mut(&mut self) -> Option<&mut Arc> {
+        match self {
+            Entity::Arc(arc) => Some(arc),
+            _ => None,
+        }
+    }
+
+    pub fn as_ellipse_mut(&mut self) -> Option<&mut Ellipse> {
+        match self {
+            Entity::Ellipse(ellipse) => Some(ellipse),
+            _ => None,
+        }
+    }
+
+    pub fn as_polyline_mut(&mut self) -> Option<&mut Polyline> {
+        match self {
+            Entity::Polyline(polyline) => Some(polyline),
+            _ => None,
+        }
+    }
+}
+
+impl Geometry for Entity {
+    fn hit_distance(&self, point: [f32; 2]) -> f32 {
+        each!(self, shape => shape.hit_distance(point))
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        each!(self, shape => shape.bounding_box())
+    }
+
+    fn crosses(&self, area: &BoundingBox) -> bool {
+        each!(self, shape => shape.crosses(area))
+    }
+
+    fn snap_points(&self) -> Vec<[f32; 2]> {
+        each!(self, shape => shape.snap_points())
+    }
+
+    fn grips(&self) -> Vec<Grip> {
+        each!(self, shape => shape.grips())
+    }
+
+    fn move_grip(&mut self, index: usize, position: [f32; 2]) {
+        each!(self, shape => shape.move_grip(index, position))
+    }
+
+    fn translate(&mut self, dx: f32, dy: f32) {
+        each!(self, shape => shape.translate(dx, dy))
+    }
+
+    fn stretch(&mut self, area: &BoundingBox, by: [f32; 2]) {
+        each!(self, shape => shape.stretch(area, by))
+    }
+
+    fn transform(&mut self, transform: &Transform) {
+        each!(self, shape => shape.transform(transform))
+    }
+
+    fn properties(&self) -> Vec<Property> {
+        each!(self, shape => shape.properties())
+    }
+
+    fn set_property(&mut self, name: &str, value: f32) {
+        // a thickness typed in overrides the layer's lineweight
+        if name == "Thickness" {
+            self.set_thickness_by_layer(false);
+        }
+        each!(self, shape => shape.set_property(name, value))
+    }
+
+    fn to_dxf(&self) -> EntityType {
+        each!(self, shape => shape.to_dxf())
+    }
+
+    fn push_instances(&self, batch: &mut RenderBatch, stroke: Stroke) {
+        each!(self, shape => shape.push_instances(batch, stroke))
+    }
+}
+
+// selected entities get a wider copy of themselves in this color drawn underneath, selected texts
+// are painted in it
+pub const SELECTION_COLOR: [f32; 3] = [0.25, 0.55, 1.0];
+const SELECTION_HALO: f32 = 6.0;
+
+// turn every entity on a shown layer into GPU instances
+pub fn flatten_entities_to_instances(
+    document: &Document,
+    color_scheme: ColorScheme,
+) -> RenderBatch {
+    let mut batch = RenderBatch::default();
+    let layers = &document.layers;
+    let shown = || {
+        document
+            .entities
+            .iter()
+            .filter(|entity| layers.is_shown(entity.layer()))
+    };
+
+    // all halos first so none of them covers an entity, they stay solid to read as one outline
+    for entity in shown().filter(|entity| entity.selected()) {
+        let stroke = Stroke {
+            color: SELECTION_COLOR,
+            thickness: layers.thickness(entity) + SELECTION_HALO,
+            dashes: Dashes::SOLID,
+        };
+        entity.push_instances(&mut batch, stroke);
+    }
+
+    for entity in shown() {
+        let stroke = Stroke {
+            color: layers.color(entity, color_scheme),
+            thickness: layers.thickness(entity),
+            dashes: document.dashes(entity),
+        };
+        entity.push_instances(&mut batch, stroke);
+    }
+
+    batch
+}
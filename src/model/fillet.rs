@@ -0,0 +1,485 @@
+use crate::model::arc::Arc;
+use crate::model::document::Document;
+use crate::model::entity::Entity;
+use crate::model::geometry::{add, along, cross, distance, dot, line_line, scale, sub};
+use crate::model::history::{Change, HistoryOps};
+use crate::model::line::Line;
+use crate::model::path::{rebuild, Path, Piece, EPSILON};
+use crate::model::polyline::PolylineVertex;
+use std::f32::consts::PI;
+
+// how a chamfer is measured, the angle is in degrees and taken from the first line
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Chamfer {
+    Distances(f32, f32),
+    DistanceAngle(f32, f32),
+}
+
+impl Chamfer {
+    // "2" for 2 on both lines, "2,3" for 2 on the first and 3 on the second, "2<30" for 2 on
+    // the first line at 30 degrees
+    pub fn parse(text: &str) -> Option<Chamfer> {
+        let number = |text: &str| text.trim().parse::<f32>().ok().filter(|v| *v >= 0.0);
+
+        let chamfer = if let Some((length, angle)) = text.split_once('<') {
+            Chamfer::DistanceAngle(number(length)?, number(angle)?)
+        } else if let Some((first, second)) = text.split_once(',') {
+            Chamfer::Distances(number(first)?, number(second)?)
+        } else {
+            let length = number(text)?;
+            Chamfer::Distances(length, length)
+        };
+        Some(chamfer)
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Chamfer::Distances(first, second) if first == second => format!("{}", first),
+            Chamfer::Distances(first, second) => format!("{}, {}", first, second),
+            Chamfer::DistanceAngle(length, angle) => format!("{} at {}°", length, angle),
+        }
+    }
+
+    // distances along both lines for lines that meet at `corner` radians
+    pub fn distances(&self, corner: f32) -> Option<(f32, f32)> {
+        match *self {
+            Chamfer::Distances(first, second) => Some((first, second)),
+            Chamfer::DistanceAngle(length, angle) => {
+                let angle = angle.to_radians();
+                // the rest of the triangle the chamfer cuts off, by the law of sines
+                let far = PI - corner - angle;
+                (angle > 0.0 && far > EPSILON).then(|| (length, length * angle.sin() / far.sin()))
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Cut {
+    Round(f32),
+    Bevel(Chamfer),
+}
+
+impl Cut {
+    fn name(&self) -> &'static str {
+        match self {
+            Cut::Round(_) => "Fillet",
+            Cut::Bevel(_) => "Chamfer",
+        }
+    }
+}
+
+// Two straight runs that meet at `apex` and go on to `first` and `second`. Returns where each
+// gets cut and what bridges the cut, nothing in between for a sharp corner. None if the runs
+// are parallel or too short for the cut.
+fn cut_corner(
+    apex: [f32; 2],
+    first: [f32; 2],
+    second: [f32; 2],
+    cut: Cut,
+) -> Option<([f32; 2], [f32; 2], Option<Piece>)> {
+    let (first_length, second_length) = (distance(apex, first), distance(apex, second));
+    if first_length < EPSILON || second_length < EPSILON {
+        return None;
+    }
+    let (u, v) = (
+        scale(sub(first, apex), 1.0 / first_length),
+        scale(sub(second, apex), 1.0 / second_length),
+    );
+
+    let corner = dot(u, v).clamp(-1.0, 1.0).acos();
+    if !(EPSILON..=PI - EPSILON).contains(&corner) {
+        return None;
+    }
+
+    let (along_first, along_second) = match cut {
+        Cut::Round(radius) => {
+            let tangent = radius / (corner / 2.0).tan();
+            (tangent, tangent)
+        }
+        Cut::Bevel(chamfer) => chamfer.distances(corner)?,
+    };
+    if along_first > first_length + EPSILON || along_second > second_length + EPSILON {
+        return None;
+    }
+
+    let start = add(apex, scale(u, along_first));
+    let end = add(apex, scale(v, along_second));
+    if distance(start, end) < EPSILON {
+        return Some((apex, apex, None));
+    }
+
+    let bridge = match cut {
+        Cut::Round(radius) => {
+            let bisector = add(u, v);
+            let center = add(
+                apex,
+                scale(
+                    bisector,
+                    radius / (corner / 2.0).sin() / dot(bisector, bisector).sqrt(),
+                ),
+            );
+            let angle_of = |p: [f32; 2]| (p[1] - center[1]).atan2(p[0] - center[0]);
+            let turn = cross(sub(start, center), sub(end, center)).signum();
+            Piece::Arc {
+                center,
+                radius,
+                start: angle_of(start),
+                sweep: turn * (PI - corner),
+            }
+        }
+        Cut::Bevel(_) => Piece::Line(start, end),
+    };
+    Some((start, end, Some(bridge)))
+}
+
+pub trait FilletOps {
+    // Rounds the corner between two picked lines, or two neighbouring straight segments of a
+    // polyline, with an arc of `radius`. The lines are cut back or run on to the arc and keep the
+    // side they were picked on, a radius of 0 just makes them meet. Returns whether it worked.
+    fn fillet(&mut self, first: (u64, [f32; 2]), second: (u64, [f32; 2]), radius: f32) -> bool;
+    // same with a straight line across the corner
+    fn chamfer(
+        &mut self,
+        first: (u64, [f32; 2]),
+        second: (u64, [f32; 2]),
+        chamfer: Chamfer,
+    ) -> bool;
+}
+
+fn cut_lines(
+    document: &mut Document,
+    first: (u64, [f32; 2]),
+    second: (u64, [f32; 2]),
+    cut: Cut,
+) -> bool {
+    let (Some(Entity::Line(a)), Some(Entity::Line(b))) = (
+        document.entities.get(first.0),
+        document.entities.get(second.0),
+    ) else {
+        return false;
+    };
+    let (a, b) = (*a, *b);
+
+    let Some(apex) = line_line((a.start(), a.end()), (b.start(), b.end())).pop() else {
+        return false;
+    };
+
+    // each line keeps the end on the side of the corner it was picked on, the other end
+    // moves to the cut
+    let kept_end = |start: [f32; 2], end: [f32; 2], pick: [f32; 2]| {
+        let picked = along(pick, start, end).unwrap_or(0.0);
+        let corner = along(apex, start, end).unwrap_or(0.0);
+        usize::from(picked > corner)
+    };
+    let a_kept = kept_end(a.start(), a.end(), first.1);
+    let b_kept = kept_end(b.start(), b.end(), second.1);
+    let far = |line: &Line, kept: usize| {
+        let position = line.vertices[kept].position;
+        [position[0], position[1]]
+    };
+
+    let Some((a_cut, b_cut, bridge)) = cut_corner(apex, far(&a, a_kept), far(&b, b_kept), cut)
+    else {
+        return false;
+    };
+
+    let mut changes = Vec::new();
+    for (line, kept, point) in [(a, a_kept, a_cut), (b, b_kept, b_cut)] {
+        let mut after = line;
+        after.vertices[1 - kept].position = [point[0], point[1], 0.0];
+        if let Some(entity) = document.entities.get_mut(line.id) {
+            *entity = Entity::Line(after);
+        }
+        changes.push(Change::Modify(Entity::Line(line), Entity::Line(after)));
+    }
+
+    let template = Entity::Line(a);
+    let bridge = match bridge {
+        Some(piece @ Piece::Line(..)) => rebuild(&template, &[piece]),
+        Some(Piece::Arc {
+            center,
+            radius,
+            start,
+            sweep,
+        }) => {
+            let (from, to) = if sweep > 0.0 {
+                (start, start + sweep)
+            } else {
+                (start + sweep, start)
+            };
+            let mut arc = Entity::Arc(Arc::new(center, radius, from, to));
+            arc.copy_style(&template);
+            Some(arc)
+        }
+        None => None,
+    };
+    if let Some(mut bridge) = bridge {
+        let id = document.next_id();
+        bridge.set_id(id);
+        document.entities.insert(id, bridge.clone());
+        changes.push(Change::Add(bridge));
+    }
+
+    document.record(cut.name(), changes);
+    true
+}
+
+// cuts the vertex between the two picked segments of polyline `id`
+fn cut_vertex(document: &mut Document, id: u64, picks: [[f32; 2]; 2], cut: Cut) -> bool {
+    let Some(Entity::Polyline(polyline)) = document.entities.get(id) else {
+        return false;
+    };
+    let before = polyline.clone();
+    let Some(path) = Path::of(&Entity::Polyline(before.clone())) else {
+        return false;
+    };
+
+    let count = before.vertices.len();
+    let [first, second] = picks.map(|pick| path.param_near(pick).floor() as usize);
+    let segments = path.pieces.len();
+    // the vertex both segments share, the first pick gets the first chamfer distance
+    let follows = |a: usize, b: usize| {
+        if path.closed {
+            (a + 1) % segments == b
+        } else {
+            a + 1 == b
+        }
+    };
+    let (vertex, picked_previous_first) = if follows(first, second) {
+        ((first + 1) % count, true)
+    } else if follows(second, first) {
+        ((second + 1) % count, false)
+    } else {
+        return false;
+    };
+
+    let previous = (vertex + count - 1) % count;
+    let next = (vertex + 1) % count;
+    if before.vertices[previous].bulge != 0.0 || before.vertices[vertex].bulge != 0.0 {
+        return false;
+    }
+
+    let (apex, previous, next) = (
+        before.vertices[vertex].position,
+        before.vertices[previous].position,
+        before.vertices[next].position,
+    );
+    let corner = if picked_previous_first {
+        cut_corner(apex, previous, next, cut)
+    } else {
+        cut_corner(apex, next, previous, cut)
+    };
+    let Some((first_cut, second_cut, Some(bridge))) = corner else {
+        return false;
+    };
+    let (start, end, bulge) = if picked_previous_first {
+        (first_cut, second_cut, bridge.bulge())
+    } else {
+        (second_cut, first_cut, -bridge.bulge())
+    };
+
+    let mut after = before.clone();
+    after.vertices.splice(
+        vertex..=vertex,
+        [
+            PolylineVertex::new(start, bulge),
+            PolylineVertex::new(end, 0.0),
+        ],
+    );
+    if let Some(entity) = document.entities.get_mut(id) {
+        *entity = Entity::Polyline(after.clone());
+    }
+
+    document.record(
+        cut.name(),
+        vec![Change::Modify(
+            Entity::Polyline(before),
+            Entity::Polyline(after),
+        )],
+    );
+    true
+}
+
+impl FilletOps for Document {
+    fn fillet(&mut self, first: (u64, [f32; 2]), second: (u64, [f32; 2]), radius: f32) -> bool {
+        if first.0 == second.0 {
+            cut_vertex(self, first.0, [first.1, second.1], Cut::Round(radius))
+        } else {
+            cut_lines(self, first, second, Cut::Round(radius))
+        }
+    }
+
+    fn chamfer(
+        &mut self,
+        first: (u64, [f32; 2]),
+        second: (u64, [f32; 2]),
+        chamfer: Chamfer,
+    ) -> bool {
+        if first.0 == second.0 {
+            cut_vertex(self, first.0, [first.1, second.1], Cut::Bevel(chamfer))
+        } else {
+            cut_lines(self, first, second, Cut::Bevel(chamfer))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::line::LineOps;
+    use crate::model::polyline::PolylineOps;
+
+    fn near(a: [f32; 2], b: [f32; 2]) -> bool {
+        distance(a, b) < 1e-4
+    }
+
+    fn line_ends(document: &Document, id: u64) -> ([f32; 2], [f32; 2]) {
+        match document.entities.get(id) {
+            Some(Entity::Line(line)) => (line.start(), line.end()),
+            other => panic!("expected a line, got {:?}", other),
+        }
+    }
+
+    // two lines crossing at the origin, running on past it by 2
+    fn crossing_lines(document: &mut Document) -> (u64, u64) {
+        let a = document.add_line([-2.0, 0.0], [10.0, 0.0], false);
+        let b = document.add_line([0.0, -2.0], [0.0, 10.0], false);
+        (a, b)
+    }
+
+    #[test]
+    fn chamfer_sizes_are_parsed() {
+        assert_eq!(Chamfer::parse("2"), Some(Chamfer::Distances(2.0, 2.0)));
+        assert_eq!(Chamfer::parse("2, 3"), Some(Chamfer::Distances(2.0, 3.0)));
+        assert_eq!(
+            Chamfer::parse("2<30"),
+            Some(Chamfer::DistanceAngle(2.0, 30.0))
+        );
+        assert_eq!(Chamfer::parse("-1"), None);
+        assert_eq!(Chamfer::parse("a,b"), None);
+    }
+
+    #[test]
+    fn filleting_two_lines_cuts_them_back_to_an_arc() {
+        let mut document = Document::new();
+        let (a, b) = crossing_lines(&mut document);
+
+        assert!(document.fillet((a, [5.0, 0.0]), (b, [0.0, 5.0]), 2.0));
+
+        let (start, end) = line_ends(&document, a);
+        assert!(near(start, [2.0, 0.0]) && near(end, [10.0, 0.0]));
+        let (start, end) = line_ends(&document, b);
+        assert!(near(start, [0.0, 2.0]) && near(end, [0.0, 10.0]));
+
+        let arc = document.entities.ids().max().unwrap();
+        match document.entities.get(arc) {
+            Some(Entity::Arc(arc)) => {
+                assert!(near(arc.center, [2.0, 2.0]));
+                assert!((arc.radius - 2.0).abs() < 1e-4);
+            }
+            other => panic!("expected an arc, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn a_zero_radius_makes_the_lines_meet() {
+        let mut document = Document::new();
+        let a = document.add_line([3.0, 0.0], [10.0, 0.0], false);
+        let b = document.add_line([0.0, 3.0], [0.0, 10.0], false);
+
+        assert!(document.fillet((a, [5.0, 0.0]), (b, [0.0, 5.0]), 0.0));
+
+        assert!(near(line_ends(&document, a).0, [0.0, 0.0]));
+        assert!(near(line_ends(&document, b).0, [0.0, 0.0]));
+        assert_eq!(document.entities.len(), 2);
+    }
+
+    #[test]
+    fn impossible_fillets_change_nothing() {
+        let mut document = Document::new();
+        let (a, b) = crossing_lines(&mut document);
+        let parallel = document.add_line([0.0, 5.0], [10.0, 5.0], false);
+
+        assert!(!document.fillet((a, [5.0, 0.0]), (parallel, [5.0, 5.0]), 1.0));
+        // the arc wouldn't fit on the lines
+        assert!(!document.fillet((a, [5.0, 0.0]), (b, [0.0, 5.0]), 20.0));
+        assert!(!document.history.can_undo());
+    }
+
+    #[test]
+    fn the_picked_side_of_each_line_is_kept() {
+        let mut document = Document::new();
+        let (a, b) = crossing_lines(&mut document);
+
+        assert!(document.fillet((a, [-1.0, 0.0]), (b, [0.0, 5.0]), 0.0));
+
+        let (start, end) = line_ends(&document, a);
+        assert!(near(start, [-2.0, 0.0]) && near(end, [0.0, 0.0]));
+    }
+
+    #[test]
+    fn chamfering_two_lines_bridges_them_with_a_line() {
+        let mut document = Document::new();
+        let (a, b) = crossing_lines(&mut document);
+
+        let chamfer = Chamfer::Distances(2.0, 3.0);
+        assert!(document.chamfer((a, [5.0, 0.0]), (b, [0.0, 5.0]), chamfer));
+
+        assert!(near(line_ends(&document, a).0, [2.0, 0.0]));
+        assert!(near(line_ends(&document, b).0, [0.0, 3.0]));
+        let bridge = document.entities.ids().max().unwrap();
+        let (start, end) = line_ends(&document, bridge);
+        assert!(near(start, [2.0, 0.0]) && near(end, [0.0, 3.0]));
+
+        assert_eq!(document.undo(), Some("Chamfer"));
+        assert_eq!(document.entities.len(), 2);
+        assert!(near(line_ends(&document, a).0, [-2.0, 0.0]));
+    }
+
+    #[test]
+    fn a_chamfer_angle_sets_the_second_distance() {
+        let chamfer = Chamfer::DistanceAngle(2.0, 45.0);
+        let (first, second) = chamfer.distances(PI / 2.0).unwrap();
+
+        assert!((first - 2.0).abs() < 1e-4 && (second - 2.0).abs() < 1e-4);
+        // the chamfer would never get back to the second line
+        assert_eq!(Chamfer::DistanceAngle(2.0, 120.0).distances(PI / 2.0), None);
+    }
+
+    #[test]
+    fn a_polyline_corner_gets_an_arc_segment() {
+        let mut document = Document::new();
+        let vertices = [[0.0, 0.0], [10.0, 0.0], [10.0, 10.0]]
+            .iter()
+            .map(|position| PolylineVertex::new(*position, 0.0))
+            .collect();
+        let id = document.add_polyline(vertices, false, false);
+
+        assert!(document.fillet((id, [5.0, 0.0]), (id, [10.0, 5.0]), 2.0));
+
+        match document.entities.get(id) {
+            Some(Entity::Polyline(polyline)) => {
+                let vertices = &polyline.vertices;
+                assert_eq!(vertices.len(), 4);
+                assert!(near(vertices[1].position, [8.0, 0.0]));
+                assert!(near(vertices[2].position, [10.0, 2.0]));
+                // a left turn through a quarter circle
+                assert!((vertices[1].bulge - (PI / 8.0).tan()).abs() < 1e-4);
+            }
+            other => panic!("expected a polyline, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn polyline_segments_have_to_share_a_vertex() {
+        let mut document = Document::new();
+        let vertices = [[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0]]
+            .iter()
+            .map(|position| PolylineVertex::new(*position, 0.0))
+            .collect();
+        let id = document.add_polyline(vertices, false, false);
+
+        assert!(!document.fillet((id, [5.0, 0.0]), (id, [5.0, 10.0]), 1.0));
+    }
+}
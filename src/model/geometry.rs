@@ -0,0 +1,197 @@
+// plain 2D math shared by every entity type
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl BoundingBox {
+    pub fn new(a: [f32; 2], b: [f32; 2]) -> Self {
+        Self {
+            min: [a[0].min(b[0]), a[1].min(b[1])],
+            max: [a[0].max(b[0]), a[1].max(b[1])],
+        }
+    }
+
+    pub fn around(center: [f32; 2], radius: f32) -> Self {
+        Self {
+            min: [center[0] - radius, center[1] - radius],
+            max: [center[0] + radius, center[1] + radius],
+        }
+    }
+
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox {
+        BoundingBox {
+            min: [self.min[0].min(other.min[0]), self.min[1].min(other.min[1])],
+            max: [self.max[0].max(other.max[0]), self.max[1].max(other.max[1])],
+        }
+    }
+
+    pub fn expanded(&self, margin: f32) -> BoundingBox {
+        BoundingBox {
+            min: [self.min[0] - margin, self.min[1] - margin],
+            max: [self.max[0] + margin, self.max[1] + margin],
+        }
+    }
+
+    pub fn contains_point(&self, p: [f32; 2]) -> bool {
+        p[0] >= self.min[0] && p[0] <= self.max[0] && p[1] >= self.min[1] && p[1] <= self.max[1]
+    }
+
+    pub fn contains(&self, other: &BoundingBox) -> bool {
+        self.contains_point(other.min) && self.contains_point(other.max)
+    }
+
+    pub fn corners(&self) -> [[f32; 2]; 4] {
+        [
+            self.min,
+            [self.max[0], self.min[1]],
+            self.max,
+            [self.min[0], self.max[1]],
+        ]
+    }
+
+    pub fn edges(&self) -> [([f32; 2], [f32; 2]); 4] {
+        let c = self.corners();
+        [(c[0], c[1]), (c[1], c[2]), (c[2], c[3]), (c[3], c[0])]
+    }
+}
+
+pub fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    let dx = b[0] - a[0];
+    let dy = b[1] - a[1];
+    (dx * dx + dy * dy).sqrt()
+}
+
+pub fn point_segment_distance(p: [f32; 2], a: [f32; 2], b: [f32; 2]) -> f32 {
+    let abx: f32 = b[0] - a[0];
+    let aby: f32 = b[1] - a[1];
+
+    // handle zero length segment
+    let ab_len_squared = abx * abx + aby * aby;
+    if ab_len_squared == 0.0 {
+        return distance(p, a);
+    }
+
+    let t = ((p[0] - a[0]) * abx + (p[1] - a[1]) * aby) / ab_len_squared;
+
+    let t = t.clamp(0.0, 1.0);
+
+    distance(p, [a[0] + t * abx, a[1] + t * aby])
+}
+
+pub fn circle_hit(p: [f32; 2], center: [f32; 2], r: f32) -> f32 {
+    (distance(p, center) - r).abs()
+}
+
+pub fn add(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+pub fn sub(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+pub fn scale(a: [f32; 2], factor: f32) -> [f32; 2] {
+    [a[0] * factor, a[1] * factor]
+}
+
+pub fn dot(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+pub fn cross(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[1] - a[1] * b[0]
+}
+
+// how far along the line from `a` to `b` the foot of `point` is, 0 at `a` and 1 at `b`
+pub fn along(point: [f32; 2], a: [f32; 2], b: [f32; 2]) -> Option<f32> {
+    let direction = sub(b, a);
+    let length_squared = dot(direction, direction);
+    (length_squared > 0.0).then(|| dot(sub(point, a), direction) / length_squared)
+}
+
+// intersections of the infinite lines and full circles, callers cut them down to their segments
+// and arcs
+pub fn line_line(a: ([f32; 2], [f32; 2]), b: ([f32; 2], [f32; 2])) -> Vec<[f32; 2]> {
+    let r = sub(a.1, a.0);
+    let s = sub(b.1, b.0);
+    let denominator = cross(r, s);
+    if denominator.abs() <= f32::EPSILON * dot(r, r).max(dot(s, s)) {
+        return Vec::new();
+    }
+
+    let t = cross(sub(b.0, a.0), s) / denominator;
+    vec![add(a.0, scale(r, t))]
+}
+
+pub fn line_circle(line: ([f32; 2], [f32; 2]), center: [f32; 2], radius: f32) -> Vec<[f32; 2]> {
+    let Some(t) = along(center, line.0, line.1) else {
+        return Vec::new();
+    };
+
+    let direction = sub(line.1, line.0);
+    let foot = add(line.0, scale(direction, t));
+    let off = distance(foot, center);
+    if off > radius {
+        return Vec::new();
+    }
+
+    let half_chord = (radius * radius - off * off).sqrt() / dot(direction, direction).sqrt();
+    vec![
+        add(foot, scale(direction, -half_chord)),
+        add(foot, scale(direction, half_chord)),
+    ]
+}
+
+pub fn circle_circle(a: ([f32; 2], f32), b: ([f32; 2], f32)) -> Vec<[f32; 2]> {
+    let between = distance(a.0, b.0);
+    if between == 0.0 || between > a.1 + b.1 || between < (a.1 - b.1).abs() {
+        return Vec::new();
+    }
+
+    // distance from a's center to the chord through both intersections
+    let to_chord = (a.1 * a.1 - b.1 * b.1 + between * between) / (2.0 * between);
+    let half_chord = (a.1 * a.1 - to_chord * to_chord).max(0.0).sqrt();
+    let direction = scale(sub(b.0, a.0), 1.0 / between);
+    let foot = add(a.0, scale(direction, to_chord));
+    let normal = [-direction[1], direction[0]];
+
+    vec![
+        add(foot, scale(normal, half_chord)),
+        add(foot, scale(normal, -half_chord)),
+    ]
+}
+
+// whether the segment from `a` to `b` has any part inside `area`
+pub fn segment_crosses_box(a: [f32; 2], b: [f32; 2], area: &BoundingBox) -> bool {
+    if area.contains_point(a) || area.contains_point(b) {
+        return true;
+    }
+
+    // both ends are outside, so it has to cut through an edge
+    let on_segment = |p: [f32; 2], from: [f32; 2], to: [f32; 2]| {
+        along(p, from, to).is_some_and(|t| (0.0..=1.0).contains(&t))
+    };
+    area.edges().iter().any(|&(from, to)| {
+        line_line((a, b), (from, to))
+            .into_iter()
+            .any(|p| on_segment(p, a, b) && on_segment(p, from, to))
+    })
+}
+
+// whether the outline of the circle runs through `area`, a box all inside the circle doesn't count
+pub fn circle_crosses_box(center: [f32; 2], radius: f32, area: &BoundingBox) -> bool {
+    let nearest = [
+        center[0].clamp(area.min[0], area.max[0]),
+        center[1].clamp(area.min[1], area.max[1]),
+    ];
+    let farthest = area
+        .corners()
+        .iter()
+        .map(|corner| distance(center, *corner))
+        .fold(0.0, f32::max);
+
+    distance(center, nearest) <= radius && farthest >= radius
+}
@@ -0,0 +1,356 @@
+use crate::model::document::Document;
+use crate::model::entity::{Entity, Geometry};
+use crate::model::geometry::distance;
+use crate::model::history::{Change, HistoryOps};
+
+// half the size of a grip's square and how close a click has to be to hit it, in pixels
+pub const GRIP_SIZE: f32 = 5.0;
+
+// a bigger selection than this doesn't show grips, there'd be nothing to see but squares
+pub const MAX_GRIP_ENTITIES: usize = 100;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Grip {
+    pub position: [f32; 2],
+    // what typed lengths and ortho are measured from while the grip is dragged
+    pub anchor: [f32; 2],
+}
+
+impl Grip {
+    pub fn new(position: [f32; 2]) -> Self {
+        Self {
+            position,
+            anchor: position,
+        }
+    }
+
+    pub fn anchored(position: [f32; 2], anchor: [f32; 2]) -> Self {
+        Self { position, anchor }
+    }
+}
+
+// The grip being dragged and the entity as it was before. Every step of the drag starts over from
+// the original, so a grip keeps pointing at the same spot even if the shape flips around.
+#[derive(Clone, Debug)]
+pub struct GripDrag {
+    pub id: u64,
+    pub index: usize,
+    pub original: Entity,
+}
+
+pub trait GripOps {
+    // the grips of the selection and whether each is the one being dragged, nothing if there's
+    // too much selected
+    fn shown_grips(&self) -> Vec<([f32; 2], bool)>;
+    // entity and index of the shown grip closest to `point`, if it's within `radius`
+    fn grip_at(&self, point: [f32; 2], radius: f32) -> Option<(u64, usize)>;
+    // returns the grip's anchor
+    fn start_grip_drag(&mut self, id: u64, index: usize) -> Option<[f32; 2]>;
+    fn drag_grip(&mut self, position: [f32; 2]);
+    fn finish_grip_drag(&mut self);
+    fn cancel_grip_drag(&mut self);
+}
+
+impl GripOps for Document {
+    fn shown_grips(&self) -> Vec<([f32; 2], bool)> {
+        let selected: Vec<&Entity> = self
+            .entities
+            .iter()
+            .filter(|entity| entity.selected())
+            .take(MAX_GRIP_ENTITIES + 1)
+            .collect();
+        if selected.len() > MAX_GRIP_ENTITIES {
+            return Vec::new();
+        }
+
+        let hot = self.active_grip.as_ref().map(|drag| (drag.id, drag.index));
+        selected
+            .into_iter()
+            .flat_map(|entity| {
+                entity
+                    .grips()
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(index, grip)| (grip.position, hot == Some((entity.id(), index))))
+            })
+            .collect()
+    }
+
+    fn grip_at(&self, point: [f32; 2], radius: f32) -> Option<(u64, usize)> {
+        if self.shown_grips().is_empty() {
+            return None;
+        }
+
+        self.entities
+            .iter()
+            .filter(|entity| entity.selected())
+            .flat_map(|entity| {
+                entity
+                    .grips()
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(index, grip)| (entity.id(), index, grip.position))
+            })
+            .map(|(id, index, position)| (id, index, distance(point, position)))
+            .filter(|(_, _, off)| *off <= radius)
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+            .map(|(id, index, _)| (id, index))
+    }
+
+    fn start_grip_drag(&mut self, id: u64, index: usize) -> Option<[f32; 2]> {
+        let entity = self.entities.get_mut(id)?;
+        let anchor = entity.grips().get(index)?.anchor;
+        let original = entity.clone();
+
+        // keeps snaps and picking off the entity while it follows the cursor
+        entity.set_drawing(true);
+        self.active_grip = Some(GripDrag {
+            id,
+            index,
+            original,
+        });
+        Some(anchor)
+    }
+
+    fn drag_grip(&mut self, position: [f32; 2]) {
+        let Some(drag) = &self.active_grip else {
+            return;
+        };
+
+        let mut moved = drag.original.clone();
+        moved.move_grip(drag.index, position);
+        moved.set_drawing(true);
+
+        if let Some(entity) = self.entities.get_mut(drag.id) {
+            *entity = moved;
+        }
+    }
+
+    fn finish_grip_drag(&mut self) {
+        let Some(drag) = self.active_grip.take() else {
+            return;
+        };
+
+        if let Some(entity) = self.entities.get_mut(drag.id) {
+            entity.set_drawing(false);
+            let after = entity.clone();
+            if after != drag.original {
+                self.record("Grip", vec![Change::Modify(drag.original, after)]);
+            }
+        }
+    }
+
+    fn cancel_grip_drag(&mut self) {
+        if let Some(drag) = self.active_grip.take() {
+            if let Some(entity) = self.entities.get_mut(drag.id) {
+                *entity = drag.original;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::arc::{Arc, ArcOps};
+    use crate::model::circle::CircleOps;
+    use crate::model::line::LineOps;
+    use crate::model::polyline::{PolylineOps, PolylineVertex};
+
+    fn close(a: [f32; 2], b: [f32; 2]) -> bool {
+        (a[0] - b[0]).abs() < 1e-4 && (a[1] - b[1]).abs() < 1e-4
+    }
+
+    fn select(document: &mut Document, id: u64) {
+        document.entities.get_mut(id).unwrap().set_selected(true);
+    }
+
+    // picks up the grip at `from` and drops it at `to`, by way of somewhere else
+    fn drag(document: &mut Document, from: [f32; 2], to: [f32; 2]) {
+        let (id, index) = document.grip_at(from, 0.1).expect("no grip there");
+        document.start_grip_drag(id, index);
+        document.drag_grip([from[0] + 1.0, from[1] + 1.0]);
+        document.drag_grip(to);
+        document.finish_grip_drag();
+        assert!(!document.entities.get(id).unwrap().is_drawing());
+    }
+
+    #[test]
+    fn only_the_selection_shows_grips() {
+        let mut document = Document::new();
+        let line = document.add_line([0.0, 0.0], [4.0, 0.0], false);
+        document.add_circle([10.0, 0.0], 1.0, false, false, false);
+        assert!(document.shown_grips().is_empty());
+        assert_eq!(document.grip_at([0.0, 0.0], 1.0), None);
+
+        select(&mut document, line);
+        let grips: Vec<[f32; 2]> = document.shown_grips().iter().map(|g| g.0).collect();
+        assert_eq!(grips, vec![[0.0, 0.0], [4.0, 0.0], [2.0, 0.0]]);
+        assert_eq!(document.grip_at([2.1, 0.1], 0.5), Some((line, 2)));
+        assert_eq!(document.grip_at([3.0, 0.0], 0.5), None);
+    }
+
+    #[test]
+    fn too_big_a_selection_shows_none() {
+        let mut document = Document::new();
+        for i in 0..=MAX_GRIP_ENTITIES {
+            let id = document.add_line([i as f32, 0.0], [i as f32, 1.0], false);
+            select(&mut document, id);
+        }
+        assert!(document.shown_grips().is_empty());
+        assert_eq!(document.grip_at([0.0, 0.0], 1.0), None);
+    }
+
+    #[test]
+    fn the_dragged_grip_is_marked() {
+        let mut document = Document::new();
+        let line = document.add_line([0.0, 0.0], [4.0, 0.0], false);
+        select(&mut document, line);
+
+        assert_eq!(document.start_grip_drag(line, 1), Some([0.0, 0.0]));
+        let hot: Vec<bool> = document.shown_grips().iter().map(|g| g.1).collect();
+        assert_eq!(hot, vec![false, true, false]);
+        assert!(document.entities.get(line).unwrap().is_drawing());
+    }
+
+    #[test]
+    fn line_ends_and_middle() {
+        let mut document = Document::new();
+        let id = document.add_line([0.0, 0.0], [4.0, 0.0], false);
+        select(&mut document, id);
+
+        drag(&mut document, [4.0, 0.0], [4.0, 3.0]);
+        let Some(Entity::Line(line)) = document.entities.get(id) else {
+            panic!("not a line");
+        };
+        assert!(close(line.start(), [0.0, 0.0]) && close(line.end(), [4.0, 3.0]));
+
+        drag(&mut document, [2.0, 1.5], [12.0, 1.5]);
+        let Some(Entity::Line(line)) = document.entities.get(id) else {
+            panic!("not a line");
+        };
+        assert!(close(line.start(), [10.0, 0.0]) && close(line.end(), [14.0, 3.0]));
+
+        assert_eq!(document.undo(), Some("Grip"));
+        assert_eq!(document.undo(), Some("Grip"));
+        let Some(Entity::Line(line)) = document.entities.get(id) else {
+            panic!("not a line");
+        };
+        assert!(close(line.start(), [0.0, 0.0]) && close(line.end(), [4.0, 0.0]));
+    }
+
+    #[test]
+    fn circle_center_and_quadrants() {
+        let mut document = Document::new();
+        let id = document.add_circle([0.0, 0.0], 2.0, true, false, false);
+
+        drag(&mut document, [0.0, 2.0], [0.0, 5.0]);
+        let Some(Entity::Circle(circle)) = document.entities.get(id) else {
+            panic!("not a circle");
+        };
+        assert!((circle.radius - 5.0).abs() < 1e-4);
+
+        drag(&mut document, [0.0, 0.0], [3.0, 1.0]);
+        let Some(Entity::Circle(circle)) = document.entities.get(id) else {
+            panic!("not a circle");
+        };
+        assert!(close(circle.center(), [3.0, 1.0]));
+        assert!((circle.radius - 5.0).abs() < 1e-4);
+
+        document.undo();
+        document.undo();
+        let Some(Entity::Circle(circle)) = document.entities.get(id) else {
+            panic!("not a circle");
+        };
+        assert!(close(circle.center(), [0.0, 0.0]));
+        assert!((circle.radius - 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn arc_through_the_moved_point() {
+        let mut document = Document::new();
+        // the top half of a circle of radius 2
+        let id = document.add_arc(Arc::new([0.0, 0.0], 2.0, 0.0, std::f32::consts::PI), false);
+        select(&mut document, id);
+        let original = document.entities.get(id).unwrap().clone();
+
+        // the arc goes through both ends and wherever its middle is dropped
+        drag(&mut document, [0.0, 2.0], [0.0, 1.0]);
+        let Some(Entity::Arc(arc)) = document.entities.get(id) else {
+            panic!("not an arc");
+        };
+        assert!(close(arc.start_point(), [2.0, 0.0]));
+        assert!(close(arc.end_point(), [-2.0, 0.0]));
+        assert!(close(arc.mid_point(), [0.0, 1.0]));
+        assert!((arc.radius - 2.5).abs() < 1e-3, "{}", arc.radius);
+
+        // the center grip moves it whole
+        let center = arc.center;
+        drag(&mut document, center, [center[0] + 1.0, center[1]]);
+        let Some(Entity::Arc(arc)) = document.entities.get(id) else {
+            panic!("not an arc");
+        };
+        assert!(close(arc.start_point(), [3.0, 0.0]));
+
+        document.undo();
+        document.undo();
+        assert_eq!(document.entities.get(id), Some(&original));
+    }
+
+    #[test]
+    fn polyline_vertex() {
+        let mut document = Document::new();
+        let vertex = |x: f32, y: f32, bulge: f32| PolylineVertex {
+            position: [x, y],
+            bulge,
+        };
+        let id = document.add_polyline(
+            vec![
+                vertex(0.0, 0.0, 0.0),
+                vertex(4.0, 0.0, 1.0),
+                vertex(4.0, 4.0, 0.0),
+            ],
+            false,
+            false,
+        );
+        select(&mut document, id);
+        assert_eq!(document.shown_grips().len(), 3);
+
+        drag(&mut document, [4.0, 0.0], [5.0, -1.0]);
+        let Some(Entity::Polyline(polyline)) = document.entities.get(id) else {
+            panic!("not a polyline");
+        };
+        assert_eq!(
+            polyline.vertices,
+            vec![
+                vertex(0.0, 0.0, 0.0),
+                vertex(5.0, -1.0, 1.0),
+                vertex(4.0, 4.0, 0.0)
+            ]
+        );
+
+        assert_eq!(document.undo(), Some("Grip"));
+        let Some(Entity::Polyline(polyline)) = document.entities.get(id) else {
+            panic!("not a polyline");
+        };
+        assert_eq!(polyline.vertices[1], vertex(4.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn cancelling_or_not_moving_leaves_no_history() {
+        let mut document = Document::new();
+        let id = document.add_line([0.0, 0.0], [4.0, 0.0], false);
+        select(&mut document, id);
+        let original = document.entities.get(id).unwrap().clone();
+
+        document.start_grip_drag(id, 0);
+        document.drag_grip([9.0, 9.0]);
+        document.cancel_grip_drag();
+        assert_eq!(document.entities.get(id), Some(&original));
+
+        document.start_grip_drag(id, 0);
+        document.drag_grip([0.0, 0.0]);
+        document.finish_grip_drag();
+        assert_eq!(document.undo(), None);
+    }
+}
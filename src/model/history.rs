@@ -0,0 +1,361 @@
+use crate::model::document::Document;
+use crate::model::entity::Entity;
+use crate::model::text::Text;
+use std::time::{Duration, Instant};
+
+// how many steps we keep around before dropping the oldest ones
+const HISTORY_LIMIT: usize = 200;
+// property edits (dragging a value in the side panel) closer together than this become one step
+const MERGE_WINDOW: Duration = Duration::from_millis(1000);
+
+// A single reversible edit. Entities carry their own id, so a change always finds its target
+// no matter what got added or deleted around it.
+#[derive(Clone, Debug)]
+pub enum Change {
+    Add(Entity),
+    Remove(Entity),
+    // before, after
+    Modify(Entity, Entity),
+    AddText(Text),
+    RemoveText(Text),
+    ModifyText(Text, Text),
+}
+
+// a group of changes that gets undone/redone in one step (eg. moving 20 lines)
+#[derive(Clone, Debug)]
+pub struct Command {
+    pub name: &'static str,
+    pub changes: Vec<Change>,
+    pub created_at: Instant,
+}
+
+#[derive(Debug, Default)]
+pub struct History {
+    undo_stack: Vec<Command>,
+    redo_stack: Vec<Command>,
+}
+
+impl Change {
+    pub fn inverse(&self) -> Change {
+        match self {
+            Change::Add(entity) => Change::Remove(entity.clone()),
+            Change::Remove(entity) => Change::Add(entity.clone()),
+            Change::Modify(before, after) => Change::Modify(after.clone(), before.clone()),
+            Change::AddText(text) => Change::RemoveText(text.clone()),
+            Change::RemoveText(text) => Change::AddText(text.clone()),
+            Change::ModifyText(before, after) => Change::ModifyText(after.clone(), before.clone()),
+        }
+    }
+
+    // strip the transient flags so restoring an entity doesn't bring back a half finished state
+    fn settle(&mut self) {
+        match self {
+            Change::Add(entity) | Change::Remove(entity) => settle_entity(entity),
+            Change::Modify(before, after) => {
+                settle_entity(before);
+                settle_entity(after);
+            }
+            Change::AddText(text) | Change::RemoveText(text) => settle_text(text),
+            Change::ModifyText(before, after) => {
+                settle_text(before);
+                settle_text(after);
+            }
+        }
+    }
+
+    fn target(&self) -> u64 {
+        match self {
+            Change::Add(entity) | Change::Remove(entity) | Change::Modify(entity, _) => entity.id(),
+            Change::AddText(text) | Change::RemoveText(text) | Change::ModifyText(text, _) => {
+                text.id
+            }
+        }
+    }
+}
+
+fn settle_entity(entity: &mut Entity) {
+    entity.set_selected(false);
+    entity.set_del(false);
+    entity.set_drawing(false);
+}
+
+fn settle_text(text: &mut Text) {
+    text.editing = false;
+    text.selected = false;
+    text.rect = None;
+}
+
+impl Command {
+    pub fn new(name: &'static str, changes: Vec<Change>) -> Self {
+        Self {
+            name,
+            changes,
+            created_at: Instant::now(),
+        }
+    }
+
+    fn only_modifies(&self) -> bool {
+        self.changes
+            .iter()
+            .all(|change| matches!(change, Change::Modify(..) | Change::ModifyText(..)))
+    }
+
+    // true if both commands modify exactly the same entities in the same order
+    fn same_targets(&self, other: &Command) -> bool {
+        self.changes.len() == other.changes.len()
+            && self
+                .changes
+                .iter()
+                .zip(other.changes.iter())
+                .all(|(a, b)| a.target() == b.target())
+    }
+}
+
+impl History {
+    pub fn push(&mut self, mut command: Command) {
+        if command.changes.is_empty() {
+            return;
+        }
+
+        for change in &mut command.changes {
+            change.settle();
+        }
+
+        self.redo_stack.clear();
+        self.undo_stack.push(command);
+
+        if self.undo_stack.len() > HISTORY_LIMIT {
+            self.undo_stack.remove(0);
+        }
+    }
+
+    // like push, but folds the command into the previous one if it's a continuation of the same edit
+    pub fn push_or_merge(&mut self, command: Command) {
+        if let Some(last) = self.undo_stack.last_mut() {
+            if self.redo_stack.is_empty()
+                && last.name == command.name
+                && last.only_modifies()
+                && command.only_modifies()
+                && last.same_targets(&command)
+                && command.created_at.duration_since(last.created_at) < MERGE_WINDOW
+            {
+                for (old, mut new) in last.changes.iter_mut().zip(command.changes) {
+                    new.settle();
+                    match (old, new) {
+                        (Change::Modify(_, after), Change::Modify(_, new_after)) => {
+                            *after = new_after
+                        }
+                        (Change::ModifyText(_, after), Change::ModifyText(_, new_after)) => {
+                            *after = new_after
+                        }
+                        _ => {}
+                    }
+                }
+                last.created_at = command.created_at;
+                return;
+            }
+        }
+
+        self.push(command);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+}
+
+pub trait HistoryOps {
+    fn record(&mut self, name: &'static str, changes: Vec<Change>);
+    fn apply_change(&mut self, change: &Change);
+    // both return the name of the step that was undone/redone
+    fn undo(&mut self) -> Option<&'static str>;
+    fn redo(&mut self) -> Option<&'static str>;
+}
+
+impl HistoryOps for Document {
+    fn record(&mut self, name: &'static str, changes: Vec<Change>) {
+        self.history.push(Command::new(name, changes));
+    }
+
+    fn apply_change(&mut self, change: &Change) {
+        match change {
+            Change::Add(entity) => {
+                self.entities.insert(entity.id(), entity.clone());
+            }
+            Change::Remove(entity) => {
+                self.entities.remove(entity.id());
+            }
+            Change::Modify(_, after) => {
+                if let Some(entity) = self.entities.get_mut(after.id()) {
+                    // keep whatever the user has selected right now
+                    let selected = entity.selected();
+                    *entity = after.clone();
+                    entity.set_selected(selected);
+                }
+            }
+            Change::AddText(text) => {
+                // texts are kept sorted by id, same as the entity store
+                let i = self.texts.partition_point(|t| t.id < text.id);
+                self.texts.insert(i, text.clone());
+            }
+            Change::RemoveText(text) => {
+                self.texts.retain(|t| t.id != text.id);
+            }
+            Change::ModifyText(_, after) => {
+                if let Some(text) = self.texts.iter_mut().find(|t| t.id == after.id) {
+                    let selected = text.selected;
+                    *text = after.clone();
+                    text.selected = selected;
+                }
+            }
+        }
+    }
+
+    fn undo(&mut self) -> Option<&'static str> {
+        let command = self.history.undo_stack.pop()?;
+        for change in command.changes.iter().rev() {
+            self.apply_change(&change.inverse());
+        }
+
+        let name = command.name;
+        self.history.redo_stack.push(command);
+        Some(name)
+    }
+
+    fn redo(&mut self) -> Option<&'static str> {
+        let command = self.history.redo_stack.pop()?;
+        for change in command.changes.iter() {
+            self.apply_change(change);
+        }
+
+        let name = command.name;
+        self.history.undo_stack.push(command);
+        Some(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::line::LineOps;
+
+    fn modify_thickness(document: &Document, id: u64, thickness: f32) -> Command {
+        let before = document.entities.get(id).unwrap().clone();
+        let mut after = before.clone();
+        after.set_thickness(thickness);
+        Command::new("Properties", vec![Change::Modify(before, after)])
+    }
+
+    #[test]
+    fn drawn_line_is_undone_and_redone() {
+        let mut document = Document::new();
+        let id = document.add_line([0.0, 0.0], [0.0, 0.0], true);
+        document.update_line([10.0, 0.0], false, false);
+
+        assert_eq!(document.undo(), Some("Line"));
+        assert!(document.entities.get(id).is_none());
+        assert!(document.history.can_redo());
+
+        assert_eq!(document.redo(), Some("Line"));
+        let line = document.entities.get(id).unwrap();
+        assert!(!line.is_drawing());
+        assert!(!document.history.can_redo());
+    }
+
+    #[test]
+    fn undoing_a_delete_brings_the_entity_back_unselected() {
+        let mut document = Document::new();
+        let id = document.add_line([0.0, 0.0], [10.0, 0.0], false);
+        document.entities.get_mut(id).unwrap().set_selected(true);
+        document.delete_selected();
+        assert!(document.entities.is_empty());
+
+        assert_eq!(document.undo(), Some("Delete"));
+        assert!(!document.entities.get(id).unwrap().selected());
+        assert_eq!(document.undo(), None);
+    }
+
+    #[test]
+    fn a_new_step_clears_redo() {
+        let mut document = Document::new();
+        let id = document.add_line([0.0, 0.0], [10.0, 0.0], false);
+        let command = modify_thickness(&document, id, 3.0);
+        document.history.push(command);
+        document.undo();
+        assert!(document.history.can_redo());
+
+        let command = modify_thickness(&document, id, 4.0);
+        document.history.push(command);
+        assert!(!document.history.can_redo());
+    }
+
+    #[test]
+    fn edits_to_the_same_entities_merge_into_one_step() {
+        let mut document = Document::new();
+        let id = document.add_line([0.0, 0.0], [10.0, 0.0], false);
+        let original = document.entities.get(id).unwrap().thickness();
+
+        for thickness in [2.0, 3.0, 4.0] {
+            let command = modify_thickness(&document, id, thickness);
+            document.apply_change(&command.changes[0]);
+            document.history.push_or_merge(command);
+        }
+        assert_eq!(document.history.undo_stack.len(), 1);
+
+        document.undo();
+        assert_eq!(document.entities.get(id).unwrap().thickness(), original);
+        assert!(!document.history.can_undo());
+
+        document.redo();
+        assert_eq!(document.entities.get(id).unwrap().thickness(), 4.0);
+    }
+
+    #[test]
+    fn edits_to_other_entities_or_steps_stay_apart() {
+        let mut document = Document::new();
+        let first = document.add_line([0.0, 0.0], [10.0, 0.0], false);
+        let second = document.add_line([0.0, 5.0], [10.0, 5.0], false);
+
+        let command = modify_thickness(&document, first, 2.0);
+        document.history.push_or_merge(command);
+        let command = modify_thickness(&document, second, 2.0);
+        document.history.push_or_merge(command);
+
+        let mut command = modify_thickness(&document, second, 3.0);
+        command.name = "Move";
+        document.history.push_or_merge(command);
+
+        assert_eq!(document.history.undo_stack.len(), 3);
+    }
+
+    #[test]
+    fn edits_far_apart_in_time_stay_apart() {
+        let mut document = Document::new();
+        let id = document.add_line([0.0, 0.0], [10.0, 0.0], false);
+
+        let mut command = modify_thickness(&document, id, 2.0);
+        command.created_at -= MERGE_WINDOW * 2;
+        document.history.push_or_merge(command);
+        let command = modify_thickness(&document, id, 3.0);
+        document.history.push_or_merge(command);
+
+        assert_eq!(document.history.undo_stack.len(), 2);
+    }
+
+    #[test]
+    fn only_the_newest_steps_are_kept() {
+        let mut document = Document::new();
+        let id = document.add_line([0.0, 0.0], [10.0, 0.0], false);
+
+        for i in 0..HISTORY_LIMIT + 10 {
+            let command = modify_thickness(&document, id, i as f32);
+            document.history.push(command);
+        }
+
+        assert_eq!(document.history.undo_stack.len(), HISTORY_LIMIT);
+    }
+}
@@ -0,0 +1,1582 @@
+use crate::model::arc::{Arc, ArcOps};
+use crate::model::circle::CircleOps;
+use crate::model::color::{Color, EntityColor, FOREGROUND};
+use crate::model::document::{
+    lineweight_to_thickness, Document, LINEWEIGHT_BY_BLOCK, LINEWEIGHT_BY_LAYER, LINEWEIGHT_DEFAULT,
+};
+use crate::model::ellipse::{Ellipse, EllipseOps};
+use crate::model::entity::Entity;
+use crate::model::layer::{LayerOps, DEFAULT_LAYER, FLAG_FROZEN, FLAG_LOCKED};
+use crate::model::line::LineOps;
+use crate::model::linetype::{EntityLinetype, LinetypeOps, CONTINUOUS};
+use crate::model::point::Point;
+use crate::model::polyline::{arc_from_bulge, PolylineOps, PolylineVertex};
+use crate::model::style::Style;
+use crate::model::text::Text;
+use dxf::entities::{EntityCommon, EntityType, Insert};
+use dxf::enums::{AcadVersion, Units};
+use dxf::{Drawing, Vector};
+use std::collections::{BTreeMap, HashMap};
+use std::f64::consts::TAU;
+use std::fs;
+
+// Turns the contents of a DXF file into entities and texts of a document.
+// The dxf crate leaves out entity types it doesn't know (HATCH, DIMENSION, ...), so the file is
+// also read as plain group codes: that's where hatch boundaries come from and how we can tell the
+// user about everything that didn't make it in. Binary files can't be read that way, the summary
+// says what they lose.
+
+// nested blocks deeper than this are most likely a block inserting itself
+const MAX_BLOCK_DEPTH: usize = 16;
+
+// segments a full turn of a curve gets cut into when it has to become a polyline
+const SEGMENTS_PER_TURN: f64 = 64.0;
+
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    // counts per DXF entity type
+    pub imported: BTreeMap<String, usize>,
+    pub skipped: BTreeMap<String, usize>,
+    // the file's units when the drawing had to be scaled to the document's
+    pub scaled_from: Option<Units>,
+    // there are no group codes to read hatches, layer flags and true colors from in a binary file
+    pub binary: bool,
+    // the file wasn't taken as the one to save to, saving over it would lose what was skipped
+    pub kept_original: bool,
+}
+
+impl ImportSummary {
+    fn count_imported(&mut self, kind: &str) {
+        *self.imported.entry(kind.to_string()).or_default() += 1;
+    }
+
+    // whether the document is missing something the file has
+    pub fn incomplete(&self) -> bool {
+        !self.skipped.is_empty() || self.binary
+    }
+
+    // e.g. "Imported 12: LINE 10, ARC 2. Skipped 3: DIMENSION 3"
+    pub fn message(&self) -> String {
+        let list = |counts: &BTreeMap<String, usize>| {
+            counts
+                .iter()
+                .map(|(kind, count)| format!("{} {}", kind, count))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        let imported: usize = self.imported.values().sum();
+        let mut message = format!("Imported {}: {}", imported, list(&self.imported));
+
+        if !self.skipped.is_empty() {
+            let skipped: usize = self.skipped.values().sum();
+            message += &format!(". Skipped {}: {}", skipped, list(&self.skipped));
+        }
+
+        if let Some(units) = self.scaled_from {
+            message += &format!(". Scaled from {:?}", units);
+        }
+
+        if self.binary {
+            message += ". Binary DXF, hatches, layer flags and true colors were not imported";
+        }
+
+        if self.kept_original {
+            message += ". Save As to keep the original file intact";
+        }
+
+        message
+    }
+}
+
+// 2D affine transform: x' = a*x + b*y + c, y' = d*x + e*y + f
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Transform([f64; 6]);
+
+impl Transform {
+    fn identity() -> Self {
+        Transform([1.0, 0.0, 0.0, 0.0, 1.0, 0.0])
+    }
+
+    fn scale(factor: f64) -> Self {
+        Transform([factor, 0.0, 0.0, 0.0, factor, 0.0])
+    }
+
+    // entities with an extrusion pointing down are stored in a coordinate system with x flipped
+    fn ocs(extrusion: &Vector) -> Self {
+        if extrusion.z < 0.0 {
+            Transform([-1.0, 0.0, 0.0, 0.0, 1.0, 0.0])
+        } else {
+            Self::identity()
+        }
+    }
+
+    // puts the block's base point at the insert location, scaled and rotated
+    fn insert(insert: &Insert, base: &dxf::Point, offset: [f64; 2]) -> Self {
+        let (sin, cos) = insert.rotation.to_radians().sin_cos();
+        let (sx, sy) = (insert.x_scale_factor, insert.y_scale_factor);
+
+        // array inserts repeat the block along the rotated rows and columns
+        let x = insert.location.x + offset[0] * cos - offset[1] * sin;
+        let y = insert.location.y + offset[0] * sin + offset[1] * cos;
+
+        let (a, b, d, e) = (cos * sx, -sin * sy, sin * sx, cos * sy);
+        Transform([
+            a,
+            b,
+            x - a * base.x - b * base.y,
+            d,
+            e,
+            y - d * base.x - e * base.y,
+        ])
+    }
+
+    // this transform followed by `outer`
+    fn then(&self, outer: &Transform) -> Transform {
+        let [a, b, c, d, e, f] = self.0;
+        let [oa, ob, oc, od, oe, of] = outer.0;
+
+        Transform([
+            oa * a + ob * d,
+            oa * b + ob * e,
+            oa * c + ob * f + oc,
+            od * a + oe * d,
+            od * b + oe * e,
+            od * c + oe * f + of,
+        ])
+    }
+
+    fn apply(&self, point: [f64; 2]) -> [f32; 2] {
+        let [a, b, c, d, e, f] = self.0;
+        [
+            (a * point[0] + b * point[1] + c) as f32,
+            (d * point[0] + e * point[1] + f) as f32,
+        ]
+    }
+
+    // where a direction and length end up, the translation left out
+    fn apply_vector(&self, vector: [f64; 2]) -> [f32; 2] {
+        let [a, b, _, d, e, _] = self.0;
+        [
+            (a * vector[0] + b * vector[1]) as f32,
+            (d * vector[0] + e * vector[1]) as f32,
+        ]
+    }
+
+    fn determinant(&self) -> f64 {
+        self.0[0] * self.0[4] - self.0[1] * self.0[3]
+    }
+
+    fn mirrors(&self) -> bool {
+        self.determinant() < 0.0
+    }
+
+    // how much lengths grow, None if it depends on the direction (circles would turn into ellipses)
+    fn uniform_scale(&self) -> Option<f64> {
+        let [a, b, _, d, e, _] = self.0;
+        let x_len = (a * a + d * d).sqrt();
+        let y_len = (b * b + e * e).sqrt();
+        let orthogonal = (a * b + d * e).abs() <= 1e-9 * x_len * y_len;
+
+        if orthogonal && (x_len - y_len).abs() <= 1e-9 * x_len {
+            Some(x_len)
+        } else {
+            None
+        }
+    }
+
+    // how much lengths grow on average, what dash patterns get scaled by
+    fn average_scale(&self) -> f64 {
+        self.determinant().abs().sqrt()
+    }
+
+    // direction the x axis ends up pointing at
+    fn rotation(&self) -> f64 {
+        self.0[3].atan2(self.0[0])
+    }
+}
+
+// what an entity inherits from the INSERTs it sits in
+#[derive(Copy, Clone, Debug)]
+struct Context {
+    transform: Transform,
+    // lineweight ByBlock entities take
+    block_lineweight: i16,
+    // layer the entities on layer "0" of a block end up on
+    block_layer: u64,
+    block_color: EntityColor,
+    block_linetype: EntityLinetype,
+    depth: usize,
+}
+
+// what an imported entity gets besides its geometry
+struct ImportedStyle {
+    style: Style,
+    // None leaves the default for its type
+    thickness: Option<f32>,
+}
+
+struct Importer<'a> {
+    document: &'a mut Document,
+    drawing: &'a Drawing,
+    layer_lineweights: HashMap<String, i16>,
+    // document layer of every layer name seen so far
+    layer_ids: HashMap<String, u64>,
+    summary: ImportSummary,
+}
+
+pub fn import_drawing(document: &mut Document, drawing: &Drawing, path: &str) -> ImportSummary {
+    let text = read_ascii(path);
+    let raw_entities = text
+        .as_deref()
+        .and_then(|text| read_raw_section(text, "ENTITIES"));
+    let raw_tables = text
+        .as_deref()
+        .and_then(|text| read_raw_section(text, "TABLES"));
+
+    let mut importer = Importer {
+        layer_lineweights: drawing
+            .layers()
+            .map(|layer| {
+                // same as for entities, a missing lineweight reads as 0
+                let lineweight = match layer.line_weight.get_raw_value() {
+                    0 => LINEWEIGHT_DEFAULT,
+                    lineweight => lineweight,
+                };
+                (layer.name.clone(), lineweight)
+            })
+            .collect(),
+        layer_ids: HashMap::new(),
+        summary: ImportSummary::default(),
+        document,
+        drawing,
+    };
+
+    importer.import_linetypes();
+    importer.import_layers(raw_tables.as_deref().unwrap_or_default());
+
+    let scale = importer.units_scale();
+    let context = Context {
+        transform: Transform::scale(scale),
+        block_lineweight: LINEWEIGHT_DEFAULT,
+        block_layer: DEFAULT_LAYER,
+        block_color: EntityColor::ByBlock,
+        block_linetype: EntityLinetype::ByBlock,
+        depth: 0,
+    };
+
+    for entity in drawing.entities() {
+        if entity.common.is_in_paper_space {
+            continue;
+        }
+
+        if let Some(kind) = importer.import_entity(entity, &context) {
+            importer.summary.count_imported(kind);
+        }
+    }
+
+    match raw_entities {
+        Some(raw_entities) => {
+            for raw in raw_entities.iter().filter(|raw| raw.kind == "HATCH") {
+                if raw.value(67) != Some("1") && importer.import_hatch(raw, &context) {
+                    importer.summary.count_imported("HATCH");
+                }
+            }
+
+            // whatever is in the file but didn't get imported was skipped
+            let mut totals: BTreeMap<String, usize> = BTreeMap::new();
+            for raw in &raw_entities {
+                *totals.entry(raw.kind.clone()).or_default() += 1;
+            }
+
+            for (kind, total) in totals {
+                let imported = importer.summary.imported.get(&kind).copied().unwrap_or(0);
+                if total > imported {
+                    importer.summary.skipped.insert(kind, total - imported);
+                }
+            }
+        }
+        // binary DXF, all we can report is what the dxf crate read and we didn't use
+        None => {
+            importer.summary.binary = true;
+            let imported: usize = importer.summary.imported.values().sum();
+            let skipped = drawing.entities().count().saturating_sub(imported);
+            if skipped > 0 {
+                importer
+                    .summary
+                    .skipped
+                    .insert("other".to_string(), skipped);
+            }
+        }
+    }
+
+    importer.summary
+}
+
+impl Importer<'_> {
+    // Same as layers: an empty document takes over the file's patterns and linetype scale,
+    // otherwise only linetypes it doesn't have yet are added.
+    fn import_linetypes(&mut self) {
+        let adopt = self.document.entities.is_empty() && self.document.texts.is_empty();
+        if adopt {
+            self.document.linetype_scale = self.drawing.header.line_type_scale as f32;
+        }
+
+        for linetype in self.drawing.line_types() {
+            let name = linetype.name.as_str();
+            if ["BYLAYER", "BYBLOCK", "CONTINUOUS"]
+                .iter()
+                .any(|special| special.eq_ignore_ascii_case(name))
+            {
+                continue;
+            }
+
+            let pattern: Vec<f32> = linetype
+                .dash_dot_space_lengths
+                .iter()
+                .map(|length| *length as f32)
+                .collect();
+
+            match self.document.linetypes.find(name).map(|lt| lt.id) {
+                Some(id) if adopt => {
+                    if let Some(existing) = self.document.linetypes.get_mut(id) {
+                        existing.description = linetype.description.clone();
+                        existing.pattern = pattern;
+                    }
+                }
+                Some(_) => {}
+                None => {
+                    self.document
+                        .add_linetype(name, &linetype.description, pattern);
+                }
+            }
+        }
+    }
+
+    // linetype of a name in the file, names that aren't in its LTYPE table get a solid one
+    fn linetype_id(&mut self, name: &str) -> u64 {
+        if name.is_empty() || name.eq_ignore_ascii_case("CONTINUOUS") {
+            return CONTINUOUS;
+        }
+
+        self.document.add_linetype(name, "", Vec::new())
+    }
+
+    // Layers are taken over as they are by an empty document. Otherwise only the ones it doesn't
+    // have yet are added, an existing layer keeps what the user set up.
+    fn import_layers(&mut self, raw_tables: &[RawEntity]) {
+        let adopt = self.document.entities.is_empty() && self.document.texts.is_empty();
+
+        for layer in self.drawing.layers() {
+            let existing = self.document.layers.find(&layer.name).map(|l| l.id);
+            if existing.is_some() && !adopt {
+                continue;
+            }
+
+            // flags and true colors are only in the group codes
+            let raw = raw_tables
+                .iter()
+                .find(|raw| raw.kind == "LAYER" && raw.value(2) == Some(layer.name.as_str()));
+            let flags: i32 = raw
+                .and_then(|raw| raw.value(70))
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(0);
+            let color = match raw.and_then(|raw| raw.value(420)) {
+                Some(value) => Color::from_true_color(value.parse().unwrap_or(0)),
+                None => Color::Index(layer.color.index().unwrap_or(FOREGROUND)),
+            };
+
+            let id = existing.unwrap_or_else(|| self.document.add_layer(&layer.name));
+            let linetype = self.linetype_id(&layer.line_type_name);
+            if let Some(imported) = self.document.layers.get_mut(id) {
+                imported.color = color;
+                imported.linetype = linetype;
+                // a missing lineweight reads as 0, same as for the entities
+                imported.lineweight = Some(layer.line_weight.get_raw_value()).filter(|w| *w > 0);
+                imported.visible = layer.is_layer_on;
+                imported.frozen = flags & FLAG_FROZEN != 0 && id != self.document.current_layer;
+                imported.locked = flags & FLAG_LOCKED != 0;
+            }
+        }
+
+        if adopt {
+            if let Some(layer) = self
+                .document
+                .layers
+                .find(&self.drawing.header.current_layer)
+            {
+                let id = layer.id;
+                self.document.set_current_layer(id);
+            }
+        }
+    }
+
+    // the document layer an entity goes on, blocks put what's on "0" on the insert's layer
+    fn layer(&mut self, common: &EntityCommon, context: &Context) -> u64 {
+        if context.depth > 0 && common.layer == "0" {
+            return context.block_layer;
+        }
+
+        if let Some(id) = self.layer_ids.get(&common.layer) {
+            return *id;
+        }
+
+        // entities can be on layers the table doesn't list
+        let id = self.document.add_layer(&common.layer);
+        self.layer_ids.insert(common.layer.clone(), id);
+        id
+    }
+
+    // the file's units are taken over by an empty document, otherwise the drawing gets scaled
+    fn units_scale(&mut self) -> f64 {
+        let file_units = self.drawing.header.default_drawing_units;
+
+        if self.document.entities.is_empty() && self.document.texts.is_empty() {
+            self.document.units = file_units;
+            return 1.0;
+        }
+
+        match (
+            millimeters_per_unit(file_units),
+            millimeters_per_unit(self.document.units),
+        ) {
+            (Some(from), Some(to)) if from != to => {
+                self.summary.scaled_from = Some(file_units);
+                from / to
+            }
+            _ => 1.0,
+        }
+    }
+
+    // the lineweight that actually applies to an entity, following ByLayer and ByBlock
+    fn lineweight(&self, common: &EntityCommon, context: &Context) -> i16 {
+        match common.lineweight_enum_value {
+            // files leave the code out for ByLayer, which the dxf crate reads as 0
+            LINEWEIGHT_BY_LAYER | 0 => self
+                .layer_lineweights
+                .get(&common.layer)
+                .copied()
+                .unwrap_or(LINEWEIGHT_DEFAULT),
+            LINEWEIGHT_BY_BLOCK => context.block_lineweight,
+            lineweight => lineweight,
+        }
+    }
+
+    fn color(&self, common: &EntityCommon, context: &Context) -> EntityColor {
+        if common.color_24_bit != 0 {
+            return EntityColor::Explicit(Color::from_true_color(common.color_24_bit));
+        }
+
+        match common.color.index() {
+            Some(index) => EntityColor::Explicit(Color::Index(index)),
+            None if common.color.is_by_block() => context.block_color,
+            None => EntityColor::ByLayer,
+        }
+    }
+
+    fn linetype(&mut self, common: &EntityCommon, context: &Context) -> EntityLinetype {
+        let name = common.line_type_name.as_str();
+
+        if name.is_empty() || name.eq_ignore_ascii_case("BYLAYER") {
+            EntityLinetype::ByLayer
+        } else if name.eq_ignore_ascii_case("BYBLOCK") {
+            context.block_linetype
+        } else {
+            EntityLinetype::Explicit(self.linetype_id(name))
+        }
+    }
+
+    fn style(&mut self, common: &EntityCommon, context: &Context) -> ImportedStyle {
+        let layer = self.layer(common, context);
+        ImportedStyle {
+            style: Style {
+                color: self.color(common, context),
+                thickness_by_layer: matches!(common.lineweight_enum_value, LINEWEIGHT_BY_LAYER | 0),
+                linetype: self.linetype(common, context),
+                // unlike lineweights, dashes grow with the drawing
+                linetype_scale: (common.line_type_scale * context.transform.average_scale()) as f32,
+                ..Style::new(layer, 0.0)
+            },
+            // widths are in paper units, they don't grow with the drawing
+            thickness: lineweight_to_thickness(self.lineweight(common, context)),
+        }
+    }
+
+    fn set_style(&mut self, id: u64, imported: &ImportedStyle) {
+        if let Some(entity) = self.document.entities.get_mut(id) {
+            let thickness = imported.thickness.unwrap_or(entity.thickness());
+            entity.set_style(Style {
+                thickness,
+                ..imported.style
+            });
+        }
+    }
+
+    // adds whatever `entity` turns into, returns the DXF type name if anything was added
+    fn import_entity(
+        &mut self,
+        entity: &dxf::entities::Entity,
+        context: &Context,
+    ) -> Option<&'static str> {
+        let imported = self.style(&entity.common, context);
+        let transform = context.transform;
+
+        let (kind, ids) = match entity.specific {
+            EntityType::Line(ref line) => {
+                let start = transform.apply([line.p1.x, line.p1.y]);
+                let end = transform.apply([line.p2.x, line.p2.y]);
+                ("LINE", vec![self.document.add_line(start, end, false)])
+            }
+            EntityType::Circle(ref circle) => {
+                let transform = Transform::ocs(&circle.normal).then(&transform);
+                let center = [circle.center.x, circle.center.y];
+                (
+                    "CIRCLE",
+                    self.add_arc_like(center, circle.radius, 0.0, TAU, true, &transform),
+                )
+            }
+            EntityType::Arc(ref arc) => {
+                let transform = Transform::ocs(&arc.normal).then(&transform);
+                let start = arc.start_angle.to_radians();
+                let sweep = (arc.end_angle.to_radians() - start).rem_euclid(TAU);
+                let center = [arc.center.x, arc.center.y];
+                (
+                    "ARC",
+                    self.add_arc_like(center, arc.radius, start, sweep, false, &transform),
+                )
+            }
+            EntityType::LwPolyline(ref polyline) => {
+                let transform = Transform::ocs(&polyline.extrusion_direction).then(&transform);
+                let vertices = polyline
+                    .vertices
+                    .iter()
+                    .map(|v| ([v.x, v.y], v.bulge))
+                    .collect();
+                (
+                    "LWPOLYLINE",
+                    self.add_polyline(vertices, polyline.get_is_closed(), &transform),
+                )
+            }
+            // meshes are surfaces, not outlines, so there's nothing sensible to draw for them
+            EntityType::Polyline(ref polyline)
+                if !polyline.get_is_polyface_mesh() && !polyline.get_is_3d_polygon_mesh() =>
+            {
+                let transform = if polyline.get_is_3d_polyline() {
+                    transform
+                } else {
+                    Transform::ocs(&polyline.normal).then(&transform)
+                };
+                let vertices = polyline
+                    .vertices()
+                    // the frame of a splined polyline isn't part of the curve itself
+                    .filter(|v| !v.get_is_spline_frame_control_point())
+                    .map(|v| ([v.location.x, v.location.y], v.bulge))
+                    .collect();
+                (
+                    "POLYLINE",
+                    self.add_polyline(vertices, polyline.get_is_closed(), &transform),
+                )
+            }
+            EntityType::Ellipse(ref ellipse) => {
+                let major = [ellipse.major_axis.x, ellipse.major_axis.y];
+                // the minor axis is the major one turned a quarter around the normal
+                let side = ellipse.normal.z.signum();
+                let ratio = ellipse.minor_axis_ratio;
+                let minor = [-major[1] * ratio * side, major[0] * ratio * side];
+                let center = [ellipse.center.x, ellipse.center.y];
+                (
+                    "ELLIPSE",
+                    self.add_ellipse(
+                        center,
+                        major,
+                        minor,
+                        ellipse.start_parameter,
+                        ellipse.end_parameter,
+                        &transform,
+                    ),
+                )
+            }
+            EntityType::Spline(ref spline) => {
+                let points = spline_points(spline);
+                let closed = spline.flags & 1 != 0;
+                ("SPLINE", self.add_points(points, closed, &transform))
+            }
+            EntityType::Solid(ref solid) => {
+                let transform = Transform::ocs(&solid.extrusion_direction).then(&transform);
+                // the corners zigzag: 1, 2, 4, 3 goes around, a triangle repeats the third
+                let mut corners = vec![
+                    [solid.first_corner.x, solid.first_corner.y],
+                    [solid.second_corner.x, solid.second_corner.y],
+                    [solid.fourth_corner.x, solid.fourth_corner.y],
+                    [solid.third_corner.x, solid.third_corner.y],
+                ];
+                corners.dedup();
+                ("SOLID", self.add_points(corners, true, &transform))
+            }
+            EntityType::ModelPoint(ref point) => {
+                let position = transform.apply([point.location.x, point.location.y]);
+                // dots keep their default size, a lineweight would make most of them invisible
+                let mut point = Point::new(position, self.document.settings.circle_thickness);
+                point.style.layer = imported.style.layer;
+                point.style.color = imported.style.color;
+                point.style.thickness_by_layer = false;
+                self.document.add_entity(Entity::Point(point));
+                ("POINT", Vec::new())
+            }
+            EntityType::Text(ref text) => {
+                // justified texts are placed by their alignment point, the location is recalculated
+                let location = if text.horizontal_text_justification as i16 == 0
+                    && text.vertical_text_justification as i16 == 0
+                {
+                    &text.location
+                } else {
+                    &text.second_alignment_point
+                };
+                let transform = Transform::ocs(&text.normal).then(&transform);
+                self.add_text(
+                    transform.apply([location.x, location.y]),
+                    &special_characters(&text.value),
+                    imported.style.layer,
+                );
+                ("TEXT", Vec::new())
+            }
+            EntityType::MText(ref m_text) => {
+                let contents = special_characters(&strip_formatting(
+                    &(m_text.extended_text.concat() + &m_text.text),
+                ));
+                let lines = contents.lines().count().max(1) as f64;
+                let block_height = lines * m_text.initial_text_height * 5.0 / 3.0;
+
+                // our texts hang off their bottom left corner
+                let drop = match m_text.attachment_point as i16 {
+                    1..=3 => block_height,
+                    4..=6 => block_height / 2.0,
+                    _ => 0.0,
+                };
+                let position =
+                    transform.apply([m_text.insertion_point.x, m_text.insertion_point.y - drop]);
+                self.add_text(position, &contents, imported.style.layer);
+                ("MTEXT", Vec::new())
+            }
+            EntityType::Insert(ref insert) => {
+                let transform = Transform::ocs(&insert.extrusion_direction).then(&transform);
+                let lineweight = self.lineweight(&entity.common, context);
+
+                // ByBlock inside means whatever the insert has, its layer's color if that's ByLayer
+                let block_color = match imported.style.color {
+                    EntityColor::ByLayer => self
+                        .document
+                        .layers
+                        .of(imported.style.layer)
+                        .map_or(EntityColor::ByLayer, |layer| {
+                            EntityColor::Explicit(layer.color)
+                        }),
+                    color => color,
+                };
+                let block_linetype = match imported.style.linetype {
+                    EntityLinetype::ByLayer => self
+                        .document
+                        .layers
+                        .of(imported.style.layer)
+                        .map_or(EntityLinetype::ByLayer, |layer| {
+                            EntityLinetype::Explicit(layer.linetype)
+                        }),
+                    linetype => linetype,
+                };
+                let block_context = Context {
+                    transform,
+                    block_lineweight: lineweight,
+                    block_layer: imported.style.layer,
+                    block_color,
+                    block_linetype,
+                    depth: context.depth,
+                };
+
+                if !self.import_insert(insert, &block_context) {
+                    return None;
+                }
+
+                // attributes are stored where they ended up, they only need the outer transform,
+                // the dxf crate doesn't read their layer so they go on the insert's
+                for attribute in insert.attributes().filter(|a| a.flags & 1 == 0) {
+                    let location = [attribute.location.x, attribute.location.y];
+                    self.add_text(
+                        context.transform.apply(location),
+                        &attribute.value,
+                        imported.style.layer,
+                    );
+                }
+
+                ("INSERT", Vec::new())
+            }
+            _ => return None,
+        };
+
+        // points, texts and inserts don't hand back what they added, anything else that added
+        // nothing, like a spline without points, didn't make it in
+        if ids.is_empty() && !matches!(kind, "POINT" | "TEXT" | "MTEXT" | "INSERT") {
+            return None;
+        }
+
+        for id in ids {
+            self.set_style(id, &imported);
+        }
+
+        Some(kind)
+    }
+
+    // `outer` is what the insert itself sits in, with its own lineweight and layer to hand down
+    fn import_insert(&mut self, insert: &Insert, outer: &Context) -> bool {
+        if outer.depth >= MAX_BLOCK_DEPTH {
+            return false;
+        }
+
+        let drawing = self.drawing;
+        let Some(block) = drawing.blocks().find(|block| block.name == insert.name) else {
+            return false;
+        };
+
+        for column in 0..insert.column_count.max(1) {
+            for row in 0..insert.row_count.max(1) {
+                let offset = [
+                    column as f64 * insert.column_spacing,
+                    row as f64 * insert.row_spacing,
+                ];
+                let context = Context {
+                    transform: Transform::insert(insert, &block.base_point, offset)
+                        .then(&outer.transform),
+                    depth: outer.depth + 1,
+                    ..*outer
+                };
+
+                for entity in &block.entities {
+                    self.import_entity(entity, &context);
+                }
+            }
+        }
+
+        true
+    }
+
+    fn import_hatch(&mut self, raw: &RawEntity, context: &Context) -> bool {
+        let common = EntityCommon {
+            layer: raw.value(8).unwrap_or("0").to_string(),
+            lineweight_enum_value: raw
+                .value(370)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(LINEWEIGHT_BY_LAYER),
+            color: match raw.value(62).and_then(|v| v.parse::<i16>().ok()) {
+                Some(0) => dxf::Color::by_block(),
+                Some(index @ 1..=255) => dxf::Color::from_index(index as u8),
+                _ => dxf::Color::by_layer(),
+            },
+            color_24_bit: raw.value(420).and_then(|v| v.parse().ok()).unwrap_or(0),
+            line_type_name: raw.value(6).unwrap_or("BYLAYER").to_string(),
+            line_type_scale: raw.value(48).and_then(|v| v.parse().ok()).unwrap_or(1.0),
+            ..Default::default()
+        };
+        let imported = self.style(&common, context);
+
+        // spline edges carry their fit points too from R2010 on
+        let has_fit_data = self.drawing.header.version >= AcadVersion::R2010;
+        let paths = hatch_boundaries(raw, has_fit_data);
+        for path in &paths {
+            let ids = match path {
+                Boundary::Polyline(vertices) => {
+                    self.add_polyline(vertices.clone(), true, &context.transform)
+                }
+                Boundary::Points(points) => {
+                    self.add_points(points.clone(), true, &context.transform)
+                }
+            };
+
+            for id in ids {
+                self.set_style(id, &imported);
+            }
+        }
+
+        !paths.is_empty()
+    }
+
+    // circles and arcs keep their shape as long as the transform doesn't stretch them, otherwise
+    // they become ellipses
+    fn add_arc_like(
+        &mut self,
+        center: [f64; 2],
+        radius: f64,
+        start: f64,
+        sweep: f64,
+        full_circle: bool,
+        transform: &Transform,
+    ) -> Vec<u64> {
+        let Some(scale) = transform.uniform_scale() else {
+            let (p, q) = ([radius, 0.0], [0.0, radius]);
+            return self.add_ellipse(center, p, q, start, start + sweep, transform);
+        };
+
+        let center_point = transform.apply(center);
+        let radius = (radius * scale) as f32;
+
+        if full_circle {
+            let id = self
+                .document
+                .add_circle(center_point, radius, false, false, false);
+            return vec![id];
+        }
+
+        // a mirror turns the arc around, it then runs from the image of its end
+        let rotation = transform.rotation();
+        let (start, end) = if transform.mirrors() {
+            (rotation - (start + sweep), rotation - start)
+        } else {
+            (start + rotation, start + sweep + rotation)
+        };
+
+        let arc = Arc::new(center_point, radius, start as f32, end as f32);
+        vec![self.document.add_arc(arc, false)]
+    }
+
+    // an ellipse stays one under any transform, its axes just turn into some other pair of
+    // conjugate half diameters `p` and `q`
+    fn add_ellipse(
+        &mut self,
+        center: [f64; 2],
+        p: [f64; 2],
+        q: [f64; 2],
+        start: f64,
+        end: f64,
+        transform: &Transform,
+    ) -> Vec<u64> {
+        let ellipse = Ellipse::from_conjugate(
+            transform.apply(center),
+            transform.apply_vector(p),
+            transform.apply_vector(q),
+            start as f32,
+            end as f32,
+        );
+        vec![self.document.add_ellipse(ellipse, false)]
+    }
+
+    fn add_polyline(
+        &mut self,
+        vertices: Vec<([f64; 2], f64)>,
+        closed: bool,
+        transform: &Transform,
+    ) -> Vec<u64> {
+        if vertices.len() < 2 {
+            return Vec::new();
+        }
+
+        // bulged segments stay arcs unless the transform would stretch them into ellipses
+        if transform.uniform_scale().is_none() && vertices.iter().any(|(_, bulge)| *bulge != 0.0) {
+            let points = flatten_bulges(&vertices, closed);
+            return self.add_points(points, closed, transform);
+        }
+
+        let flip = if transform.mirrors() { -1.0 } else { 1.0 };
+        let vertices = vertices
+            .iter()
+            .map(|(position, bulge)| {
+                PolylineVertex::new(transform.apply(*position), (bulge * flip) as f32)
+            })
+            .collect();
+
+        vec![self.document.add_polyline(vertices, closed, false)]
+    }
+
+    // straight segments through `points`
+    fn add_points(
+        &mut self,
+        points: Vec<[f64; 2]>,
+        closed: bool,
+        transform: &Transform,
+    ) -> Vec<u64> {
+        let vertices = points.into_iter().map(|point| (point, 0.0)).collect();
+        self.add_polyline(vertices, closed, transform)
+    }
+
+    fn add_text(&mut self, position: [f32; 2], contents: &str, layer: u64) {
+        if contents.trim().is_empty() {
+            return;
+        }
+
+        let id = self.document.next_id();
+        self.document.texts.push(Text {
+            id,
+            position,
+            contents: egui::WidgetText::from(contents),
+            rect: None,
+            editing: false,
+            selected: false,
+            annotative: false,
+            layer,
+        });
+    }
+}
+
+pub fn millimeters_per_unit(units: Units) -> Option<f64> {
+    let millimeters = match units {
+        Units::Unitless => return None,
+        Units::Inches => 25.4,
+        Units::Feet => 304.8,
+        Units::Miles => 1_609_344.0,
+        Units::Millimeters => 1.0,
+        Units::Centimeters => 10.0,
+        Units::Meters => 1_000.0,
+        Units::Kilometers => 1_000_000.0,
+        Units::Microinches => 25.4e-6,
+        Units::Mils => 25.4e-3,
+        Units::Yards => 914.4,
+        Units::Angstroms => 1e-7,
+        Units::Nanometers => 1e-6,
+        Units::Microns => 1e-3,
+        Units::Decimeters => 100.0,
+        Units::Decameters => 10_000.0,
+        Units::Hectometers => 100_000.0,
+        Units::Gigameters => 1e12,
+        Units::AstronomicalUnits => 1.495_978_707e14,
+        Units::LightYears => 9.460_730_472_580_8e18,
+        Units::Parsecs => 3.085_677_581_49e19,
+        Units::USSurveyFeet => 1_200_000.0 / 3_937.0,
+        Units::USSurveyInch => 100_000.0 / 3_937.0,
+        Units::USSurveyYard => 3_600_000.0 / 3_937.0,
+        Units::USSurveyMile => 6_336_000_000.0 / 3_937.0,
+    };
+
+    Some(millimeters)
+}
+
+// curve approximations
+
+fn arc_points(center: [f64; 2], radius: f64, start: f64, sweep: f64) -> Vec<[f64; 2]> {
+    let segments = (sweep.abs() / TAU * SEGMENTS_PER_TURN).ceil().max(4.0) as usize;
+
+    (0..=segments)
+        .map(|i| {
+            let angle = start + sweep * i as f64 / segments as f64;
+            [
+                center[0] + radius * angle.cos(),
+                center[1] + radius * angle.sin(),
+            ]
+        })
+        .collect()
+}
+
+fn flatten_bulges(vertices: &[([f64; 2], f64)], closed: bool) -> Vec<[f64; 2]> {
+    let mut points = Vec::new();
+    let count = if closed {
+        vertices.len()
+    } else {
+        vertices.len() - 1
+    };
+
+    for i in 0..count {
+        let (start, bulge) = vertices[i];
+        let (end, _) = vertices[(i + 1) % vertices.len()];
+        points.push(start);
+
+        if bulge != 0.0 {
+            let start_f32 = [start[0] as f32, start[1] as f32];
+            let end_f32 = [end[0] as f32, end[1] as f32];
+            let arc = arc_from_bulge(start_f32, end_f32, bulge as f32);
+            let center = [arc.center[0] as f64, arc.center[1] as f64];
+            let sweep = arc.sweep() as f64;
+
+            // the arc always runs counter-clockwise, a negative bulge walks it backwards
+            let mut inner = arc_points(center, arc.radius as f64, arc.start_angle as f64, sweep);
+            if bulge < 0.0 {
+                inner.reverse();
+            }
+            points.extend(&inner[1..inner.len() - 1]);
+        }
+    }
+
+    if !closed {
+        points.push(vertices[vertices.len() - 1].0);
+    }
+
+    points
+}
+
+fn spline_points(spline: &dxf::entities::Spline) -> Vec<[f64; 2]> {
+    let degree = spline.degree_of_curve.max(1) as usize;
+    let control: Vec<[f64; 2]> = spline.control_points.iter().map(|p| [p.x, p.y]).collect();
+    let knots = &spline.knot_values;
+
+    if control.len() > degree && knots.len() == control.len() + degree + 1 {
+        let (low, high) = (knots[degree], knots[control.len()]);
+        let samples = control.len() * 8;
+
+        (0..=samples)
+            .map(|i| {
+                let t = low + (high - low) * i as f64 / samples as f64;
+                de_boor(degree, knots, &control, t)
+            })
+            .collect()
+    } else {
+        // no usable definition, the fit points are on the curve at least
+        spline.fit_points.iter().map(|p| [p.x, p.y]).collect()
+    }
+}
+
+// point of a B-spline at parameter `t`
+fn de_boor(degree: usize, knots: &[f64], control: &[[f64; 2]], t: f64) -> [f64; 2] {
+    let mut span = degree;
+    while span < control.len() - 1 && t >= knots[span + 1] {
+        span += 1;
+    }
+
+    let mut d: Vec<[f64; 2]> = control[span - degree..=span].to_vec();
+    for r in 1..=degree {
+        for j in (r..=degree).rev() {
+            let i = j + span - degree;
+            let denominator = knots[i + degree + 1 - r] - knots[i];
+            let alpha = if denominator.abs() < f64::EPSILON {
+                0.0
+            } else {
+                (t - knots[i]) / denominator
+            };
+
+            d[j] = [
+                (1.0 - alpha) * d[j - 1][0] + alpha * d[j][0],
+                (1.0 - alpha) * d[j - 1][1] + alpha * d[j][1],
+            ];
+        }
+    }
+
+    d[degree]
+}
+
+// turns the %% codes into the characters they stand for
+fn special_characters(text: &str) -> String {
+    let mut cleaned = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '%' && chars.peek() == Some(&'%') {
+            chars.next();
+            match chars.next().map(|c| c.to_ascii_lowercase()) {
+                Some('d') => cleaned.push('°'),
+                Some('c') => cleaned.push('⌀'),
+                Some('p') => cleaned.push('±'),
+                Some('%') => cleaned.push('%'),
+                _ => {}
+            }
+        } else {
+            cleaned.push(c);
+        }
+    }
+
+    cleaned
+}
+
+// drops the inline formatting of MTEXT, paragraph breaks become new lines
+fn strip_formatting(text: &str) -> String {
+    let mut cleaned = String::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' | '}' => {}
+            '\\' => match chars.next() {
+                Some('\\') => cleaned.push('\\'),
+                Some('{') => cleaned.push('{'),
+                Some('}') => cleaned.push('}'),
+                Some('~') => cleaned.push(' '),
+                Some('P') | Some('X') => cleaned.push('\n'),
+                // codes with a value run up to the next semicolon
+                Some('f' | 'F' | 'H' | 'W' | 'Q' | 'T' | 'A' | 'C' | 'c' | 'p' | 'S') => {
+                    for c in chars.by_ref() {
+                        if c == ';' {
+                            break;
+                        }
+                    }
+                }
+                // underline, overline and strike through toggles
+                Some(_) | None => {}
+            },
+            c => cleaned.push(c),
+        }
+    }
+
+    cleaned
+}
+
+// group codes as written in the file
+
+struct RawEntity {
+    kind: String,
+    pairs: Vec<(i32, String)>,
+}
+
+impl RawEntity {
+    fn value(&self, code: i32) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(c, _)| *c == code)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+// None for binary files
+fn read_ascii(path: &str) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    if bytes.starts_with(b"AutoCAD Binary DXF") {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+// the top level records of a section: entities of ENTITIES, table entries of TABLES
+fn read_raw_section(text: &str, section: &str) -> Option<Vec<RawEntity>> {
+    let mut lines = text.lines();
+    let mut entities: Vec<RawEntity> = Vec::new();
+    let mut in_entities = false;
+    let mut section_starting = false;
+
+    while let (Some(code), Some(value)) = (lines.next(), lines.next()) {
+        let code: i32 = code.trim().parse().ok()?;
+        let value = value.trim();
+
+        if code == 0 {
+            if value == "ENDSEC" {
+                in_entities = false;
+            }
+            section_starting = value == "SECTION";
+
+            // these belong to the entity in front of them
+            if in_entities && !matches!(value, "VERTEX" | "SEQEND" | "ATTRIB") {
+                entities.push(RawEntity {
+                    kind: value.to_string(),
+                    pairs: Vec::new(),
+                });
+            }
+        } else if code == 2 && section_starting {
+            in_entities = value == section;
+            section_starting = false;
+        } else if in_entities {
+            if let Some(entity) = entities.last_mut() {
+                entity.pairs.push((code, value.to_string()));
+            }
+        }
+    }
+
+    Some(entities)
+}
+
+enum Boundary {
+    Polyline(Vec<([f64; 2], f64)>),
+    Points(Vec<[f64; 2]>),
+}
+
+// Boundary paths of a HATCH. The format is a flat list of group codes, so it's read front to
+// back: each path says how many vertices or edges follow and every edge type has its own layout.
+fn hatch_boundaries(raw: &RawEntity, has_fit_data: bool) -> Vec<Boundary> {
+    let mut reader = PairReader {
+        pairs: &raw.pairs,
+        next: 0,
+    };
+    let mut boundaries = Vec::new();
+
+    let Some(path_count) = reader.int(91) else {
+        return boundaries;
+    };
+
+    for _ in 0..path_count {
+        let Some(flags) = reader.int(92) else {
+            break;
+        };
+
+        if flags & 2 != 0 {
+            let has_bulge = reader.int(72).unwrap_or(0) != 0;
+            let _closed = reader.int(73);
+            let count = reader.int(93).unwrap_or(0);
+            let mut vertices = Vec::new();
+
+            for _ in 0..count {
+                let x = reader.float(10).unwrap_or(0.0);
+                let y = reader.float(20).unwrap_or(0.0);
+                let bulge = if has_bulge && reader.peek() == Some(42) {
+                    reader.float(42).unwrap_or(0.0)
+                } else {
+                    0.0
+                };
+                vertices.push(([x, y], bulge));
+            }
+            boundaries.push(Boundary::Polyline(vertices));
+        } else {
+            let edge_count = reader.int(93).unwrap_or(0);
+            let mut points: Vec<[f64; 2]> = Vec::new();
+
+            for _ in 0..edge_count {
+                let edge = match reader.int(72) {
+                    Some(1) => {
+                        let start = [reader.float(10), reader.float(20)];
+                        let end = [reader.float(11), reader.float(21)];
+                        match (start, end) {
+                            ([Some(x1), Some(y1)], [Some(x2), Some(y2)]) => {
+                                vec![[x1, y1], [x2, y2]]
+                            }
+                            _ => Vec::new(),
+                        }
+                    }
+                    Some(2) => {
+                        let center = [
+                            reader.float(10).unwrap_or(0.0),
+                            reader.float(20).unwrap_or(0.0),
+                        ];
+                        let radius = reader.float(40).unwrap_or(0.0);
+                        let start = reader.float(50).unwrap_or(0.0).to_radians();
+                        let end = reader.float(51).unwrap_or(0.0).to_radians();
+                        let ccw = reader.int(73).unwrap_or(1) != 0;
+                        edge_arc(center, [radius, 0.0], 1.0, start, end, ccw)
+                    }
+                    Some(3) => {
+                        let center = [
+                            reader.float(10).unwrap_or(0.0),
+                            reader.float(20).unwrap_or(0.0),
+                        ];
+                        let major = [
+                            reader.float(11).unwrap_or(0.0),
+                            reader.float(21).unwrap_or(0.0),
+                        ];
+                        let ratio = reader.float(40).unwrap_or(1.0);
+                        let start = reader.float(50).unwrap_or(0.0).to_radians();
+                        let end = reader.float(51).unwrap_or(0.0).to_radians();
+                        let ccw = reader.int(73).unwrap_or(1) != 0;
+                        edge_arc(center, major, ratio, start, end, ccw)
+                    }
+                    Some(4) => {
+                        let degree = reader.int(94).unwrap_or(3).max(1) as usize;
+                        let _rational = reader.int(73);
+                        let _periodic = reader.int(74);
+                        let knot_count = reader.int(95).unwrap_or(0);
+                        let control_count = reader.int(96).unwrap_or(0);
+
+                        let knots: Vec<f64> =
+                            (0..knot_count).map_while(|_| reader.float(40)).collect();
+                        let mut control = Vec::new();
+                        for _ in 0..control_count {
+                            let x = reader.float(10).unwrap_or(0.0);
+                            let y = reader.float(20).unwrap_or(0.0);
+                            if reader.peek() == Some(42) {
+                                reader.float(42);
+                            }
+                            control.push([x, y]);
+                        }
+
+                        if has_fit_data {
+                            let fit_count = reader.int(97).unwrap_or(0);
+                            for _ in 0..fit_count {
+                                reader.skip(11);
+                                reader.skip(21);
+                            }
+                            // start and end tangents
+                            if reader.peek() == Some(12) {
+                                reader.skip(22);
+                                reader.skip(23);
+                            }
+                        }
+
+                        if control.len() > degree && knots.len() == control.len() + degree + 1 {
+                            let (low, high) = (knots[degree], knots[control.len()]);
+                            let samples = control.len() * 8;
+                            (0..=samples)
+                                .map(|i| {
+                                    let t = low + (high - low) * i as f64 / samples as f64;
+                                    de_boor(degree, &knots, &control, t)
+                                })
+                                .collect()
+                        } else {
+                            control
+                        }
+                    }
+                    _ => Vec::new(),
+                };
+
+                // edges share their end points, only the first one keeps its start
+                let skip = usize::from(points.last().is_some_and(|last| {
+                    edge.first().is_some_and(|first| {
+                        (last[0] - first[0]).abs() < 1e-9 && (last[1] - first[1]).abs() < 1e-9
+                    })
+                }));
+                points.extend(edge.into_iter().skip(skip));
+            }
+
+            if points.len() > 1 {
+                let first = points[0];
+                let last = points[points.len() - 1];
+                if (first[0] - last[0]).abs() < 1e-9 && (first[1] - last[1]).abs() < 1e-9 {
+                    points.pop();
+                }
+                boundaries.push(Boundary::Points(points));
+            }
+        }
+
+        // handles of the entities the boundary was picked from
+        let source_count = reader.int(97).unwrap_or(0);
+        for _ in 0..source_count {
+            reader.skip(330);
+        }
+    }
+
+    boundaries
+}
+
+// arc or elliptical arc edge of a hatch, clockwise ones have their angles mirrored
+fn edge_arc(
+    center: [f64; 2],
+    major: [f64; 2],
+    ratio: f64,
+    start: f64,
+    end: f64,
+    ccw: bool,
+) -> Vec<[f64; 2]> {
+    let minor = [-major[1] * ratio, major[0] * ratio];
+    let (start, sweep) = if ccw {
+        (start, (end - start).rem_euclid(TAU))
+    } else {
+        (-start, -(end - start).rem_euclid(TAU))
+    };
+    let sweep = if sweep.abs() < 1e-9 { TAU } else { sweep };
+
+    let segments = (sweep.abs() / TAU * SEGMENTS_PER_TURN).ceil().max(4.0) as usize;
+    (0..=segments)
+        .map(|i| {
+            let t = start + sweep * i as f64 / segments as f64;
+            let (sin, cos) = t.sin_cos();
+            [
+                center[0] + major[0] * cos + minor[0] * sin,
+                center[1] + major[1] * cos + minor[1] * sin,
+            ]
+        })
+        .collect()
+}
+
+// walks the group codes of one entity in order
+struct PairReader<'a> {
+    pairs: &'a [(i32, String)],
+    next: usize,
+}
+
+impl PairReader<'_> {
+    fn peek(&self) -> Option<i32> {
+        self.pairs.get(self.next).map(|(code, _)| *code)
+    }
+
+    // value of the next pair with `code`, everything in front of it is passed over
+    fn skip(&mut self, code: i32) -> Option<&str> {
+        while let Some((c, value)) = self.pairs.get(self.next) {
+            self.next += 1;
+            if *c == code {
+                return Some(value.as_str());
+            }
+        }
+        None
+    }
+
+    fn int(&mut self, code: i32) -> Option<i64> {
+        self.skip(code).and_then(|v| v.parse().ok())
+    }
+
+    fn float(&mut self, code: i32) -> Option<f64> {
+        self.skip(code).and_then(|v| v.parse().ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dxf::entities::{LwPolyline, Spline};
+    use dxf::{Block, LwPolylineVertex, Point as DxfPoint};
+
+    fn entity(specific: EntityType) -> dxf::entities::Entity {
+        dxf::entities::Entity::new(specific)
+    }
+
+    fn dxf_line(start: [f64; 2], end: [f64; 2]) -> dxf::entities::Entity {
+        entity(EntityType::Line(dxf::entities::Line::new(
+            DxfPoint::new(start[0], start[1], 0.0),
+            DxfPoint::new(end[0], end[1], 0.0),
+        )))
+    }
+
+    fn drawing() -> Drawing {
+        let mut drawing = Drawing::new();
+        drawing.header.version = AcadVersion::R2000;
+        drawing
+    }
+
+    // Saves `drawing` with the raw entities `extra` slipped in, for what the dxf crate can't
+    // write, and opens it in `document`.
+    fn open(document: &mut Document, drawing: &Drawing, extra: &str, name: &str) -> ImportSummary {
+        let path = std::env::temp_dir().join(format!(
+            "easycad-import-{}-{}.dxf",
+            name,
+            std::process::id()
+        ));
+        let path = path.to_string_lossy().into_owned();
+        drawing.save_file(&path).unwrap();
+        if !extra.is_empty() {
+            let text = fs::read_to_string(&path).unwrap();
+            let text = text.replacen("ENTITIES\r\n", &format!("ENTITIES\r\n{}", extra), 1);
+            fs::write(&path, text).unwrap();
+        }
+
+        let summary = document.load_from_dxf(path.clone()).unwrap();
+        fs::remove_file(path).unwrap();
+        summary
+    }
+
+    fn lines(document: &Document) -> Vec<[[f32; 2]; 2]> {
+        document
+            .entities
+            .iter()
+            .filter_map(|entity| match entity {
+                Entity::Line(line) => Some([line.start(), line.end()]),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn near(a: [f32; 2], b: [f32; 2]) -> bool {
+        (a[0] - b[0]).abs() < 1e-4 && (a[1] - b[1]).abs() < 1e-4
+    }
+
+    #[test]
+    fn inserts_expand_their_block() {
+        let mut drawing = drawing();
+        let mut block = Block {
+            name: "TICK".to_string(),
+            base_point: DxfPoint::new(1.0, 0.0, 0.0),
+            ..Default::default()
+        };
+        block.entities.push(dxf_line([1.0, 0.0], [2.0, 0.0]));
+        drawing.add_block(block);
+
+        // twice the size, turned a quarter and in two columns 3 apart
+        let insert = Insert {
+            name: "TICK".to_string(),
+            location: DxfPoint::new(10.0, 5.0, 0.0),
+            x_scale_factor: 2.0,
+            y_scale_factor: 2.0,
+            rotation: 90.0,
+            column_count: 2,
+            column_spacing: 3.0,
+            ..Default::default()
+        };
+        drawing.add_entity(entity(EntityType::Insert(insert)));
+
+        let mut document = Document::new();
+        let summary = open(&mut document, &drawing, "", "insert");
+
+        assert_eq!(summary.imported.get("INSERT"), Some(&1));
+        assert!(summary.skipped.is_empty());
+        let lines = lines(&document);
+        assert_eq!(lines.len(), 2);
+        for (line, start) in lines.iter().zip([[10.0, 5.0], [10.0, 8.0]]) {
+            assert!(near(line[0], start), "{:?}", line);
+            assert!(near(line[1], [start[0], start[1] + 2.0]), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn a_drawing_in_other_units_is_scaled_to_the_documents() {
+        let mut drawing = drawing();
+        drawing.header.default_drawing_units = Units::Meters;
+        drawing.add_entity(dxf_line([0.0, 0.0], [1.5, 0.0]));
+
+        let mut document = Document::new();
+        document.units = Units::Millimeters;
+        document.add_line([0.0, 0.0], [1.0, 1.0], false);
+        let summary = open(&mut document, &drawing, "", "units");
+
+        assert_eq!(summary.scaled_from, Some(Units::Meters));
+        assert!(lines(&document)
+            .iter()
+            .any(|line| near(line[0], [0.0, 0.0]) && near(line[1], [1500.0, 0.0])));
+    }
+
+    #[test]
+    fn an_empty_document_takes_over_the_units() {
+        let mut drawing = drawing();
+        drawing.header.default_drawing_units = Units::Meters;
+        drawing.add_entity(dxf_line([0.0, 0.0], [1.5, 0.0]));
+
+        let mut document = Document::new();
+        let summary = open(&mut document, &drawing, "", "adopt-units");
+
+        assert_eq!(summary.scaled_from, None);
+        assert_eq!(document.units, Units::Meters);
+        assert!(near(lines(&document)[0][1], [1.5, 0.0]));
+    }
+
+    #[test]
+    fn splines_and_hatches_without_a_shape_are_skipped() {
+        let mut drawing = drawing();
+        let spline = Spline {
+            degree_of_curve: 3,
+            control_points: [[0.0, 0.0], [1.0, 2.0], [3.0, 2.0], [4.0, 0.0]]
+                .iter()
+                .map(|p| DxfPoint::new(p[0], p[1], 0.0))
+                .collect(),
+            knot_values: vec![0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0],
+            ..Default::default()
+        };
+        drawing.add_entity(entity(EntityType::Spline(spline)));
+        // no control or fit points, nothing to draw
+        drawing.add_entity(entity(EntityType::Spline(Spline::default())));
+
+        let square = "  0\r\nHATCH\r\n  8\r\n0\r\n 91\r\n1\r\n 92\r\n2\r\n 72\r\n0\r\n 73\r\n1\r\n 93\r\n4\r\n 10\r\n0\r\n 20\r\n0\r\n 10\r\n5\r\n 20\r\n0\r\n 10\r\n5\r\n 20\r\n5\r\n 10\r\n0\r\n 20\r\n5\r\n";
+        let empty = "  0\r\nHATCH\r\n  8\r\n0\r\n 91\r\n0\r\n";
+        let mut document = Document::new();
+        let summary = open(
+            &mut document,
+            &drawing,
+            &format!("{}{}", square, empty),
+            "skipped",
+        );
+
+        assert_eq!(summary.imported.get("SPLINE"), Some(&1));
+        assert_eq!(summary.imported.get("HATCH"), Some(&1));
+        assert_eq!(summary.skipped.get("SPLINE"), Some(&1));
+        assert_eq!(summary.skipped.get("HATCH"), Some(&1));
+        assert!(summary.incomplete());
+        assert!(summary.message().contains("Skipped 2: HATCH 1, SPLINE 1"));
+        assert_eq!(document.entities.len(), 2);
+    }
+
+    #[test]
+    fn lwpolyline_bulges_stay_arcs() {
+        let mut drawing = drawing();
+        let mut polyline = LwPolyline::default();
+        for (x, y, bulge) in [(0.0, 0.0, 1.0), (10.0, 0.0, 0.0), (10.0, 5.0, -0.5)] {
+            polyline.vertices.push(LwPolylineVertex {
+                x,
+                y,
+                bulge,
+                ..Default::default()
+            });
+        }
+        polyline.set_is_closed(true);
+        drawing.add_entity(entity(EntityType::LwPolyline(polyline)));
+
+        let mut document = Document::new();
+        let summary = open(&mut document, &drawing, "", "bulges");
+
+        assert_eq!(summary.imported.get("LWPOLYLINE"), Some(&1));
+        let Some(Entity::Polyline(polyline)) = document.entities.iter().next() else {
+            panic!("expected a polyline");
+        };
+        assert!(polyline.closed);
+        assert_eq!(
+            polyline.vertices,
+            vec![
+                PolylineVertex::new([0.0, 0.0], 1.0),
+                PolylineVertex::new([10.0, 0.0], 0.0),
+                PolylineVertex::new([10.0, 5.0], -0.5),
+            ]
+        );
+    }
+}
@@ -0,0 +1,465 @@
+use crate::model::document::Document;
+use crate::model::entity::Entity;
+use crate::model::geometry::{add, cross, distance, dot, scale, sub};
+use crate::model::history::{Change, HistoryOps};
+use crate::model::path::{piece_entity, rebuild, Path, Piece, EPSILON};
+use crate::model::polyline::{Polyline, PolylineVertex};
+use crate::model::trim::replace_with_parts;
+
+// ends closer than this count as touching, imported drawings are rarely exact
+pub const JOIN_GAP: f32 = 1e-3;
+
+pub trait JoinOps {
+    // Splits entity `id` at `first`, or takes out what's between `first` and `second`. A closed
+    // shape opens up, a circle needs both points and becomes an arc. Returns whether it changed.
+    fn break_at(&mut self, id: u64, first: [f32; 2], second: Option<[f32; 2]>) -> bool;
+    // Collinear lines that touch or overlap become one line, lines, arcs and open polylines that
+    // meet end to end one polyline.
+    fn join(&mut self, ids: &[u64]) -> JoinReport;
+    // turns polylines into their lines and arcs, returns how many there were
+    fn explode(&mut self, ids: &[u64]) -> usize;
+}
+
+// what a join did, `entities` went into `results` new ones
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct JoinReport {
+    pub entities: usize,
+    pub results: usize,
+}
+
+// entities that end up as one, and the pieces they make together
+struct Chain {
+    ids: Vec<u64>,
+    pieces: Vec<Piece>,
+}
+
+impl Chain {
+    fn start(&self) -> [f32; 2] {
+        self.pieces[0].start()
+    }
+
+    fn end(&self) -> [f32; 2] {
+        self.pieces[self.pieces.len() - 1].end()
+    }
+
+    fn reverse(&mut self) {
+        self.pieces.reverse();
+        for piece in &mut self.pieces {
+            *piece = piece.reversed();
+        }
+    }
+}
+
+// Both lines along one infinite line and overlapping, or only touching if `touching`, the line
+// covering the two.
+pub fn merged(a: &Piece, b: &Piece, tolerance: f32, touching: bool) -> Option<Piece> {
+    let (Piece::Line(p, q), Piece::Line(r, s)) = (*a, *b) else {
+        return None;
+    };
+    let direction = sub(q, p);
+    let length = dot(direction, direction).sqrt();
+    if length < EPSILON {
+        return None;
+    }
+    let off_line = |point: [f32; 2]| (cross(direction, sub(point, p)) / length).abs();
+    if off_line(r) > tolerance || off_line(s) > tolerance {
+        return None;
+    }
+
+    let along = |point: [f32; 2]| dot(sub(point, p), direction) / (length * length);
+    let (tr, ts) = (along(r), along(s));
+    // how far apart they can be along the line, below 0 they have to overlap by that much
+    let reach = if touching { tolerance } else { -tolerance } / length;
+    if tr.min(ts) > 1.0 + reach || tr.max(ts) < -reach {
+        return None;
+    }
+
+    let at = |t: f32| add(p, scale(direction, t));
+    Some(Piece::Line(
+        at(tr.min(ts).min(0.0)),
+        at(tr.max(ts).max(1.0)),
+    ))
+}
+
+// the chains joining what's given ends up as, in the order they were started
+fn chains(mut pool: Vec<Chain>) -> Vec<Chain> {
+    // collinear lines first, so they end up as one piece and not a row of them
+    let mut i = 0;
+    while i < pool.len() {
+        let mut j = i + 1;
+        while j < pool.len() {
+            let joined = match (pool[i].pieces.as_slice(), pool[j].pieces.as_slice()) {
+                ([a], [b]) => merged(a, b, JOIN_GAP, true),
+                _ => None,
+            };
+            match joined {
+                Some(piece) => {
+                    let other = pool.remove(j);
+                    pool[i].ids.extend(other.ids);
+                    pool[i].pieces = vec![piece];
+                    // the longer line can reach ones that were passed over already
+                    j = i + 1;
+                }
+                None => j += 1,
+            }
+        }
+        i += 1;
+    }
+
+    let mut done = Vec::new();
+    while !pool.is_empty() {
+        let mut chain = pool.remove(0);
+        while let Some(index) = pool.iter().position(|other| {
+            [other.start(), other.end()].iter().any(|p| {
+                distance(*p, chain.end()) < JOIN_GAP || distance(*p, chain.start()) < JOIN_GAP
+            })
+        }) {
+            let mut other = pool.remove(index);
+            if distance(other.start(), chain.end()) < JOIN_GAP {
+                chain.pieces.extend(other.pieces);
+            } else if distance(other.end(), chain.end()) < JOIN_GAP {
+                other.reverse();
+                chain.pieces.extend(other.pieces);
+            } else {
+                if distance(other.start(), chain.start()) < JOIN_GAP {
+                    other.reverse();
+                }
+                other.pieces.extend(chain.pieces);
+                chain.pieces = other.pieces;
+            }
+            chain.ids.extend(other.ids);
+        }
+        done.push(chain);
+    }
+    done
+}
+
+impl JoinOps for Document {
+    fn break_at(&mut self, id: u64, first: [f32; 2], second: Option<[f32; 2]>) -> bool {
+        let Some(target) = self.entities.get(id).cloned() else {
+            return false;
+        };
+        let Some(path) = Path::of(&target) else {
+            return false;
+        };
+        let end = path.length();
+        let a = path.param_near(first);
+
+        let parts = match second {
+            // a circle split at one point would still be a whole circle
+            None if matches!(target, Entity::Circle(_)) => return false,
+            None if path.closed => {
+                let mut kept = path.between(a, end);
+                kept.extend(path.between(0.0, a));
+                vec![kept]
+            }
+            None if a < EPSILON || a > end - EPSILON => return false,
+            None => vec![path.between(0.0, a), path.between(a, end)],
+            // a closed shape loses what runs from the first point on to the second one
+            Some(second) if path.closed => {
+                let b = path.param_near(second);
+                if b < a {
+                    vec![path.between(b, a)]
+                } else {
+                    let mut kept = path.between(b, end);
+                    kept.extend(path.between(0.0, a));
+                    vec![kept]
+                }
+            }
+            Some(second) => {
+                let b = path.param_near(second);
+                vec![path.between(0.0, a.min(b)), path.between(a.max(b), end)]
+            }
+        };
+
+        let parts = parts
+            .iter()
+            .filter(|pieces| !pieces.is_empty())
+            .filter_map(|pieces| rebuild(&target, pieces))
+            .collect();
+        let changes = replace_with_parts(self, target, parts);
+
+        self.record("Break", changes);
+        true
+    }
+
+    fn join(&mut self, ids: &[u64]) -> JoinReport {
+        let pool: Vec<Chain> = ids
+            .iter()
+            .filter_map(|id| self.entities.get(*id))
+            .filter(|entity| !entity.is_drawing())
+            .filter_map(|entity| {
+                let path = Path::of(entity).filter(|path| !path.closed)?;
+                Some(Chain {
+                    ids: vec![entity.id()],
+                    pieces: path.pieces,
+                })
+            })
+            .collect();
+
+        let mut changes = Vec::new();
+        let mut report = JoinReport::default();
+        for chain in chains(pool) {
+            if chain.ids.len() < 2 {
+                continue;
+            }
+            let Some(first) = self.entities.get(chain.ids[0]).cloned() else {
+                continue;
+            };
+
+            let mut after = match chain.pieces.as_slice() {
+                [piece @ Piece::Line(..)] => piece_entity(piece, &first),
+                pieces => {
+                    let closed =
+                        pieces.len() > 2 && distance(chain.start(), chain.end()) < JOIN_GAP;
+                    let mut vertices: Vec<PolylineVertex> = pieces
+                        .iter()
+                        .map(|piece| PolylineVertex::new(piece.start(), piece.bulge()))
+                        .collect();
+                    if !closed {
+                        vertices.push(PolylineVertex::new(chain.end(), 0.0));
+                    }
+                    Entity::Polyline(Polyline {
+                        id: 0,
+                        vertices,
+                        closed,
+                        selected: false,
+                        del: false,
+                        is_drawing: false,
+                        style: first.style(),
+                    })
+                }
+            };
+            after.set_id(first.id());
+            if let Some(entity) = self.entities.get_mut(first.id()) {
+                *entity = after.clone();
+            }
+            changes.push(Change::Modify(first, after));
+            report.entities += 1;
+            report.results += 1;
+
+            for id in &chain.ids[1..] {
+                if let Some(entity) = self.entities.remove(*id) {
+                    changes.push(Change::Remove(entity));
+                    report.entities += 1;
+                }
+            }
+        }
+
+        self.record("Join", changes);
+        report
+    }
+
+    fn explode(&mut self, ids: &[u64]) -> usize {
+        let mut changes = Vec::new();
+        let mut exploded = 0;
+
+        for id in ids {
+            let Some(target @ Entity::Polyline(_)) = self.entities.get(*id).cloned() else {
+                continue;
+            };
+            let Some(path) = Path::of(&target) else {
+                continue;
+            };
+            let parts = path
+                .pieces
+                .iter()
+                .filter(|piece| piece.arc_length() > EPSILON)
+                .map(|piece| piece_entity(piece, &target))
+                .collect();
+            changes.extend(replace_with_parts(self, target, parts));
+            exploded += 1;
+        }
+
+        self.record("Explode", changes);
+        exploded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::arc::{Arc, ArcOps};
+    use crate::model::circle::CircleOps;
+    use crate::model::entity::Geometry;
+    use crate::model::layer::LayerOps;
+    use crate::model::line::LineOps;
+    use crate::model::polyline::PolylineOps;
+    use std::f32::consts::PI;
+
+    fn near(a: [f32; 2], b: [f32; 2]) -> bool {
+        distance(a, b) < 1e-4
+    }
+
+    fn lines(document: &Document) -> Vec<([f32; 2], [f32; 2])> {
+        document
+            .entities
+            .iter()
+            .filter_map(|entity| match entity {
+                Entity::Line(line) => Some((line.start(), line.end())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn polyline(document: &Document, id: u64) -> Polyline {
+        match document.entities.get(id) {
+            Some(Entity::Polyline(polyline)) => polyline.clone(),
+            other => panic!("expected a polyline, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn breaking_a_line_at_a_point_splits_it() {
+        let mut document = Document::new();
+        let id = document.add_line([0.0, 0.0], [10.0, 0.0], false);
+
+        assert!(document.break_at(id, [4.0, 1.0], None));
+
+        let lines = lines(&document);
+        assert_eq!(lines.len(), 2);
+        assert!(near(lines[0].1, [4.0, 0.0]) && near(lines[1].0, [4.0, 0.0]));
+        assert_eq!(document.undo(), Some("Break"));
+        assert_eq!(document.entities.len(), 1);
+    }
+
+    #[test]
+    fn breaking_between_two_points_leaves_a_gap() {
+        let mut document = Document::new();
+        let id = document.add_line([0.0, 0.0], [10.0, 0.0], false);
+
+        assert!(document.break_at(id, [7.0, 0.0], Some([3.0, 0.0])));
+
+        let lines = lines(&document);
+        assert!(near(lines[0].0, [0.0, 0.0]) && near(lines[0].1, [3.0, 0.0]));
+        assert!(near(lines[1].0, [7.0, 0.0]) && near(lines[1].1, [10.0, 0.0]));
+    }
+
+    #[test]
+    fn a_circle_needs_two_points_and_becomes_an_arc() {
+        let mut document = Document::new();
+        let id = document.add_circle([0.0, 0.0], 5.0, false, false, false);
+
+        assert!(!document.break_at(id, [5.0, 0.0], None));
+        assert!(document.break_at(id, [0.0, 5.0], Some([5.0, 0.0])));
+
+        match document.entities.get(id) {
+            Some(Entity::Arc(arc)) => {
+                // the counter-clockwise run from the first point round to the second is gone
+                let diagonal = 5.0 / 2.0f32.sqrt();
+                assert!(arc.hit_distance([diagonal, diagonal]) < 1e-3);
+                assert!(arc.hit_distance([-5.0, 0.0]) > 1.0);
+                assert!(arc.hit_distance([0.0, -5.0]) > 1.0);
+            }
+            other => panic!("expected an arc, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn breaking_at_an_end_changes_nothing() {
+        let mut document = Document::new();
+        let id = document.add_line([0.0, 0.0], [10.0, 0.0], false);
+
+        assert!(!document.break_at(id, [10.0, 0.0], None));
+        assert!(!document.history.can_undo());
+    }
+
+    #[test]
+    fn collinear_lines_join_into_one() {
+        let mut document = Document::new();
+        let a = document.add_line([0.0, 0.0], [5.0, 0.0], false);
+        let b = document.add_line([4.0, 0.0], [10.0, 0.0], false);
+
+        let report = document.join(&[a, b]);
+
+        assert_eq!(
+            report,
+            JoinReport {
+                entities: 2,
+                results: 1
+            }
+        );
+        assert_eq!(lines(&document), vec![([0.0, 0.0], [10.0, 0.0])]);
+    }
+
+    #[test]
+    fn ends_that_meet_become_a_polyline() {
+        let mut document = Document::new();
+        let line = document.add_line([10.0, 0.0], [0.0, 0.0], false);
+        let arc = document.add_arc(Arc::new([10.0, 5.0], 5.0, -PI / 2.0, PI / 2.0), false);
+
+        let report = document.join(&[line, arc]);
+        assert_eq!(report.results, 1);
+
+        let joined = polyline(&document, line);
+        assert!(!joined.closed);
+        assert_eq!(joined.vertices.len(), 3);
+        assert!(joined.vertices.iter().any(|vertex| vertex.bulge != 0.0));
+        assert!(document.entities.get(arc).is_none());
+    }
+
+    #[test]
+    fn a_loop_of_lines_joins_closed() {
+        let mut document = Document::new();
+        let ids = [
+            document.add_line([0.0, 0.0], [10.0, 0.0], false),
+            document.add_line([10.0, 0.0], [5.0, 8.0], false),
+            document.add_line([0.0, 0.0], [5.0, 8.0], false),
+        ];
+
+        document.join(&ids);
+
+        let joined = polyline(&document, ids[0]);
+        assert!(joined.closed);
+        assert_eq!(joined.vertices.len(), 3);
+    }
+
+    #[test]
+    fn separate_chains_are_counted_apart() {
+        let mut document = Document::new();
+        let ids = [
+            document.add_line([0.0, 0.0], [5.0, 0.0], false),
+            document.add_line([5.0, 0.0], [5.0, 5.0], false),
+            document.add_line([20.0, 0.0], [25.0, 0.0], false),
+            document.add_line([25.0, 0.0], [30.0, 0.0], false),
+            // touches neither
+            document.add_line([50.0, 0.0], [60.0, 0.0], false),
+        ];
+
+        let report = document.join(&ids);
+
+        assert_eq!(
+            report,
+            JoinReport {
+                entities: 4,
+                results: 2
+            }
+        );
+        assert_eq!(document.entities.len(), 3);
+        assert_eq!(document.undo(), Some("Join"));
+        assert_eq!(document.entities.len(), 5);
+    }
+
+    #[test]
+    fn exploding_a_polyline_gives_lines_and_arcs_on_its_layer() {
+        let mut document = Document::new();
+        let walls = document.add_layer("Walls");
+        document.current_layer = walls;
+        let vertices = vec![
+            PolylineVertex::new([0.0, 0.0], 0.0),
+            PolylineVertex::new([10.0, 0.0], 1.0),
+            PolylineVertex::new([10.0, 10.0], 0.0),
+        ];
+        let id = document.add_polyline(vertices, false, false);
+        let line = document.add_line([0.0, 20.0], [5.0, 20.0], false);
+
+        assert_eq!(document.explode(&[id, line]), 1);
+
+        let kinds: Vec<&str> = document.entities.iter().map(Entity::kind_name).collect();
+        assert_eq!(kinds, vec!["Line", "Line", "Arc"]);
+        assert!(document
+            .entities
+            .iter()
+            .all(|entity| entity.layer() == walls));
+    }
+}
@@ -0,0 +1,245 @@
+use crate::graphics::gui_elements::ColorScheme;
+use crate::model::color::{Color, FOREGROUND};
+use crate::model::document::{lineweight_to_thickness, Document, LINEWEIGHT_DEFAULT};
+use crate::model::entity::Entity;
+use crate::model::linetype::{Linetype, CONTINUOUS};
+use crate::model::store::EntityStore;
+use std::collections::HashMap;
+
+// layer "0" exists in every drawing and can't be renamed or deleted
+pub const DEFAULT_LAYER: u64 = 0;
+
+// bits of a LAYER's flags (group code 70)
+pub const FLAG_FROZEN: i32 = 1;
+pub const FLAG_LOCKED: i32 = 4;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layer {
+    pub id: u64,
+    pub name: String,
+    pub color: Color,
+    // in 1/100 mm like DXF, None leaves every entity type at its own default
+    pub lineweight: Option<i16>,
+    pub linetype: u64,
+    pub visible: bool,
+    // hidden like an invisible layer, but also can't be the current layer
+    pub frozen: bool,
+    // still drawn and snapped to, just can't be picked
+    pub locked: bool,
+}
+
+impl Layer {
+    pub fn new(id: u64, name: &str) -> Self {
+        Self {
+            id,
+            name: name.to_string(),
+            color: Color::Index(FOREGROUND),
+            lineweight: None,
+            linetype: CONTINUOUS,
+            visible: true,
+            frozen: false,
+            locked: false,
+        }
+    }
+
+    pub fn is_shown(&self) -> bool {
+        self.visible && !self.frozen
+    }
+
+    pub fn is_selectable(&self) -> bool {
+        self.is_shown() && !self.locked
+    }
+
+    pub fn write_dxf(&self, layer: &mut dxf::tables::Layer, linetypes: &EntityStore<Linetype>) {
+        layer.name = self.name.clone();
+        layer.line_type_name = linetypes.name(self.linetype).to_string();
+        layer.color = dxf::Color::from_index(self.color.to_index());
+        layer.is_layer_on = self.visible;
+    }
+
+    // what the dxf crate can't read or write on its own
+    fn flags(&self) -> i32 {
+        let mut flags = 0;
+        if self.frozen {
+            flags |= FLAG_FROZEN;
+        }
+        if self.locked {
+            flags |= FLAG_LOCKED;
+        }
+        flags
+    }
+}
+
+// Undo can bring back entities whose layer has been deleted in the meantime, those behave as if
+// they were on layer "0".
+impl EntityStore<Layer> {
+    pub fn of(&self, id: u64) -> Option<&Layer> {
+        self.get(id).or_else(|| self.get(DEFAULT_LAYER))
+    }
+
+    pub fn is_shown(&self, id: u64) -> bool {
+        self.of(id).is_none_or(Layer::is_shown)
+    }
+
+    pub fn is_selectable(&self, id: u64) -> bool {
+        self.of(id).is_none_or(Layer::is_selectable)
+    }
+
+    // what an entity is drawn with once ByLayer and ByBlock are looked up
+    pub fn color(&self, entity: &Entity, color_scheme: ColorScheme) -> [f32; 3] {
+        let layer_color = self
+            .of(entity.layer())
+            .map_or(Color::Index(FOREGROUND), |layer| layer.color);
+        entity.color().to_render(layer_color, color_scheme)
+    }
+
+    // a layer at the default lineweight leaves its entities at their own thickness
+    pub fn thickness(&self, entity: &Entity) -> f32 {
+        let by_layer = entity
+            .thickness_by_layer()
+            .then(|| self.of(entity.layer()))
+            .flatten()
+            .and_then(|layer| layer.lineweight)
+            .and_then(lineweight_to_thickness);
+
+        by_layer.unwrap_or(entity.thickness())
+    }
+
+    pub fn name(&self, id: u64) -> &str {
+        self.of(id).map_or("0", |layer| layer.name.as_str())
+    }
+
+    // layer names are case insensitive in DXF
+    pub fn find(&self, name: &str) -> Option<&Layer> {
+        self.iter()
+            .find(|layer| layer.name.eq_ignore_ascii_case(name))
+    }
+}
+
+// characters DXF doesn't allow in table names
+fn is_valid_name(name: &str) -> bool {
+    !name.trim().is_empty()
+        && !name.contains(['<', '>', '/', '\\', '"', ':', ';', '?', '*', '|', '=', '`'])
+}
+
+pub trait LayerOps {
+    // the layer called `name`, created if there's none yet
+    fn add_layer(&mut self, name: &str) -> u64;
+    // a fresh "Layer1", "Layer2", ...
+    fn new_layer(&mut self) -> u64;
+    fn rename_layer(&mut self, id: u64, name: &str) -> bool;
+    fn delete_layer(&mut self, id: u64) -> bool;
+    fn layer_in_use(&self, id: u64) -> bool;
+    fn set_current_layer(&mut self, id: u64) -> bool;
+}
+
+impl LayerOps for Document {
+    fn add_layer(&mut self, name: &str) -> u64 {
+        if let Some(layer) = self.layers.find(name) {
+            return layer.id;
+        }
+
+        let id = self.next_id();
+        self.layers.insert(id, Layer::new(id, name));
+        id
+    }
+
+    fn new_layer(&mut self) -> u64 {
+        let name = (1..)
+            .map(|n| format!("Layer{}", n))
+            .find(|name| self.layers.find(name).is_none())
+            .unwrap_or_default();
+
+        self.add_layer(&name)
+    }
+
+    fn rename_layer(&mut self, id: u64, name: &str) -> bool {
+        let name = name.trim();
+        let taken = self.layers.find(name).is_some_and(|layer| layer.id != id);
+
+        if id == DEFAULT_LAYER || taken || !is_valid_name(name) {
+            return false;
+        }
+
+        match self.layers.get_mut(id) {
+            Some(layer) => {
+                layer.name = name.to_string();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn delete_layer(&mut self, id: u64) -> bool {
+        if id == DEFAULT_LAYER || id == self.current_layer || self.layer_in_use(id) {
+            return false;
+        }
+
+        self.layers.remove(id).is_some()
+    }
+
+    fn layer_in_use(&self, id: u64) -> bool {
+        self.entities.iter().any(|entity| entity.layer() == id)
+            || self.texts.iter().any(|text| text.layer == id)
+    }
+
+    fn set_current_layer(&mut self, id: u64) -> bool {
+        match self.layers.get(id) {
+            Some(layer) if !layer.frozen => {
+                self.current_layer = id;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+// Writes what the dxf crate leaves out of the LAYER table into the text it wrote: it always puts
+// 0 for the flags and can only write the default lineweight, and it doesn't know true colors.
+pub fn patch_layer_table(text: &str, layers: &EntityStore<Layer>, true_color: bool) -> String {
+    let by_name: HashMap<&str, &Layer> = layers
+        .iter()
+        .map(|layer| (layer.name.as_str(), layer))
+        .collect();
+
+    let mut patched = String::with_capacity(text.len());
+    let mut lines = text.split("\r\n");
+    let mut in_layer = false;
+    let mut layer: Option<&Layer> = None;
+
+    while let Some(code) = lines.next() {
+        let Some(mut value) = lines.next().map(str::to_string) else {
+            patched += code;
+            break;
+        };
+
+        match code.trim() {
+            "0" => {
+                in_layer = value == "LAYER";
+                layer = None;
+            }
+            "2" if in_layer => layer = by_name.get(value.as_str()).copied(),
+            "70" => {
+                if let Some(layer) = layer {
+                    value = layer.flags().to_string();
+                }
+            }
+            "370" => {
+                if let Some(layer) = layer {
+                    value = layer.lineweight.unwrap_or(LINEWEIGHT_DEFAULT).to_string();
+                }
+            }
+            _ => {}
+        }
+
+        patched += &format!("{}\r\n{}\r\n", code, value);
+
+        if let (Some(layer), "62", true) = (layer, code.trim(), true_color) {
+            if let Some(color) = layer.color.true_color() {
+                patched += &format!("420\r\n{}\r\n", color);
+            }
+        }
+    }
+
+    patched
+}
@@ -1,14 +1,22 @@
 use crate::graphics::gui_elements::ColorScheme;
 use crate::graphics::vertex::Vertex;
-use crate::{DrawLineMode, DrawingState, Mode, State};
+use crate::model::document::Document;
+use crate::model::entity::{Entity, Geometry, Property, RenderBatch, Stroke};
+use crate::model::geometry::{distance, point_segment_distance, segment_crosses_box, BoundingBox};
+use crate::model::grip::Grip;
+use crate::model::history::{Change, HistoryOps};
+use crate::model::linetype::Dashes;
+use crate::model::style::Style;
+use crate::model::transform::Transform;
+use dxf::entities::EntityType;
 use egui_wgpu::wgpu;
 
 // app line struct
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Line {
-    // pub id: u64,
+    pub id: u64,
     pub vertices: [Vertex; 2],
-    pub thickness: f32,
+    pub style: Style,
     pub selected: bool,
     pub del: bool,
     pub is_drawing: bool,
@@ -22,14 +30,21 @@ pub struct LineInstance {
     pub end: [f32; 3],
     pub color: [f32; 3],
     pub thickness: f32,
+    pub dashes: Dashes,
 }
 
 impl Line {
-    pub fn move_line(&mut self, dx: f32, dy: f32) {
-        for v in &mut self.vertices {
-            v.position[0] -= dx;
-            v.position[1] -= dy;
-        }
+    pub fn start(&self) -> [f32; 2] {
+        [self.vertices[0].position[0], self.vertices[0].position[1]]
+    }
+
+    pub fn end(&self) -> [f32; 2] {
+        [self.vertices[1].position[0], self.vertices[1].position[1]]
+    }
+
+    pub fn mid(&self) -> [f32; 2] {
+        let [start, end] = [self.start(), self.end()];
+        [(start[0] + end[0]) / 2.0, (start[1] + end[1]) / 2.0]
     }
 
     pub fn finish_line_with_length(&mut self, start_pos: [f32; 2], desired_len: f32) {
@@ -46,14 +61,127 @@ impl Line {
     }
 
     pub fn get_len(&self) -> f32 {
-        let dx = self.vertices[0].position[0] - self.vertices[1].position[0];
-        let dy = self.vertices[0].position[1] - self.vertices[1].position[1];
-        let sum = (dx * dx + dy * dy).sqrt();
+        let sum = distance(self.start(), self.end());
 
         (sum * 1000.0).round() / 1000.0
     }
 }
 
+impl Geometry for Line {
+    fn hit_distance(&self, point: [f32; 2]) -> f32 {
+        point_segment_distance(point, self.start(), self.end())
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        BoundingBox::new(self.start(), self.end())
+    }
+
+    fn crosses(&self, area: &BoundingBox) -> bool {
+        segment_crosses_box(self.start(), self.end(), area)
+    }
+
+    fn snap_points(&self) -> Vec<[f32; 2]> {
+        vec![self.start(), self.end()]
+    }
+
+    // the ends measure typed lengths from the other end, so they set the line's length
+    fn grips(&self) -> Vec<Grip> {
+        vec![
+            Grip::anchored(self.start(), self.end()),
+            Grip::anchored(self.end(), self.start()),
+            Grip::new(self.mid()),
+        ]
+    }
+
+    fn move_grip(&mut self, index: usize, position: [f32; 2]) {
+        match index {
+            0 | 1 => {
+                self.vertices[index].position = [position[0], position[1], 0.0];
+            }
+            _ => {
+                let mid = self.mid();
+                self.translate(position[0] - mid[0], position[1] - mid[1]);
+            }
+        }
+    }
+
+    fn translate(&mut self, dx: f32, dy: f32) {
+        for v in &mut self.vertices {
+            v.position[0] += dx;
+            v.position[1] += dy;
+        }
+    }
+
+    fn stretch(&mut self, area: &BoundingBox, by: [f32; 2]) {
+        for v in &mut self.vertices {
+            if area.contains_point([v.position[0], v.position[1]]) {
+                v.position[0] += by[0];
+                v.position[1] += by[1];
+            }
+        }
+    }
+
+    fn transform(&mut self, transform: &Transform) {
+        for v in &mut self.vertices {
+            let [x, y] = transform.apply([v.position[0], v.position[1]]);
+            v.position = [x, y, v.position[2]];
+        }
+    }
+
+    fn properties(&self) -> Vec<Property> {
+        vec![
+            Property::new("Thickness", self.style.thickness, 0.1, true),
+            Property::new("Length", self.get_len(), 0.1, true),
+            Property::new(
+                "Start X coordinate",
+                self.vertices[0].position[0],
+                1.0,
+                false,
+            ),
+            Property::new(
+                "Start Y coordinate",
+                self.vertices[0].position[1],
+                1.0,
+                false,
+            ),
+            Property::new("End X coordinate", self.vertices[1].position[0], 1.0, false),
+            Property::new("End Y coordinate", self.vertices[1].position[1], 1.0, false),
+        ]
+    }
+
+    fn set_property(&mut self, name: &str, value: f32) {
+        match name {
+            "Thickness" => self.style.thickness = value,
+            "Length" => self.finish_line_with_length(self.start(), value),
+            "Start X coordinate" => self.vertices[0].position[0] = value,
+            "Start Y coordinate" => self.vertices[0].position[1] = value,
+            "End X coordinate" => self.vertices[1].position[0] = value,
+            "End Y coordinate" => self.vertices[1].position[1] = value,
+            _ => {}
+        }
+    }
+
+    fn to_dxf(&self) -> EntityType {
+        let [x1, y1] = self.start();
+        let [x2, y2] = self.end();
+
+        EntityType::Line(dxf::entities::Line::new(
+            dxf::Point::new(x1 as f64, y1 as f64, 0.0),
+            dxf::Point::new(x2 as f64, y2 as f64, 0.0),
+        ))
+    }
+
+    fn push_instances(&self, batch: &mut RenderBatch, stroke: Stroke) {
+        batch.lines.push(LineInstance {
+            start: self.vertices[0].position,
+            end: self.vertices[1].position,
+            color: stroke.color,
+            thickness: stroke.thickness,
+            dashes: stroke.dashes,
+        });
+    }
+}
+
 impl LineInstance {
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
@@ -84,6 +212,34 @@ impl LineInstance {
                     shader_location: 3,
                     format: wgpu::VertexFormat::Float32,
                 },
+                // dash pattern, four elements each
+                wgpu::VertexAttribute {
+                    offset: 40,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: 56,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: 72,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                // pattern elements used
+                wgpu::VertexAttribute {
+                    offset: 88,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                // pattern offset
+                wgpu::VertexAttribute {
+                    offset: 92,
+                    shader_location: 8,
+                    format: wgpu::VertexFormat::Float32,
+                },
             ],
         }
     }
@@ -111,136 +267,94 @@ pub fn flatten_lines(lines: &mut [Line], color_scheme: ColorScheme) -> Vec<Verte
     flat
 }
 
-// flatten lines into a vec of instances
-pub fn flatten_lines_to_instances(
-    lines: &mut [Line],
-    color_scheme: ColorScheme,
-) -> Vec<LineInstance> {
-    lines
-        .iter()
-        .map(|line| LineInstance {
-            start: line.vertices[0].position,
-            end: line.vertices[1].position,
-            color: if line.selected {
-                [1.0, 0.0, 0.0]
-            } else if color_scheme == ColorScheme::Light {
-                [0.0, 0.0, 0.0]
-            } else {
-                [1.0, 1.0, 1.0]
-            },
-            thickness: line.thickness,
-        })
-        .collect()
-}
-
 pub trait LineOps {
-    fn add_line(&mut self, start: [f32; 2], end: [f32; 2], is_drawing_flag: bool);
-    fn update_line(&mut self, position: [f32; 2], is_drawing_flag: bool);
+    fn add_line(&mut self, start: [f32; 2], end: [f32; 2], is_drawing_flag: bool) -> u64;
+    fn update_line(&mut self, position: [f32; 2], ortho: bool, is_drawing_flag: bool);
+    fn finish_active_line(&mut self, start_pos: [f32; 2], length: f32);
     fn cancel_drawing_line(&mut self);
-    fn unselect_lines(&mut self);
 }
 
-// add offsets
-impl<'a> LineOps for State<'a> {
-    fn add_line(&mut self, start: [f32; 2], end: [f32; 2], is_drawing_flag: bool) {
-        // let id = self.next_line_id;
-        // self.next_line_id += 1;
-
-        self.lines.push(Line {
-            vertices: [
-                Vertex {
-                    position: [start[0], start[1], 0.0],
-                    color: [1.0, 1.0, 1.0],
-                },
-                Vertex {
-                    position: [end[0], end[1], 0.0],
-                    color: [1.0, 1.0, 1.0],
-                },
-            ],
-            // id,
-            thickness: 2.0,
-            selected: false,
-            del: false,
-            is_drawing: is_drawing_flag,
-        });
+impl LineOps for Document {
+    fn add_line(&mut self, start: [f32; 2], end: [f32; 2], is_drawing_flag: bool) -> u64 {
+        let id = self.next_id();
+
+        self.entities.insert(
+            id,
+            Entity::Line(Line {
+                vertices: [
+                    Vertex {
+                        position: [start[0], start[1], 0.0],
+                        color: [1.0, 1.0, 1.0],
+                    },
+                    Vertex {
+                        position: [end[0], end[1], 0.0],
+                        color: [1.0, 1.0, 1.0],
+                    },
+                ],
+                id,
+                style: Style::new(self.current_layer, self.settings.line_thickness),
+                selected: false,
+                del: false,
+                is_drawing: is_drawing_flag,
+            }),
+        );
 
         if is_drawing_flag {
-            let index = self.lines.len() - 1;
-            self.active_line_index = Some(index);
+            self.active_line_id = Some(id);
         }
-        // self.active_line_id = Some(id);
 
-        self.update_instance_buffer();
+        id
     }
 
-    fn update_line(&mut self, position: [f32; 2], is_drawing_flag: bool) {
-        let world_x = position[0];
-        let world_y = position[1];
+    fn update_line(&mut self, position: [f32; 2], ortho: bool, is_drawing_flag: bool) {
+        if let Some(last_line) = self
+            .active_line_id
+            .and_then(|id| self.entities.get_mut(id))
+            .and_then(Entity::as_line_mut)
+        {
+            let start = last_line.start();
 
-        if self.mode == Mode::DrawLine(DrawLineMode::Normal) {
-            if let Some(i) = self.active_line_index {
-                // println!("{:?}", self.active_line_index);
-                // println!("{:?}", self.active_line_id);
-                let last_line = &mut self.lines[i];
-                // println!("{:?}", last_line);
+            // ortho keeps whichever axis the cursor moved along the most
+            let end = if !ortho {
+                position
+            } else if (start[0] - position[0]).abs() > (start[1] - position[1]).abs() {
+                [position[0], start[1]]
+            } else {
+                [start[0], position[1]]
+            };
 
-                last_line.vertices[1] = Vertex {
-                    position: [world_x, world_y, 0.0],
-                    color: [1.0, 1.0, 1.0],
-                };
-                last_line.is_drawing = is_drawing_flag;
+            last_line.vertices[1] = Vertex {
+                position: [end[0], end[1], 0.0],
+                color: [1.0, 1.0, 1.0],
+            };
+            last_line.is_drawing = is_drawing_flag;
+            let finished_line = *last_line;
 
-                if !is_drawing_flag {
-                    // self.active_line_id = None;
-                    self.active_line_index = None;
-                }
-            }
-        } else if self.mode == Mode::DrawLine(DrawLineMode::Ortho) {
-            if let Some(i) = self.active_line_index {
-                let last_line = &mut self.lines[i];
-                let prev_vertice = last_line.vertices[0];
-
-                if (prev_vertice.position[0] - world_x).abs()
-                    > (prev_vertice.position[1] - world_y).abs()
-                {
-                    last_line.vertices[1] = Vertex {
-                        position: [world_x, prev_vertice.position[1], 0.0],
-                        color: [1.0, 1.0, 1.0],
-                    };
-                    last_line.is_drawing = is_drawing_flag;
-                } else {
-                    last_line.vertices[1] = Vertex {
-                        position: [prev_vertice.position[0], world_y, 0.0],
-                        color: [1.0, 1.0, 1.0],
-                    };
-                    last_line.is_drawing = is_drawing_flag;
-                }
-
-                if !is_drawing_flag {
-                    // self.active_line_id = None;
-                    self.active_line_index = None;
-                }
+            if !is_drawing_flag {
+                self.active_line_id = None;
+                self.record("Line", vec![Change::Add(Entity::Line(finished_line))]);
             }
         }
-
-        self.update_instance_buffer();
     }
 
-    // needs to be updated to fit for active lines index
-    fn cancel_drawing_line(&mut self) {
-        self.lines.pop();
-        self.drawing_state = DrawingState::Idle;
-        self.mode = Mode::Normal;
-        self.update_instance_buffer();
-    }
+    // finishes the line being drawn with a typed in length
+    fn finish_active_line(&mut self, start_pos: [f32; 2], length: f32) {
+        if let Some(last_line) = self
+            .active_line_id
+            .and_then(|id| self.entities.get_mut(id))
+            .and_then(Entity::as_line_mut)
+        {
+            last_line.finish_line_with_length(start_pos, length);
+            let finished_line = *last_line;
 
-    fn unselect_lines(&mut self) {
-        for line in &mut self.lines {
-            if line.selected {
-                line.selected = false;
-            }
+            self.active_line_id = None;
+            self.record("Line", vec![Change::Add(Entity::Line(finished_line))]);
         }
+    }
 
-        self.update_instance_buffer();
+    fn cancel_drawing_line(&mut self) {
+        if let Some(id) = self.active_line_id.take() {
+            self.entities.remove(id);
+        }
     }
 }
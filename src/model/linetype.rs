@@ -0,0 +1,197 @@
+use crate::model::document::Document;
+use crate::model::entity::Entity;
+use crate::model::store::EntityStore;
+
+// "Continuous" exists in every drawing and can't be deleted
+pub const CONTINUOUS: u64 = 0;
+
+// pattern elements the shaders can take, longer patterns get cut off
+pub const MAX_DASHES: usize = 12;
+
+// A dash pattern like the LTYPE table has it, lengths in drawing units: positive is a dash,
+// negative a gap and 0 a dot. An empty pattern is a solid line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Linetype {
+    pub id: u64,
+    pub name: String,
+    pub description: String,
+    pub pattern: Vec<f32>,
+}
+
+// the metric ones from acadiso.lin, every new drawing has them
+const STANDARD: [(&str, &str, &[f32]); 8] = [
+    ("DASHED", "Dashed __ __ __ __ __ __", &[12.7, -6.35]),
+    ("HIDDEN", "Hidden __ __ __ __ __ __", &[6.35, -3.175]),
+    (
+        "CENTER",
+        "Center ____ _ ____ _ ____",
+        &[31.75, -6.35, 6.35, -6.35],
+    ),
+    (
+        "PHANTOM",
+        "Phantom ______  __  __  ______",
+        &[31.75, -6.35, 6.35, -6.35, 6.35, -6.35],
+    ),
+    ("DOT", "Dot . . . . . . . . . . . .", &[0.0, -6.35]),
+    (
+        "DASHDOT",
+        "Dash dot __ . __ . __ . __",
+        &[12.7, -6.35, 0.0, -6.35],
+    ),
+    (
+        "BORDER",
+        "Border __ __ . __ __ . __ __",
+        &[12.7, -6.35, 12.7, -6.35, 0.0, -6.35],
+    ),
+    (
+        "DIVIDE",
+        "Divide ____ . . ____ . . ____",
+        &[12.7, -6.35, 0.0, -6.35, 0.0, -6.35],
+    ),
+];
+
+impl Linetype {
+    pub fn new(id: u64, name: &str, description: &str, pattern: Vec<f32>) -> Self {
+        Self {
+            id,
+            name: name.to_string(),
+            description: description.to_string(),
+            pattern,
+        }
+    }
+
+    pub fn continuous() -> Self {
+        Self::new(CONTINUOUS, "CONTINUOUS", "Solid line", Vec::new())
+    }
+
+    pub fn pattern_length(&self) -> f32 {
+        self.pattern.iter().map(|element| element.abs()).sum()
+    }
+
+    // what the shaders get, `scale` is the entity's times the drawing's
+    pub fn dashes(&self, scale: f32) -> Dashes {
+        if self.pattern_length() <= 0.0 {
+            return Dashes::SOLID;
+        }
+
+        let mut dashes = Dashes::SOLID;
+        for (slot, element) in dashes.pattern.iter_mut().zip(&self.pattern) {
+            *slot = element * scale;
+        }
+        dashes.count = self.pattern.len().min(MAX_DASHES) as u32;
+        dashes
+    }
+
+    pub fn to_dxf(&self) -> dxf::tables::LineType {
+        dxf::tables::LineType {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            // 'A', the only alignment there is
+            alignment_code: 65,
+            element_count: self.pattern.len() as i32,
+            total_pattern_length: self.pattern_length() as f64,
+            dash_dot_space_lengths: self.pattern.iter().map(|x| *x as f64).collect(),
+            ..Default::default()
+        }
+    }
+}
+
+// The linetype an entity asks for. Like with colors, ByBlock only survives on entities that
+// weren't in a block and those are drawn continuous.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EntityLinetype {
+    ByLayer,
+    ByBlock,
+    Explicit(u64),
+}
+
+impl EntityLinetype {
+    pub fn to_dxf(self, linetypes: &EntityStore<Linetype>) -> String {
+        match self {
+            EntityLinetype::ByLayer => "BYLAYER".to_string(),
+            EntityLinetype::ByBlock => "BYBLOCK".to_string(),
+            EntityLinetype::Explicit(id) => linetypes.name(id).to_string(),
+        }
+    }
+}
+
+// A dash pattern ready for the GPU, part of every line, circle and arc instance. `offset` is how
+// far into the pattern the instance starts, so it runs on across the segments of a polyline.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Dashes {
+    pub pattern: [f32; MAX_DASHES],
+    pub count: u32,
+    pub offset: f32,
+}
+
+impl Dashes {
+    pub const SOLID: Dashes = Dashes {
+        pattern: [0.0; MAX_DASHES],
+        count: 0,
+        offset: 0.0,
+    };
+
+    pub fn starting_at(self, offset: f32) -> Self {
+        Self { offset, ..self }
+    }
+}
+
+impl EntityStore<Linetype> {
+    pub fn name(&self, id: u64) -> &str {
+        self.get(id)
+            .map_or("CONTINUOUS", |linetype| linetype.name.as_str())
+    }
+
+    // names are case insensitive in DXF
+    pub fn find(&self, name: &str) -> Option<&Linetype> {
+        self.iter()
+            .find(|linetype| linetype.name.eq_ignore_ascii_case(name))
+    }
+}
+
+pub fn standard_linetypes(document: &mut Document) {
+    for (name, description, pattern) in STANDARD {
+        document.add_linetype(name, description, pattern.to_vec());
+    }
+}
+
+pub trait LinetypeOps {
+    // the linetype called `name`, created with `pattern` if there's none yet
+    fn add_linetype(&mut self, name: &str, description: &str, pattern: Vec<f32>) -> u64;
+    // the linetype an entity is drawn with once ByLayer and ByBlock are looked up
+    fn linetype_of(&self, entity: &Entity) -> u64;
+    fn dashes(&self, entity: &Entity) -> Dashes;
+}
+
+impl LinetypeOps for Document {
+    fn add_linetype(&mut self, name: &str, description: &str, pattern: Vec<f32>) -> u64 {
+        if let Some(linetype) = self.linetypes.find(name) {
+            return linetype.id;
+        }
+
+        let id = self.next_id();
+        self.linetypes
+            .insert(id, Linetype::new(id, name, description, pattern));
+        id
+    }
+
+    fn linetype_of(&self, entity: &Entity) -> u64 {
+        match entity.linetype() {
+            EntityLinetype::ByLayer => self
+                .layers
+                .of(entity.layer())
+                .map_or(CONTINUOUS, |layer| layer.linetype),
+            EntityLinetype::ByBlock => CONTINUOUS,
+            EntityLinetype::Explicit(id) => id,
+        }
+    }
+
+    fn dashes(&self, entity: &Entity) -> Dashes {
+        let scale = entity.linetype_scale() * self.linetype_scale;
+
+        self.linetypes
+            .get(self.linetype_of(entity))
+            .map_or(Dashes::SOLID, |linetype| linetype.dashes(scale))
+    }
+}
@@ -1,2 +1,31 @@
+pub mod arc;
+pub mod array;
 pub mod circle;
+pub mod color;
+pub mod document;
+pub mod ellipse;
+pub mod entity;
+pub mod fillet;
+pub mod geometry;
+pub mod grip;
+pub mod history;
+pub mod import;
+pub mod join;
+pub mod layer;
 pub mod line;
+pub mod linetype;
+pub mod offset;
+pub mod overkill;
+pub mod path;
+pub mod point;
+pub mod polyline;
+pub mod selection;
+pub mod shape;
+pub mod snap;
+pub mod spatial;
+pub mod store;
+pub mod stretch;
+pub mod style;
+pub mod text;
+pub mod transform;
+pub mod trim;
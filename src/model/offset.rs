@@ -0,0 +1,301 @@
+use crate::model::document::Document;
+use crate::model::entity::Entity;
+use crate::model::geometry::{cross, distance, dot};
+use crate::model::history::{Change, HistoryOps};
+use crate::model::path::{rebuild, Path, Piece, EPSILON};
+use crate::model::polyline::PolylineVertex;
+use std::f32::consts::{PI, TAU};
+
+pub trait OffsetOps {
+    // Adds a copy of entity `id` moved `distance` over to the side `side` is on, or through `side`
+    // itself without a distance. Polyline corners that open up get a round instead of a sharp
+    // corner with `round`. Returns the new id, None if the shape can't shrink that far.
+    fn offset(
+        &mut self,
+        id: u64,
+        distance: Option<f32>,
+        side: [f32; 2],
+        round: bool,
+    ) -> Option<u64>;
+}
+
+// the pieces of `path` moved `amount` to their left and joined up again at the corners
+fn offset_pieces(path: &Path, amount: f32, round: bool) -> Option<Vec<Piece>> {
+    // a segment between two vertices in the same spot has no sides
+    let pieces: Vec<Piece> = path
+        .pieces
+        .iter()
+        .copied()
+        .filter(|piece| !matches!(piece, Piece::Line(a, b) if distance(*a, *b) < EPSILON))
+        .collect();
+    let moved: Vec<Piece> = pieces
+        .iter()
+        .map(|piece| piece.offset(amount))
+        .collect::<Option<_>>()?;
+    if moved.is_empty() {
+        return None;
+    }
+
+    let count = moved.len();
+    let corners = if path.closed {
+        count
+    } else {
+        count.saturating_sub(1)
+    };
+    let mut joined = moved.clone();
+    // whatever fills the corner after each piece
+    let mut fillers: Vec<Option<Piece>> = vec![None; count];
+
+    for i in 0..corners {
+        let j = (i + 1) % count;
+        let (a, b) = (joined[i], joined[j]);
+        if distance(a.end(), b.start()) < EPSILON {
+            continue;
+        }
+
+        let corner = pieces[i].end();
+        // turning away from the offset side leaves a gap between the moved pieces
+        let opens_up = cross(a.direction_at(1.0), b.direction_at(0.0)) * amount < 0.0;
+        if round && opens_up {
+            let angle_of = |p: [f32; 2]| (p[1] - corner[1]).atan2(p[0] - corner[0]);
+            let (start, end) = (angle_of(a.end()), angle_of(b.start()));
+            fillers[i] = Some(Piece::Arc {
+                center: corner,
+                radius: amount.abs(),
+                start,
+                sweep: (end - start + PI).rem_euclid(TAU) - PI,
+            });
+            continue;
+        }
+
+        // both pieces run on or get cut back to where they meet closest to the corner
+        let meet = a
+            .intersections(&b)
+            .into_iter()
+            .min_by(|p, q| distance(*p, corner).total_cmp(&distance(*q, corner)));
+        let Some(point) = meet else {
+            fillers[i] = Some(Piece::Line(a.end(), b.start()));
+            continue;
+        };
+
+        // having to run on backwards means the offset folded over itself
+        let end = a.param_on(point).or_else(|| a.extension_to(point, false))?;
+        let start = b.param_on(point).or_else(|| b.extension_to(point, true))?;
+        joined[i] = a.part(0.0, end);
+        joined[j] = joined[j].part(start, 1.0);
+    }
+
+    // so does a piece that got cut back past its other end
+    let folded = joined
+        .iter()
+        .zip(&moved)
+        .any(|(piece, before)| match (piece, before) {
+            (Piece::Line(..), Piece::Line(..)) => {
+                dot(piece.direction_at(0.0), before.direction_at(0.0)) <= 0.0
+            }
+            (Piece::Arc { sweep, .. }, Piece::Arc { sweep: before, .. }) => {
+                sweep * before <= 0.0 || sweep.abs() >= TAU
+            }
+            _ => true,
+        });
+    if folded {
+        return None;
+    }
+
+    Some(
+        joined
+            .into_iter()
+            .zip(fillers)
+            .flat_map(|(piece, filler)| [Some(piece), filler])
+            .flatten()
+            .collect(),
+    )
+}
+
+impl OffsetOps for Document {
+    fn offset(
+        &mut self,
+        id: u64,
+        distance: Option<f32>,
+        side: [f32; 2],
+        round: bool,
+    ) -> Option<u64> {
+        let entity = self.entities.get(id)?.clone();
+        // the curve alongside an ellipse isn't an ellipse, so ellipses can't be offset
+        let path = Path::of(&entity)?;
+
+        // the side comes from the part of the entity closest to the click
+        let across = path
+            .pieces
+            .iter()
+            .min_by(|a, b| a.distance_to(side).total_cmp(&b.distance_to(side)))?
+            .side_of(side);
+        let amount = distance.unwrap_or(across.abs()) * across.signum();
+        if amount.abs() < EPSILON {
+            return None;
+        }
+
+        let mut offset = match &entity {
+            Entity::Circle(circle) => {
+                let mut circle = *circle;
+                circle.radius -= amount;
+                if circle.radius <= EPSILON {
+                    return None;
+                }
+                Entity::Circle(circle)
+            }
+            Entity::Polyline(polyline) => {
+                let pieces = offset_pieces(&path, amount, round)?;
+                let mut polyline = polyline.clone();
+                polyline.vertices = pieces
+                    .iter()
+                    .map(|piece| PolylineVertex::new(piece.start(), piece.bulge()))
+                    .collect();
+                if !path.closed {
+                    let end = pieces.last()?.end();
+                    polyline.vertices.push(PolylineVertex::new(end, 0.0));
+                }
+                Entity::Polyline(polyline)
+            }
+            _ => rebuild(&entity, &[path.pieces.first()?.offset(amount)?])?,
+        };
+        offset.set_selected(false);
+
+        let new_id = self.add_entity(offset);
+        let added = self.entities.get(new_id)?.clone();
+        self.record("Offset", vec![Change::Add(added)]);
+        Some(new_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::arc::{Arc, ArcOps};
+    use crate::model::circle::CircleOps;
+    use crate::model::line::LineOps;
+    use crate::model::polyline::PolylineOps;
+
+    fn near(a: [f32; 2], b: [f32; 2]) -> bool {
+        distance(a, b) < 1e-4
+    }
+
+    fn square(document: &mut Document, size: f32) -> u64 {
+        let corners = [[0.0, 0.0], [size, 0.0], [size, size], [0.0, size]];
+        let vertices = corners
+            .iter()
+            .map(|corner| PolylineVertex::new(*corner, 0.0))
+            .collect();
+        document.add_polyline(vertices, true, false)
+    }
+
+    fn vertices(document: &Document, id: u64) -> Vec<PolylineVertex> {
+        match document.entities.get(id) {
+            Some(Entity::Polyline(polyline)) => polyline.vertices.clone(),
+            other => panic!("expected a polyline, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn a_line_moves_to_the_picked_side() {
+        let mut document = Document::new();
+        let id = document.add_line([0.0, 0.0], [10.0, 0.0], false);
+
+        let above = document.offset(id, Some(2.0), [5.0, 7.0], false).unwrap();
+        let below = document.offset(id, Some(2.0), [5.0, -1.0], false).unwrap();
+
+        match (document.entities.get(above), document.entities.get(below)) {
+            (Some(Entity::Line(above)), Some(Entity::Line(below))) => {
+                assert!(near(above.start(), [0.0, 2.0]) && near(above.end(), [10.0, 2.0]));
+                assert!(near(below.start(), [0.0, -2.0]) && near(below.end(), [10.0, -2.0]));
+            }
+            other => panic!("expected two lines, got {:?}", other),
+        }
+        assert_eq!(document.undo(), Some("Offset"));
+        assert!(document.entities.get(below).is_none());
+    }
+
+    #[test]
+    fn without_a_distance_the_copy_goes_through_the_point() {
+        let mut document = Document::new();
+        let id = document.add_line([0.0, 0.0], [10.0, 0.0], false);
+
+        let new = document.offset(id, None, [4.0, 3.0], false).unwrap();
+
+        match document.entities.get(new) {
+            Some(Entity::Line(line)) => assert!(near(line.start(), [0.0, 3.0])),
+            other => panic!("expected a line, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn circles_and_arcs_change_radius() {
+        let mut document = Document::new();
+        let circle = document.add_circle([0.0, 0.0], 5.0, false, false, false);
+        let arc = document.add_arc(Arc::new([0.0, 0.0], 5.0, 0.0, PI), false);
+
+        let inside = document
+            .offset(circle, Some(2.0), [1.0, 0.0], false)
+            .unwrap();
+        let outside = document.offset(arc, Some(2.0), [0.0, 9.0], false).unwrap();
+
+        match (
+            document.entities.get(inside),
+            document.entities.get(outside),
+        ) {
+            (Some(Entity::Circle(inside)), Some(Entity::Arc(outside))) => {
+                assert!((inside.radius - 3.0).abs() < 1e-4);
+                assert!((outside.radius - 7.0).abs() < 1e-4);
+            }
+            other => panic!("expected a circle and an arc, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn shapes_too_small_to_shrink_are_left_alone() {
+        let mut document = Document::new();
+        let circle = document.add_circle([0.0, 0.0], 5.0, false, false, false);
+        let square = square(&mut document, 4.0);
+
+        assert_eq!(document.offset(circle, Some(6.0), [1.0, 0.0], false), None);
+        assert_eq!(document.offset(square, Some(3.0), [2.0, 1.0], false), None);
+        assert_eq!(document.entities.len(), 2);
+        assert!(!document.history.can_undo());
+    }
+
+    #[test]
+    fn polyline_corners_stay_sharp_or_get_rounded() {
+        let mut document = Document::new();
+        let id = square(&mut document, 10.0);
+
+        let sharp = document.offset(id, Some(1.0), [5.0, -3.0], false).unwrap();
+        let corners: Vec<[f32; 2]> = vertices(&document, sharp)
+            .iter()
+            .map(|vertex| vertex.position)
+            .collect();
+        let expected = [[-1.0, -1.0], [11.0, -1.0], [11.0, 11.0], [-1.0, 11.0]];
+        assert_eq!(corners.len(), 4);
+        for corner in expected {
+            assert!(corners.iter().any(|c| near(*c, corner)), "{:?}", corner);
+        }
+
+        let round = document.offset(id, Some(1.0), [5.0, -3.0], true).unwrap();
+        let round = vertices(&document, round);
+        // a quarter circle at every corner
+        assert_eq!(round.len(), 8);
+        assert_eq!(round.iter().filter(|vertex| vertex.bulge != 0.0).count(), 4);
+    }
+
+    #[test]
+    fn shrinking_a_polyline_cuts_the_corners_back() {
+        let mut document = Document::new();
+        let id = square(&mut document, 10.0);
+
+        let inner = document.offset(id, Some(1.0), [5.0, 5.0], true).unwrap();
+
+        let inner = vertices(&document, inner);
+        assert_eq!(inner.len(), 4);
+        assert!(inner.iter().any(|vertex| near(vertex.position, [1.0, 1.0])));
+        assert!(inner.iter().all(|vertex| vertex.bulge == 0.0));
+    }
+}
@@ -0,0 +1,315 @@
+use crate::model::document::Document;
+use crate::model::entity::{Entity, Geometry};
+use crate::model::geometry::distance;
+use crate::model::history::{Change, HistoryOps};
+use crate::model::join::merged;
+use crate::model::path::{Path, Piece};
+use crate::model::spatial::SpatialOps;
+use std::collections::HashSet;
+
+// small enough to only catch what's meant to be on top of each other
+pub const DEFAULT_OVERKILL_TOLERANCE: f32 = 1e-3;
+
+// what a cleanup took out
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct OverkillReport {
+    pub duplicates: usize,
+    // lines that went into a collinear line they overlapped
+    pub merged: usize,
+    // lines, arcs, ellipses and polylines without length, circles and arcs without radius
+    pub degenerate: usize,
+}
+
+impl OverkillReport {
+    pub fn total(&self) -> usize {
+        self.duplicates + self.merged + self.degenerate
+    }
+}
+
+pub trait OverkillOps {
+    // Removes duplicates and degenerate entities among `ids` and merges collinear lines that
+    // overlap, everything closer than `tolerance` counts as the same.
+    fn overkill(&mut self, ids: &[u64], tolerance: f32) -> OverkillReport;
+}
+
+fn degenerate(entity: &Entity, tolerance: f32) -> bool {
+    match entity {
+        Entity::Circle(circle) => circle.radius < tolerance,
+        Entity::Arc(arc) => arc.radius < tolerance || arc.length() < tolerance,
+        Entity::Ellipse(ellipse) => {
+            ellipse.major_radius() < tolerance || ellipse.length() < tolerance
+        }
+        Entity::Point(_) => false,
+        _ => Path::of(entity).is_none_or(|path| path.arc_length() < tolerance),
+    }
+}
+
+// Whether the two are the same shape on the same layer. Lines and polylines can run either way,
+// an arc runs counter-clockwise so its ends have to match as they are.
+fn duplicates(a: &Entity, b: &Entity, tolerance: f32) -> bool {
+    if a.layer() != b.layer() {
+        return false;
+    }
+    let near = |p: [f32; 2], q: [f32; 2]| distance(p, q) < tolerance;
+
+    match (a, b) {
+        (Entity::Line(a), Entity::Line(b)) => {
+            (near(a.start(), b.start()) && near(a.end(), b.end()))
+                || (near(a.start(), b.end()) && near(a.end(), b.start()))
+        }
+        (Entity::Circle(a), Entity::Circle(b)) => {
+            near(a.center(), b.center()) && (a.radius - b.radius).abs() < tolerance
+        }
+        (Entity::Arc(a), Entity::Arc(b)) => {
+            near(a.center, b.center)
+                && (a.radius - b.radius).abs() < tolerance
+                && near(a.start_point(), b.start_point())
+                && near(a.end_point(), b.end_point())
+        }
+        // the major axis can point either way, an elliptical arc's ends have to match as they are
+        (Entity::Ellipse(a), Entity::Ellipse(b)) => {
+            let [end, other_end] = [a.quadrants()[0], b.quadrants()[0]];
+            near(a.center, b.center)
+                && (near(end, other_end) || near(end, b.quadrants()[2]))
+                && (a.minor_radius() - b.minor_radius()).abs() < tolerance
+                && a.is_closed() == b.is_closed()
+                && (a.is_closed()
+                    || (near(a.start_point(), b.start_point())
+                        && near(a.end_point(), b.end_point())))
+        }
+        (Entity::Point(a), Entity::Point(b)) => near(a.position, b.position),
+        (Entity::Polyline(_), Entity::Polyline(_)) => {
+            let (Some(a), Some(b)) = (Path::of(a), Path::of(b)) else {
+                return false;
+            };
+            let same = |b: &[Piece]| {
+                a.pieces.len() == b.len()
+                    && a.pieces.iter().zip(b).all(|(p, q)| {
+                        near(p.start(), q.start())
+                            && near(p.end(), q.end())
+                            && (p.bulge() - q.bulge()).abs() < tolerance
+                    })
+            };
+            let reversed: Vec<Piece> = b.pieces.iter().rev().map(Piece::reversed).collect();
+            a.closed == b.closed && (same(&b.pieces) || (!a.closed && same(&reversed)))
+        }
+        _ => false,
+    }
+}
+
+impl OverkillOps for Document {
+    fn overkill(&mut self, ids: &[u64], tolerance: f32) -> OverkillReport {
+        let mut report = OverkillReport::default();
+        let mut changes = Vec::new();
+
+        let mut ids: Vec<u64> = ids
+            .iter()
+            .copied()
+            .filter(|id| {
+                self.entities
+                    .get(*id)
+                    .is_some_and(|entity| !entity.is_drawing())
+            })
+            .collect();
+        ids.sort_unstable();
+
+        ids.retain(|id| {
+            let Some(entity) = self.entities.get(*id) else {
+                return false;
+            };
+            if !degenerate(entity, tolerance) {
+                return true;
+            }
+            if let Some(entity) = self.entities.remove(*id) {
+                changes.push(Change::Remove(entity));
+                report.degenerate += 1;
+            }
+            false
+        });
+
+        // the one with the lowest id stays and takes in the others
+        let candidates: HashSet<u64> = ids.iter().copied().collect();
+        for id in ids {
+            // a merged line is longer and can reach lines it couldn't before
+            while let Some(kept) = self.entities.get(id).cloned() {
+                let mut grown = false;
+
+                for other_id in self.entities_in(kept.bounding_box().expanded(tolerance)) {
+                    if other_id == id || !candidates.contains(&other_id) {
+                        continue;
+                    }
+                    let Some(other) = self.entities.get(other_id).cloned() else {
+                        continue;
+                    };
+
+                    if duplicates(&kept, &other, tolerance) {
+                        self.entities.remove(other_id);
+                        changes.push(Change::Remove(other));
+                        report.duplicates += 1;
+                        continue;
+                    }
+
+                    let (Entity::Line(line), Entity::Line(overlapping)) = (&kept, &other) else {
+                        continue;
+                    };
+                    if kept.layer() != other.layer() {
+                        continue;
+                    }
+                    let covering = merged(
+                        &Piece::Line(line.start(), line.end()),
+                        &Piece::Line(overlapping.start(), overlapping.end()),
+                        tolerance,
+                        false,
+                    );
+                    let Some(Piece::Line(start, end)) = covering else {
+                        continue;
+                    };
+
+                    let mut after = *line;
+                    after.vertices[0].position = [start[0], start[1], 0.0];
+                    after.vertices[1].position = [end[0], end[1], 0.0];
+                    if let Some(entity) = self.entities.get_mut(id) {
+                        *entity = Entity::Line(after);
+                    }
+                    changes.push(Change::Modify(kept.clone(), Entity::Line(after)));
+                    self.entities.remove(other_id);
+                    changes.push(Change::Remove(other));
+                    report.merged += 1;
+                    grown = true;
+                    break;
+                }
+
+                if !grown {
+                    break;
+                }
+            }
+        }
+
+        self.record("Overkill", changes);
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::arc::{Arc, ArcOps};
+    use crate::model::circle::CircleOps;
+    use crate::model::layer::LayerOps;
+    use crate::model::line::LineOps;
+    use crate::model::polyline::{PolylineOps, PolylineVertex};
+    use std::f32::consts::PI;
+
+    fn all(document: &Document) -> Vec<u64> {
+        document.entities.ids().collect()
+    }
+
+    #[test]
+    fn duplicates_go_whichever_way_they_run() {
+        let mut document = Document::new();
+        let kept = document.add_line([0.0, 0.0], [10.0, 0.0], false);
+        document.add_line([10.0, 0.0], [0.0, 0.0], false);
+        document.add_circle([5.0, 5.0], 2.0, false, false, false);
+        document.add_circle([5.0, 5.0005], 2.0, false, false, false);
+
+        let ids = all(&document);
+        let report = document.overkill(&ids, DEFAULT_OVERKILL_TOLERANCE);
+
+        assert_eq!(report.duplicates, 2);
+        assert_eq!(document.entities.len(), 2);
+        assert!(document.entities.get(kept).is_some());
+    }
+
+    #[test]
+    fn arcs_have_to_match_end_for_end() {
+        let mut document = Document::new();
+        document.add_arc(Arc::new([0.0, 0.0], 5.0, 0.0, PI / 2.0), false);
+        document.add_arc(Arc::new([0.0, 0.0], 5.0, PI / 2.0, 0.0), false);
+
+        let ids = all(&document);
+        let report = document.overkill(&ids, DEFAULT_OVERKILL_TOLERANCE);
+
+        assert_eq!(report, OverkillReport::default());
+        assert!(!document.history.can_undo());
+    }
+
+    #[test]
+    fn overlapping_collinear_lines_merge() {
+        let mut document = Document::new();
+        let first = document.add_line([0.0, 0.0], [6.0, 0.0], false);
+        document.add_line([4.0, 0.0], [10.0, 0.0], false);
+        // only reached once the first merge made the line longer
+        document.add_line([9.0, 0.0], [15.0, 0.0], false);
+
+        let ids = all(&document);
+        let report = document.overkill(&ids, DEFAULT_OVERKILL_TOLERANCE);
+
+        assert_eq!(report.merged, 2);
+        match document.entities.get(first) {
+            Some(Entity::Line(line)) => {
+                assert!(distance(line.start(), [0.0, 0.0]) < 1e-4);
+                assert!(distance(line.end(), [15.0, 0.0]) < 1e-4);
+            }
+            other => panic!("expected a line, got {:?}", other),
+        }
+        assert_eq!(document.entities.len(), 1);
+    }
+
+    #[test]
+    fn other_layers_are_left_alone() {
+        let mut document = Document::new();
+        document.add_line([0.0, 0.0], [10.0, 0.0], false);
+        document.add_line([5.0, 0.0], [15.0, 0.0], false);
+        document.current_layer = document.add_layer("Other");
+        document.add_line([0.0, 0.0], [10.0, 0.0], false);
+
+        let ids = all(&document);
+        let report = document.overkill(&ids, DEFAULT_OVERKILL_TOLERANCE);
+
+        assert_eq!(
+            report,
+            OverkillReport {
+                merged: 1,
+                ..OverkillReport::default()
+            }
+        );
+        assert_eq!(document.entities.len(), 2);
+    }
+
+    #[test]
+    fn degenerate_entities_are_removed() {
+        let mut document = Document::new();
+        document.add_line([1.0, 1.0], [1.0, 1.0], false);
+        document.add_circle([0.0, 0.0], 0.0, false, false, false);
+        let vertices = vec![
+            PolylineVertex::new([2.0, 2.0], 0.0),
+            PolylineVertex::new([2.0, 2.0], 0.0),
+        ];
+        document.add_polyline(vertices, false, false);
+        let kept = document.add_line([0.0, 0.0], [1.0, 0.0], false);
+
+        let ids = all(&document);
+        let report = document.overkill(&ids, DEFAULT_OVERKILL_TOLERANCE);
+
+        assert_eq!(report.degenerate, 3);
+        assert_eq!(report.total(), 3);
+        assert_eq!(all(&document), vec![kept]);
+    }
+
+    #[test]
+    fn only_the_given_entities_are_cleaned_up() {
+        let mut document = Document::new();
+        let a = document.add_line([0.0, 0.0], [10.0, 0.0], false);
+        document.add_line([0.0, 0.0], [10.0, 0.0], false);
+        let c = document.add_line([0.0, 0.0], [10.0, 0.0], false);
+
+        let report = document.overkill(&[a, c], DEFAULT_OVERKILL_TOLERANCE);
+
+        assert_eq!(report.duplicates, 1);
+        assert_eq!(document.entities.len(), 2);
+        assert!(document.entities.get(c).is_none());
+
+        assert_eq!(document.undo(), Some("Overkill"));
+        assert_eq!(document.entities.len(), 3);
+    }
+}
@@ -0,0 +1,480 @@
+use crate::graphics::vertex::Vertex;
+use crate::model::arc::Arc;
+use crate::model::entity::Entity;
+use crate::model::geometry::{
+    add, along, circle_circle, cross, distance, line_circle, line_line, point_segment_distance,
+    scale, sub,
+};
+use crate::model::line::Line;
+use crate::model::polyline::{arc_from_bulge, PolylineVertex};
+use std::f32::consts::TAU;
+
+// parameters closer than this are the same spot on a curve
+pub const EPSILON: f32 = 1e-4;
+
+// how far off an arc's ends an intersection can be and still be on it, in radians
+pub const ANGLE_EPSILON: f32 = 1e-4;
+
+// A straight or round stretch of a curve. Parameter 0 is the start and 1 the end, an arc runs
+// counter-clockwise for a positive sweep, same as a polyline's bulge.
+#[derive(Copy, Clone, Debug)]
+pub enum Piece {
+    Line([f32; 2], [f32; 2]),
+    Arc {
+        center: [f32; 2],
+        radius: f32,
+        start: f32,
+        sweep: f32,
+    },
+}
+
+impl Piece {
+    pub fn between(start: PolylineVertex, end: [f32; 2]) -> Piece {
+        if start.bulge.abs() < f32::EPSILON || distance(start.position, end) < f32::EPSILON {
+            return Piece::Line(start.position, end);
+        }
+
+        let arc = arc_from_bulge(start.position, end, start.bulge);
+        let start_angle =
+            (start.position[1] - arc.center[1]).atan2(start.position[0] - arc.center[0]);
+        Piece::Arc {
+            center: arc.center,
+            radius: arc.radius,
+            start: start_angle,
+            sweep: 4.0 * start.bulge.atan(),
+        }
+    }
+
+    pub fn point_at(&self, t: f32) -> [f32; 2] {
+        match *self {
+            Piece::Line(a, b) => add(a, scale(sub(b, a), t)),
+            Piece::Arc {
+                center,
+                radius,
+                start,
+                sweep,
+            } => {
+                let angle = start + sweep * t;
+                [
+                    center[0] + radius * angle.cos(),
+                    center[1] + radius * angle.sin(),
+                ]
+            }
+        }
+    }
+
+    pub fn start(&self) -> [f32; 2] {
+        self.point_at(0.0)
+    }
+
+    // how long it is measured along the curve, parameters are spread evenly over that
+    pub fn arc_length(&self) -> f32 {
+        match *self {
+            Piece::Line(a, b) => distance(a, b),
+            Piece::Arc { radius, sweep, .. } => radius * sweep.abs(),
+        }
+    }
+
+    pub fn end(&self) -> [f32; 2] {
+        self.point_at(1.0)
+    }
+
+    // Where `point` sits along the line or circle the piece follows. Around a circle this keeps
+    // going past the end of the arc, until it's a full turn from the start.
+    pub fn param_of(&self, point: [f32; 2]) -> f32 {
+        match *self {
+            Piece::Line(a, b) => along(point, a, b).unwrap_or(0.0),
+            Piece::Arc {
+                center,
+                start,
+                sweep,
+                ..
+            } => {
+                let angle = (point[1] - center[1]).atan2(point[0] - center[0]);
+                let turned = if sweep > 0.0 {
+                    (angle - start).rem_euclid(TAU)
+                } else {
+                    (start - angle).rem_euclid(TAU)
+                };
+                turned / sweep.abs()
+            }
+        }
+    }
+
+    // the parameter of `point` if it's on the piece itself and not only on its line or circle
+    pub fn param_on(&self, point: [f32; 2]) -> Option<f32> {
+        let t = self.param_of(point);
+        match *self {
+            Piece::Line(..) => (-EPSILON..=1.0 + EPSILON)
+                .contains(&t)
+                .then(|| t.clamp(0.0, 1.0)),
+            Piece::Arc { sweep, .. } => {
+                if t * sweep.abs() <= sweep.abs() + ANGLE_EPSILON {
+                    Some(t.min(1.0))
+                } else if t * sweep.abs() >= TAU - ANGLE_EPSILON {
+                    Some(0.0)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    // the parameter `point` would get if the piece was made longer at its start (below 0) or
+    // at its end (above 1) to reach it, None if it's already on the piece or behind the other end
+    pub fn extension_to(&self, point: [f32; 2], at_start: bool) -> Option<f32> {
+        let t = self.param_of(point);
+        match *self {
+            Piece::Line(..) if at_start => (t < -EPSILON).then_some(t),
+            Piece::Line(..) => (t > 1.0 + EPSILON).then_some(t),
+            Piece::Arc { sweep, .. } => {
+                if self.param_on(point).is_some() {
+                    None
+                } else if at_start {
+                    Some(t - TAU / sweep.abs())
+                } else {
+                    Some(t)
+                }
+            }
+        }
+    }
+
+    pub fn distance_to(&self, point: [f32; 2]) -> f32 {
+        match *self {
+            Piece::Line(a, b) => point_segment_distance(point, a, b),
+            Piece::Arc { center, radius, .. } => match self.param_on(point) {
+                Some(_) => (distance(point, center) - radius).abs(),
+                None => distance(point, self.start()).min(distance(point, self.end())),
+            },
+        }
+    }
+
+    // where the piece's line or circle meets the other one's
+    pub fn intersections(&self, other: &Piece) -> Vec<[f32; 2]> {
+        match (*self, *other) {
+            (Piece::Line(a, b), Piece::Line(c, d)) => line_line((a, b), (c, d)),
+            (Piece::Line(a, b), Piece::Arc { center, radius, .. })
+            | (Piece::Arc { center, radius, .. }, Piece::Line(a, b)) => {
+                line_circle((a, b), center, radius)
+            }
+            (
+                Piece::Arc {
+                    center: a,
+                    radius: ra,
+                    ..
+                },
+                Piece::Arc {
+                    center: b,
+                    radius: rb,
+                    ..
+                },
+            ) => circle_circle((a, ra), (b, rb)),
+        }
+    }
+
+    // the stretch between two parameters, either of which can be outside of the piece
+    pub fn part(&self, t0: f32, t1: f32) -> Piece {
+        match *self {
+            Piece::Line(..) => Piece::Line(self.point_at(t0), self.point_at(t1)),
+            Piece::Arc {
+                center,
+                radius,
+                start,
+                sweep,
+            } => Piece::Arc {
+                center,
+                radius,
+                start: start + sweep * t0,
+                sweep: sweep * (t1 - t0),
+            },
+        }
+    }
+
+    // which way the piece runs at parameter `t`, not normalized
+    pub fn direction_at(&self, t: f32) -> [f32; 2] {
+        match *self {
+            Piece::Line(a, b) => sub(b, a),
+            Piece::Arc { start, sweep, .. } => {
+                let angle = start + sweep * t;
+                scale([-angle.sin(), angle.cos()], sweep)
+            }
+        }
+    }
+
+    // the piece moved `amount` over to its left, None if an arc would shrink away
+    pub fn offset(&self, amount: f32) -> Option<Piece> {
+        match *self {
+            Piece::Line(a, b) => {
+                let length = distance(a, b);
+                if length < f32::EPSILON {
+                    return None;
+                }
+                let normal = scale([a[1] - b[1], b[0] - a[0]], amount / length);
+                Some(Piece::Line(add(a, normal), add(b, normal)))
+            }
+            Piece::Arc {
+                center,
+                radius,
+                start,
+                sweep,
+            } => {
+                // the center is on the left of a counter-clockwise arc
+                let radius = radius - amount * sweep.signum();
+                (radius > EPSILON).then_some(Piece::Arc {
+                    center,
+                    radius,
+                    start,
+                    sweep,
+                })
+            }
+        }
+    }
+
+    // how far `point` is from the piece's line or circle, positive on the left
+    pub fn side_of(&self, point: [f32; 2]) -> f32 {
+        match *self {
+            Piece::Line(a, b) => {
+                let length = distance(a, b);
+                if length < f32::EPSILON {
+                    return 0.0;
+                }
+                cross(sub(b, a), sub(point, a)) / length
+            }
+            Piece::Arc {
+                center,
+                radius,
+                sweep,
+                ..
+            } => (radius - distance(center, point)) * sweep.signum(),
+        }
+    }
+
+    // the same piece running the other way
+    pub fn reversed(&self) -> Piece {
+        match *self {
+            Piece::Line(a, b) => Piece::Line(b, a),
+            Piece::Arc {
+                center,
+                radius,
+                start,
+                sweep,
+            } => Piece::Arc {
+                center,
+                radius,
+                start: start + sweep,
+                sweep: -sweep,
+            },
+        }
+    }
+
+    pub fn bulge(&self) -> f32 {
+        match *self {
+            Piece::Line(..) => 0.0,
+            Piece::Arc { sweep, .. } => (sweep / 4.0).tan(),
+        }
+    }
+}
+
+// An entity as a chain of pieces. Parameter i + t is at t along piece i, a closed path wraps
+// around from its last piece to the first one.
+pub struct Path {
+    pub pieces: Vec<Piece>,
+    pub closed: bool,
+}
+
+impl Path {
+    pub fn of(entity: &Entity) -> Option<Path> {
+        match entity {
+            Entity::Line(line) => Some(Path {
+                pieces: vec![Piece::Line(line.start(), line.end())],
+                closed: false,
+            }),
+            Entity::Circle(circle) => Some(Path {
+                pieces: vec![Piece::Arc {
+                    center: circle.center(),
+                    radius: circle.radius,
+                    start: 0.0,
+                    sweep: TAU,
+                }],
+                closed: true,
+            }),
+            Entity::Arc(arc) => Some(Path {
+                pieces: vec![Piece::Arc {
+                    center: arc.center,
+                    radius: arc.radius,
+                    start: arc.start_angle,
+                    sweep: arc.sweep(),
+                }],
+                closed: false,
+            }),
+            Entity::Polyline(polyline) => {
+                let vertices = &polyline.vertices;
+                let mut pieces: Vec<Piece> = vertices
+                    .windows(2)
+                    .map(|pair| Piece::between(pair[0], pair[1].position))
+                    .collect();
+
+                let closed = polyline.closed && vertices.len() > 2;
+                if closed {
+                    pieces.push(Piece::between(
+                        vertices[vertices.len() - 1],
+                        vertices[0].position,
+                    ));
+                }
+
+                (!pieces.is_empty()).then_some(Path { pieces, closed })
+            }
+            // not made of lines and arcs, offsetting, joining and the like leave it alone and
+            // trimming cuts an ellipse on its own
+            Entity::Ellipse(_) | Entity::Point(_) => None,
+        }
+    }
+
+    pub fn length(&self) -> f32 {
+        self.pieces.len() as f32
+    }
+
+    pub fn arc_length(&self) -> f32 {
+        self.pieces.iter().map(Piece::arc_length).sum()
+    }
+
+    // the point `walked` along the path from its start and which way the path runs there, not
+    // normalized
+    pub fn at_distance(&self, walked: f32) -> ([f32; 2], [f32; 2]) {
+        let mut left = walked.max(0.0);
+        for (index, piece) in self.pieces.iter().enumerate() {
+            let length = piece.arc_length();
+            if left <= length || index == self.pieces.len() - 1 {
+                let t = if length > 0.0 {
+                    (left / length).min(1.0)
+                } else {
+                    0.0
+                };
+                return (piece.point_at(t), piece.direction_at(t));
+            }
+            left -= length;
+        }
+        ([0.0, 0.0], [1.0, 0.0])
+    }
+
+    // parameter of the spot on the path closest to `point`
+    pub fn param_near(&self, point: [f32; 2]) -> f32 {
+        let Some((index, piece)) = self
+            .pieces
+            .iter()
+            .enumerate()
+            .min_by(|a, b| a.1.distance_to(point).total_cmp(&b.1.distance_to(point)))
+        else {
+            return 0.0;
+        };
+
+        let t = match piece.param_on(point) {
+            Some(t) => t,
+            None if distance(point, piece.start()) < distance(point, piece.end()) => 0.0,
+            None => 1.0,
+        };
+        index as f32 + t
+    }
+
+    // the pieces between two parameters, `from` below `to`
+    pub fn between(&self, from: f32, to: f32) -> Vec<Piece> {
+        self.pieces
+            .iter()
+            .enumerate()
+            .filter_map(|(index, piece)| {
+                let index = index as f32;
+                let (t0, t1) = (from.max(index), to.min(index + 1.0));
+                (t1 - t0 > EPSILON).then(|| piece.part(t0 - index, t1 - index))
+            })
+            .collect()
+    }
+}
+
+// a piece on its own as a line or an arc, styled like `style`
+pub fn piece_entity(piece: &Piece, style: &Entity) -> Entity {
+    match *piece {
+        Piece::Line(a, b) => Entity::Line(Line {
+            id: 0,
+            vertices: [a, b].map(|point| Vertex {
+                position: [point[0], point[1], 0.0],
+                color: [1.0, 1.0, 1.0],
+            }),
+            style: style.style(),
+            selected: false,
+            del: false,
+            is_drawing: false,
+        }),
+        Piece::Arc {
+            center,
+            radius,
+            start,
+            sweep,
+        } => {
+            let (from, to) = if sweep > 0.0 {
+                (start, start + sweep)
+            } else {
+                (start + sweep, start)
+            };
+            Entity::Arc(Arc {
+                style: style.style(),
+                ..Arc::new(center, radius, from, to)
+            })
+        }
+    }
+}
+
+// `original` reshaped to follow `pieces`, a circle turns into an arc and a polyline stays open
+pub fn rebuild(original: &Entity, pieces: &[Piece]) -> Option<Entity> {
+    let (first, last) = (pieces.first()?, pieces.last()?);
+
+    let mut entity = match original {
+        Entity::Line(line) => {
+            let mut line = *line;
+            for (vertex, point) in line.vertices.iter_mut().zip([first.start(), last.end()]) {
+                vertex.position = [point[0], point[1], 0.0];
+            }
+            Entity::Line(line)
+        }
+        Entity::Circle(_) | Entity::Arc(_) => {
+            let Piece::Arc {
+                center,
+                radius,
+                start,
+                ..
+            } = *first
+            else {
+                return None;
+            };
+            // a trimmed circle can come back in two pieces that meet where it started
+            let sweep: f32 = pieces
+                .iter()
+                .map(|piece| match piece {
+                    Piece::Arc { sweep, .. } => *sweep,
+                    Piece::Line(..) => 0.0,
+                })
+                .sum();
+            let (start, end) = if sweep > 0.0 {
+                (start, start + sweep)
+            } else {
+                (start + sweep, start)
+            };
+            Entity::Arc(Arc::new(center, radius, start, end))
+        }
+        Entity::Polyline(polyline) => {
+            let mut polyline = polyline.clone();
+            polyline.vertices = pieces
+                .iter()
+                .map(|piece| PolylineVertex::new(piece.start(), piece.bulge()))
+                .chain([PolylineVertex::new(last.end(), 0.0)])
+                .collect();
+            polyline.closed = false;
+            Entity::Polyline(polyline)
+        }
+        Entity::Ellipse(_) | Entity::Point(_) => return None,
+    };
+
+    entity.set_id(original.id());
+    entity.copy_style(original);
+    entity.set_selected(false);
+    Some(entity)
+}
@@ -0,0 +1,109 @@
+use crate::model::circle::CircleInstance;
+use crate::model::entity::{Geometry, Property, RenderBatch, Stroke};
+use crate::model::geometry::{distance, BoundingBox};
+use crate::model::grip::Grip;
+use crate::model::linetype::Dashes;
+use crate::model::style::Style;
+use crate::model::transform::Transform;
+use dxf::entities::{EntityType, ModelPoint};
+
+// a single marked position, drawn as a dot that keeps its size on screen
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Point {
+    pub id: u64,
+    pub position: [f32; 2],
+    pub selected: bool,
+    pub del: bool,
+    pub is_drawing: bool,
+    pub style: Style,
+}
+
+impl Point {
+    pub fn new(position: [f32; 2], thickness: f32) -> Self {
+        Self {
+            id: 0,
+            position,
+            selected: false,
+            del: false,
+            is_drawing: false,
+            style: Style::new(0, thickness),
+        }
+    }
+}
+
+impl Geometry for Point {
+    fn hit_distance(&self, point: [f32; 2]) -> f32 {
+        distance(point, self.position)
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        BoundingBox::new(self.position, self.position)
+    }
+
+    fn crosses(&self, area: &BoundingBox) -> bool {
+        area.contains_point(self.position)
+    }
+
+    fn snap_points(&self) -> Vec<[f32; 2]> {
+        vec![self.position]
+    }
+
+    fn grips(&self) -> Vec<Grip> {
+        vec![Grip::new(self.position)]
+    }
+
+    fn move_grip(&mut self, _index: usize, position: [f32; 2]) {
+        self.position = position;
+    }
+
+    fn translate(&mut self, dx: f32, dy: f32) {
+        self.position[0] += dx;
+        self.position[1] += dy;
+    }
+
+    fn stretch(&mut self, area: &BoundingBox, by: [f32; 2]) {
+        if area.contains_point(self.position) {
+            self.translate(by[0], by[1]);
+        }
+    }
+
+    fn transform(&mut self, transform: &Transform) {
+        self.position = transform.apply(self.position);
+    }
+
+    fn properties(&self) -> Vec<Property> {
+        vec![
+            Property::new("Thickness", self.style.thickness, 0.1, true),
+            Property::new("X coordinate", self.position[0], 1.0, false),
+            Property::new("Y coordinate", self.position[1], 1.0, false),
+        ]
+    }
+
+    fn set_property(&mut self, name: &str, value: f32) {
+        match name {
+            "Thickness" => self.style.thickness = value,
+            "X coordinate" => self.position[0] = value,
+            "Y coordinate" => self.position[1] = value,
+            _ => {}
+        }
+    }
+
+    fn to_dxf(&self) -> EntityType {
+        EntityType::ModelPoint(ModelPoint::new(dxf::Point::new(
+            self.position[0] as f64,
+            self.position[1] as f64,
+            0.0,
+        )))
+    }
+
+    // a ring with no radius is a filled dot as wide as the thickness
+    fn push_instances(&self, batch: &mut RenderBatch, stroke: Stroke) {
+        batch.circles.push(CircleInstance {
+            position: [self.position[0], self.position[1], 0.0],
+            color: stroke.color,
+            radius: 0.0,
+            thickness: stroke.thickness,
+            dashes: Dashes::SOLID,
+        });
+    }
+}
@@ -0,0 +1,389 @@
+use crate::model::arc::Arc;
+use crate::model::document::Document;
+use crate::model::entity::{Entity, Geometry, Property, RenderBatch, Stroke};
+use crate::model::geometry::{distance, point_segment_distance, segment_crosses_box, BoundingBox};
+use crate::model::grip::Grip;
+use crate::model::history::{Change, HistoryOps};
+use crate::model::line::LineInstance;
+use crate::model::style::Style;
+use crate::model::transform::Transform;
+use dxf::entities::{EntityType, LwPolyline};
+use dxf::LwPolylineVertex;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PolylineVertex {
+    pub position: [f32; 2],
+    // shape of the segment starting at this vertex: tan(included angle / 4), 0 is straight and
+    // positive bends counter-clockwise, same as DXF
+    pub bulge: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polyline {
+    pub id: u64,
+    pub vertices: Vec<PolylineVertex>,
+    // a closed polyline has an extra segment from the last vertex back to the first
+    pub closed: bool,
+    pub selected: bool,
+    pub del: bool,
+    pub is_drawing: bool,
+    pub style: Style,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Segment {
+    Line([f32; 2], [f32; 2]),
+    Arc(Arc),
+}
+
+impl PolylineVertex {
+    pub fn new(position: [f32; 2], bulge: f32) -> Self {
+        Self { position, bulge }
+    }
+}
+
+impl Segment {
+    fn between(start: PolylineVertex, end: [f32; 2]) -> Segment {
+        if start.bulge.abs() < f32::EPSILON || distance(start.position, end) < f32::EPSILON {
+            Segment::Line(start.position, end)
+        } else {
+            Segment::Arc(arc_from_bulge(start.position, end, start.bulge))
+        }
+    }
+
+    fn hit_distance(&self, point: [f32; 2]) -> f32 {
+        match self {
+            Segment::Line(a, b) => point_segment_distance(point, *a, *b),
+            Segment::Arc(arc) => arc.hit_distance(point),
+        }
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        match self {
+            Segment::Line(a, b) => BoundingBox::new(*a, *b),
+            Segment::Arc(arc) => arc.bounding_box(),
+        }
+    }
+
+    fn crosses(&self, area: &BoundingBox) -> bool {
+        match self {
+            Segment::Line(a, b) => segment_crosses_box(*a, *b, area),
+            Segment::Arc(arc) => arc.crosses(area),
+        }
+    }
+
+    fn mid_point(&self) -> [f32; 2] {
+        match self {
+            Segment::Line(a, b) => [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0],
+            Segment::Arc(arc) => arc.mid_point(),
+        }
+    }
+}
+
+// the arc a bulged segment from `start` to `end` follows
+pub fn arc_from_bulge(start: [f32; 2], end: [f32; 2], bulge: f32) -> Arc {
+    let chord = [end[0] - start[0], end[1] - start[1]];
+    let mid = [(start[0] + end[0]) / 2.0, (start[1] + end[1]) / 2.0];
+
+    // the center sits on the perpendicular bisector of the chord, on the left for positive bulges
+    let offset = (1.0 - bulge * bulge) / (4.0 * bulge);
+    let center = [mid[0] - chord[1] * offset, mid[1] + chord[0] * offset];
+
+    let angle_of = |p: [f32; 2]| (p[1] - center[1]).atan2(p[0] - center[0]);
+    let radius = distance(center, start);
+
+    if bulge > 0.0 {
+        Arc::new(center, radius, angle_of(start), angle_of(end))
+    } else {
+        Arc::new(center, radius, angle_of(end), angle_of(start))
+    }
+}
+
+impl Polyline {
+    pub fn segments(&self) -> Vec<Segment> {
+        let mut segments: Vec<Segment> = self
+            .vertices
+            .windows(2)
+            .map(|pair| Segment::between(pair[0], pair[1].position))
+            .collect();
+
+        if self.closed && self.vertices.len() > 2 {
+            let last = self.vertices[self.vertices.len() - 1];
+            segments.push(Segment::between(last, self.vertices[0].position));
+        }
+
+        segments
+    }
+
+    // bulge for a segment starting at vertex `index` and ending at `end` that continues the
+    // previous segment without a kink
+    pub fn tangent_bulge(&self, index: usize, end: [f32; 2]) -> f32 {
+        if index == 0 || index >= self.vertices.len() {
+            return 0.0;
+        }
+
+        let prev = self.vertices[index - 1];
+        let here = self.vertices[index].position;
+
+        // direction we arrive in: the chord of the previous segment turned by half its angle
+        let half_angle = 2.0 * prev.bulge.atan();
+        let chord_angle = (here[1] - prev.position[1]).atan2(here[0] - prev.position[0]);
+        let tangent_angle = chord_angle + half_angle;
+
+        let new_chord_angle = (end[1] - here[1]).atan2(end[0] - here[0]);
+        let turn = (new_chord_angle - tangent_angle + std::f32::consts::PI)
+            .rem_euclid(std::f32::consts::TAU)
+            - std::f32::consts::PI;
+
+        (turn / 2.0).tan()
+    }
+}
+
+impl Geometry for Polyline {
+    fn hit_distance(&self, point: [f32; 2]) -> f32 {
+        self.segments()
+            .iter()
+            .map(|segment| segment.hit_distance(point))
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        let first = self.vertices[0].position;
+
+        self.segments()
+            .iter()
+            .fold(BoundingBox::new(first, first), |bbox, segment| {
+                bbox.union(&segment.bounding_box())
+            })
+    }
+
+    fn crosses(&self, area: &BoundingBox) -> bool {
+        self.segments().iter().any(|segment| segment.crosses(area))
+    }
+
+    // every vertex and the middle of every segment
+    fn snap_points(&self) -> Vec<[f32; 2]> {
+        let mut points: Vec<[f32; 2]> = self.vertices.iter().map(|v| v.position).collect();
+        points.extend(self.segments().iter().map(|segment| segment.mid_point()));
+        points
+    }
+
+    // one on every vertex, bulges stay as they are
+    fn grips(&self) -> Vec<Grip> {
+        self.vertices
+            .iter()
+            .map(|vertex| Grip::new(vertex.position))
+            .collect()
+    }
+
+    fn move_grip(&mut self, index: usize, position: [f32; 2]) {
+        if let Some(vertex) = self.vertices.get_mut(index) {
+            vertex.position = position;
+        }
+    }
+
+    fn translate(&mut self, dx: f32, dy: f32) {
+        for vertex in &mut self.vertices {
+            vertex.position[0] += dx;
+            vertex.position[1] += dy;
+        }
+    }
+
+    // arc segments keep their bulge, so they keep how far around they go
+    fn stretch(&mut self, area: &BoundingBox, by: [f32; 2]) {
+        for vertex in &mut self.vertices {
+            if area.contains_point(vertex.position) {
+                vertex.position[0] += by[0];
+                vertex.position[1] += by[1];
+            }
+        }
+    }
+
+    fn transform(&mut self, transform: &Transform) {
+        for vertex in &mut self.vertices {
+            vertex.position = transform.apply(vertex.position);
+            // arcs bulge out to the other side once mirrored
+            if transform.flips() {
+                vertex.bulge = -vertex.bulge;
+            }
+        }
+    }
+
+    fn properties(&self) -> Vec<Property> {
+        vec![Property::new("Thickness", self.style.thickness, 0.1, true)]
+    }
+
+    fn set_property(&mut self, name: &str, value: f32) {
+        if name == "Thickness" {
+            self.style.thickness = value;
+        }
+    }
+
+    fn to_dxf(&self) -> EntityType {
+        let mut polyline = LwPolyline::default();
+        polyline.set_is_closed(self.closed);
+        polyline.vertices = self
+            .vertices
+            .iter()
+            .map(|vertex| LwPolylineVertex {
+                x: vertex.position[0] as f64,
+                y: vertex.position[1] as f64,
+                bulge: vertex.bulge as f64,
+                ..Default::default()
+            })
+            .collect();
+
+        EntityType::LwPolyline(polyline)
+    }
+
+    // the dash pattern runs on from one segment into the next instead of starting over at every
+    // vertex, imported curves are made of lots of short segments
+    fn push_instances(&self, batch: &mut RenderBatch, stroke: Stroke) {
+        let mut offset = 0.0;
+
+        for segment in self.segments() {
+            let dashes = stroke.dashes.starting_at(offset);
+            match segment {
+                Segment::Line(a, b) => {
+                    batch.lines.push(LineInstance {
+                        start: [a[0], a[1], 0.0],
+                        end: [b[0], b[1], 0.0],
+                        color: stroke.color,
+                        thickness: stroke.thickness,
+                        dashes,
+                    });
+                    offset += distance(a, b);
+                }
+                Segment::Arc(arc) => {
+                    arc.push_instances(batch, Stroke { dashes, ..stroke });
+                    offset += arc.length();
+                }
+            }
+        }
+    }
+}
+
+pub trait PolylineOps {
+    fn add_polyline(
+        &mut self,
+        vertices: Vec<PolylineVertex>,
+        closed: bool,
+        is_drawing: bool,
+    ) -> u64;
+    fn start_polyline(&mut self, position: [f32; 2]);
+    fn update_polyline(&mut self, position: [f32; 2], arc_segment: bool);
+    fn add_polyline_vertex(&mut self, position: [f32; 2], arc_segment: bool);
+    fn undo_polyline_vertex(&mut self);
+    fn finish_polyline(&mut self, close: bool);
+}
+
+// While a polyline is drawn its last vertex follows the cursor, every click pins it down and adds
+// a new one behind it.
+impl PolylineOps for Document {
+    fn add_polyline(
+        &mut self,
+        vertices: Vec<PolylineVertex>,
+        closed: bool,
+        is_drawing: bool,
+    ) -> u64 {
+        let id = self.next_id();
+
+        self.entities.insert(
+            id,
+            Entity::Polyline(Polyline {
+                id,
+                vertices,
+                closed,
+                selected: false,
+                del: false,
+                is_drawing,
+                style: Style::new(self.current_layer, self.settings.line_thickness),
+            }),
+        );
+
+        if is_drawing {
+            self.active_polyline_id = Some(id);
+        }
+
+        id
+    }
+
+    fn start_polyline(&mut self, position: [f32; 2]) {
+        let first = PolylineVertex::new(position, 0.0);
+        self.add_polyline(vec![first, first], false, true);
+    }
+
+    fn update_polyline(&mut self, position: [f32; 2], arc_segment: bool) {
+        if let Some(polyline) = self
+            .active_polyline_id
+            .and_then(|id| self.entities.get_mut(id))
+            .and_then(Entity::as_polyline_mut)
+        {
+            let n = polyline.vertices.len();
+            let bulge = if arc_segment {
+                polyline.tangent_bulge(n - 2, position)
+            } else {
+                0.0
+            };
+
+            polyline.vertices[n - 2].bulge = bulge;
+            polyline.vertices[n - 1].position = position;
+        }
+    }
+
+    fn add_polyline_vertex(&mut self, position: [f32; 2], arc_segment: bool) {
+        self.update_polyline(position, arc_segment);
+
+        if let Some(polyline) = self
+            .active_polyline_id
+            .and_then(|id| self.entities.get_mut(id))
+            .and_then(Entity::as_polyline_mut)
+        {
+            // clicking the same spot twice would leave a zero length segment behind
+            let n = polyline.vertices.len();
+            if distance(polyline.vertices[n - 2].position, position) > f32::EPSILON {
+                polyline.vertices.push(PolylineVertex::new(position, 0.0));
+            }
+        }
+    }
+
+    // takes back the last vertex that was clicked, the whole polyline once none are left
+    fn undo_polyline_vertex(&mut self) {
+        if let Some(polyline) = self
+            .active_polyline_id
+            .and_then(|id| self.entities.get_mut(id))
+            .and_then(Entity::as_polyline_mut)
+        {
+            let n = polyline.vertices.len();
+            if n > 2 {
+                polyline.vertices.remove(n - 2);
+                polyline.vertices[n - 3].bulge = 0.0;
+            } else if let Some(id) = self.active_polyline_id.take() {
+                self.entities.remove(id);
+            }
+        }
+    }
+
+    fn finish_polyline(&mut self, close: bool) {
+        if let Some(id) = self.active_polyline_id.take() {
+            if let Some(polyline) = self.entities.get_mut(id).and_then(Entity::as_polyline_mut) {
+                // the vertex stuck to the cursor isn't part of the result
+                polyline.vertices.pop();
+                if let Some(last) = polyline.vertices.last_mut() {
+                    last.bulge = 0.0;
+                }
+
+                if polyline.vertices.len() < 2 {
+                    self.entities.remove(id);
+                    return;
+                }
+
+                polyline.closed = close && polyline.vertices.len() > 2;
+                polyline.is_drawing = false;
+                let finished = polyline.clone();
+
+                self.record("Polyline", vec![Change::Add(Entity::Polyline(finished))]);
+            }
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use crate::model::document::Document;
+use crate::model::entity::{Entity, Geometry};
+use crate::model::geometry::BoundingBox;
+use crate::model::spatial::SpatialOps;
+
+// A rubber band dragged out with the left mouse button, in drawing coordinates. Dragged to the
+// right it's a window that takes what's fully inside, dragged to the left a crossing box that
+// takes anything it touches.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SelectionBox {
+    pub start: [f32; 2],
+    pub end: [f32; 2],
+}
+
+impl SelectionBox {
+    pub fn new(start: [f32; 2]) -> Self {
+        Self { start, end: start }
+    }
+
+    pub fn crossing(&self) -> bool {
+        self.end[0] < self.start[0]
+    }
+
+    pub fn area(&self) -> BoundingBox {
+        BoundingBox::new(self.start, self.end)
+    }
+}
+
+pub trait SelectionOps {
+    // what `selection` would pick from the layers that can be picked from, in draw order
+    fn entities_in_box(&mut self, selection: SelectionBox) -> Vec<u64>;
+    // texts are picked by where they're anchored, for windows and crossing boxes alike
+    fn texts_in_box(&self, selection: SelectionBox) -> Vec<u64>;
+    // returns how many entities and texts weren't selected before
+    fn select_all(&mut self) -> usize;
+    fn set_selected(&mut self, ids: &[u64], selected: bool);
+    fn set_texts_selected(&mut self, ids: &[u64], selected: bool);
+    // adds what was clicked or boxed to the selection, or takes it out again with `unselect`
+    // (shift held)
+    fn pick(&mut self, ids: &[u64], texts: &[u64], unselect: bool);
+}
+
+fn pickable(document: &Document, entity: &Entity) -> bool {
+    !entity.is_drawing() && document.layers.is_selectable(entity.layer())
+}
+
+impl SelectionOps for Document {
+    fn entities_in_box(&mut self, selection: SelectionBox) -> Vec<u64> {
+        let area = selection.area();
+
+        self.entities_in(area)
+            .into_iter()
+            .filter(|id| {
+                self.entities.get(*id).is_some_and(|entity| {
+                    pickable(self, entity)
+                        && if selection.crossing() {
+                            entity.crosses(&area)
+                        } else {
+                            area.contains(&entity.bounding_box())
+                        }
+                })
+            })
+            .collect()
+    }
+
+    fn texts_in_box(&self, selection: SelectionBox) -> Vec<u64> {
+        let area = selection.area();
+
+        self.texts
+            .iter()
+            .filter(|text| {
+                area.contains_point(text.position) && self.layers.is_selectable(text.layer)
+            })
+            .map(|text| text.id)
+            .collect()
+    }
+
+    fn select_all(&mut self) -> usize {
+        let ids: Vec<u64> = self
+            .entities
+            .iter()
+            .filter(|entity| !entity.selected() && pickable(self, entity))
+            .map(Entity::id)
+            .collect();
+
+        let texts: Vec<u64> = self
+            .texts
+            .iter()
+            .filter(|text| !text.selected && self.layers.is_selectable(text.layer))
+            .map(|text| text.id)
+            .collect();
+
+        self.set_selected(&ids, true);
+        self.set_texts_selected(&texts, true);
+        ids.len() + texts.len()
+    }
+
+    fn set_selected(&mut self, ids: &[u64], selected: bool) {
+        for id in ids {
+            if let Some(entity) = self.entities.get_flags_mut(*id) {
+                entity.set_selected(selected);
+            }
+        }
+    }
+
+    fn set_texts_selected(&mut self, ids: &[u64], selected: bool) {
+        for text in self.texts.iter_mut().filter(|text| ids.contains(&text.id)) {
+            text.selected = selected;
+        }
+    }
+
+    fn pick(&mut self, ids: &[u64], texts: &[u64], unselect: bool) {
+        self.set_selected(ids, !unselect);
+        self.set_texts_selected(texts, !unselect);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::circle::CircleOps;
+    use crate::model::layer::{LayerOps, DEFAULT_LAYER};
+    use crate::model::line::LineOps;
+    use crate::model::store::Changes;
+    use crate::model::text::Text;
+
+    fn selected(document: &Document) -> Vec<u64> {
+        document
+            .entities
+            .iter()
+            .filter(|entity| entity.selected())
+            .map(Entity::id)
+            .collect()
+    }
+
+    fn add_text(document: &mut Document, position: [f32; 2], layer: u64) -> u64 {
+        let id = document.next_id();
+        document.texts.push(Text {
+            id,
+            position,
+            contents: egui::WidgetText::from("note"),
+            rect: None,
+            editing: false,
+            selected: false,
+            annotative: false,
+            layer,
+        });
+        id
+    }
+
+    #[test]
+    fn a_window_takes_only_what_is_inside() {
+        let mut document = Document::new();
+        let inside = document.add_line([1.0, 1.0], [4.0, 1.0], false);
+        let crossing = document.add_line([4.0, 2.0], [20.0, 2.0], false);
+        document.add_circle([50.0, 50.0], 1.0, false, false, false);
+
+        // dragged to the right
+        let mut window = SelectionBox::new([0.0, 0.0]);
+        window.end = [10.0, 10.0];
+        assert!(!window.crossing());
+
+        assert_eq!(document.entities_in_box(window), vec![inside]);
+
+        // dragged to the left
+        let mut crossing_box = SelectionBox::new([10.0, 10.0]);
+        crossing_box.end = [0.0, 0.0];
+        assert!(crossing_box.crossing());
+
+        assert_eq!(
+            document.entities_in_box(crossing_box),
+            vec![inside, crossing]
+        );
+    }
+
+    #[test]
+    fn boxes_skip_locked_layers_and_entities_being_drawn() {
+        let mut document = Document::new();
+        let locked = document.add_layer("Locked");
+        document.layers.get_mut(locked).unwrap().locked = true;
+        document.current_layer = locked;
+        document.add_line([1.0, 1.0], [2.0, 1.0], false);
+        add_text(&mut document, [1.0, 1.0], locked);
+        document.current_layer = DEFAULT_LAYER;
+        document.add_line([1.0, 2.0], [2.0, 2.0], true);
+        let text = add_text(&mut document, [2.0, 2.0], DEFAULT_LAYER);
+
+        let mut window = SelectionBox::new([0.0, 0.0]);
+        window.end = [10.0, 10.0];
+
+        assert!(document.entities_in_box(window).is_empty());
+        assert_eq!(document.texts_in_box(window), vec![text]);
+    }
+
+    #[test]
+    fn picking_with_shift_takes_things_out_of_the_selection() {
+        let mut document = Document::new();
+        let a = document.add_line([0.0, 0.0], [1.0, 0.0], false);
+        let b = document.add_line([0.0, 1.0], [1.0, 1.0], false);
+        let text = add_text(&mut document, [0.0, 2.0], DEFAULT_LAYER);
+
+        document.pick(&[a, b], &[text], false);
+        assert_eq!(selected(&document), vec![a, b]);
+
+        document.pick(&[a], &[text], true);
+        assert_eq!(selected(&document), vec![b]);
+        assert!(!document.texts[0].selected);
+
+        // shift on something that isn't selected leaves it that way
+        document.pick(&[a], &[], true);
+        assert_eq!(selected(&document), vec![b]);
+    }
+
+    #[test]
+    fn select_all_skips_locked_frozen_and_hidden_layers() {
+        let mut document = Document::new();
+        let shown = document.add_line([0.0, 0.0], [1.0, 0.0], false);
+        for name in ["Locked", "Frozen", "Hidden"] {
+            let id = document.add_layer(name);
+            let layer = document.layers.get_mut(id).unwrap();
+            layer.locked = name == "Locked";
+            layer.frozen = name == "Frozen";
+            layer.visible = name != "Hidden";
+            document.current_layer = id;
+            document.add_line([0.0, 1.0], [1.0, 1.0], false);
+            add_text(&mut document, [0.0, 2.0], id);
+        }
+        let text = add_text(&mut document, [0.0, 3.0], DEFAULT_LAYER);
+
+        assert_eq!(document.select_all(), 2);
+        assert_eq!(selected(&document), vec![shown]);
+        let texts: Vec<u64> = document
+            .texts
+            .iter()
+            .filter(|text| text.selected)
+            .map(|text| text.id)
+            .collect();
+        assert_eq!(texts, vec![text]);
+
+        // nothing new the second time
+        assert_eq!(document.select_all(), 0);
+    }
+
+    #[test]
+    fn selecting_doesnt_make_the_spatial_index_catch_up() {
+        let mut document = Document::new();
+        for i in 0..10 {
+            document.add_line([0.0, i as f32], [1.0, i as f32], false);
+        }
+        document.entities.take_changes();
+
+        document.select_all();
+        document.unselect_all();
+
+        assert_eq!(document.entities.take_changes(), Changes::Ids(Vec::new()));
+    }
+}
@@ -0,0 +1,287 @@
+use crate::model::document::Document;
+use crate::model::entity::Entity;
+use crate::model::geometry::{add, distance, scale, sub};
+use crate::model::history::{Change, HistoryOps};
+use crate::model::path::EPSILON;
+use crate::model::polyline::{PolylineOps, PolylineVertex};
+use std::f32::consts::{FRAC_PI_8, PI, TAU};
+
+// more sides than this look like a circle anyway
+pub const MAX_POLYGON_SIDES: usize = 1024;
+
+// how the corners of a rectangle are drawn
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Corners {
+    Sharp,
+    // rounded with this radius
+    Fillet(f32),
+    // cut off this far along the side coming in and the side going out
+    Chamfer(f32, f32),
+}
+
+// A closed rectangle with opposite corners `a` and `b`, counter-clockwise from its lower left
+// corner. Rounded and cut corners are made smaller if the sides are too short for them.
+pub fn rectangle(a: [f32; 2], b: [f32; 2], corners: Corners) -> Vec<PolylineVertex> {
+    let (min, max) = (
+        [a[0].min(b[0]), a[1].min(b[1])],
+        [a[0].max(b[0]), a[1].max(b[1])],
+    );
+    let points = [min, [max[0], min[1]], max, [min[0], max[1]]];
+    let sides = [max[0] - min[0], max[1] - min[1]];
+
+    let (before, after, bulge) = match corners {
+        Corners::Sharp => (0.0, 0.0, 0.0),
+        Corners::Fillet(radius) => {
+            let radius = radius.min(sides[0] / 2.0).min(sides[1] / 2.0);
+            // a quarter circle, counter-clockwise like the rectangle
+            (radius, radius, FRAC_PI_8.tan())
+        }
+        Corners::Chamfer(first, second) => (first, second, 0.0),
+    };
+    if before < EPSILON && after < EPSILON {
+        return points
+            .into_iter()
+            .map(|point| PolylineVertex::new(point, 0.0))
+            .collect();
+    }
+
+    let mut vertices: Vec<PolylineVertex> = Vec::new();
+    for (i, corner) in points.iter().enumerate() {
+        let previous = points[(i + 3) % 4];
+        let next = points[(i + 1) % 4];
+        // the side coming in is upright at even corners and level at odd ones
+        let (incoming, outgoing) = (sides[(i + 1) % 2], sides[i % 2]);
+        let inset = |towards: [f32; 2], length: f32, by: f32| {
+            add(
+                *corner,
+                scale(sub(towards, *corner), by.min(length / 2.0) / length),
+            )
+        };
+
+        vertices.push(PolylineVertex::new(
+            inset(previous, incoming, before),
+            bulge,
+        ));
+        vertices.push(PolylineVertex::new(inset(next, outgoing, after), 0.0));
+    }
+
+    // corners rounded all the way meet in the middle of a side, one vertex is enough there
+    let mut i = 0;
+    while i < vertices.len() && vertices.len() > 2 {
+        let next = vertices[(i + 1) % vertices.len()];
+        if distance(vertices[i].position, next.position) < EPSILON {
+            vertices.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+    vertices
+}
+
+// A regular polygon around `center` with a corner at `through`, or the middle of a side there if
+// it's circumscribed.
+pub fn polygon(
+    center: [f32; 2],
+    through: [f32; 2],
+    sides: usize,
+    inscribed: bool,
+) -> Vec<PolylineVertex> {
+    let sides = sides.clamp(3, MAX_POLYGON_SIDES);
+    let [dx, dy] = sub(through, center);
+    let step = TAU / sides as f32;
+
+    let (radius, first) = if inscribed {
+        (distance(center, through), dy.atan2(dx))
+    } else {
+        // `through` is the middle of a side, the corners are half a step to either side of it
+        (
+            distance(center, through) / (PI / sides as f32).cos(),
+            dy.atan2(dx) - step / 2.0,
+        )
+    };
+
+    (0..sides)
+        .map(|i| {
+            let angle = first + step * i as f32;
+            PolylineVertex::new(
+                [
+                    center[0] + radius * angle.cos(),
+                    center[1] + radius * angle.sin(),
+                ],
+                0.0,
+            )
+        })
+        .collect()
+}
+
+pub trait ShapeOps {
+    // shows a closed polyline through `vertices` in place of the last preview
+    fn preview_shape(&mut self, vertices: Vec<PolylineVertex>);
+    // the preview becomes part of the drawing as `vertices`, `name` is what undo calls it
+    fn finish_shape(&mut self, name: &'static str, vertices: Vec<PolylineVertex>);
+    fn cancel_shape(&mut self);
+}
+
+impl ShapeOps for Document {
+    fn preview_shape(&mut self, vertices: Vec<PolylineVertex>) {
+        let active = self
+            .active_shape_id
+            .and_then(|id| self.entities.get_mut(id))
+            .and_then(Entity::as_polyline_mut);
+        if let Some(polyline) = active {
+            polyline.vertices = vertices;
+            return;
+        }
+
+        // not drawing it as a polyline being clicked together, that one has its own handling
+        let id = self.add_polyline(vertices, true, false);
+        if let Some(entity) = self.entities.get_mut(id) {
+            entity.set_drawing(true);
+        }
+        self.active_shape_id = Some(id);
+    }
+
+    fn finish_shape(&mut self, name: &'static str, vertices: Vec<PolylineVertex>) {
+        if vertices.len() < 3 {
+            self.cancel_shape();
+            return;
+        }
+
+        self.preview_shape(vertices);
+        let Some(entity) = self
+            .active_shape_id
+            .take()
+            .and_then(|id| self.entities.get_mut(id))
+        else {
+            return;
+        };
+        entity.set_drawing(false);
+        let finished = entity.clone();
+        self.record(name, vec![Change::Add(finished)]);
+    }
+
+    fn cancel_shape(&mut self) {
+        if let Some(id) = self.active_shape_id.take() {
+            self.entities.remove(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn near(a: [f32; 2], b: [f32; 2]) -> bool {
+        distance(a, b) < 1e-4
+    }
+
+    fn positions(vertices: &[PolylineVertex]) -> Vec<[f32; 2]> {
+        vertices.iter().map(|vertex| vertex.position).collect()
+    }
+
+    #[test]
+    fn rectangles_start_lower_left_whichever_corners_are_given() {
+        let vertices = rectangle([10.0, 5.0], [0.0, 0.0], Corners::Sharp);
+
+        assert_eq!(
+            positions(&vertices),
+            vec![[0.0, 0.0], [10.0, 0.0], [10.0, 5.0], [0.0, 5.0]]
+        );
+        assert!(vertices.iter().all(|vertex| vertex.bulge == 0.0));
+    }
+
+    #[test]
+    fn filleted_corners_are_quarter_circles() {
+        let vertices = rectangle([0.0, 0.0], [10.0, 6.0], Corners::Fillet(1.0));
+
+        assert_eq!(vertices.len(), 8);
+        assert!(near(vertices[0].position, [0.0, 1.0]));
+        assert!(near(vertices[1].position, [1.0, 0.0]));
+        assert!((vertices[0].bulge - FRAC_PI_8.tan()).abs() < 1e-6);
+        assert_eq!(vertices[1].bulge, 0.0);
+    }
+
+    #[test]
+    fn a_fillet_too_big_for_the_sides_is_made_smaller() {
+        let vertices = rectangle([0.0, 0.0], [10.0, 4.0], Corners::Fillet(5.0));
+
+        // the short sides are all round, their ends share a vertex
+        assert_eq!(vertices.len(), 6);
+        assert!(near(vertices[0].position, [0.0, 2.0]));
+        assert!(near(vertices[1].position, [2.0, 0.0]));
+    }
+
+    #[test]
+    fn chamfered_corners_cut_both_sides() {
+        let vertices = rectangle([0.0, 0.0], [10.0, 6.0], Corners::Chamfer(1.0, 2.0));
+
+        assert_eq!(vertices.len(), 8);
+        // coming in down the left side, going out along the bottom
+        assert!(near(vertices[0].position, [0.0, 1.0]));
+        assert!(near(vertices[1].position, [2.0, 0.0]));
+        assert!(vertices.iter().all(|vertex| vertex.bulge == 0.0));
+    }
+
+    #[test]
+    fn inscribed_polygons_have_a_corner_at_the_point() {
+        let vertices = polygon([0.0, 0.0], [0.0, 5.0], 4, true);
+
+        assert_eq!(vertices.len(), 4);
+        assert!(near(vertices[0].position, [0.0, 5.0]));
+        assert!(near(vertices[1].position, [-5.0, 0.0]));
+    }
+
+    #[test]
+    fn circumscribed_polygons_have_a_side_through_the_point() {
+        let vertices = polygon([0.0, 0.0], [5.0, 0.0], 4, false);
+
+        assert!(near(vertices[0].position, [5.0, -5.0]));
+        assert!(near(vertices[1].position, [5.0, 5.0]));
+    }
+
+    #[test]
+    fn polygon_sides_are_kept_in_range() {
+        assert_eq!(polygon([0.0, 0.0], [1.0, 0.0], 1, true).len(), 3);
+        let many = polygon([0.0, 0.0], [1.0, 0.0], usize::MAX, true);
+        assert_eq!(many.len(), MAX_POLYGON_SIDES);
+    }
+
+    #[test]
+    fn the_preview_follows_until_it_is_finished() {
+        let mut document = Document::new();
+
+        document.preview_shape(rectangle([0.0, 0.0], [1.0, 1.0], Corners::Sharp));
+        let id = document.active_shape_id.unwrap();
+        document.preview_shape(rectangle([0.0, 0.0], [4.0, 4.0], Corners::Sharp));
+        assert_eq!(document.entities.len(), 1);
+        assert!(document.entities.get(id).unwrap().is_drawing());
+
+        document.finish_shape("Polygon", polygon([0.0, 0.0], [3.0, 0.0], 6, true));
+
+        assert!(document.active_shape_id.is_none());
+        match document.entities.get(id) {
+            Some(Entity::Polyline(polyline)) => {
+                assert!(polyline.closed && !polyline.is_drawing);
+                assert_eq!(polyline.vertices.len(), 6);
+            }
+            other => panic!("expected a polyline, got {:?}", other),
+        }
+        assert_eq!(document.undo(), Some("Polygon"));
+        assert!(document.entities.is_empty());
+    }
+
+    #[test]
+    fn cancelling_leaves_nothing_behind() {
+        let mut document = Document::new();
+
+        document.preview_shape(rectangle([0.0, 0.0], [1.0, 1.0], Corners::Sharp));
+        document.cancel_shape();
+        assert!(document.entities.is_empty());
+
+        // too few vertices for a shape cancels it too
+        document.preview_shape(rectangle([0.0, 0.0], [1.0, 1.0], Corners::Sharp));
+        document.finish_shape("Rectangle", Vec::new());
+        assert!(document.entities.is_empty());
+        assert!(!document.history.can_undo());
+    }
+}
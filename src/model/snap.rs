@@ -0,0 +1,651 @@
+use crate::model::arc::Arc;
+use crate::model::document::Document;
+use crate::model::ellipse::Ellipse;
+use crate::model::entity::Entity;
+use crate::model::geometry::{
+    add, along, circle_circle, distance, line_circle, line_line, scale, sub,
+};
+use crate::model::polyline::Segment;
+use crate::model::spatial::SpatialOps;
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+// how far from the cursor a snap is picked up, in pixels
+pub const APERTURE: f32 = 10.0;
+
+// entities whose endpoints were hovered last, extension snaps follow them past their ends
+const MAX_TRACKED: usize = 4;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SnapKind {
+    Endpoint,
+    Midpoint,
+    Center,
+    Quadrant,
+    Intersection,
+    // from the last point onto a line or circle at a right angle
+    Perpendicular,
+    // from the last point, touching a circle or arc
+    Tangent,
+    Nearest,
+    // on the continuation of a line or arc whose endpoint was hovered before
+    Extension,
+}
+
+pub const SNAP_KINDS: [SnapKind; 9] = [
+    SnapKind::Endpoint,
+    SnapKind::Midpoint,
+    SnapKind::Center,
+    SnapKind::Quadrant,
+    SnapKind::Intersection,
+    SnapKind::Perpendicular,
+    SnapKind::Tangent,
+    SnapKind::Nearest,
+    SnapKind::Extension,
+];
+
+impl SnapKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            SnapKind::Endpoint => "Endpoint",
+            SnapKind::Midpoint => "Midpoint",
+            SnapKind::Center => "Center",
+            SnapKind::Quadrant => "Quadrant",
+            SnapKind::Intersection => "Intersection",
+            SnapKind::Perpendicular => "Perpendicular",
+            SnapKind::Tangent => "Tangent",
+            SnapKind::Nearest => "Nearest",
+            SnapKind::Extension => "Extension",
+        }
+    }
+
+    // what the status bar shows
+    pub fn abbreviation(self) -> &'static str {
+        match self {
+            SnapKind::Endpoint => "END",
+            SnapKind::Midpoint => "MID",
+            SnapKind::Center => "CEN",
+            SnapKind::Quadrant => "QUA",
+            SnapKind::Intersection => "INT",
+            SnapKind::Perpendicular => "PER",
+            SnapKind::Tangent => "TAN",
+            SnapKind::Nearest => "NEA",
+            SnapKind::Extension => "EXT",
+        }
+    }
+
+    // How much further away than a snap of a better kind this one may be and still lose, as a
+    // share of the aperture. Nearest is on the curve everywhere and only wins when nothing else
+    // is around.
+    fn penalty(self) -> f32 {
+        match self {
+            SnapKind::Endpoint | SnapKind::Midpoint => 0.0,
+            // an intersection at an endpoint is shown as the endpoint
+            SnapKind::Intersection => 0.02,
+            SnapKind::Center | SnapKind::Quadrant => 0.1,
+            SnapKind::Perpendicular | SnapKind::Tangent => 0.3,
+            SnapKind::Extension => 0.6,
+            SnapKind::Nearest => 1.0,
+        }
+    }
+
+    fn bit(self) -> u16 {
+        1 << self as u16
+    }
+
+    // the marker drawn on the snap point, as line segments in a box from -1 to 1
+    pub fn marker(self) -> Vec<[[f32; 2]; 2]> {
+        let outline = |points: &[[f32; 2]]| -> Vec<[[f32; 2]; 2]> {
+            (0..points.len())
+                .map(|i| [points[i], points[(i + 1) % points.len()]])
+                .collect()
+        };
+        let circle = |radius: f32| -> Vec<[[f32; 2]; 2]> {
+            let points: Vec<[f32; 2]> = (0..16)
+                .map(|i| {
+                    let angle = i as f32 * TAU / 16.0;
+                    [radius * angle.cos(), radius * angle.sin()]
+                })
+                .collect();
+            outline(&points)
+        };
+
+        match self {
+            SnapKind::Endpoint => outline(&[[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]]),
+            SnapKind::Midpoint => outline(&[[-1.0, -1.0], [1.0, -1.0], [0.0, 1.0]]),
+            SnapKind::Center => circle(1.0),
+            SnapKind::Quadrant => outline(&[[0.0, -1.0], [1.0, 0.0], [0.0, 1.0], [-1.0, 0.0]]),
+            SnapKind::Intersection => vec![[[-1.0, -1.0], [1.0, 1.0]], [[-1.0, 1.0], [1.0, -1.0]]],
+            SnapKind::Perpendicular => vec![
+                [[-1.0, 1.0], [-1.0, -1.0]],
+                [[-1.0, -1.0], [1.0, -1.0]],
+                [[-1.0, 0.0], [0.0, 0.0]],
+                [[0.0, 0.0], [0.0, -1.0]],
+            ],
+            SnapKind::Tangent => {
+                let mut lines = circle(0.7);
+                lines.push([[-1.0, 0.7], [1.0, 0.7]]);
+                lines
+            }
+            SnapKind::Nearest => outline(&[[-1.0, 1.0], [1.0, 1.0], [-1.0, -1.0], [1.0, -1.0]]),
+            SnapKind::Extension => vec![[[-0.6, 0.0], [0.6, 0.0]], [[0.0, -0.6], [0.0, 0.6]]],
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Snap {
+    pub point: [f32; 2],
+    pub kind: SnapKind,
+    // where the line or arc an extension snap continues ends
+    pub from: Option<[f32; 2]>,
+}
+
+impl Snap {
+    fn new(point: [f32; 2], kind: SnapKind) -> Self {
+        Self {
+            point,
+            kind,
+            from: None,
+        }
+    }
+}
+
+// which kinds of snaps are looked for, toggled in the status bar
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SnapModes {
+    pub enabled: bool,
+    bits: u16,
+}
+
+impl Default for SnapModes {
+    fn default() -> Self {
+        let bits = SNAP_KINDS
+            .iter()
+            .filter(|kind| **kind != SnapKind::Nearest)
+            .fold(0, |bits, kind| bits | kind.bit());
+
+        Self {
+            enabled: true,
+            bits,
+        }
+    }
+}
+
+impl SnapModes {
+    pub fn contains(&self, kind: SnapKind) -> bool {
+        self.bits & kind.bit() != 0
+    }
+
+    pub fn toggle(&mut self, kind: SnapKind) {
+        self.bits ^= kind.bit();
+    }
+}
+
+// the pieces of an entity snaps are found on, polylines come apart into their segments
+#[derive(Copy, Clone, Debug)]
+enum Curve {
+    Segment([f32; 2], [f32; 2]),
+    Circle([f32; 2], f32),
+    Arc(Arc),
+    Ellipse(Ellipse),
+    Node([f32; 2]),
+}
+
+fn curves(entity: &Entity) -> Vec<Curve> {
+    match entity {
+        Entity::Line(line) => {
+            let [a, b] = line.vertices.map(|v| [v.position[0], v.position[1]]);
+            vec![Curve::Segment(a, b)]
+        }
+        Entity::Circle(circle) => vec![Curve::Circle(
+            [circle.center.position[0], circle.center.position[1]],
+            circle.radius,
+        )],
+        Entity::Arc(arc) => vec![Curve::Arc(*arc)],
+        Entity::Ellipse(ellipse) => vec![Curve::Ellipse(*ellipse)],
+        Entity::Polyline(polyline) => polyline
+            .segments()
+            .into_iter()
+            .map(|segment| match segment {
+                Segment::Line(a, b) => Curve::Segment(a, b),
+                Segment::Arc(arc) => Curve::Arc(arc),
+            })
+            .collect(),
+        Entity::Point(point) => vec![Curve::Node(point.position)],
+    }
+}
+
+fn angle_of(center: [f32; 2], point: [f32; 2]) -> f32 {
+    (point[1] - center[1]).atan2(point[0] - center[0])
+}
+
+fn on_circle(center: [f32; 2], radius: f32, angle: f32) -> [f32; 2] {
+    [
+        center[0] + radius * angle.cos(),
+        center[1] + radius * angle.sin(),
+    ]
+}
+
+// a little slack, so an intersection right at the end of a segment isn't lost to rounding
+const T_EPSILON: f32 = 1e-4;
+
+impl Curve {
+    fn circle(&self) -> Option<([f32; 2], f32)> {
+        match self {
+            Curve::Circle(center, radius) => Some((*center, *radius)),
+            Curve::Arc(arc) => Some((arc.center, arc.radius)),
+            _ => None,
+        }
+    }
+
+    // whether a point known to be on the curve's line or circle is on the curve itself
+    fn contains(&self, point: [f32; 2]) -> bool {
+        match self {
+            Curve::Segment(a, b) => {
+                along(point, *a, *b).is_some_and(|t| (-T_EPSILON..=1.0 + T_EPSILON).contains(&t))
+            }
+            Curve::Circle(..) => true,
+            Curve::Arc(arc) => arc.contains_angle(angle_of(arc.center, point)),
+            // found on the pieces it's drawn with, which end where it does
+            Curve::Ellipse(_) => true,
+            Curve::Node(_) => false,
+        }
+    }
+
+    fn snaps(&self, cursor: [f32; 2], last: Option<[f32; 2]>, found: &mut Vec<Snap>) {
+        match self {
+            Curve::Segment(a, b) => {
+                found.push(Snap::new(*a, SnapKind::Endpoint));
+                found.push(Snap::new(*b, SnapKind::Endpoint));
+                found.push(Snap::new(scale(add(*a, *b), 0.5), SnapKind::Midpoint));
+
+                if let Some(t) = along(cursor, *a, *b) {
+                    let nearest = add(*a, scale(sub(*b, *a), t.clamp(0.0, 1.0)));
+                    found.push(Snap::new(nearest, SnapKind::Nearest));
+                }
+                if let Some(t) = last.and_then(|last| along(last, *a, *b)) {
+                    if (0.0..=1.0).contains(&t) {
+                        let foot = add(*a, scale(sub(*b, *a), t));
+                        found.push(Snap::new(foot, SnapKind::Perpendicular));
+                    }
+                }
+            }
+            Curve::Circle(..) | Curve::Arc(_) => {
+                let (center, radius) = self.circle().unwrap_or_default();
+                let mut on_curve = Vec::new();
+
+                if let Curve::Arc(arc) = self {
+                    found.push(Snap::new(arc.start_point(), SnapKind::Endpoint));
+                    found.push(Snap::new(arc.end_point(), SnapKind::Endpoint));
+                    found.push(Snap::new(arc.mid_point(), SnapKind::Midpoint));
+                }
+                found.push(Snap::new(center, SnapKind::Center));
+
+                for quadrant in 0..4 {
+                    let point = on_circle(center, radius, quadrant as f32 * FRAC_PI_2);
+                    on_curve.push(Snap::new(point, SnapKind::Quadrant));
+                }
+                if distance(cursor, center) > 0.0 {
+                    let point = on_circle(center, radius, angle_of(center, cursor));
+                    on_curve.push(Snap::new(point, SnapKind::Nearest));
+                }
+                if let Some(last) = last.filter(|last| distance(*last, center) > 0.0) {
+                    let towards = angle_of(center, last);
+                    for angle in [towards, towards + std::f32::consts::PI] {
+                        let point = on_circle(center, radius, angle);
+                        on_curve.push(Snap::new(point, SnapKind::Perpendicular));
+                    }
+
+                    let length = distance(last, center);
+                    if length > radius {
+                        let spread = (radius / length).acos();
+                        for angle in [towards - spread, towards + spread] {
+                            let point = on_circle(center, radius, angle);
+                            on_curve.push(Snap::new(point, SnapKind::Tangent));
+                        }
+                    }
+                }
+
+                found.extend(
+                    on_curve
+                        .into_iter()
+                        .filter(|snap| self.contains(snap.point)),
+                );
+            }
+            Curve::Ellipse(ellipse) => {
+                if !ellipse.is_closed() {
+                    found.push(Snap::new(ellipse.start_point(), SnapKind::Endpoint));
+                    found.push(Snap::new(ellipse.end_point(), SnapKind::Endpoint));
+                    found.push(Snap::new(ellipse.mid_point(), SnapKind::Midpoint));
+                }
+                found.push(Snap::new(ellipse.center, SnapKind::Center));
+
+                for quadrant in 0..4 {
+                    let t = quadrant as f32 * FRAC_PI_2;
+                    if ellipse.contains_param(t) {
+                        found.push(Snap::new(ellipse.point_at(t), SnapKind::Quadrant));
+                    }
+                }
+                let nearest = ellipse.point_at(ellipse.param_near(cursor));
+                found.push(Snap::new(nearest, SnapKind::Nearest));
+                // the closest point is where a line from the last point meets it at a right angle
+                if let Some(last) = last {
+                    let foot = ellipse.point_at(ellipse.param_near(last));
+                    found.push(Snap::new(foot, SnapKind::Perpendicular));
+                }
+            }
+            Curve::Node(point) => found.push(Snap::new(*point, SnapKind::Endpoint)),
+        }
+    }
+
+    // the point on the curve's continuation closest to the cursor, if it's past one of its ends
+    fn extension(&self, cursor: [f32; 2]) -> Option<Snap> {
+        match self {
+            Curve::Segment(a, b) => {
+                let t = along(cursor, *a, *b)?;
+                let from = if t > 1.0 {
+                    *b
+                } else if t < 0.0 {
+                    *a
+                } else {
+                    return None;
+                };
+
+                Some(Snap {
+                    point: add(*a, scale(sub(*b, *a), t)),
+                    kind: SnapKind::Extension,
+                    from: Some(from),
+                })
+            }
+            Curve::Arc(arc) => {
+                let angle = angle_of(arc.center, cursor);
+                if arc.contains_angle(angle) || distance(cursor, arc.center) == 0.0 {
+                    return None;
+                }
+
+                // the end the cursor went past
+                let past_end = (angle - arc.end_angle).rem_euclid(TAU);
+                let before_start = (arc.start_angle - angle).rem_euclid(TAU);
+                let from = if past_end < before_start {
+                    arc.end_point()
+                } else {
+                    arc.start_point()
+                };
+
+                Some(Snap {
+                    point: arc.point_at(angle),
+                    kind: SnapKind::Extension,
+                    from: Some(from),
+                })
+            }
+            Curve::Circle(..) | Curve::Ellipse(_) | Curve::Node(_) => None,
+        }
+    }
+}
+
+fn intersections(a: &Curve, b: &Curve) -> Vec<[f32; 2]> {
+    // an ellipse is cut where the straight pieces it's drawn with are
+    let pieces = |ellipse: &Ellipse| -> Vec<Curve> {
+        ellipse
+            .points()
+            .windows(2)
+            .map(|pair| Curve::Segment(pair[0], pair[1]))
+            .collect()
+    };
+    if let Curve::Ellipse(ellipse) = a {
+        return pieces(ellipse)
+            .iter()
+            .flat_map(|piece| intersections(piece, b))
+            .collect();
+    }
+    if let Curve::Ellipse(ellipse) = b {
+        return pieces(ellipse)
+            .iter()
+            .flat_map(|piece| intersections(a, piece))
+            .collect();
+    }
+
+    let points = match (a, b, a.circle(), b.circle()) {
+        (Curve::Segment(a0, a1), Curve::Segment(b0, b1), ..) => line_line((*a0, *a1), (*b0, *b1)),
+        (Curve::Segment(a0, a1), _, _, Some((center, radius)))
+        | (_, Curve::Segment(a0, a1), Some((center, radius)), _) => {
+            line_circle((*a0, *a1), center, radius)
+        }
+        (_, _, Some(a), Some(b)) => circle_circle(a, b),
+        _ => Vec::new(),
+    };
+
+    points
+        .into_iter()
+        .filter(|point| a.contains(*point) && b.contains(*point))
+        .collect()
+}
+
+// Finds the snap the cursor is closest to, in whatever kinds are turned on. Snaps closer than
+// the aperture compete on their distance plus a penalty for their kind, so an endpoint a few
+// pixels further away still beats a nearest snap right under the cursor.
+#[derive(Debug, Default)]
+pub struct SnapEngine {
+    tracked: Vec<u64>,
+}
+
+impl SnapEngine {
+    // forgets the entities extension snaps were following
+    pub fn reset(&mut self) {
+        self.tracked.clear();
+    }
+
+    // `aperture` is in drawing units, `last` is the point perpendicular and tangent snaps are
+    // measured from
+    pub fn find(
+        &mut self,
+        document: &mut Document,
+        cursor: [f32; 2],
+        aperture: f32,
+        last: Option<[f32; 2]>,
+        modes: SnapModes,
+    ) -> Option<Snap> {
+        if !modes.enabled {
+            self.reset();
+            return None;
+        }
+
+        let ids = document.entities_near(cursor, aperture);
+        let snappable =
+            |entity: &&Entity| !entity.is_drawing() && document.layers.is_shown(entity.layer());
+        let nearby: Vec<(u64, Curve)> = ids
+            .into_iter()
+            .filter_map(|id| document.entities.get(id))
+            .filter(snappable)
+            .flat_map(|entity| {
+                let id = entity.id();
+                curves(entity).into_iter().map(move |curve| (id, curve))
+            })
+            .collect();
+
+        let mut found: Vec<(u64, Snap)> = Vec::new();
+        for (id, curve) in &nearby {
+            let mut snaps = Vec::new();
+            curve.snaps(cursor, last, &mut snaps);
+            found.extend(snaps.into_iter().map(|snap| (*id, snap)));
+        }
+
+        if modes.contains(SnapKind::Intersection) {
+            for (i, (id, a)) in nearby.iter().enumerate() {
+                for (_, b) in &nearby[i + 1..] {
+                    found.extend(
+                        intersections(a, b)
+                            .into_iter()
+                            .map(|point| (*id, Snap::new(point, SnapKind::Intersection))),
+                    );
+                }
+            }
+        }
+
+        if modes.contains(SnapKind::Extension) {
+            for id in &self.tracked {
+                let Some(entity) = document.entities.get(*id).filter(snappable) else {
+                    continue;
+                };
+                found.extend(
+                    curves(entity)
+                        .iter()
+                        .filter_map(|curve| curve.extension(cursor))
+                        .map(|snap| (*id, snap)),
+                );
+            }
+        }
+
+        let score = |snap: &Snap| distance(snap.point, cursor) + snap.kind.penalty() * aperture;
+        let (id, snap) = found
+            .into_iter()
+            .filter(|(_, snap)| modes.contains(snap.kind))
+            .filter(|(_, snap)| distance(snap.point, cursor) <= aperture)
+            .min_by(|a, b| score(&a.1).total_cmp(&score(&b.1)))?;
+
+        // hovering an endpoint starts following the entity for extension snaps
+        if snap.kind == SnapKind::Endpoint {
+            self.tracked.retain(|tracked| *tracked != id);
+            self.tracked.push(id);
+            if self.tracked.len() > MAX_TRACKED {
+                self.tracked.remove(0);
+            }
+        }
+
+        Some(snap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::circle::CircleOps;
+    use crate::model::layer::LayerOps;
+    use crate::model::line::LineOps;
+
+    fn snap(document: &mut Document, cursor: [f32; 2], modes: SnapModes) -> Option<Snap> {
+        SnapEngine::default().find(document, cursor, 1.0, None, modes)
+    }
+
+    fn kind_at(document: &mut Document, cursor: [f32; 2]) -> Option<SnapKind> {
+        snap(document, cursor, SnapModes::default()).map(|snap| snap.kind)
+    }
+
+    #[test]
+    fn an_endpoint_beats_a_closer_center() {
+        let mut document = Document::new();
+        document.add_circle([0.0, 0.0], 5.0, false, false, false);
+        document.add_line([0.3, 0.0], [0.3, 10.0], false);
+
+        let found = snap(&mut document, [0.12, 0.0], SnapModes::default()).unwrap();
+
+        assert_eq!(found.kind, SnapKind::Endpoint);
+        assert_eq!(found.point, [0.3, 0.0]);
+        // right on the center it's the center after all
+        assert_eq!(kind_at(&mut document, [0.0, 0.0]), Some(SnapKind::Center));
+    }
+
+    #[test]
+    fn endpoints_and_midpoints_go_by_distance() {
+        let mut document = Document::new();
+        document.add_line([0.0, 0.0], [2.0, 0.0], false);
+
+        assert_eq!(kind_at(&mut document, [0.4, 0.1]), Some(SnapKind::Endpoint));
+        assert_eq!(kind_at(&mut document, [0.6, 0.1]), Some(SnapKind::Midpoint));
+    }
+
+    #[test]
+    fn nearest_only_wins_with_nothing_else_around() {
+        let mut document = Document::new();
+        document.add_line([0.0, 0.0], [10.0, 0.0], false);
+        let mut modes = SnapModes::default();
+
+        // off by default
+        assert_eq!(kind_at(&mut document, [3.0, 0.5]), None);
+
+        modes.toggle(SnapKind::Nearest);
+        let found = snap(&mut document, [3.0, 0.5], modes).unwrap();
+        assert_eq!((found.kind, found.point), (SnapKind::Nearest, [3.0, 0.0]));
+        let found = snap(&mut document, [0.5, 0.1], modes).unwrap();
+        assert_eq!(found.kind, SnapKind::Endpoint);
+    }
+
+    #[test]
+    fn turned_off_kinds_are_ignored() {
+        let mut document = Document::new();
+        document.add_line([0.0, 0.0], [2.0, 0.0], false);
+        let mut modes = SnapModes::default();
+
+        modes.toggle(SnapKind::Endpoint);
+        assert!(!modes.contains(SnapKind::Endpoint));
+        let found = snap(&mut document, [0.1, 0.0], modes).unwrap();
+        assert_eq!(found.kind, SnapKind::Midpoint);
+
+        modes.toggle(SnapKind::Endpoint);
+        modes.enabled = false;
+        assert_eq!(snap(&mut document, [0.1, 0.0], modes), None);
+    }
+
+    #[test]
+    fn entities_being_drawn_or_on_hidden_layers_are_skipped() {
+        let mut document = Document::new();
+        document.add_line([0.0, 0.0], [2.0, 0.0], true);
+        let frozen = document.add_layer("Frozen");
+        document.layers.get_mut(frozen).unwrap().frozen = true;
+        document.current_layer = frozen;
+        document.add_line([0.0, 0.0], [0.0, 2.0], false);
+
+        assert_eq!(kind_at(&mut document, [0.1, 0.0]), None);
+
+        // locked layers are still snapped to
+        document.layers.get_mut(frozen).unwrap().frozen = false;
+        document.layers.get_mut(frozen).unwrap().locked = true;
+        assert_eq!(kind_at(&mut document, [0.1, 0.0]), Some(SnapKind::Endpoint));
+    }
+
+    #[test]
+    fn crossings_snap_to_the_intersection() {
+        let mut document = Document::new();
+        document.add_line([-5.0, 0.0], [15.0, 2.0], false);
+        document.add_line([0.0, -5.0], [0.0, 9.0], false);
+        document.add_circle([10.0, 0.0], 3.0, false, false, false);
+        document.add_line([8.0, -5.0], [8.0, 5.0], false);
+
+        let found = snap(&mut document, [0.2, 0.7], SnapModes::default()).unwrap();
+        assert_eq!(found.kind, SnapKind::Intersection);
+        assert!(distance(found.point, [0.0, 0.5]) < 1e-4);
+
+        // a line running through a circle
+        let found = snap(&mut document, [8.1, 2.1], SnapModes::default()).unwrap();
+        assert_eq!(found.kind, SnapKind::Intersection);
+        assert!(distance(found.point, [8.0, 5.0f32.sqrt()]) < 1e-4);
+
+        let mut modes = SnapModes::default();
+        modes.toggle(SnapKind::Intersection);
+        assert_eq!(snap(&mut document, [0.2, 0.7], modes), None);
+    }
+
+    #[test]
+    fn hovered_endpoints_are_followed_past_the_end() {
+        let mut document = Document::new();
+        document.add_line([0.0, 0.0], [2.0, 0.0], false);
+        let mut engine = SnapEngine::default();
+        let modes = SnapModes::default();
+
+        let hovered = engine.find(&mut document, [2.1, 0.0], 1.0, None, modes);
+        assert_eq!(hovered.map(|snap| snap.kind), Some(SnapKind::Endpoint));
+
+        let found = engine
+            .find(&mut document, [6.0, 0.3], 1.0, None, modes)
+            .unwrap();
+        assert_eq!(found.kind, SnapKind::Extension);
+        assert_eq!(found.point, [6.0, 0.0]);
+        assert_eq!(found.from, Some([2.0, 0.0]));
+
+        engine.reset();
+        assert_eq!(
+            engine.find(&mut document, [6.0, 0.3], 1.0, None, modes),
+            None
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use crate::model::document::Document;
+use crate::model::entity::{Entity, Geometry};
+use crate::model::geometry::BoundingBox;
+use crate::model::store::{Changes, EntityStore};
+use std::collections::{BTreeSet, HashMap};
+
+// An entity wider or taller than this many cells goes on a list that every query checks instead
+// of being copied into all of them.
+const MAX_CELLS_PER_AXIS: i64 = 8;
+
+// cell size of an empty index, the first bigger batch of entities picks a better one
+const DEFAULT_CELL_SIZE: f32 = 100.0;
+
+// A uniform grid over the extents of the entities, so picking and snapping only look at what is
+// near the cursor instead of the whole drawing. It follows the entity store through the changes
+// the store keeps track of and catches up right before a query.
+#[derive(Debug)]
+pub struct SpatialIndex {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<u64>>,
+    large: BTreeSet<u64>,
+    // what every entity was filed under, needed to take it out again
+    extents: HashMap<u64, BoundingBox>,
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        Self::new(DEFAULT_CELL_SIZE)
+    }
+}
+
+// everything a query has to find an entity by: its outline and the snap points, an arc's center
+// can be well outside of the arc
+fn extent(entity: &Entity) -> BoundingBox {
+    entity
+        .snap_points()
+        .into_iter()
+        .fold(entity.bounding_box(), |extent, point| {
+            extent.union(&BoundingBox::new(point, point))
+        })
+}
+
+fn intersects(a: &BoundingBox, b: &BoundingBox) -> bool {
+    a.min[0] <= b.max[0] && a.max[0] >= b.min[0] && a.min[1] <= b.max[1] && a.max[1] >= b.min[1]
+}
+
+impl SpatialIndex {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            large: BTreeSet::new(),
+            extents: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.extents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.extents.is_empty()
+    }
+
+    // cells are about as big as an average entity, but never so small the drawing needs more
+    // than about a million of them
+    pub fn build(entities: &EntityStore<Entity>) -> Self {
+        let extents: Vec<(u64, BoundingBox)> = entities
+            .iter()
+            .map(|entity| (entity.id(), extent(entity)))
+            .filter(|(_, extent)| extent.min.iter().chain(&extent.max).all(|v| v.is_finite()))
+            .collect();
+
+        let Some(total) = extents
+            .iter()
+            .map(|(_, extent)| *extent)
+            .reduce(|a, b| a.union(&b))
+        else {
+            return Self::default();
+        };
+
+        let average = extents
+            .iter()
+            .map(|(_, e)| (e.max[0] - e.min[0]).max(e.max[1] - e.min[1]) as f64)
+            .sum::<f64>()
+            / extents.len() as f64;
+        let span = (total.max[0] - total.min[0]).max(total.max[1] - total.min[1]);
+        let cell_size = (average as f32).max(span / 1024.0);
+
+        let mut index = Self::new(if cell_size > 0.0 {
+            cell_size
+        } else {
+            DEFAULT_CELL_SIZE
+        });
+        for (id, extent) in extents {
+            index.insert(id, extent);
+        }
+        index
+    }
+
+    // brings the index up to date with everything the store changed since the last call
+    pub fn sync(&mut self, entities: &mut EntityStore<Entity>) {
+        let ids = match entities.take_changes() {
+            Changes::Ids(ids) if ids.len() <= entities.len() / 2 + 64 => ids,
+            // a rebuild is faster than this many single updates and picks a fresh cell size
+            _ => {
+                *self = Self::build(entities);
+                return;
+            }
+        };
+
+        for id in ids {
+            self.remove(id);
+            if let Some(entity) = entities.get(id) {
+                self.insert(id, extent(entity));
+            }
+        }
+
+        // too many entities outgrew their cells, the grid doesn't fit the drawing anymore
+        if self.large.len() > 64 + self.extents.len() / 16 {
+            *self = Self::build(entities);
+        }
+    }
+
+    // ids of every entity whose extent overlaps `area`, in draw order
+    pub fn query(&self, area: BoundingBox) -> Vec<u64> {
+        let mut found: Vec<u64> = self
+            .large
+            .iter()
+            .copied()
+            .filter(|id| intersects(&self.extents[id], &area))
+            .collect();
+
+        let (min_x, min_y) = self.cell_of(area.min);
+        let (max_x, max_y) = self.cell_of(area.max);
+
+        // an entity is filed in every cell it touches but only reported from the first one that's
+        // also in the area
+        let mut visit = |cell: (i32, i32), ids: &Vec<u64>| {
+            for id in ids {
+                let extent = &self.extents[id];
+                let (first_x, first_y) = self.cell_of(extent.min);
+                if cell == (first_x.max(min_x), first_y.max(min_y)) && intersects(extent, &area) {
+                    found.push(*id);
+                }
+            }
+        };
+
+        // a huge area is cheaper to check cell by cell of the ones that exist
+        let area_cells = (max_x as i64 - min_x as i64 + 1) * (max_y as i64 - min_y as i64 + 1);
+        if area_cells <= self.cells.len() as i64 {
+            for x in min_x..=max_x {
+                for y in min_y..=max_y {
+                    if let Some(ids) = self.cells.get(&(x, y)) {
+                        visit((x, y), ids);
+                    }
+                }
+            }
+        } else {
+            for (&(x, y), ids) in &self.cells {
+                if (min_x..=max_x).contains(&x) && (min_y..=max_y).contains(&y) {
+                    visit((x, y), ids);
+                }
+            }
+        }
+
+        found.sort_unstable();
+        found
+    }
+
+    fn cell_of(&self, point: [f32; 2]) -> (i32, i32) {
+        (
+            (point[0] / self.cell_size).floor() as i32,
+            (point[1] / self.cell_size).floor() as i32,
+        )
+    }
+
+    // every cell `extent` touches, None if that's too many
+    fn cells_of(&self, extent: &BoundingBox) -> Option<impl Iterator<Item = (i32, i32)>> {
+        let (min_x, min_y) = self.cell_of(extent.min);
+        let (max_x, max_y) = self.cell_of(extent.max);
+
+        if max_x as i64 - min_x as i64 >= MAX_CELLS_PER_AXIS
+            || max_y as i64 - min_y as i64 >= MAX_CELLS_PER_AXIS
+        {
+            return None;
+        }
+
+        Some((min_x..=max_x).flat_map(move |x| (min_y..=max_y).map(move |y| (x, y))))
+    }
+
+    fn insert(&mut self, id: u64, extent: BoundingBox) {
+        match self.cells_of(&extent) {
+            Some(cells) => {
+                for cell in cells {
+                    self.cells.entry(cell).or_default().push(id);
+                }
+            }
+            None => {
+                self.large.insert(id);
+            }
+        }
+        self.extents.insert(id, extent);
+    }
+
+    fn remove(&mut self, id: u64) {
+        let Some(extent) = self.extents.remove(&id) else {
+            return;
+        };
+
+        if self.large.remove(&id) {
+            return;
+        }
+
+        if let Some(cells) = self.cells_of(&extent) {
+            for cell in cells {
+                if let Some(ids) = self.cells.get_mut(&cell) {
+                    ids.retain(|filed| *filed != id);
+                    if ids.is_empty() {
+                        self.cells.remove(&cell);
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub trait SpatialOps {
+    // entities whose extent overlaps `area`, in draw order
+    fn entities_in(&mut self, area: BoundingBox) -> Vec<u64>;
+    // entities that come within `radius` of `point` (on their bounding box), in draw order
+    fn entities_near(&mut self, point: [f32; 2], radius: f32) -> Vec<u64>;
+}
+
+impl SpatialOps for Document {
+    fn entities_in(&mut self, area: BoundingBox) -> Vec<u64> {
+        self.index.sync(&mut self.entities);
+        self.index.query(area)
+    }
+
+    fn entities_near(&mut self, point: [f32; 2], radius: f32) -> Vec<u64> {
+        self.entities_in(BoundingBox::around(point, radius))
+    }
+}
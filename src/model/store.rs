@@ -0,0 +1,152 @@
+use std::collections::btree_map::{self, BTreeMap};
+use std::collections::BTreeSet;
+
+// Entities keyed by their id.
+// Ids are handed out by a counter that only ever goes up, so ordering by id is the same as ordering
+// by creation. That gives us the draw order for free and an entity restored by undo lands back in
+// its old spot.
+#[derive(Debug, Clone)]
+pub struct EntityStore<T> {
+    items: BTreeMap<u64, T>,
+    // what was added, removed or handed out mutably since the last `take_changes`, so whatever is
+    // built on top of the store (the spatial index) only has to catch up on those
+    changed: BTreeSet<u64>,
+    all_changed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Changes {
+    Ids(Vec<u64>),
+    All,
+}
+
+impl<T> Default for EntityStore<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> EntityStore<T> {
+    pub fn new() -> Self {
+        Self {
+            items: BTreeMap::new(),
+            changed: BTreeSet::new(),
+            all_changed: false,
+        }
+    }
+
+    pub fn insert(&mut self, id: u64, item: T) -> Option<T> {
+        self.changed.insert(id);
+        self.items.insert(id, item)
+    }
+
+    pub fn remove(&mut self, id: u64) -> Option<T> {
+        self.changed.insert(id);
+        self.items.remove(&id)
+    }
+
+    pub fn get(&self, id: u64) -> Option<&T> {
+        self.items.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: u64) -> Option<&mut T> {
+        self.changed.insert(id);
+        self.items.get_mut(&id)
+    }
+
+    // for flags that don't move an item (selected, del), it doesn't count as changed and the
+    // spatial index doesn't have to file it again
+    pub fn get_flags_mut(&mut self, id: u64) -> Option<&mut T> {
+        self.items.get_mut(&id)
+    }
+
+    pub fn contains(&self, id: u64) -> bool {
+        self.items.contains_key(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    // entities in draw order
+    pub fn iter(&self) -> btree_map::Values<'_, u64, T> {
+        self.items.values()
+    }
+
+    // every item counts as changed afterwards, `filter_mut` is cheaper when only a few are edited
+    pub fn iter_mut(&mut self) -> btree_map::ValuesMut<'_, u64, T> {
+        self.all_changed = true;
+        self.items.values_mut()
+    }
+
+    // the items `keep` picks, in draw order, only those count as changed
+    pub fn filter_mut(&mut self, mut keep: impl FnMut(&T) -> bool) -> Vec<&mut T> {
+        let mut picked = Vec::new();
+
+        for (id, item) in self.items.iter_mut() {
+            if keep(item) {
+                self.changed.insert(*id);
+                picked.push(item);
+            }
+        }
+
+        picked
+    }
+
+    // `filter_mut` for flag-only edits, see `get_flags_mut`
+    pub fn filter_flags_mut(&mut self, mut keep: impl FnMut(&T) -> bool) -> Vec<&mut T> {
+        self.items.values_mut().filter(|item| keep(item)).collect()
+    }
+
+    // entities created at or after `id`, in draw order
+    pub fn iter_from(&self, id: u64) -> impl Iterator<Item = &T> + '_ {
+        self.items.range(id..).map(|(_, item)| item)
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.items.keys().copied()
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&T) -> bool) {
+        let changed = &mut self.changed;
+        self.items.retain(|id, item| {
+            let kept = keep(item);
+            if !kept {
+                changed.insert(*id);
+            }
+            kept
+        });
+    }
+
+    pub fn take_changes(&mut self) -> Changes {
+        let ids = std::mem::take(&mut self.changed);
+
+        if std::mem::take(&mut self.all_changed) {
+            Changes::All
+        } else {
+            Changes::Ids(ids.into_iter().collect())
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a EntityStore<T> {
+    type Item = &'a T;
+    type IntoIter = btree_map::Values<'a, u64, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut EntityStore<T> {
+    type Item = &'a mut T;
+    type IntoIter = btree_map::ValuesMut<'a, u64, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
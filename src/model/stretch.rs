@@ -0,0 +1,247 @@
+use crate::model::document::Document;
+use crate::model::entity::{Entity, Geometry};
+use crate::model::geometry::BoundingBox;
+use crate::model::history::{Change, HistoryOps};
+use crate::model::text::Text;
+
+// The crossing window of a stretch and the selection as it was before. Every cursor move starts
+// over from the originals, same as a grip drag.
+#[derive(Clone, Debug)]
+pub struct StretchPreview {
+    pub area: BoundingBox,
+    pub originals: Vec<Entity>,
+    pub texts: Vec<Text>,
+}
+
+pub trait StretchOps {
+    // Starts stretching the selection, only what's inside `area` will move. Returns false if
+    // nothing is selected.
+    fn start_stretch(&mut self, area: BoundingBox) -> bool;
+    fn preview_stretch(&mut self, by: [f32; 2]);
+    fn finish_stretch(&mut self, by: [f32; 2]);
+    fn cancel_stretch(&mut self);
+}
+
+// selected texts were picked by their anchor being inside the window, they move as a whole
+fn stretched(preview: &StretchPreview, by: [f32; 2]) -> (Vec<Entity>, Vec<Text>) {
+    let entities = preview
+        .originals
+        .iter()
+        .map(|original| {
+            let mut entity = original.clone();
+            entity.stretch(&preview.area, by);
+            entity
+        })
+        .collect();
+    let texts = preview
+        .texts
+        .iter()
+        .map(|original| {
+            let mut text = original.clone();
+            text.position = [text.position[0] + by[0], text.position[1] + by[1]];
+            text
+        })
+        .collect();
+    (entities, texts)
+}
+
+impl StretchOps for Document {
+    fn start_stretch(&mut self, area: BoundingBox) -> bool {
+        let originals: Vec<Entity> = self
+            .entities
+            .iter()
+            .filter(|entity| entity.selected())
+            .cloned()
+            .collect();
+        let texts: Vec<Text> = self
+            .texts
+            .iter()
+            .filter(|text| text.selected)
+            .cloned()
+            .collect();
+        if originals.is_empty() && texts.is_empty() {
+            return false;
+        }
+
+        // keeps snaps and picking off the entities while they follow the cursor
+        for entity in self.entities.filter_mut(Entity::selected) {
+            entity.set_drawing(true);
+        }
+        self.active_stretch = Some(StretchPreview {
+            area,
+            originals,
+            texts,
+        });
+        true
+    }
+
+    fn preview_stretch(&mut self, by: [f32; 2]) {
+        let Some(preview) = &self.active_stretch else {
+            return;
+        };
+        let (entities, texts) = stretched(preview, by);
+
+        for mut moved in entities {
+            if let Some(entity) = self.entities.get_mut(moved.id()) {
+                moved.set_drawing(true);
+                *entity = moved;
+            }
+        }
+        for moved in texts {
+            if let Some(text) = self.texts.iter_mut().find(|text| text.id == moved.id) {
+                text.position = moved.position;
+            }
+        }
+    }
+
+    fn finish_stretch(&mut self, by: [f32; 2]) {
+        let Some(preview) = self.active_stretch.take() else {
+            return;
+        };
+        let (entities, texts) = stretched(&preview, by);
+        let mut changes = Vec::new();
+
+        for (original, mut after) in preview.originals.into_iter().zip(entities) {
+            // a circle the window only crosses doesn't move at all
+            let moved = after != original;
+            after.set_selected(false);
+            after.set_drawing(false);
+            if let Some(entity) = self.entities.get_mut(after.id()) {
+                *entity = after.clone();
+            }
+            if moved {
+                changes.push(Change::Modify(original, after));
+            }
+        }
+        for (original, mut after) in preview.texts.into_iter().zip(texts) {
+            after.selected = false;
+            if let Some(text) = self.texts.iter_mut().find(|text| text.id == after.id) {
+                *text = after.clone();
+            }
+            changes.push(Change::ModifyText(original, after));
+        }
+
+        self.unselect_all();
+        self.record("Stretch", changes);
+    }
+
+    fn cancel_stretch(&mut self) {
+        let Some(preview) = self.active_stretch.take() else {
+            return;
+        };
+
+        for original in preview.originals {
+            if let Some(entity) = self.entities.get_mut(original.id()) {
+                *entity = original;
+            }
+        }
+        for original in preview.texts {
+            if let Some(text) = self.texts.iter_mut().find(|text| text.id == original.id) {
+                *text = original;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::circle::CircleOps;
+    use crate::model::line::LineOps;
+    use crate::model::polyline::{PolylineOps, PolylineVertex};
+
+    // the right half of a 10 by 10 square, selected
+    fn square() -> (Document, u64) {
+        let mut document = Document::new();
+        let vertices = [[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0]]
+            .iter()
+            .map(|position| PolylineVertex::new(*position, 0.0))
+            .collect();
+        let id = document.add_polyline(vertices, true, false);
+        document.entities.get_mut(id).unwrap().set_selected(true);
+        (document, id)
+    }
+
+    fn right_half() -> BoundingBox {
+        BoundingBox::new([5.0, -1.0], [11.0, 11.0])
+    }
+
+    fn positions(document: &Document, id: u64) -> Vec<[f32; 2]> {
+        match document.entities.get(id) {
+            Some(Entity::Polyline(polyline)) => {
+                polyline.vertices.iter().map(|v| v.position).collect()
+            }
+            other => panic!("expected a polyline, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn only_the_vertices_inside_the_window_move() {
+        let (mut document, id) = square();
+
+        assert!(document.start_stretch(right_half()));
+        document.finish_stretch([5.0, 0.0]);
+
+        assert_eq!(
+            positions(&document, id),
+            vec![[0.0, 0.0], [15.0, 0.0], [15.0, 10.0], [0.0, 10.0]]
+        );
+        let entity = document.entities.get(id).unwrap();
+        assert!(!entity.selected() && !entity.is_drawing());
+        assert!(document.active_stretch.is_none());
+    }
+
+    #[test]
+    fn previews_start_over_from_the_originals() {
+        let (mut document, id) = square();
+
+        document.start_stretch(right_half());
+        document.preview_stretch([3.0, 0.0]);
+        document.preview_stretch([1.0, 0.0]);
+        assert_eq!(positions(&document, id)[1], [11.0, 0.0]);
+        assert!(document.entities.get(id).unwrap().is_drawing());
+
+        document.cancel_stretch();
+        assert_eq!(positions(&document, id)[1], [10.0, 0.0]);
+        assert!(!document.history.can_undo());
+    }
+
+    #[test]
+    fn nothing_selected_means_nothing_to_stretch() {
+        let mut document = Document::new();
+        document.add_line([0.0, 0.0], [10.0, 0.0], false);
+
+        assert!(!document.start_stretch(right_half()));
+        assert!(document.active_stretch.is_none());
+    }
+
+    #[test]
+    fn circles_move_with_their_center() {
+        let mut document = Document::new();
+        let inside = document.add_circle([8.0, 5.0], 4.0, true, false, false);
+        let crossed = document.add_circle([2.0, 5.0], 4.0, true, false, false);
+
+        document.start_stretch(right_half());
+        document.finish_stretch([0.0, 2.0]);
+
+        let center = |id: u64| match document.entities.get(id) {
+            Some(Entity::Circle(circle)) => circle.center.position,
+            other => panic!("expected a circle, got {:?}", other),
+        };
+        assert_eq!(center(inside), [8.0, 7.0, 0.0]);
+        assert_eq!(center(crossed), [2.0, 5.0, 0.0]);
+    }
+
+    #[test]
+    fn undo_and_redo_take_the_stretch_back_and_forth() {
+        let (mut document, id) = square();
+
+        document.start_stretch(right_half());
+        document.finish_stretch([5.0, 0.0]);
+
+        assert_eq!(document.undo(), Some("Stretch"));
+        assert_eq!(positions(&document, id)[1], [10.0, 0.0]);
+        assert_eq!(document.redo(), Some("Stretch"));
+        assert_eq!(positions(&document, id)[1], [15.0, 0.0]);
+    }
+}
@@ -0,0 +1,29 @@
+use crate::model::color::EntityColor;
+use crate::model::linetype::EntityLinetype;
+
+// What an entity is drawn with besides its shape, the same for every entity type.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Style {
+    pub layer: u64,
+    pub color: EntityColor,
+    pub thickness: f32,
+    // follows the layer's lineweight, `thickness` is only used while the layer has none
+    pub thickness_by_layer: bool,
+    pub linetype: EntityLinetype,
+    // on top of the drawing's linetype scale
+    pub linetype_scale: f32,
+}
+
+impl Style {
+    // everything ByLayer, `thickness` is what's drawn while the layer has no lineweight
+    pub fn new(layer: u64, thickness: f32) -> Self {
+        Self {
+            layer,
+            color: EntityColor::ByLayer,
+            thickness,
+            thickness_by_layer: true,
+            linetype: EntityLinetype::ByLayer,
+            linetype_scale: 1.0,
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use dxf::entities::{EntityType, MText};
+use dxf::enums::AttachmentPoint;
+use dxf::Point;
+use std::fmt;
+
+// font size texts are painted with, in world units when zoomed to 1
+pub const TEXT_HEIGHT: f32 = 14.0;
+
+#[derive(Clone)]
+pub struct Text {
+    pub id: u64,
+    pub position: [f32; 2],
+    pub contents: egui::WidgetText,
+    // where egui last painted it, used to pick texts on screen
+    pub rect: Option<egui::Rect>,
+    pub editing: bool,
+    pub selected: bool,
+    pub annotative: bool,
+    pub layer: u64,
+}
+
+impl fmt::Debug for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Text")
+            .field("contents", &self.contents.text())
+            .field("position", &self.position)
+            .finish()
+    }
+}
+
+impl Text {
+    // one line goes out as TEXT, anything longer as MTEXT
+    pub fn to_dxf(&self) -> EntityType {
+        let contents = self.contents.text();
+        let location = Point::new(self.position[0] as f64, self.position[1] as f64, 0.0);
+
+        if contents.contains('\n') {
+            EntityType::MText(MText {
+                insertion_point: location,
+                initial_text_height: TEXT_HEIGHT as f64,
+                // texts are anchored at their bottom left corner on screen too
+                attachment_point: AttachmentPoint::BottomLeft,
+                text: contents.lines().collect::<Vec<_>>().join("\\P"),
+                ..Default::default()
+            })
+        } else {
+            EntityType::Text(dxf::entities::Text {
+                location,
+                text_height: TEXT_HEIGHT as f64,
+                value: contents.to_string(),
+                ..Default::default()
+            })
+        }
+    }
+}
@@ -0,0 +1,504 @@
+use crate::model::document::Document;
+use crate::model::entity::{Entity, Geometry};
+use crate::model::geometry::{add, dot, scale, sub};
+use crate::model::history::{Change, HistoryOps};
+use crate::model::text::Text;
+use std::collections::HashSet;
+use std::f32::consts::TAU;
+
+// what Rotate, Scale, Mirror and the items of an array do to the drawing plane
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Transform {
+    Translate { by: [f32; 2] },
+    // counter-clockwise, in radians
+    Rotate { center: [f32; 2], angle: f32 },
+    Scale { center: [f32; 2], factor: f32 },
+    // across the line through `a` and `b`
+    Mirror { a: [f32; 2], b: [f32; 2] },
+}
+
+impl Transform {
+    pub fn apply(&self, point: [f32; 2]) -> [f32; 2] {
+        match *self {
+            Transform::Translate { by } => add(point, by),
+            Transform::Rotate { center, angle } => {
+                let [x, y] = sub(point, center);
+                let (sin, cos) = angle.sin_cos();
+                add(center, [x * cos - y * sin, x * sin + y * cos])
+            }
+            Transform::Scale { center, factor } => add(center, scale(sub(point, center), factor)),
+            Transform::Mirror { a, b } => {
+                let axis = sub(b, a);
+                let length = dot(axis, axis);
+                if length == 0.0 {
+                    return point;
+                }
+                let foot = add(a, scale(axis, dot(sub(point, a), axis) / length));
+                sub(scale(foot, 2.0), point)
+            }
+        }
+    }
+
+    // where a direction `angle` radians from the x axis ends up pointing
+    pub fn angle(&self, angle: f32) -> f32 {
+        let turned = match *self {
+            Transform::Rotate { angle: by, .. } => angle + by,
+            Transform::Translate { .. } | Transform::Scale { .. } => angle,
+            Transform::Mirror { a, b } => 2.0 * (b[1] - a[1]).atan2(b[0] - a[0]) - angle,
+        };
+        turned.rem_euclid(TAU)
+    }
+
+    pub fn length(&self, length: f32) -> f32 {
+        match *self {
+            Transform::Scale { factor, .. } => length * factor.abs(),
+            _ => length,
+        }
+    }
+
+    // whether it turns the drawing over, counter-clockwise becomes clockwise
+    pub fn flips(&self) -> bool {
+        matches!(self, Transform::Mirror { .. })
+    }
+}
+
+// The selection as it was when a rotate, scale or mirror started and what's shown in its place.
+// Every cursor move starts over from the originals, so rounding doesn't pile up.
+#[derive(Clone, Debug)]
+pub struct TransformPreview {
+    pub originals: Vec<Entity>,
+    pub texts: Vec<Text>,
+    // the previews are new entities and texts next to the originals, with these ids
+    pub copies: Option<(Vec<u64>, Vec<u64>)>,
+}
+
+pub trait TransformOps {
+    // Starts transforming what's selected, on copies that leave the originals where they are if
+    // `copy`. Returns false if nothing is selected.
+    fn start_transform(&mut self, copy: bool) -> bool;
+    fn preview_transform(&mut self, transform: &Transform);
+    // Applies `transform` for good. The copies of a copying transform are kept if `keep_source`,
+    // otherwise the originals take their place.
+    fn finish_transform(&mut self, name: &'static str, transform: &Transform, keep_source: bool);
+    fn cancel_transform(&mut self);
+}
+
+// the preview's entity ids, the originals' own when they're transformed in place
+fn preview_ids(preview: &TransformPreview) -> (Vec<u64>, Vec<u64>) {
+    match &preview.copies {
+        Some(copies) => copies.clone(),
+        None => (
+            preview.originals.iter().map(Entity::id).collect(),
+            preview.texts.iter().map(|text| text.id).collect(),
+        ),
+    }
+}
+
+// Texts have no angle or height of their own, so a transform only moves where they sit: they don't
+// turn with a rotate, flip with a mirror or grow with a scale, and stay readable either way.
+fn transform_text(text: &Text, transform: &Transform) -> Text {
+    let mut moved = text.clone();
+    moved.position = transform.apply(text.position);
+    moved
+}
+
+fn remove_copies(document: &mut Document, (entity_ids, text_ids): &(Vec<u64>, Vec<u64>)) {
+    let entity_ids: HashSet<u64> = entity_ids.iter().copied().collect();
+    let text_ids: HashSet<u64> = text_ids.iter().copied().collect();
+    document
+        .entities
+        .retain(|entity| !entity_ids.contains(&entity.id()));
+    document.texts.retain(|text| !text_ids.contains(&text.id));
+}
+
+impl TransformOps for Document {
+    fn start_transform(&mut self, copy: bool) -> bool {
+        let originals: Vec<Entity> = self
+            .entities
+            .iter()
+            .filter(|entity| entity.selected())
+            .cloned()
+            .collect();
+        let texts: Vec<Text> = self
+            .texts
+            .iter()
+            .filter(|text| text.selected)
+            .cloned()
+            .collect();
+        if originals.is_empty() && texts.is_empty() {
+            return false;
+        }
+
+        let copies = if copy {
+            let mut entity_ids = Vec::new();
+            for original in &originals {
+                let mut preview = original.clone();
+                preview.set_selected(false);
+                preview.set_drawing(true);
+                entity_ids.push(self.add_entity(preview));
+            }
+            let mut text_ids = Vec::new();
+            for original in &texts {
+                let mut preview = original.clone();
+                preview.id = self.next_id();
+                preview.selected = false;
+                text_ids.push(preview.id);
+                self.texts.push(preview);
+            }
+            Some((entity_ids, text_ids))
+        } else {
+            // keeps snaps and picking off the entities while they follow the cursor
+            for original in &originals {
+                if let Some(entity) = self.entities.get_mut(original.id()) {
+                    entity.set_drawing(true);
+                }
+            }
+            None
+        };
+
+        self.active_transform = Some(TransformPreview {
+            originals,
+            texts,
+            copies,
+        });
+        true
+    }
+
+    fn preview_transform(&mut self, transform: &Transform) {
+        let Some(preview) = &self.active_transform else {
+            return;
+        };
+        let (entity_ids, text_ids) = preview_ids(preview);
+
+        for (original, id) in preview.originals.iter().zip(entity_ids) {
+            if let Some(entity) = self.entities.get_mut(id) {
+                let mut moved = original.clone();
+                moved.transform(transform);
+                moved.set_id(id);
+                moved.set_selected(entity.selected());
+                moved.set_drawing(true);
+                *entity = moved;
+            }
+        }
+        for (original, id) in preview.texts.iter().zip(text_ids) {
+            if let Some(text) = self.texts.iter_mut().find(|text| text.id == id) {
+                text.position = transform_text(original, transform).position;
+            }
+        }
+    }
+
+    fn finish_transform(&mut self, name: &'static str, transform: &Transform, keep_source: bool) {
+        let Some(preview) = self.active_transform.take() else {
+            return;
+        };
+        let mut changes = Vec::new();
+
+        if let (Some((entity_ids, text_ids)), true) = (&preview.copies, keep_source) {
+            for (original, id) in preview.originals.iter().zip(entity_ids) {
+                let mut copy = original.clone();
+                copy.transform(transform);
+                copy.set_id(*id);
+                copy.set_selected(false);
+                if let Some(entity) = self.entities.get_mut(*id) {
+                    *entity = copy.clone();
+                }
+                changes.push(Change::Add(copy));
+            }
+            for (original, id) in preview.texts.iter().zip(text_ids) {
+                if let Some(text) = self.texts.iter_mut().find(|text| text.id == *id) {
+                    text.position = transform_text(original, transform).position;
+                    changes.push(Change::AddText(text.clone()));
+                }
+            }
+        } else {
+            if let Some(copies) = &preview.copies {
+                remove_copies(self, copies);
+            }
+
+            for original in preview.originals {
+                let mut after = original.clone();
+                after.transform(transform);
+                after.set_selected(false);
+                after.set_drawing(false);
+                if let Some(entity) = self.entities.get_mut(original.id()) {
+                    *entity = after.clone();
+                }
+                changes.push(Change::Modify(original, after));
+            }
+            for original in preview.texts {
+                let after = transform_text(&original, transform);
+                if let Some(text) = self.texts.iter_mut().find(|text| text.id == original.id) {
+                    *text = after.clone();
+                }
+                changes.push(Change::ModifyText(original, after));
+            }
+        }
+
+        self.unselect_all();
+        self.record(name, changes);
+    }
+
+    fn cancel_transform(&mut self) {
+        let Some(preview) = self.active_transform.take() else {
+            return;
+        };
+
+        if let Some(copies) = &preview.copies {
+            remove_copies(self, copies);
+        }
+        for original in preview.originals {
+            if let Some(entity) = self.entities.get_mut(original.id()) {
+                *entity = original;
+            }
+        }
+        for original in preview.texts {
+            if let Some(text) = self.texts.iter_mut().find(|text| text.id == original.id) {
+                *text = original;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::arc::{Arc, ArcOps};
+    use crate::model::line::LineOps;
+    use crate::model::polyline::{PolylineOps, PolylineVertex};
+    use crate::model::selection::SelectionOps;
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    fn close(a: [f32; 2], b: [f32; 2]) -> bool {
+        (a[0] - b[0]).abs() < 1e-4 && (a[1] - b[1]).abs() < 1e-4
+    }
+
+    fn line_ends(document: &Document, id: u64) -> [[f32; 2]; 2] {
+        match document.entities.get(id) {
+            Some(Entity::Line(line)) => [
+                [line.vertices[0].position[0], line.vertices[0].position[1]],
+                [line.vertices[1].position[0], line.vertices[1].position[1]],
+            ],
+            other => panic!("not a line: {:?}", other),
+        }
+    }
+
+    fn add_text(document: &mut Document, position: [f32; 2]) -> u64 {
+        let id = document.next_id();
+        document.texts.push(Text {
+            id,
+            position,
+            contents: egui::WidgetText::from("note"),
+            rect: None,
+            editing: false,
+            selected: true,
+            annotative: false,
+            layer: 0,
+        });
+        id
+    }
+
+    fn selected_line(document: &mut Document, start: [f32; 2], end: [f32; 2]) -> u64 {
+        let id = document.add_line(start, end, false);
+        document.entities.get_mut(id).unwrap().set_selected(true);
+        id
+    }
+
+    fn transform_once(document: &mut Document, name: &'static str, transform: Transform) {
+        assert!(document.start_transform(false));
+        document.preview_transform(&transform);
+        document.finish_transform(name, &transform, true);
+    }
+
+    #[test]
+    fn rotating_turns_around_the_center() {
+        let mut document = Document::new();
+        let id = selected_line(&mut document, [1.0, 0.0], [3.0, 0.0]);
+
+        let rotate = Transform::Rotate {
+            center: [1.0, 0.0],
+            angle: FRAC_PI_2,
+        };
+        transform_once(&mut document, "Rotate", rotate);
+
+        let [start, end] = line_ends(&document, id);
+        assert!(close(start, [1.0, 0.0]), "{:?}", start);
+        assert!(close(end, [1.0, 2.0]), "{:?}", end);
+        assert!(!document.entities.get(id).unwrap().selected());
+        assert!(!document.entities.get(id).unwrap().is_drawing());
+    }
+
+    #[test]
+    fn scaling_grows_away_from_the_center() {
+        let mut document = Document::new();
+        let id = document.add_arc(Arc::new([2.0, 0.0], 1.0, 0.0, PI), false);
+        document.entities.get_mut(id).unwrap().set_selected(true);
+
+        let scale = Transform::Scale {
+            center: [0.0, 0.0],
+            factor: 3.0,
+        };
+        transform_once(&mut document, "Scale", scale);
+
+        let Some(Entity::Arc(arc)) = document.entities.get(id) else {
+            panic!("not an arc");
+        };
+        assert!(close(arc.center, [6.0, 0.0]));
+        assert!((arc.radius - 3.0).abs() < 1e-4);
+        assert!((arc.start_angle - 0.0).abs() < 1e-4 && (arc.end_angle - PI).abs() < 1e-4);
+    }
+
+    #[test]
+    fn mirroring_flips_arcs_and_bulges() {
+        let mut document = Document::new();
+        // the top half of a circle, mirrored across the x axis it's the bottom half
+        let arc = document.add_arc(Arc::new([0.0, 0.0], 1.0, 0.0, PI), false);
+        let polyline = document.add_polyline(
+            vec![
+                PolylineVertex {
+                    position: [0.0, 1.0],
+                    bulge: 0.5,
+                },
+                PolylineVertex {
+                    position: [2.0, 1.0],
+                    bulge: 0.0,
+                },
+            ],
+            false,
+            false,
+        );
+        document.select_all();
+
+        let mirror = Transform::Mirror {
+            a: [0.0, 0.0],
+            b: [1.0, 0.0],
+        };
+        transform_once(&mut document, "Mirror", mirror);
+
+        let Some(Entity::Arc(arc)) = document.entities.get(arc) else {
+            panic!("not an arc");
+        };
+        assert!((arc.start_angle - PI).abs() < 1e-4, "{}", arc.start_angle);
+        assert!(arc.end_angle.abs() < 1e-4, "{}", arc.end_angle);
+
+        let Some(Entity::Polyline(polyline)) = document.entities.get(polyline) else {
+            panic!("not a polyline");
+        };
+        assert!(close(polyline.vertices[0].position, [0.0, -1.0]));
+        assert_eq!(polyline.vertices[0].bulge, -0.5);
+    }
+
+    #[test]
+    fn mirroring_with_keep_source_adds_copies() {
+        let mut document = Document::new();
+        let id = selected_line(&mut document, [1.0, 1.0], [2.0, 1.0]);
+
+        let mirror = Transform::Mirror {
+            a: [0.0, 0.0],
+            b: [0.0, 1.0],
+        };
+        assert!(document.start_transform(true));
+        document.preview_transform(&mirror);
+        document.finish_transform("Mirror", &mirror, true);
+
+        assert_eq!(document.entities.len(), 2);
+        assert!(close(line_ends(&document, id)[0], [1.0, 1.0]));
+        let copy = document
+            .entities
+            .iter()
+            .map(Entity::id)
+            .find(|other| *other != id)
+            .unwrap();
+        let [start, end] = line_ends(&document, copy);
+        assert!(close(start, [-1.0, 1.0]) && close(end, [-2.0, 1.0]));
+
+        document.undo();
+        assert_eq!(document.entities.len(), 1);
+    }
+
+    #[test]
+    fn undo_puts_everything_back() {
+        let mut document = Document::new();
+        let id = selected_line(&mut document, [1.0, 0.0], [3.0, 0.0]);
+        let text = add_text(&mut document, [5.0, 0.0]);
+
+        let rotate = Transform::Rotate {
+            center: [0.0, 0.0],
+            angle: PI,
+        };
+        transform_once(&mut document, "Rotate", rotate);
+        assert!(close(line_ends(&document, id)[1], [-3.0, 0.0]));
+
+        assert_eq!(document.undo(), Some("Rotate"));
+        let [start, end] = line_ends(&document, id);
+        assert!(close(start, [1.0, 0.0]) && close(end, [3.0, 0.0]));
+        assert!(close(document.texts[0].position, [5.0, 0.0]));
+        assert_eq!(document.texts[0].id, text);
+
+        assert_eq!(document.redo(), Some("Rotate"));
+        assert!(close(line_ends(&document, id)[1], [-3.0, 0.0]));
+        assert!(close(document.texts[0].position, [-5.0, 0.0]));
+    }
+
+    #[test]
+    fn texts_are_only_moved() {
+        let mut document = Document::new();
+        add_text(&mut document, [2.0, 0.0]);
+        let before = document.texts[0].contents.text().to_string();
+
+        let rotate = Transform::Rotate {
+            center: [0.0, 0.0],
+            angle: FRAC_PI_2,
+        };
+        transform_once(&mut document, "Rotate", rotate);
+        assert!(close(document.texts[0].position, [0.0, 2.0]));
+
+        document.texts[0].selected = true;
+        let scale = Transform::Scale {
+            center: [0.0, 0.0],
+            factor: 2.0,
+        };
+        transform_once(&mut document, "Scale", scale);
+        assert!(close(document.texts[0].position, [0.0, 4.0]));
+
+        document.texts[0].selected = true;
+        let mirror = Transform::Mirror {
+            a: [0.0, 0.0],
+            b: [1.0, 0.0],
+        };
+        transform_once(&mut document, "Mirror", mirror);
+        let text = &document.texts[0];
+        assert!(close(text.position, [0.0, -4.0]));
+        // no angle, size or mirrored flag to carry along, the contents are as they were
+        assert_eq!(text.contents.text(), before);
+    }
+
+    #[test]
+    fn cancelling_drops_the_copies_and_restores_the_originals() {
+        let mut document = Document::new();
+        let id = selected_line(&mut document, [1.0, 0.0], [3.0, 0.0]);
+        add_text(&mut document, [5.0, 0.0]);
+
+        let scale = Transform::Scale {
+            center: [0.0, 0.0],
+            factor: 2.0,
+        };
+        assert!(document.start_transform(true));
+        document.preview_transform(&scale);
+        assert_eq!(document.entities.len(), 2);
+        assert_eq!(document.texts.len(), 2);
+
+        document.cancel_transform();
+        assert_eq!(document.entities.len(), 1);
+        assert_eq!(document.texts.len(), 1);
+        assert!(close(line_ends(&document, id)[0], [1.0, 0.0]));
+        assert!(!document.entities.get(id).unwrap().is_drawing());
+    }
+
+    #[test]
+    fn nothing_selected_doesnt_start() {
+        let mut document = Document::new();
+        document.add_line([0.0, 0.0], [1.0, 0.0], false);
+        assert!(!document.start_transform(false));
+        assert!(document.active_transform.is_none());
+    }
+}
@@ -0,0 +1,369 @@
+use crate::model::document::Document;
+use crate::model::entity::{Entity, Geometry};
+use crate::model::geometry::distance;
+use crate::model::history::{Change, HistoryOps};
+use crate::model::path::{rebuild, Path, Piece, EPSILON};
+use crate::model::spatial::SpatialOps;
+
+impl Path {
+    // sorted parameters of everywhere an edge crosses the path
+    fn cuts(&self, edges: &[Piece]) -> Vec<f32> {
+        let mut cuts: Vec<f32> = self
+            .pieces
+            .iter()
+            .enumerate()
+            .flat_map(|(index, piece)| {
+                edges
+                    .iter()
+                    .flat_map(|edge| {
+                        piece
+                            .intersections(edge)
+                            .into_iter()
+                            .filter(|p| edge.param_on(*p).is_some())
+                    })
+                    .filter_map(|p| piece.param_on(p))
+                    .map(move |t| index as f32 + t)
+                    .collect::<Vec<f32>>()
+            })
+            .map(|cut| {
+                if self.closed && cut >= self.length() - EPSILON {
+                    0.0
+                } else {
+                    cut
+                }
+            })
+            .collect();
+
+        cuts.sort_by(f32::total_cmp);
+        cuts.dedup_by(|a, b| (*a - *b).abs() < EPSILON);
+        cuts
+    }
+
+    // What's left of the path after cutting out the stretch around `pick` between the cuts on
+    // either side of it. None if there's nothing to cut it off at.
+    fn trimmed(&self, cuts: &[f32], pick: f32) -> Option<Vec<Vec<Piece>>> {
+        let end = self.length();
+
+        if self.closed {
+            if cuts.len() < 2 {
+                return None;
+            }
+            // from the first cut after the pick around to the last one before it
+            let before = cuts.iter().rev().find(|cut| **cut < pick).or(cuts.last())?;
+            let after = cuts.iter().find(|cut| **cut > pick).or(cuts.first())?;
+
+            let kept = if after < before {
+                self.between(*after, *before)
+            } else {
+                let mut kept = self.between(*after, end);
+                kept.extend(self.between(0.0, *before));
+                kept
+            };
+            return Some(vec![kept]);
+        }
+
+        let before = cuts
+            .iter()
+            .rev()
+            .find(|cut| **cut < pick && **cut > EPSILON);
+        let after = cuts
+            .iter()
+            .find(|cut| **cut > pick && **cut < end - EPSILON);
+        if before.is_none() && after.is_none() {
+            return None;
+        }
+
+        let mut parts = Vec::new();
+        if let Some(before) = before {
+            parts.push(self.between(0.0, *before));
+        }
+        if let Some(after) = after {
+            parts.push(self.between(*after, end));
+        }
+        Some(parts)
+    }
+}
+
+pub trait TrimOps {
+    // Cuts the part of entity `id` around `pick` off at the nearest edges on either side. With no
+    // edges everything shown is an edge. Returns whether anything was cut.
+    fn trim(&mut self, id: u64, pick: [f32; 2], edges: &[u64]) -> bool;
+    // makes the end of entity `id` closest to `pick` longer until it meets the nearest edge
+    fn extend(&mut self, id: u64, pick: [f32; 2], edges: &[u64]) -> bool;
+}
+
+// Puts `parts` where `target` was, the first one keeps its id and any other one is new. Without
+// parts the target is just gone.
+pub fn replace_with_parts(
+    document: &mut Document,
+    target: Entity,
+    parts: Vec<Entity>,
+) -> Vec<Change> {
+    let mut parts = parts.into_iter();
+    let mut changes = Vec::new();
+
+    match parts.next() {
+        Some(first) => {
+            if let Some(entity) = document.entities.get_mut(target.id()) {
+                *entity = first.clone();
+            }
+            changes.push(Change::Modify(target, first));
+        }
+        None => {
+            document.entities.remove(target.id());
+            changes.push(Change::Remove(target));
+        }
+    }
+
+    for mut part in parts {
+        let id = document.next_id();
+        part.set_id(id);
+        document.entities.insert(id, part.clone());
+        changes.push(Change::Add(part));
+    }
+    changes
+}
+
+// the pieces of the edges other than the target, only what overlaps it if `nearby`
+fn edge_pieces(
+    document: &mut Document,
+    target: &Entity,
+    edges: &[u64],
+    nearby: bool,
+) -> Vec<Piece> {
+    let ids = if !edges.is_empty() {
+        edges.to_vec()
+    } else if nearby {
+        document.entities_in(target.bounding_box())
+    } else {
+        document.entities.iter().map(Entity::id).collect()
+    };
+
+    ids.into_iter()
+        .filter(|id| *id != target.id())
+        .filter_map(|id| document.entities.get(id))
+        .filter(|entity| !entity.is_drawing() && document.layers.is_shown(entity.layer()))
+        .flat_map(|entity| match entity {
+            Entity::Ellipse(ellipse) => ellipse.pieces(),
+            _ => Path::of(entity).map_or_else(Vec::new, |path| path.pieces),
+        })
+        .collect()
+}
+
+// what's left of `target` once the part around `pick` is cut off
+fn trimmed_parts(
+    document: &mut Document,
+    target: &Entity,
+    pick: [f32; 2],
+    edges: &[u64],
+) -> Option<Vec<Entity>> {
+    let edges = edge_pieces(document, target, edges, true);
+
+    // an ellipse isn't made of lines and arcs, it's cut at its own parameters
+    if let Entity::Ellipse(ellipse) = target {
+        let cuts = ellipse.cuts(&edges);
+        let parts = ellipse.trimmed(&cuts, ellipse.param_near(pick))?;
+        return Some(parts.into_iter().map(Entity::Ellipse).collect());
+    }
+
+    let path = Path::of(target)?;
+    let cuts = path.cuts(&edges);
+    let parts = path.trimmed(&cuts, path.param_near(pick))?;
+    Some(
+        parts
+            .iter()
+            .filter_map(|pieces| rebuild(target, pieces))
+            .collect(),
+    )
+}
+
+impl TrimOps for Document {
+    fn trim(&mut self, id: u64, pick: [f32; 2], edges: &[u64]) -> bool {
+        let Some(target) = self.entities.get(id).cloned() else {
+            return false;
+        };
+        let Some(parts) = trimmed_parts(self, &target, pick, edges) else {
+            return false;
+        };
+
+        let changes = replace_with_parts(self, target, parts);
+
+        self.record("Trim", changes);
+        true
+    }
+
+    fn extend(&mut self, id: u64, pick: [f32; 2], edges: &[u64]) -> bool {
+        let Some(target) = self.entities.get(id).cloned() else {
+            return false;
+        };
+        let Some(mut path) = Path::of(&target).filter(|path| !path.closed) else {
+            return false;
+        };
+
+        let last = path.pieces.len() - 1;
+        let at_start =
+            distance(pick, path.pieces[0].start()) < distance(pick, path.pieces[last].end());
+        let index = if at_start { 0 } else { last };
+        let piece = path.pieces[index];
+
+        let edges = edge_pieces(self, &target, edges, false);
+        let reach = edges.iter().flat_map(|edge| {
+            piece
+                .intersections(edge)
+                .into_iter()
+                .filter(|p| edge.param_on(*p).is_some())
+                .filter_map(|p| piece.extension_to(p, at_start))
+        });
+
+        // the nearest edge past the end, the start runs to negative parameters
+        let extended = if at_start {
+            reach.max_by(f32::total_cmp).map(|t| piece.part(t, 1.0))
+        } else {
+            reach.min_by(f32::total_cmp).map(|t| piece.part(0.0, t))
+        };
+        let Some(extended) = extended else {
+            return false;
+        };
+
+        path.pieces[index] = extended;
+        let Some(after) = rebuild(&target, &path.pieces) else {
+            return false;
+        };
+
+        if let Some(entity) = self.entities.get_mut(id) {
+            *entity = after.clone();
+        }
+        self.record("Extend", vec![Change::Modify(target, after)]);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::circle::CircleOps;
+    use crate::model::line::LineOps;
+
+    fn ends(document: &Document, id: u64) -> [[f32; 2]; 2] {
+        match document.entities.get(id) {
+            Some(Entity::Line(line)) => [line.start(), line.end()],
+            other => panic!("expected a line, got {:?}", other),
+        }
+    }
+
+    fn near(a: [f32; 2], b: [f32; 2]) -> bool {
+        distance(a, b) < 1e-4
+    }
+
+    // a horizontal line from 0 to 10 crossed by verticals at `xs`
+    fn crossed_line(xs: &[f32]) -> (Document, u64, Vec<u64>) {
+        let mut document = Document::new();
+        let id = document.add_line([0.0, 0.0], [10.0, 0.0], false);
+        let edges = xs
+            .iter()
+            .map(|x| document.add_line([*x, -5.0], [*x, 5.0], false))
+            .collect();
+        (document, id, edges)
+    }
+
+    #[test]
+    fn trimming_between_two_edges_splits_the_line() {
+        let (mut document, id, edges) = crossed_line(&[3.0, 7.0]);
+
+        assert!(document.trim(id, [5.0, 0.0], &edges));
+
+        let [start, end] = ends(&document, id);
+        assert!(near(start, [0.0, 0.0]) && near(end, [3.0, 0.0]));
+        let new = document.entities.ids().max().unwrap();
+        let [start, end] = ends(&document, new);
+        assert!(near(start, [7.0, 0.0]) && near(end, [10.0, 0.0]));
+    }
+
+    #[test]
+    fn trimming_past_the_last_edge_shortens_the_line() {
+        let (mut document, id, edges) = crossed_line(&[3.0]);
+
+        assert!(document.trim(id, [8.0, 0.0], &edges));
+
+        let [start, end] = ends(&document, id);
+        assert!(near(start, [0.0, 0.0]) && near(end, [3.0, 0.0]));
+        assert_eq!(document.entities.len(), 2);
+    }
+
+    #[test]
+    fn without_edges_everything_shown_is_an_edge() {
+        let (mut document, id, _) = crossed_line(&[3.0, 7.0]);
+
+        assert!(document.trim(id, [1.0, 0.0], &[]));
+
+        let [start, end] = ends(&document, id);
+        assert!(near(start, [3.0, 0.0]) && near(end, [10.0, 0.0]));
+    }
+
+    #[test]
+    fn nothing_to_cut_at_changes_nothing() {
+        let (mut document, id, _) = crossed_line(&[]);
+
+        assert!(!document.trim(id, [5.0, 0.0], &[]));
+        assert!(!document.history.can_undo());
+    }
+
+    #[test]
+    fn a_trimmed_circle_becomes_an_arc() {
+        let mut document = Document::new();
+        let id = document.add_circle([0.0, 0.0], 5.0, false, false, false);
+        let edge = document.add_line([3.0, -10.0], [3.0, 10.0], false);
+
+        assert!(document.trim(id, [5.0, 0.0], &[edge]));
+
+        match document.entities.get(id) {
+            Some(Entity::Arc(arc)) => {
+                // the part left of the edge is kept
+                assert!(arc.hit_distance([-5.0, 0.0]) < 1e-3);
+                assert!(arc.hit_distance([5.0, 0.0]) > 1.0);
+            }
+            other => panic!("expected an arc, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn undo_puts_the_trimmed_line_back() {
+        let (mut document, id, edges) = crossed_line(&[3.0, 7.0]);
+        let before = document.entities.get(id).cloned();
+
+        document.trim(id, [5.0, 0.0], &edges);
+        assert_eq!(document.undo(), Some("Trim"));
+
+        assert_eq!(document.entities.get(id).cloned(), before);
+        assert_eq!(document.entities.len(), 3);
+    }
+
+    #[test]
+    fn extending_runs_the_nearest_end_to_the_edge() {
+        let mut document = Document::new();
+        let id = document.add_line([0.0, 0.0], [5.0, 0.0], false);
+        let near_edge = document.add_line([8.0, -5.0], [8.0, 5.0], false);
+        let far_edge = document.add_line([12.0, -5.0], [12.0, 5.0], false);
+
+        assert!(document.extend(id, [4.0, 0.0], &[near_edge, far_edge]));
+        let [start, end] = ends(&document, id);
+        assert!(near(start, [0.0, 0.0]) && near(end, [8.0, 0.0]));
+
+        // the start goes the other way
+        document.add_line([-2.0, -5.0], [-2.0, 5.0], false);
+        assert!(document.extend(id, [1.0, 0.0], &[]));
+        let [start, _] = ends(&document, id);
+        assert!(near(start, [-2.0, 0.0]));
+    }
+
+    #[test]
+    fn extending_towards_nothing_fails() {
+        let mut document = Document::new();
+        let id = document.add_line([0.0, 0.0], [5.0, 0.0], false);
+        // parallel, the line never reaches it
+        document.add_line([0.0, 2.0], [10.0, 2.0], false);
+
+        assert!(!document.extend(id, [4.0, 0.0], &[]));
+        assert!(!document.history.can_undo());
+    }
+}